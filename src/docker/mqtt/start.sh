@@ -8,6 +8,8 @@ mosquitto_passwd -U /password_file
 
 chown mosquitto:mosquitto /password_file
 
+sed -i "s/^max_connections .*/max_connections ${MQTT_MAX_CONNECTIONS:--1}/" /mosquitto/config/mosquitto.conf
+
 /usr/sbin/mosquitto -c /mosquitto/config/mosquitto.conf &
 
 sleep 5
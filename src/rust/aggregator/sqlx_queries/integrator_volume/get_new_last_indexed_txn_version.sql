@@ -0,0 +1,21 @@
+-- The user history watermark, held back before the first fill still pending there: the placement
+-- of one of its orders has not been indexed yet, so its integrator is not known. Never moves
+-- backwards, so that a user history aggregated again does not count fills twice.
+SELECT
+    GREATEST(
+        last_indexed_txn.txn_version,
+        LEAST(
+            user_history_last_indexed_txn.txn_version,
+            (
+                SELECT
+                    MIN(pending_fills.txn_version) - 1
+                FROM
+                    aggregator.pending_fills
+                WHERE
+                    pending_fills.txn_version > last_indexed_txn.txn_version
+            )
+        )
+    ) AS "txn_version!"
+FROM
+    aggregator.integrator_volume_last_indexed_txn AS last_indexed_txn,
+    aggregator.user_history_last_indexed_txn AS user_history_last_indexed_txn
@@ -0,0 +1,73 @@
+-- Aggregates the fills up to $1, the new watermark. The integrator of an order is read from its
+-- placement, so that fills of markets or event kinds the user history skips are counted too.
+WITH fills AS (
+    SELECT
+        fill_events.market_id,
+        (fill_events."time" AT TIME ZONE 'UTC')::date AS "day",
+        fill_events."size" * fill_events.price * markets.tick_size AS volume,
+        fill_events.taker_quote_fees_paid,
+        fill_events.maker_order_id,
+        fill_events.taker_order_id
+    FROM
+        fill_events
+        INNER JOIN market_registration_events AS markets ON markets.market_id = fill_events.market_id,
+        aggregator.integrator_volume_last_indexed_txn AS last_indexed_txn
+    WHERE
+        fill_events.txn_version > last_indexed_txn.txn_version
+    AND
+        fill_events.txn_version <= $1
+    AND -- remove duplicates
+        fill_events.emit_address = fill_events.maker_address
+),
+-- Not materialized, so that only the placements of the fills are looked up.
+placements AS NOT MATERIALIZED (
+    SELECT market_id, order_id, integrator FROM place_limit_order_events
+    UNION ALL
+    SELECT market_id, order_id, integrator FROM place_market_order_events
+    UNION ALL
+    SELECT market_id, order_id, integrator FROM place_swap_order_events
+),
+per_side AS (
+    SELECT
+        maker.integrator,
+        fills.market_id,
+        fills."day",
+        fills.volume AS maker_volume,
+        0 AS taker_volume,
+        0 AS taker_fees
+    FROM
+        fills
+        INNER JOIN placements AS maker
+        ON maker.market_id = fills.market_id AND maker.order_id = fills.maker_order_id
+    UNION ALL
+    SELECT
+        taker.integrator,
+        fills.market_id,
+        fills."day",
+        0 AS maker_volume,
+        fills.volume AS taker_volume,
+        fills.taker_quote_fees_paid AS taker_fees
+    FROM
+        fills
+        INNER JOIN placements AS taker
+        ON taker.market_id = fills.market_id AND taker.order_id = fills.taker_order_id
+)
+INSERT INTO aggregator.integrator_volume
+SELECT
+    integrator,
+    market_id,
+    "day",
+    SUM(maker_volume),
+    SUM(taker_volume),
+    SUM(taker_fees)
+FROM
+    per_side
+GROUP BY
+    integrator,
+    market_id,
+    "day"
+ON CONFLICT ON CONSTRAINT integrator_volume_pkey DO
+UPDATE SET
+    maker_volume_in_quote_subunits = integrator_volume.maker_volume_in_quote_subunits + EXCLUDED.maker_volume_in_quote_subunits,
+    taker_volume_in_quote_subunits = integrator_volume.taker_volume_in_quote_subunits + EXCLUDED.taker_volume_in_quote_subunits,
+    taker_fees_in_quote_subunits = integrator_volume.taker_fees_in_quote_subunits + EXCLUDED.taker_fees_in_quote_subunits;
@@ -0,0 +1,2 @@
+UPDATE aggregator.integrator_volume_last_indexed_txn
+SET txn_version = $1;
@@ -0,0 +1,54 @@
+WITH window_stats AS (
+    SELECT
+        market_id,
+        LAST(price ORDER BY txn_version, event_idx) AS last_price,
+        FIRST(price ORDER BY txn_version, event_idx) AS "open",
+        MAX(price) AS high,
+        MIN(price) AS low,
+        -- In lots and ticks, like the volumes of `api.market_daily_stats`, the tick size of the
+        -- market is left out.
+        SUM("size") AS base_volume,
+        SUM("size" * price) AS quote_volume
+    FROM fill_events
+    WHERE emit_address = maker_address
+    AND "time" > CURRENT_TIMESTAMP - interval '24 hours'
+    GROUP BY market_id
+)
+INSERT INTO aggregator.market_stats_24h
+SELECT
+    m.market_id,
+    -- Fills since the previous cycle are all in the window, so the last price only needs to be
+    -- looked up in the whole history the first time.
+    COALESCE(w.last_price, s.last_price, (
+        SELECT price
+        FROM fill_events AS f
+        WHERE f.market_id = m.market_id
+        ORDER BY f.txn_version DESC, f.event_idx DESC
+        LIMIT 1
+    )),
+    (
+        SELECT price
+        FROM fill_events AS f
+        WHERE f.market_id = m.market_id
+        AND f."time" <= CURRENT_TIMESTAMP - interval '24 hours'
+        ORDER BY f.txn_version DESC, f.event_idx DESC
+        LIMIT 1
+    ),
+    w."open",
+    w.high,
+    w.low,
+    COALESCE(w.base_volume, 0),
+    COALESCE(w.quote_volume, 0),
+    CURRENT_TIMESTAMP
+FROM market_registration_events AS m
+LEFT JOIN aggregator.market_stats_24h AS s ON s.market_id = m.market_id
+LEFT JOIN window_stats AS w ON w.market_id = m.market_id
+ON CONFLICT (market_id) DO UPDATE SET
+last_price = EXCLUDED.last_price,
+price_24h_ago = EXCLUDED.price_24h_ago,
+"open" = EXCLUDED."open",
+high = EXCLUDED.high,
+low = EXCLUDED.low,
+base_volume = EXCLUDED.base_volume,
+quote_volume = EXCLUDED.quote_volume,
+updated_at = EXCLUDED.updated_at;
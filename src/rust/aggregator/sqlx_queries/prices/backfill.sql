@@ -3,7 +3,11 @@ SELECT
     market_id,
     date_trunc('minute', "time"),
     AVG(price),
-    SUM("size")
+    SUM("size"),
+    FIRST(price ORDER BY txn_version, event_idx),
+    MAX(price),
+    MIN(price),
+    LAST(price ORDER BY txn_version, event_idx)
 FROM fill_events
 WHERE emit_address = maker_address
 AND txn_version > COALESCE((SELECT * FROM aggregator.prices_last_indexed_txn), 0)
@@ -11,4 +15,7 @@ GROUP BY date_trunc('minute', "time"), market_id
 ORDER BY date_trunc('minute', "time"), market_id
 ON CONFLICT ON CONSTRAINT prices_pkey DO UPDATE SET
 price = (EXCLUDED.price * EXCLUDED.sum_fill_size_1m_period + prices.price * prices.sum_fill_size_1m_period) / (EXCLUDED.sum_fill_size_1m_period + prices.sum_fill_size_1m_period),
-sum_fill_size_1m_period = EXCLUDED.sum_fill_size_1m_period + prices.sum_fill_size_1m_period;
+sum_fill_size_1m_period = EXCLUDED.sum_fill_size_1m_period + prices.sum_fill_size_1m_period,
+high = GREATEST(EXCLUDED.high, prices.high),
+low = LEAST(EXCLUDED.low, prices.low),
+close = EXCLUDED.close;
@@ -0,0 +1,43 @@
+INSERT INTO aggregator.trades (
+    txn_version,
+    event_idx,
+    market_id,
+    sequence_number_for_trade,
+    "time",
+    price,
+    "size",
+    side,
+    maker_address,
+    maker_custodian_id,
+    maker_order_id,
+    taker_address,
+    taker_custodian_id,
+    taker_order_id,
+    taker_quote_fees_paid
+)
+-- Both fills of a trade are emitted in the same transaction, so a batch never
+-- holds only one of them. The first one emitted is the canonical one.
+SELECT DISTINCT ON (market_id, taker_order_id, sequence_number_for_trade)
+    txn_version,
+    event_idx,
+    market_id,
+    sequence_number_for_trade,
+    "time",
+    price,
+    "size",
+    -- The taker buys from an ask maker and sells to a bid maker.
+    CASE
+        WHEN maker_side = true THEN 'buy'
+        ELSE 'sell'
+    END,
+    maker_address,
+    maker_custodian_id,
+    maker_order_id,
+    taker_address,
+    taker_custodian_id,
+    taker_order_id,
+    taker_quote_fees_paid
+FROM fill_events
+WHERE txn_version > COALESCE((SELECT * FROM aggregator.trades_last_indexed_txn), 0)
+ORDER BY market_id, taker_order_id, sequence_number_for_trade, txn_version, event_idx
+ON CONFLICT DO NOTHING;
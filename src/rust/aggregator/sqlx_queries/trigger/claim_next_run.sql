@@ -0,0 +1,31 @@
+-- Claims the oldest requested run of one of the pipelines $1, skipping runs
+-- another aggregator instance is claiming. Runs started by an instance whose
+-- connection is gone are claimed again, since they never finish otherwise.
+UPDATE
+    aggregator.pipeline_runs
+SET
+    started_at = CURRENT_TIMESTAMP,
+    claimed_by = pg_backend_pid()
+WHERE
+    id = (
+        SELECT
+            r.id
+        FROM
+            aggregator.pipeline_runs AS r
+        WHERE (r.started_at IS NULL
+            OR (r.finished_at IS NULL
+                AND NOT EXISTS (
+                    SELECT
+                    FROM
+                        pg_stat_activity AS a
+                    WHERE
+                        a.pid = r.claimed_by)))
+            AND r.model_name = ANY ($1::text[])
+        ORDER BY
+            r.id
+        LIMIT 1
+        FOR UPDATE
+            SKIP LOCKED)
+RETURNING
+    id,
+    model_name;
@@ -0,0 +1,16 @@
+-- The watermark tells tooling which version to wait for before reading the
+-- result.
+UPDATE
+    aggregator.pipeline_runs AS r
+SET
+    finished_at = CURRENT_TIMESTAMP,
+    error = $2,
+    txn_version = (
+        SELECT
+            w.txn_version
+        FROM
+            aggregator.pipeline_watermarks AS w
+        WHERE
+            w.model_name = r.model_name)
+WHERE
+    id = $1;
@@ -0,0 +1,10 @@
+-- Postgres has no ragged arrays, so the tables are passed comma-separated.
+INSERT INTO aggregator.pipelines (model_name, writes)
+SELECT
+    model_name,
+    COALESCE(string_to_array(NULLIF(writes, ''), ','), '{}')
+FROM
+    UNNEST($1::text[], $2::text[]) AS p (model_name, writes)
+ON CONFLICT (model_name)
+    DO UPDATE SET
+        writes = EXCLUDED.writes;
@@ -5,14 +5,29 @@ WITH parameters AS (
         $3::numeric fill_market_id,
         $4::timestamptz fill_time,
         $5::numeric fill_price,
-        $6::numeric fill_fees)
+        $6::numeric fill_fees),
+-- Read in the same statement, so without a round trip of its own, and before
+-- the update below since it sees the rows as they were when the statement
+-- started.
+previous AS (
+    SELECT
+        remaining_size AS previous_remaining_size,
+        -- Over-fills are clamped to the remaining size, and rejected or logged
+        -- by the caller from the previous remaining size returned below.
+        LEAST(fill_size, remaining_size) AS applied_size
+    FROM
+        parameters,
+        aggregator.user_history
+    WHERE
+        order_id = fill_order_id
+        AND market_id = fill_market_id)
 UPDATE
     aggregator.user_history
 SET
     order_status = CASE order_type
     WHEN 'limit' THEN
-        CASE remaining_size - fill_size
-        WHEN 0 THEN
+        CASE
+        WHEN remaining_size - fill_size <= 0 THEN
             'closed'
         ELSE
             order_status
@@ -20,13 +35,32 @@ SET
     ELSE
         'closed'
     END,
+    close_reason = CASE order_type
+    WHEN 'limit' THEN
+        CASE
+        WHEN remaining_size - fill_size <= 0 THEN
+            'filled'
+        ELSE
+            close_reason
+        END
+    ELSE
+        'market_exhausted'
+    END,
     last_updated_at = fill_time,
-    average_execution_price = (total_filled * COALESCE(average_execution_price, 0) + fill_size * fill_price) / (total_filled + fill_size),
-    total_filled = total_filled + fill_size,
-    remaining_size = remaining_size - fill_size,
+    average_execution_price = CASE
+    WHEN total_filled + applied_size = 0 THEN
+        average_execution_price
+    ELSE
+        (total_filled * COALESCE(average_execution_price, 0) + applied_size * fill_price) / (total_filled + applied_size)
+    END,
+    total_filled = total_filled + applied_size,
+    remaining_size = remaining_size - applied_size,
     total_fees_paid_in_quote_subunits = total_fees_paid_in_quote_subunits + fill_fees
 FROM
-    parameters
+    parameters,
+    previous
 WHERE
     order_id = fill_order_id
     AND market_id = fill_market_id
+RETURNING
+    previous_remaining_size
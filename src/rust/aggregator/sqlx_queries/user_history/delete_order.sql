@@ -0,0 +1,5 @@
+DELETE FROM
+    aggregator.user_history
+WHERE
+    market_id = $1
+    AND order_id = $2
@@ -0,0 +1,20 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS order_market_id,
+        $2::numeric AS order_order_id),
+fills AS (
+    DELETE FROM
+        aggregator.pending_fills
+    USING
+        parameters
+    WHERE
+        market_id = order_market_id
+        AND order_id = order_order_id
+)
+DELETE FROM
+    aggregator.pending_size_changes
+USING
+    parameters
+WHERE
+    market_id = order_market_id
+    AND order_id = order_order_id
@@ -0,0 +1,5 @@
+DELETE FROM
+    aggregator.order_size_changes
+WHERE
+    market_id = $1
+    AND order_id = $2
@@ -0,0 +1,4 @@
+DELETE FROM
+    aggregator.order_size_changes
+WHERE
+    txn_version > $1
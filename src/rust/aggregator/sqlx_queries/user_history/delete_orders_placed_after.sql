@@ -0,0 +1,29 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS from_txn_version),
+placed AS (
+    SELECT market_id, order_id FROM place_limit_order_events, parameters WHERE txn_version > from_txn_version
+    UNION ALL
+    SELECT market_id, order_id FROM place_market_order_events, parameters WHERE txn_version > from_txn_version
+    UNION ALL
+    SELECT market_id, order_id FROM place_swap_order_events, parameters WHERE txn_version > from_txn_version
+)
+DELETE FROM
+    aggregator.user_history AS user_history
+USING
+    placed
+WHERE
+    user_history.market_id = placed.market_id
+    AND user_history.order_id = placed.order_id
+    -- An order placed again after the version, e.g. by an event replayed
+    -- upstream, keeps its first placement, which is not rewound.
+    AND NOT EXISTS (
+        SELECT FROM place_limit_order_events AS e, parameters
+        WHERE e.market_id = placed.market_id AND e.order_id = placed.order_id AND e.txn_version <= from_txn_version
+        UNION ALL
+        SELECT FROM place_market_order_events AS e, parameters
+        WHERE e.market_id = placed.market_id AND e.order_id = placed.order_id AND e.txn_version <= from_txn_version
+        UNION ALL
+        SELECT FROM place_swap_order_events AS e, parameters
+        WHERE e.market_id = placed.market_id AND e.order_id = placed.order_id AND e.txn_version <= from_txn_version
+    )
@@ -0,0 +1,4 @@
+DELETE FROM
+    aggregator.pending_cancels
+WHERE
+    txn_version > $1
@@ -0,0 +1,10 @@
+WITH fills AS (
+    DELETE FROM
+        aggregator.pending_fills
+    WHERE
+        txn_version > $1
+)
+DELETE FROM
+    aggregator.pending_size_changes
+WHERE
+    txn_version > $1
@@ -1,10 +1,16 @@
 WITH parameters AS (
     SELECT
         $1::numeric AS txn_version_start,
-        $2::numeric AS txn_version_stop
+        $2::numeric AS txn_version_stop,
+        $3::numeric[] AS market_ids
 )
 SELECT
-    *
+    change_order_size_events.txn_version,
+    change_order_size_events.event_idx,
+    change_order_size_events."time",
+    change_order_size_events.market_id,
+    change_order_size_events.order_id,
+    change_order_size_events.new_size
 FROM
     parameters,
     change_order_size_events
@@ -12,6 +18,8 @@ WHERE
     txn_version > txn_version_start
 AND
     txn_version <= txn_version_stop
+AND
+    (market_ids IS NULL OR market_id = ANY(market_ids))
 ORDER BY
     txn_version,
     event_idx
@@ -1,10 +1,21 @@
 WITH parameters AS (
     SELECT
         $1::numeric AS txn_version_start,
-        $2::numeric AS txn_version_stop
+        $2::numeric AS txn_version_stop,
+        $3::numeric[] AS market_ids
 )
 SELECT
-    *
+    fill_events.txn_version,
+    fill_events.event_idx,
+    fill_events.emit_address,
+    fill_events."time",
+    fill_events.maker_address,
+    fill_events.maker_order_id,
+    fill_events.market_id,
+    fill_events.price,
+    fill_events."size",
+    fill_events.taker_order_id,
+    fill_events.taker_quote_fees_paid
 FROM
     parameters,
     fill_events
@@ -12,6 +23,8 @@ WHERE
     txn_version > txn_version_start
 AND
     txn_version <= txn_version_stop
+AND
+    (market_ids IS NULL OR market_id = ANY(market_ids))
 ORDER BY
     txn_version,
     event_idx
@@ -0,0 +1,30 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS max_txn_version,
+        $2::numeric[] AS market_ids),
+placed AS (
+    SELECT market_id, order_id FROM place_limit_order_events, parameters WHERE txn_version <= max_txn_version
+    UNION ALL
+    SELECT market_id, order_id FROM place_market_order_events, parameters WHERE txn_version <= max_txn_version
+    UNION ALL
+    SELECT market_id, order_id FROM place_swap_order_events, parameters WHERE txn_version <= max_txn_version
+)
+SELECT
+    placed.market_id AS "market_id!",
+    placed.order_id AS "order_id!"
+FROM
+    parameters,
+    placed
+WHERE
+    (market_ids IS NULL OR placed.market_id = ANY(market_ids))
+    AND NOT EXISTS (
+        SELECT 1 FROM aggregator.user_history AS user_history
+        WHERE user_history.market_id = placed.market_id AND user_history.order_id = placed.order_id
+    )
+    AND NOT EXISTS (
+        SELECT 1 FROM aggregator.pruned_orders AS pruned_orders
+        WHERE pruned_orders.market_id = placed.market_id AND pruned_orders.order_id = placed.order_id
+    )
+ORDER BY
+    placed.market_id,
+    placed.order_id
@@ -0,0 +1,67 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS txn_version_start,
+        $2::numeric AS txn_version_stop,
+        $3::numeric[] AS market_ids
+)
+-- Amounts are unsigned on chain, so a negative one can only come from a
+-- malformed event, which would corrupt the totals it is added to.
+SELECT
+    "table" AS "table!",
+    field AS "field!",
+    txn_version AS "txn_version!",
+    event_idx AS "event_idx!"
+FROM (
+    SELECT 'place_limit_order_events' AS "table", 'initial_size' AS field, txn_version, event_idx
+    FROM place_limit_order_events, parameters
+    WHERE initial_size < 0
+    AND txn_version > txn_version_start
+    AND txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+    UNION ALL
+    SELECT 'place_limit_order_events' AS "table", 'price' AS field, txn_version, event_idx
+    FROM place_limit_order_events, parameters
+    WHERE price < 0
+    AND txn_version > txn_version_start
+    AND txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+    UNION ALL
+    SELECT 'place_market_order_events' AS "table", 'size' AS field, txn_version, event_idx
+    FROM place_market_order_events, parameters
+    WHERE "size" < 0
+    AND txn_version > txn_version_start
+    AND txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+    UNION ALL
+    SELECT 'place_swap_order_events' AS "table", 'limit_price' AS field, txn_version, event_idx
+    FROM place_swap_order_events, parameters
+    WHERE limit_price < 0
+    AND txn_version > txn_version_start
+    AND txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+    UNION ALL
+    SELECT 'fill_events' AS "table", 'size' AS field, txn_version, event_idx
+    FROM fill_events, parameters
+    WHERE "size" < 0
+    AND txn_version > txn_version_start
+    AND txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+    UNION ALL
+    SELECT 'fill_events' AS "table", 'price' AS field, txn_version, event_idx
+    FROM fill_events, parameters
+    WHERE price < 0
+    AND txn_version > txn_version_start
+    AND txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+    UNION ALL
+    SELECT 'change_order_size_events' AS "table", 'new_size' AS field, txn_version, event_idx
+    FROM change_order_size_events, parameters
+    WHERE new_size < 0
+    AND txn_version > txn_version_start
+    AND txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+) AS negative_amounts
+ORDER BY
+    txn_version,
+    event_idx
+LIMIT 1
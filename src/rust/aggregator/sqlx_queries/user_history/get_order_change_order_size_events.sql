@@ -0,0 +1,25 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS order_market_id,
+        $2::numeric AS order_order_id,
+        $3::numeric AS txn_version_stop
+)
+SELECT
+    change_order_size_events.txn_version,
+    change_order_size_events.event_idx,
+    change_order_size_events."time",
+    change_order_size_events.market_id,
+    change_order_size_events.order_id,
+    change_order_size_events.new_size
+FROM
+    parameters,
+    change_order_size_events
+WHERE
+    market_id = order_market_id
+AND
+    order_id = order_order_id
+AND
+    txn_version <= txn_version_stop
+ORDER BY
+    txn_version,
+    event_idx
@@ -0,0 +1,30 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS order_market_id,
+        $2::numeric AS order_order_id,
+        $3::numeric AS txn_version_stop
+)
+SELECT
+    fill_events.txn_version,
+    fill_events.event_idx,
+    fill_events.emit_address,
+    fill_events."time",
+    fill_events.maker_address,
+    fill_events.maker_order_id,
+    fill_events.market_id,
+    fill_events.price,
+    fill_events."size",
+    fill_events.taker_order_id,
+    fill_events.taker_quote_fees_paid
+FROM
+    parameters,
+    fill_events
+WHERE
+    market_id = order_market_id
+AND
+    (maker_order_id = order_order_id OR taker_order_id = order_order_id)
+AND
+    txn_version <= txn_version_stop
+ORDER BY
+    txn_version,
+    event_idx
@@ -0,0 +1,8 @@
+SELECT
+    total_filled,
+    remaining_size
+FROM
+    aggregator.user_history
+WHERE
+    market_id = $1
+    AND order_id = $2
@@ -0,0 +1,21 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS from_txn_version),
+changed AS (
+    SELECT market_id, maker_order_id AS order_id FROM fill_events, parameters WHERE txn_version > from_txn_version
+    UNION
+    SELECT market_id, taker_order_id AS order_id FROM fill_events, parameters WHERE txn_version > from_txn_version
+    UNION
+    SELECT market_id, order_id FROM change_order_size_events, parameters WHERE txn_version > from_txn_version
+    UNION
+    SELECT market_id, order_id FROM cancel_order_events, parameters WHERE txn_version > from_txn_version
+)
+SELECT
+    user_history.market_id,
+    user_history.order_id
+FROM
+    aggregator.user_history AS user_history
+    INNER JOIN changed USING (market_id, order_id)
+ORDER BY
+    user_history.market_id,
+    user_history.order_id
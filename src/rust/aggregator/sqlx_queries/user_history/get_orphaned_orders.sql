@@ -0,0 +1,21 @@
+SELECT
+    user_history.market_id,
+    user_history.order_id
+FROM
+    aggregator.user_history AS user_history
+WHERE
+    NOT EXISTS (
+        SELECT 1 FROM place_limit_order_events AS p
+        WHERE p.market_id = user_history.market_id AND p.order_id = user_history.order_id
+    )
+    AND NOT EXISTS (
+        SELECT 1 FROM place_market_order_events AS p
+        WHERE p.market_id = user_history.market_id AND p.order_id = user_history.order_id
+    )
+    AND NOT EXISTS (
+        SELECT 1 FROM place_swap_order_events AS p
+        WHERE p.market_id = user_history.market_id AND p.order_id = user_history.order_id
+    )
+ORDER BY
+    user_history.market_id,
+    user_history.order_id
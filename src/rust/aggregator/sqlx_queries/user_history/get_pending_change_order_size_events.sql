@@ -0,0 +1,23 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS order_market_id,
+        $2::numeric AS order_order_id
+)
+SELECT
+    change_order_size_events.txn_version,
+    change_order_size_events.event_idx,
+    change_order_size_events."time",
+    change_order_size_events.market_id,
+    change_order_size_events.order_id,
+    change_order_size_events.new_size
+FROM
+    parameters,
+    aggregator.pending_size_changes
+    INNER JOIN change_order_size_events USING (txn_version, event_idx)
+WHERE
+    pending_size_changes.market_id = order_market_id
+AND
+    pending_size_changes.order_id = order_order_id
+ORDER BY
+    txn_version,
+    event_idx
@@ -0,0 +1,18 @@
+WITH parameters AS (
+    SELECT
+        $1::timestamptz AS since,
+        $2::numeric[] AS market_ids)
+SELECT
+    market_id,
+    order_id,
+    total_filled,
+    remaining_size
+FROM
+    parameters,
+    aggregator.user_history
+WHERE
+    COALESCE(last_updated_at, created_at) >= since
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+ORDER BY
+    market_id,
+    order_id
@@ -0,0 +1,21 @@
+-- Orders with pending fills or size changes whose placement has been
+-- aggregated since.
+SELECT
+    market_id AS "market_id!",
+    order_id AS "order_id!"
+FROM (
+    SELECT market_id, order_id FROM aggregator.pending_fills
+    UNION
+    SELECT market_id, order_id FROM aggregator.pending_size_changes
+) AS pending
+WHERE
+    EXISTS (
+        SELECT
+        FROM
+            aggregator.user_history
+        WHERE
+            user_history.market_id = pending.market_id
+            AND user_history.order_id = pending.order_id)
+ORDER BY
+    market_id,
+    order_id
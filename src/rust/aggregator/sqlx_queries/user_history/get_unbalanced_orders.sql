@@ -0,0 +1,62 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS max_txn_version,
+        $2::numeric[] AS market_ids),
+-- The size of a limit order is set at placement, then reset by each size
+-- change, and only fills after the last of them reduce it.
+sizes AS (
+    SELECT
+        user_history.market_id,
+        user_history.order_id,
+        user_history.total_filled,
+        user_history.remaining_size,
+        COALESCE(last_change.new_size, p.initial_size) AS size,
+        last_change.txn_version,
+        last_change.event_idx
+    FROM
+        parameters,
+        aggregator.user_history AS user_history
+        INNER JOIN place_limit_order_events AS p
+            ON p.market_id = user_history.market_id AND p.order_id = user_history.order_id
+        LEFT JOIN LATERAL (
+            SELECT c.new_size, c.txn_version, c.event_idx
+            FROM change_order_size_events AS c
+            WHERE c.market_id = user_history.market_id
+            AND c.order_id = user_history.order_id
+            AND c.txn_version <= max_txn_version
+            ORDER BY c.txn_version DESC, c.event_idx DESC
+            LIMIT 1
+        ) AS last_change ON true
+    WHERE
+        user_history.order_type = 'limit'
+        AND (market_ids IS NULL OR user_history.market_id = ANY(market_ids))
+),
+balances AS (
+    SELECT
+        sizes.*,
+        (
+            SELECT COALESCE(SUM(f."size"), 0)
+            FROM parameters, fill_events AS f
+            WHERE f.market_id = sizes.market_id
+            AND (f.maker_order_id = sizes.order_id OR f.taker_order_id = sizes.order_id)
+            -- Fills are emitted to both the maker and the taker, keep only one of them.
+            AND f.emit_address = f.maker_address
+            AND f.txn_version <= max_txn_version
+            AND (sizes.txn_version IS NULL OR (f.txn_version, f.event_idx) > (sizes.txn_version, sizes.event_idx))
+        ) AS filled_since_size
+    FROM
+        sizes
+)
+SELECT
+    market_id AS "market_id!",
+    order_id AS "order_id!",
+    total_filled AS "total_filled!",
+    remaining_size AS "remaining_size!",
+    total_filled - filled_since_size + size AS "expected_total_size!"
+FROM
+    balances
+WHERE
+    remaining_size + filled_since_size <> size
+ORDER BY
+    market_id,
+    order_id
@@ -0,0 +1,12 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS last_txn_version,
+        $2::numeric[] AS market_ids)
+SELECT
+    EXISTS (SELECT 1 FROM cancel_order_events, parameters WHERE txn_version > last_txn_version AND (market_ids IS NULL OR market_id = ANY(market_ids)))
+    OR EXISTS (SELECT 1 FROM change_order_size_events, parameters WHERE txn_version > last_txn_version AND (market_ids IS NULL OR market_id = ANY(market_ids)))
+    OR EXISTS (SELECT 1 FROM fill_events, parameters WHERE txn_version > last_txn_version AND (market_ids IS NULL OR market_id = ANY(market_ids)))
+    OR EXISTS (SELECT 1 FROM place_limit_order_events, parameters WHERE txn_version > last_txn_version AND (market_ids IS NULL OR market_id = ANY(market_ids)))
+    OR EXISTS (SELECT 1 FROM place_market_order_events, parameters WHERE txn_version > last_txn_version AND (market_ids IS NULL OR market_id = ANY(market_ids)))
+    OR EXISTS (SELECT 1 FROM place_swap_order_events, parameters WHERE txn_version > last_txn_version AND (market_ids IS NULL OR market_id = ANY(market_ids)))
+    AS "has_new_events!"
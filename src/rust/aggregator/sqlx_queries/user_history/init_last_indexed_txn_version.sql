@@ -6,3 +6,4 @@ SELECT
     new_max_txn_version
 FROM
     parameters
+ON CONFLICT DO NOTHING
@@ -0,0 +1,75 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS order_market_id,
+        $2::numeric AS order_order_id,
+        $3::numeric AS max_txn_version)
+INSERT INTO aggregator.user_history (
+    market_id,
+    order_id,
+    created_at,
+    last_updated_at,
+    integrator,
+    total_filled,
+    remaining_size,
+    order_status,
+    order_type,
+    "user",
+    direction,
+    price,
+    average_execution_price,
+    custodian_id,
+    self_match_behavior,
+    restriction,
+    min_base,
+    max_base,
+    min_quote,
+    max_quote,
+    total_fees_paid_in_quote_subunits,
+    close_reason
+)
+SELECT
+    market_id,
+    order_id,
+    "time",
+    NULL,
+    integrator,
+    0,
+    initial_size,
+    -- An order of size zero has nothing left to fill, and no fill will ever
+    -- close it.
+    CASE
+        WHEN initial_size = 0 THEN 'closed'::order_status
+        ELSE 'open'::order_status
+    END,
+    'limit',
+    "user",
+    CASE
+        WHEN side = true THEN 'ask'::order_direction
+        ELSE 'bid'::order_direction
+    END,
+    price,
+    NULL,
+    custodian_id,
+    self_match_behavior,
+    restriction,
+    NULL,
+    NULL,
+    NULL,
+    NULL,
+    0,
+    CASE
+        WHEN initial_size = 0 THEN 'zero_size'::order_close_reason
+    END
+FROM
+    parameters,
+    place_limit_order_events
+WHERE
+    market_id = order_market_id
+    AND order_id = order_order_id
+    AND txn_version <= max_txn_version
+ORDER BY
+    txn_version,
+    event_idx
+-- An order placed twice (e.g. replayed upstream) keeps its first placement,
+-- like in an incremental run.
+ON CONFLICT (market_id, order_id) DO NOTHING
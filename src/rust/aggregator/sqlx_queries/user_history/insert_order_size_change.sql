@@ -0,0 +1,28 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS change_market_id,
+        $2::numeric AS change_order_id,
+        $3::numeric AS change_old_size,
+        $4::numeric AS change_new_size,
+        $5::numeric AS change_txn_version,
+        $6::numeric AS change_event_idx,
+        $7::timestamptz AS change_time)
+INSERT INTO aggregator.order_size_changes (
+    market_id,
+    order_id,
+    old_size,
+    new_size,
+    txn_version,
+    event_idx,
+    "time"
+)
+SELECT
+    change_market_id,
+    change_order_id,
+    change_old_size,
+    change_new_size,
+    change_txn_version,
+    change_event_idx,
+    change_time
+FROM
+    parameters
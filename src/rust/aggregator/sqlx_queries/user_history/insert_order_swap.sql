@@ -0,0 +1,75 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS order_market_id,
+        $2::numeric AS order_order_id,
+        $3::numeric AS max_txn_version)
+INSERT INTO aggregator.user_history (
+    market_id,
+    order_id,
+    created_at,
+    last_updated_at,
+    integrator,
+    total_filled,
+    remaining_size,
+    order_status,
+    order_type,
+    "user",
+    direction,
+    price,
+    average_execution_price,
+    custodian_id,
+    self_match_behavior,
+    restriction,
+    min_base,
+    max_base,
+    min_quote,
+    max_quote,
+    total_fees_paid_in_quote_subunits
+)
+SELECT
+    swaps.market_id,
+    swaps.order_id,
+    swaps."time",
+    NULL,
+    swaps.integrator,
+    0,
+    DIV(swaps.max_base, markets.lot_size),
+    'open',
+    'swap',
+    swaps.signing_account,
+    CASE
+        WHEN swaps.direction = true THEN 'sell'::order_direction
+        ELSE 'buy'::order_direction
+    END,
+    -- Swaps without a price limit carry the most permissive price, stored as
+    -- NULL rather than as a price nobody asked for.
+    CASE
+        WHEN swaps.direction = true AND swaps.limit_price = 0 THEN NULL
+        -- HI_PRICE
+        WHEN swaps.direction = false AND swaps.limit_price = 4294967295 THEN NULL
+        ELSE swaps.limit_price
+    END,
+    NULL,
+    NULL,
+    NULL,
+    NULL,
+    swaps.min_base,
+    -- MAX_POSSIBLE, meaning no maximum.
+    NULLIF(swaps.max_base, 18446744073709551615),
+    swaps.min_quote,
+    NULLIF(swaps.max_quote, 18446744073709551615),
+    0
+FROM
+    parameters,
+    place_swap_order_events AS swaps
+    INNER JOIN market_registration_events AS markets ON markets.market_id = swaps.market_id
+WHERE
+    swaps.market_id = order_market_id
+    AND swaps.order_id = order_order_id
+    AND swaps.txn_version <= max_txn_version
+ORDER BY
+    swaps.txn_version,
+    swaps.event_idx
+-- An order placed twice (e.g. replayed upstream) keeps its first placement,
+-- like in an incremental run.
+ON CONFLICT (market_id, order_id) DO NOTHING
@@ -0,0 +1,40 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS event_txn_version,
+        $2::numeric AS event_event_idx,
+        $3::numeric AS event_market_id,
+        $4::numeric AS event_order_id)
+INSERT INTO aggregator.pending_fills (txn_version, event_idx, market_id, order_id)
+SELECT
+    event_txn_version,
+    event_event_idx,
+    event_market_id,
+    event_order_id
+FROM
+    parameters
+WHERE
+    -- An order whose placement is there but was not aggregated is of a kind
+    -- that is not aggregated, and never will be.
+    NOT EXISTS (
+        SELECT
+        FROM
+            place_limit_order_events AS p
+        WHERE
+            p.market_id = event_market_id
+            AND p.order_id = event_order_id)
+    AND NOT EXISTS (
+        SELECT
+        FROM
+            place_market_order_events AS p
+        WHERE
+            p.market_id = event_market_id
+            AND p.order_id = event_order_id)
+    AND NOT EXISTS (
+        SELECT
+        FROM
+            place_swap_order_events AS p
+        WHERE
+            p.market_id = event_market_id
+            AND p.order_id = event_order_id)
+ON CONFLICT
+    DO NOTHING
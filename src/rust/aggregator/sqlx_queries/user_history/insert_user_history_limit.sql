@@ -1,6 +1,8 @@
 WITH parameters AS (
     SELECT
-        $1::numeric AS max_txn_version)
+        $1::numeric AS max_txn_version,
+        $2::numeric[] AS market_ids,
+        $3::numeric AS txn_version_stop)
 INSERT INTO aggregator.user_history (
     market_id,
     order_id,
@@ -22,7 +24,8 @@ INSERT INTO aggregator.user_history (
     max_base,
     min_quote,
     max_quote,
-    total_fees_paid_in_quote_subunits
+    total_fees_paid_in_quote_subunits,
+    close_reason
 )
 SELECT
     market_id,
@@ -32,7 +35,12 @@ SELECT
     integrator,
     0,
     initial_size,
-    'open',
+    -- An order of size zero has nothing left to fill, and no fill will ever
+    -- close it.
+    CASE
+        WHEN initial_size = 0 THEN 'closed'::order_status
+        ELSE 'open'::order_status
+    END,
     'limit',
     "user",
     CASE
@@ -48,9 +56,20 @@ SELECT
     NULL,
     NULL,
     NULL,
-    0
+    0,
+    CASE
+        WHEN initial_size = 0 THEN 'zero_size'::order_close_reason
+    END
 FROM
     parameters,
     place_limit_order_events
 WHERE
     txn_version > max_txn_version
+    AND txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR market_id = ANY(market_ids))
+ORDER BY
+    txn_version,
+    event_idx
+-- A place event seen again (e.g. replayed upstream) leaves the order as is.
+-- Events are inserted in order, so the earliest one always wins.
+ON CONFLICT (market_id, order_id) DO NOTHING
@@ -1,6 +1,8 @@
 WITH parameters AS (
     SELECT
-        $1::numeric AS max_txn_version)
+        $1::numeric AS max_txn_version,
+        $2::numeric[] AS market_ids,
+        $3::numeric AS txn_version_stop)
 INSERT INTO aggregator.user_history (
     market_id,
     order_id,
@@ -39,15 +41,23 @@ SELECT
         WHEN swaps.direction = true THEN 'sell'::order_direction
         ELSE 'buy'::order_direction
     END,
-    swaps.limit_price,
+    -- Swaps without a price limit carry the most permissive price, stored as
+    -- NULL rather than as a price nobody asked for.
+    CASE
+        WHEN swaps.direction = true AND swaps.limit_price = 0 THEN NULL
+        -- HI_PRICE
+        WHEN swaps.direction = false AND swaps.limit_price = 4294967295 THEN NULL
+        ELSE swaps.limit_price
+    END,
     NULL,
     NULL,
     NULL,
     NULL,
     swaps.min_base,
-    swaps.max_base,
+    -- MAX_POSSIBLE, meaning no maximum.
+    NULLIF(swaps.max_base, 18446744073709551615),
     swaps.min_quote,
-    swaps.max_quote,
+    NULLIF(swaps.max_quote, 18446744073709551615),
     0
 FROM
     parameters,
@@ -55,3 +65,11 @@ FROM
     INNER JOIN market_registration_events AS markets ON markets.market_id = swaps.market_id
 WHERE
     swaps.txn_version > max_txn_version
+    AND swaps.txn_version <= txn_version_stop
+    AND (market_ids IS NULL OR swaps.market_id = ANY(market_ids))
+ORDER BY
+    swaps.txn_version,
+    swaps.event_idx
+-- A place event seen again (e.g. replayed upstream) leaves the order as is.
+-- Events are inserted in order, so the earliest one always wins.
+ON CONFLICT (market_id, order_id) DO NOTHING
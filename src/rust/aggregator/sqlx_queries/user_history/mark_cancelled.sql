@@ -1,16 +1,119 @@
 WITH parameters AS (
     SELECT
-        $1::numeric AS max_txn_version)
-UPDATE
-    aggregator.user_history AS user_history
-SET
-    order_status = 'cancelled',
-    last_updated_at = cancel_order_events."time"
+        $1::numeric AS max_txn_version,
+        $2::numeric[] AS market_ids,
+        $3::boolean AS limit_orders,
+        $4::boolean AS market_orders,
+        $5::boolean AS swaps,
+        $6::numeric AS txn_version_stop
+),
+-- An order cancelled more than once (e.g. replayed upstream) is cancelled by
+-- its earliest cancel event, whatever order Postgres returns them in.
+cancels AS (
+    SELECT DISTINCT ON (market_id, order_id)
+        *
+    FROM (
+        SELECT
+            cancel_order_events.*
+        FROM
+            parameters,
+            cancel_order_events
+        WHERE
+            cancel_order_events.txn_version > max_txn_version
+            AND cancel_order_events.txn_version <= txn_version_stop
+            AND (market_ids IS NULL OR cancel_order_events.market_id = ANY(market_ids))
+        UNION
+        SELECT
+            cancel_order_events.*
+        FROM
+            aggregator.pending_cancels
+            INNER JOIN cancel_order_events USING (txn_version, event_idx)
+    ) AS all_cancels
+    ORDER BY
+        market_id,
+        order_id,
+        txn_version,
+        event_idx
+),
+cancelled AS (
+    UPDATE
+        aggregator.user_history AS user_history
+    SET
+        order_status = 'cancelled',
+        close_reason = CASE cancels.reason
+        -- CANCEL_REASON_IMMEDIATE_OR_CANCEL
+        WHEN 2 THEN
+            'ioc_expired'::order_close_reason
+        ELSE
+            'cancelled'::order_close_reason
+        END,
+        last_updated_at = cancels."time"
+    FROM
+        cancels
+    WHERE
+        user_history.order_id = cancels.order_id
+        AND user_history.market_id = cancels.market_id
+        -- A cancel aggregated by an earlier run came first.
+        AND user_history.order_status <> 'cancelled'
+    RETURNING
+        1
+),
+resolved AS (
+    DELETE FROM aggregator.pending_cancels
+    WHERE EXISTS (
+            SELECT
+            FROM
+                aggregator.user_history
+            WHERE
+                user_history.order_id = pending_cancels.order_id
+                AND user_history.market_id = pending_cancels.market_id)
+),
+-- Cancels of orders whose placement has not been aggregated yet are kept for
+-- the next run instead of being lost.
+pending AS (
+    INSERT INTO aggregator.pending_cancels (txn_version, event_idx, market_id, order_id)
+    SELECT
+        txn_version,
+        event_idx,
+        market_id,
+        order_id
+    FROM
+        parameters,
+        cancels
+    WHERE
+        NOT EXISTS (
+            SELECT
+            FROM
+                aggregator.user_history
+            WHERE
+                user_history.order_id = cancels.order_id
+                AND user_history.market_id = cancels.market_id)
+        -- Orders of a kind that is not aggregated will never be.
+        AND (limit_orders OR NOT EXISTS (
+            SELECT
+            FROM
+                place_limit_order_events AS p
+            WHERE
+                p.order_id = cancels.order_id
+                AND p.market_id = cancels.market_id))
+        AND (market_orders OR NOT EXISTS (
+            SELECT
+            FROM
+                place_market_order_events AS p
+            WHERE
+                p.order_id = cancels.order_id
+                AND p.market_id = cancels.market_id))
+        AND (swaps OR NOT EXISTS (
+            SELECT
+            FROM
+                place_swap_order_events AS p
+            WHERE
+                p.order_id = cancels.order_id
+                AND p.market_id = cancels.market_id))
+    ON CONFLICT
+        DO NOTHING
+)
+SELECT
+    COUNT(*) AS "cancelled!"
 FROM
-    parameters,
-    cancel_order_events
-WHERE
-    cancel_order_events.txn_version > max_txn_version
-    AND user_history.order_id = cancel_order_events.order_id
-    AND user_history.market_id = cancel_order_events.market_id;
-
+    cancelled;
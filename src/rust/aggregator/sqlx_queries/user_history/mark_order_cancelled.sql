@@ -0,0 +1,36 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS order_market_id,
+        $2::numeric AS order_order_id,
+        $3::numeric AS max_txn_version)
+UPDATE
+    aggregator.user_history AS user_history
+SET
+    order_status = 'cancelled',
+    close_reason = CASE cancel_order_events.reason
+    -- CANCEL_REASON_IMMEDIATE_OR_CANCEL
+    WHEN 2 THEN
+        'ioc_expired'::order_close_reason
+    ELSE
+        'cancelled'::order_close_reason
+    END,
+    last_updated_at = cancel_order_events."time"
+FROM (
+    -- An order cancelled more than once is cancelled by its earliest cancel
+    -- event.
+    SELECT
+        cancel_order_events.*
+    FROM
+        parameters,
+        cancel_order_events
+    WHERE
+        cancel_order_events.market_id = order_market_id
+        AND cancel_order_events.order_id = order_order_id
+        AND cancel_order_events.txn_version <= max_txn_version
+    ORDER BY
+        txn_version,
+        event_idx
+    LIMIT 1) AS cancel_order_events
+WHERE
+    user_history.order_id = cancel_order_events.order_id
+    AND user_history.market_id = cancel_order_events.market_id;
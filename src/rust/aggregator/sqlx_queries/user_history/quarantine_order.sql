@@ -0,0 +1,21 @@
+WITH parameters AS (
+    SELECT
+        $1::numeric AS order_market_id,
+        $2::numeric AS order_order_id),
+moved AS (
+    DELETE FROM
+        aggregator.user_history AS user_history
+    USING
+        parameters
+    WHERE
+        market_id = order_market_id
+        AND order_id = order_order_id
+    RETURNING
+        user_history.*
+)
+INSERT INTO aggregator.orphaned_orders
+SELECT
+    *
+FROM
+    moved
+ON CONFLICT DO NOTHING
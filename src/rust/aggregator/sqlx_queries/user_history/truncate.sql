@@ -0,0 +1,7 @@
+TRUNCATE
+    aggregator.user_history,
+    aggregator.user_history_last_indexed_txn,
+    aggregator.pending_cancels,
+    aggregator.order_size_changes,
+    aggregator.pending_fills,
+    aggregator.pending_size_changes
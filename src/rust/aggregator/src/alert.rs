@@ -0,0 +1,211 @@
+//! Alerts sent to a webhook when a pipeline keeps failing, and when it recovers.
+//!
+//! Alerts are a JSON object POSTed to the webhook URL:
+//!
+//! ```json
+//! {"kind": "failing", "model_name": "UserHistory", "error": "...", "consecutive_errors": 5}
+//! ```
+//!
+//! `kind` is `failing` or `recovered`, and a recovery carries no `error`. Delivery is best-effort:
+//! an alert that cannot be delivered is logged and dropped, so that a webhook outage never slows
+//! down or stops the pipelines.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// Default number of consecutive failed runs after which a pipeline is reported as failing.
+///
+/// The runner gives up on a pipeline after its fourth failed batch in a row, so a higher
+/// threshold would only be reached by connection errors, which are retried forever.
+pub const DEFAULT_THRESHOLD: u32 = 3;
+/// Minimum time between two failing alerts of the same pipeline while it keeps failing.
+pub const REPEAT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// How long to wait for the webhook to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum AlertKind {
+    Failing,
+    Recovered,
+}
+
+#[derive(Debug, Serialize)]
+struct Alert<'a> {
+    kind: AlertKind,
+    model_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+    consecutive_errors: u32,
+}
+
+/// Sends alerts about the pipelines to a webhook, shared between the pipeline tasks.
+#[derive(Clone, Debug)]
+pub struct Alerter {
+    client: reqwest::Client,
+    url: String,
+    threshold: u32,
+    /// When the last failing alert of each pipeline was sent, for pipelines currently reported
+    /// as failing.
+    failing: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl Alerter {
+    /// Creates an alerter reporting a pipeline to `url` once it failed `threshold` runs in a row.
+    pub fn new(url: String, threshold: u32) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            threshold: threshold.max(1),
+            failing: Default::default(),
+        }
+    }
+
+    /// Records that a run of `pipeline` failed with `error`, its `consecutive_errors`th failure
+    /// in a row, and alerts if it crossed the threshold. While the pipeline keeps failing, the
+    /// alert is repeated at most every [`REPEAT_INTERVAL`].
+    pub fn record_error(&self, pipeline: &str, error: &str, consecutive_errors: u32) {
+        if consecutive_errors < self.threshold {
+            return;
+        }
+        {
+            let mut failing = self.failing.lock().unwrap();
+            let now = Instant::now();
+            match failing.get(pipeline) {
+                Some(sent_at) if now.duration_since(*sent_at) < REPEAT_INTERVAL => return,
+                _ => failing.insert(pipeline.to_string(), now),
+            };
+        }
+        self.send(Alert {
+            kind: AlertKind::Failing,
+            model_name: pipeline,
+            error: Some(error),
+            consecutive_errors,
+        });
+    }
+
+    /// Records that a run of `pipeline` succeeded, and sends a recovery alert if it was reported
+    /// as failing.
+    pub fn record_success(&self, pipeline: &str) {
+        if self.failing.lock().unwrap().remove(pipeline).is_none() {
+            return;
+        }
+        self.send(Alert {
+            kind: AlertKind::Recovered,
+            model_name: pipeline,
+            error: None,
+            consecutive_errors: 0,
+        });
+    }
+
+    /// POSTs `alert` in the background, logging it if it could not be delivered.
+    fn send(&self, alert: Alert) {
+        let body = match serde_json::to_string(&alert) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!(error = %e, "Could not serialize alert.");
+                return;
+            }
+        };
+        let request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .timeout(TIMEOUT)
+            .body(body.clone());
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => tracing::info!(alert = body, "Sent alert."),
+                Err(e) => tracing::warn!(error = %e, alert = body, "Could not send alert."),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use super::*;
+
+    /// Starts a webhook answering every request with a 200, and returns its URL and the bodies
+    /// it received.
+    async fn webhook() -> (String, mpsc::UnboundedReceiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                let body = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length: usize = head
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse().unwrap())
+                        })
+                        .unwrap();
+                    if body.len() >= length {
+                        break body.to_string();
+                    }
+                };
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+                sender.send(serde_json::from_str(&body).unwrap()).unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    #[tokio::test]
+    async fn alerts_once_the_threshold_is_crossed_and_on_recovery() {
+        let (url, mut alerts) = webhook().await;
+        let alerter = Alerter::new(url, 3);
+        alerter.record_error("Prices", "timeout", 1);
+        alerter.record_error("Prices", "timeout", 2);
+        alerter.record_error("Prices", "deadlock", 3);
+        assert_eq!(
+            alerts.recv().await.unwrap(),
+            json!({
+                "kind": "failing",
+                "model_name": "Prices",
+                "error": "deadlock",
+                "consecutive_errors": 3,
+            })
+        );
+
+        // Rate-limited until the pipeline recovers.
+        alerter.record_error("Prices", "deadlock", 4);
+        alerter.record_success("Prices");
+        assert_eq!(
+            alerts.recv().await.unwrap(),
+            json!({ "kind": "recovered", "model_name": "Prices", "consecutive_errors": 0 })
+        );
+
+        // Successes of a pipeline that was not reported are not alerted about.
+        alerter.record_success("Prices");
+        alerter.record_error("UserHistory", "timeout", 3);
+        assert_eq!(alerts.recv().await.unwrap()["model_name"], "UserHistory");
+    }
+}
@@ -0,0 +1,108 @@
+//! Arithmetic on on-chain amounts (sizes, prices, volumes) stored as [`BigDecimal`].
+//!
+//! On-chain amounts are integers, but the scale of a [`BigDecimal`] is carried through
+//! arithmetic, so results can end up with trailing zeros (`5.00` instead of `5`). These helpers
+//! keep results at the smallest scale that represents them.
+
+use bigdecimal::{BigDecimal, Signed, Zero};
+
+/// Returns `amount` with trailing fractional zeros removed, so that equal amounts have the same
+/// representation.
+pub fn normalize(amount: &BigDecimal) -> BigDecimal {
+    amount.normalized()
+}
+
+/// Returns `a - b`, or `None` if the result would be negative.
+pub fn checked_sub(a: &BigDecimal, b: &BigDecimal) -> Option<BigDecimal> {
+    let difference = a - b;
+    if difference.is_negative() {
+        None
+    } else {
+        Some(normalize(&difference))
+    }
+}
+
+/// Returns `a / b` rounded down to an integer (e.g. to convert an amount to a number of lots),
+/// or `None` if `b` is zero.
+pub fn floor_div(a: &BigDecimal, b: &BigDecimal) -> Option<BigDecimal> {
+    if b.is_zero() {
+        return None;
+    }
+    // `with_scale` truncates towards zero, which is one too high for inexact negative quotients.
+    let quotient = (a / b).with_scale(0);
+    if a.is_negative() != b.is_negative() && !(a % b).is_zero() {
+        Some(normalize(&(quotient - BigDecimal::from(1))))
+    } else {
+        Some(normalize(&quotient))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn amount(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn normalize_drops_trailing_zeros() {
+        assert_eq!(normalize(&amount("5.00")).to_string(), "5");
+        assert_eq!(normalize(&amount("5.50")).to_string(), "5.5");
+        assert_eq!(normalize(&amount("0.000")).to_string(), "0");
+        assert_eq!(normalize(&amount("5.00")), normalize(&amount("5")));
+    }
+
+    #[test]
+    fn normalize_keeps_integer_zeros() {
+        assert_eq!(normalize(&amount("100")).to_string(), "100");
+        assert_eq!(normalize(&amount("100.0")), normalize(&amount("100")));
+    }
+
+    #[test]
+    fn checked_sub_underflow() {
+        assert_eq!(checked_sub(&amount("5"), &amount("3")), Some(amount("2")));
+        assert_eq!(checked_sub(&amount("5"), &amount("5")), Some(amount("0")));
+        assert_eq!(checked_sub(&amount("5"), &amount("5.01")), None);
+        assert_eq!(checked_sub(&amount("0"), &amount("1")), None);
+    }
+
+    #[test]
+    fn checked_sub_normalizes() {
+        let difference = checked_sub(&amount("5.00"), &amount("2")).unwrap();
+        assert_eq!(difference.to_string(), "3");
+    }
+
+    #[test]
+    fn floor_div_rounds_down() {
+        assert_eq!(floor_div(&amount("7"), &amount("2")), Some(amount("3")));
+        assert_eq!(floor_div(&amount("6"), &amount("2")), Some(amount("3")));
+        assert_eq!(floor_div(&amount("1"), &amount("3")), Some(amount("0")));
+        assert_eq!(
+            floor_div(&amount("7.5"), &amount("0.5")),
+            Some(amount("15"))
+        );
+    }
+
+    #[test]
+    fn floor_div_rounds_negative_quotients_down() {
+        assert_eq!(floor_div(&amount("-7"), &amount("2")), Some(amount("-4")));
+        assert_eq!(floor_div(&amount("7"), &amount("-2")), Some(amount("-4")));
+        assert_eq!(floor_div(&amount("-6"), &amount("2")), Some(amount("-3")));
+        assert_eq!(floor_div(&amount("-7"), &amount("-2")), Some(amount("3")));
+    }
+
+    #[test]
+    fn floor_div_by_zero() {
+        assert_eq!(floor_div(&amount("7"), &amount("0")), None);
+        assert_eq!(floor_div(&amount("0"), &amount("0.00")), None);
+    }
+
+    #[test]
+    fn floor_div_normalizes() {
+        let quotient = floor_div(&amount("10.00"), &amount("2")).unwrap();
+        assert_eq!(quotient.to_string(), "5");
+    }
+}
@@ -0,0 +1,3265 @@
+//! Tests of the SQL functions behind the REST API, run against the database of `DATABASE_URL`
+//! (see [`crate::test_db`]).
+
+use sqlx::{types::BigDecimal, PgConnection};
+
+use crate::test_db;
+
+mod match_fills_pnl {
+    use super::*;
+
+    /// Matches `fills`, pairs of a signed size and a price, and returns the realized P&L, the
+    /// open position and its cost basis.
+    async fn pnl(
+        conn: &mut PgConnection,
+        fills: &[(i64, i64)],
+        method: &str,
+    ) -> Result<(i64, i64, i64), sqlx::Error> {
+        let (sizes, prices): (Vec<i64>, Vec<i64>) = fills.iter().copied().unzip();
+        test_db::as_web_anon(conn).await;
+        let (realized_pnl, position, cost_basis): (BigDecimal, BigDecimal, BigDecimal) =
+            sqlx::query_as(
+                r#"SELECT realized_pnl, "position", cost_basis
+                FROM match_fills_pnl($1::numeric[], $2::numeric[], $3)"#,
+            )
+            .bind(sizes)
+            .bind(prices)
+            .bind(method)
+            .fetch_one(conn)
+            .await?;
+        Ok((to_i64(realized_pnl), to_i64(position), to_i64(cost_basis)))
+    }
+
+    fn to_i64(x: BigDecimal) -> i64 {
+        assert!(x.is_integer(), "{x} is not an integer");
+        x.with_scale(0).to_string().parse().unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn partial_match() {
+        let mut tx = test_db::begin().await;
+        for method in ["fifo", "avg"] {
+            let fills = [(10, 100), (-4, 110)];
+            assert_eq!(pnl(&mut tx, &fills, method).await.unwrap(), (40, 6, 600));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn partial_match_across_fills() {
+        let mut tx = test_db::begin().await;
+        let fills = [(10, 100), (10, 120), (-15, 130)];
+        // The oldest fill is closed first: 10 * (130 - 100) + 5 * (130 - 120).
+        assert_eq!(pnl(&mut tx, &fills, "fifo").await.unwrap(), (350, 5, 600));
+        // Against the average price of 110: 15 * (130 - 110).
+        assert_eq!(pnl(&mut tx, &fills, "avg").await.unwrap(), (300, 5, 550));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn closing_a_position() {
+        let mut tx = test_db::begin().await;
+        for method in ["fifo", "avg"] {
+            let long = [(10, 100), (-10, 90)];
+            assert_eq!(pnl(&mut tx, &long, method).await.unwrap(), (-100, 0, 0));
+            let short = [(-10, 100), (4, 90), (6, 80)];
+            assert_eq!(pnl(&mut tx, &short, method).await.unwrap(), (160, 0, 0));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn flipping_direction() {
+        let mut tx = test_db::begin().await;
+        for method in ["fifo", "avg"] {
+            // The sell closes the long position and opens a short one of 5 at 120.
+            let fills = [(10, 100), (-15, 120)];
+            assert_eq!(pnl(&mut tx, &fills, method).await.unwrap(), (200, -5, 600));
+            // Which the next buy closes at a profit of 5 * (120 - 110).
+            let fills = [(10, 100), (-15, 120), (5, 110)];
+            assert_eq!(pnl(&mut tx, &fills, method).await.unwrap(), (250, 0, 0));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn no_fills() {
+        let mut tx = test_db::begin().await;
+        for method in ["fifo", "avg"] {
+            assert_eq!(pnl(&mut tx, &[], method).await.unwrap(), (0, 0, 0));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_method() {
+        let mut tx = test_db::begin().await;
+        let result = pnl(&mut tx, &[(1, 1)], "lifo").await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("22023"));
+    }
+}
+
+mod registered_market {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Returns the tick size of the market, and whether `api.require_registered_market` passes.
+    async fn registered_market(conn: &mut PgConnection) -> Result<(i64, bool), sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT m.tick_size::int8, require_registered_market($1) \
+             FROM registered_market($1) AS m",
+        )
+        .bind(MARKET_ID)
+        .fetch_one(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn registered_market_is_returned() {
+        let mut tx = test_db::begin().await;
+        insert(
+            &mut tx,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID, "tick_size": 7 }),
+        )
+        .await;
+        assert_eq!(registered_market(&mut tx).await.unwrap(), (7, true));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_market_is_not_found() {
+        let mut tx = test_db::begin().await;
+        let result = registered_market(&mut tx).await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("PT404"));
+    }
+}
+
+mod trades {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Registers [`MARKET_ID`] and records trades at `(100, 0)`, `(100, 2)` and `(200, 0)`, each
+    /// emitted to the maker at that event index and to the taker at the next one.
+    async fn seed(conn: &mut PgConnection) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        for (txn_version, event_idx) in [(100, 0), (100, 2), (200, 0)] {
+            for (event_idx, emit_address) in [(event_idx, "0xa"), (event_idx + 1, "0xb")] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": txn_version,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "market_id": MARKET_ID,
+                        "maker_address": "0xa",
+                        "maker_order_id": 1,
+                        "maker_side": true,
+                        "taker_address": "0xb",
+                        "taker_order_id": 2,
+                        "price": 10,
+                        "size": 1,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Returns the `(txn_version, event_idx)` of the trades after the cursor, newest first.
+    async fn trades(
+        conn: &mut PgConnection,
+        after_txn_version: Option<i64>,
+        after_event_idx: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<(i64, i64)>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT txn_version::int8, event_idx::int8 \
+             FROM trades($1, $2::numeric, $3::numeric) \
+             LIMIT $4",
+        )
+        .bind(MARKET_ID)
+        .bind(after_txn_version)
+        .bind(after_event_idx)
+        .bind(limit)
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn keeps_the_maker_copy_of_each_fill() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            trades(&mut tx, None, None, 10).await.unwrap(),
+            [(200, 0), (100, 2), (100, 0)]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn side_is_that_of_the_taker() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        // A taker selling to a bid maker.
+        insert(
+            &mut tx,
+            "fill_events",
+            json!({
+                "txn_version": 300,
+                "event_idx": 0,
+                "emit_address": "0xa",
+                "market_id": MARKET_ID,
+                "maker_address": "0xa",
+                "maker_order_id": 3,
+                "maker_side": false,
+                "taker_address": "0xb",
+                "taker_order_id": 4,
+                "price": 10,
+                "size": 1,
+                "taker_quote_fees_paid": 0,
+            }),
+        )
+        .await;
+        test_db::as_web_anon(&mut tx).await;
+        let sides: Vec<(i64, String)> =
+            sqlx::query_as("SELECT txn_version::int8, side FROM trades($1) LIMIT 2")
+                .bind(MARKET_ID)
+                .fetch_all(&mut *tx)
+                .await
+                .unwrap();
+        assert_eq!(sides, [(300, "sell".to_string()), (200, "buy".to_string())]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn cursor_is_exclusive() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            trades(&mut tx, Some(200), Some(0), 10).await.unwrap(),
+            [(100, 2), (100, 0)]
+        );
+        assert_eq!(
+            trades(&mut tx, Some(100), Some(2), 10).await.unwrap(),
+            [(100, 0)]
+        );
+        assert!(trades(&mut tx, Some(100), Some(0), 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn missing_event_idx_skips_the_whole_transaction() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert!(trades(&mut tx, Some(100), None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            trades(&mut tx, Some(200), None, 10).await.unwrap(),
+            [(100, 2), (100, 0)]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn paging_returns_every_trade_once() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        let mut pages = vec![];
+        let mut cursor = (None, None);
+        loop {
+            let page = trades(&mut tx, cursor.0, cursor.1, 1).await.unwrap();
+            let Some(&(txn_version, event_idx)) = page.last() else {
+                break;
+            };
+            cursor = (Some(txn_version), Some(event_idx));
+            pages.extend(page);
+        }
+        assert_eq!(pages, [(200, 0), (100, 2), (100, 0)]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_market_is_not_found() {
+        let mut tx = test_db::begin().await;
+        let result = trades(&mut tx, None, None, 10).await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("PT404"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn limit_is_applied_to_the_fills() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        test_db::as_web_anon(&mut tx).await;
+        let plan: Vec<String> = sqlx::query_scalar("EXPLAIN SELECT * FROM trades($1) LIMIT 1")
+            .bind(MARKET_ID)
+            .fetch_all(&mut *tx)
+            .await
+            .unwrap();
+        // An inlined function scans `fill_events` itself instead of returning every trade.
+        assert!(
+            plan.iter().any(|line| line.contains("on fill_events")),
+            "{plan:#?}"
+        );
+        assert!(
+            !plan.iter().any(|line| line.contains("Function Scan")),
+            "{plan:#?}"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn event_idx_requires_txn_version() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        let result = trades(&mut tx, None, Some(0), 10).await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("22023"));
+    }
+
+    /// Returns the `(txn_version, event_idx)` of the trades older than `before_time`, newest
+    /// first.
+    async fn trades_before(
+        conn: &mut PgConnection,
+        before_time: &str,
+        limit: i64,
+    ) -> Result<Vec<(i64, i64)>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT txn_version::int8, event_idx::int8 \
+             FROM trades($1, before_time => $2::timestamptz) \
+             LIMIT $3",
+        )
+        .bind(MARKET_ID)
+        .bind(before_time)
+        .bind(limit)
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn time_cursor_pages_across_a_tie() {
+        let mut tx = test_db::begin().await;
+        // The trades of the seed share the default time, a day before this one.
+        seed(&mut tx).await;
+        insert(
+            &mut tx,
+            "fill_events",
+            json!({
+                "txn_version": 300,
+                "event_idx": 0,
+                "emit_address": "0xa",
+                "time": "2024-01-02T00:00:00Z",
+                "market_id": MARKET_ID,
+                "maker_address": "0xa",
+                "maker_order_id": 1,
+                "maker_side": true,
+                "taker_address": "0xb",
+                "taker_order_id": 2,
+                "price": 10,
+                "size": 1,
+                "taker_quote_fees_paid": 0,
+            }),
+        )
+        .await;
+        assert_eq!(
+            trades_before(&mut tx, "2024-01-02T00:00:00Z", 10)
+                .await
+                .unwrap(),
+            [(200, 0), (100, 2), (100, 0)]
+        );
+        // A page ending inside the tie is resumed with the version cursor.
+        let page = trades_before(&mut tx, "2024-01-03T00:00:00Z", 2)
+            .await
+            .unwrap();
+        assert_eq!(page, [(300, 0), (200, 0)]);
+        let (txn_version, event_idx) = page[1];
+        assert_eq!(
+            trades(&mut tx, Some(txn_version), Some(event_idx), 2)
+                .await
+                .unwrap(),
+            [(100, 2), (100, 0)]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn time_and_version_cursors_are_exclusive() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        test_db::as_web_anon(&mut tx).await;
+        let result = sqlx::query("SELECT * FROM trades($1, 200, 0, '2024-01-02')")
+            .bind(MARKET_ID)
+            .execute(&mut *tx)
+            .await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("22023"));
+    }
+}
+
+mod get_order {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert_order, MARKET_ID};
+
+    /// Returns the IDs of the orders `api.get_order` returns for `order_id`, of [`MARKET_ID`]
+    /// unless `market_id` is given. IDs are passed as text, like PostgREST passes JSON values.
+    async fn get_order(
+        conn: &mut PgConnection,
+        market_id: Option<&str>,
+        order_id: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_scalar("SELECT order_id::text FROM get_order($1::numeric, $2::numeric)")
+            .bind(market_id.map_or(MARKET_ID.to_string(), String::from))
+            .bind(order_id)
+            .fetch_all(conn)
+            .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn present_order() {
+        let mut tx = test_db::begin().await;
+        insert_order(&mut tx, json!({ "order_id": 1 })).await;
+        assert_eq!(get_order(&mut tx, None, "1").await.unwrap(), ["1"]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unset_swap_bounds_are_null() {
+        let mut tx = test_db::begin().await;
+        insert_order(
+            &mut tx,
+            json!({
+                "order_id": 1,
+                "order_type": "swap",
+                "direction": "buy",
+                "price": null,
+                "min_base": 0,
+                "max_base": null,
+                "min_quote": 0,
+                "max_quote": 100,
+            }),
+        )
+        .await;
+        test_db::as_web_anon(&mut tx).await;
+        let order: serde_json::Value =
+            sqlx::query_scalar("SELECT to_jsonb(o) FROM get_order($1, 1) AS o")
+                .bind(MARKET_ID)
+                .fetch_one(&mut *tx)
+                .await
+                .unwrap();
+        assert_eq!(order.get("price"), Some(&json!(null)));
+        assert_eq!(order.get("max_base"), Some(&json!(null)));
+        assert_eq!(order["min_base"], 0);
+        assert_eq!(order["max_quote"], 100);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn pruned_order_is_gone() {
+        let mut tx = test_db::begin().await;
+        insert_order(
+            &mut tx,
+            json!({ "order_id": 1, "order_status": "closed", "remaining_size": 0 }),
+        )
+        .await;
+        let pruned: i64 =
+            sqlx::query_scalar("SELECT aggregator.prune_user_history('2024-01-02T00:00:00+00:00')")
+                .fetch_one(&mut *tx)
+                .await
+                .unwrap();
+        assert_eq!(pruned, 1);
+        let result = get_order(&mut tx, None, "1").await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("PT410"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_order_is_not_found() {
+        let mut tx = test_db::begin().await;
+        let result = get_order(&mut tx, None, "1").await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("PT404"));
+    }
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn ids_are_parsed_exactly() {
+        let mut tx = test_db::begin().await;
+        let max_order_id = u128::MAX.to_string();
+        insert_order(&mut tx, json!({ "order_id": max_order_id })).await;
+        assert_eq!(
+            get_order(&mut tx, None, &max_order_id).await.unwrap(),
+            [max_order_id]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn integer_valued_ids_are_accepted() {
+        let mut tx = test_db::begin().await;
+        insert_order(&mut tx, json!({ "order_id": 1 })).await;
+        assert_eq!(get_order(&mut tx, None, "1.0").await.unwrap(), ["1"]);
+        assert_eq!(get_order(&mut tx, None, "1e0").await.unwrap(), ["1"]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn fractional_and_negative_ids_are_rejected() {
+        for (market_id, order_id) in [
+            (None, "1.5"),
+            (None, "-1"),
+            (Some("999999.5"), "1"),
+            (Some("-999999"), "1"),
+        ] {
+            let mut tx = test_db::begin().await;
+            insert_order(&mut tx, json!({ "order_id": 1 })).await;
+            let result = get_order(&mut tx, market_id, order_id).await;
+            assert_eq!(
+                test_db::sqlstate(result).as_deref(),
+                Some("22023"),
+                "{market_id:?} {order_id}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn fractional_ids_are_rejected_in_batches() {
+        let mut tx = test_db::begin().await;
+        test_db::as_web_anon(&mut tx).await;
+        let result = sqlx::query("SELECT get_orders($1, $2::numeric[])")
+            .bind(MARKET_ID)
+            .bind(["1", "1.5"])
+            .fetch_one(&mut *tx)
+            .await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("22023"));
+    }
+}
+
+mod open_orders {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, insert_order, MARKET_ID};
+
+    /// The priority stamp of an order placed or grown at `(txn_version, 0)`.
+    fn stamp(txn_version: u128) -> String {
+        (txn_version << 64).to_string()
+    }
+
+    /// Records open limit orders at prices 9 and 10, placed in an order that differs from their
+    /// queue order, a closed order and a market order.
+    async fn seed(conn: &mut PgConnection) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        // Order 4 was placed first but grew after orders 1 and 3 were placed.
+        for (order_id, txn_version, price, order_status, last_increase_stamp) in [
+            (1, 100, 10, "open", None),
+            (2, 101, 9, "open", None),
+            (3, 102, 10, "open", None),
+            (4, 99, 10, "open", Some(stamp(103))),
+            (5, 104, 8, "closed", None),
+        ] {
+            insert(
+                conn,
+                "place_limit_order_events",
+                json!({
+                    "txn_version": txn_version,
+                    "market_id": MARKET_ID,
+                    "user": "0xa",
+                    "order_id": order_id,
+                    "side": false,
+                    "initial_size": 1,
+                    "price": price,
+                    "size": 1,
+                }),
+            )
+            .await;
+            insert_order(
+                conn,
+                json!({
+                    "order_id": order_id,
+                    "price": price,
+                    "order_status": order_status,
+                    "last_increase_stamp": last_increase_stamp,
+                }),
+            )
+            .await;
+        }
+        insert_order(conn, json!({ "order_id": 6, "order_type": "market" })).await;
+    }
+
+    /// Returns the `(order_id, price, priority_stamp)` of the open orders after the cursor.
+    async fn open_orders(
+        conn: &mut PgConnection,
+        after: Option<(i64, String)>,
+        max_orders: i32,
+    ) -> Result<Vec<(i64, i64, String)>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        let (after_price, after_stamp) = after.unzip();
+        sqlx::query_as(
+            "SELECT order_id::int8, price::int8, priority_stamp::text \
+             FROM open_orders($1, $2, $3::numeric, $4)",
+        )
+        .bind(MARKET_ID)
+        .bind(after_price)
+        .bind(after_stamp)
+        .bind(max_orders)
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn ordered_by_price_then_priority() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            open_orders(&mut tx, None, 1000).await.unwrap(),
+            [
+                (2, 9, stamp(101)),
+                (1, 10, stamp(100)),
+                (3, 10, stamp(102)),
+                (4, 10, stamp(103)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn paging_across_a_price_level() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        let first = open_orders(&mut tx, None, 2).await.unwrap();
+        assert_eq!(first, [(2, 9, stamp(101)), (1, 10, stamp(100))]);
+        let (_, price, last_stamp) = first.last().unwrap().clone();
+        assert_eq!(
+            open_orders(&mut tx, Some((price, last_stamp)), 2)
+                .await
+                .unwrap(),
+            [(3, 10, stamp(102)), (4, 10, stamp(103))]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_market_is_not_found() {
+        let mut tx = test_db::begin().await;
+        let result = open_orders(&mut tx, None, 10).await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("PT404"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn cursor_needs_both_parameters() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        test_db::as_web_anon(&mut tx).await;
+        let result = sqlx::query("SELECT * FROM open_orders($1, after_price => 10)")
+            .bind(MARKET_ID)
+            .fetch_all(&mut *tx)
+            .await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("22023"));
+    }
+}
+
+mod get_orders {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::test_db::{insert_order, MARKET_ID};
+
+    /// Returns the response of `api.get_orders` for `order_ids`, with only the IDs of the orders.
+    async fn get_orders(conn: &mut PgConnection, order_ids: &[i64]) -> Value {
+        test_db::as_web_anon(conn).await;
+        let mut response: Value = sqlx::query_scalar("SELECT get_orders($1, $2)::jsonb")
+            .bind(MARKET_ID)
+            .bind(order_ids)
+            .fetch_one(conn)
+            .await
+            .unwrap();
+        for order in response["orders"].as_array_mut().unwrap() {
+            *order = order["order_id"].clone();
+        }
+        response
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn found_pruned_and_missing_orders() {
+        let mut tx = test_db::begin().await;
+        for order_id in [1, 2] {
+            insert_order(&mut tx, json!({ "order_id": order_id })).await;
+        }
+        insert_order(
+            &mut tx,
+            json!({ "order_id": 3, "order_status": "closed", "remaining_size": 0 }),
+        )
+        .await;
+        sqlx::query("SELECT aggregator.prune_user_history('2024-01-02T00:00:00+00:00')")
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(
+            get_orders(&mut tx, &[4, 2, 3, 1, 5, 2]).await,
+            json!({ "orders": [1, 2], "pruned": [3], "not_found": [4, 5] })
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn no_orders() {
+        let mut tx = test_db::begin().await;
+        assert_eq!(
+            get_orders(&mut tx, &[]).await,
+            json!({ "orders": [], "pruned": [], "not_found": [] })
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn at_most_200_orders() {
+        let mut tx = test_db::begin().await;
+        test_db::as_web_anon(&mut tx).await;
+        let order_ids: Vec<i64> = (1..=201).collect();
+        let result = sqlx::query("SELECT get_orders($1, $2)")
+            .bind(MARKET_ID)
+            .bind(&order_ids)
+            .fetch_one(&mut *tx)
+            .await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("22023"));
+    }
+}
+
+mod run_pipeline {
+    use sqlx::Executor;
+
+    use super::*;
+
+    /// Registers the `Test` pipeline, sets the admin secret to `secret` and sends the
+    /// `X-Admin-Secret` header `header`, if any, then requests a run of `model_name`.
+    async fn run_pipeline(
+        conn: &mut PgConnection,
+        header: Option<&str>,
+        model_name: &str,
+    ) -> Result<i64, sqlx::Error> {
+        conn.execute(
+            "INSERT INTO aggregator.pipelines (model_name) VALUES ('Test'); \
+             SET LOCAL app.settings.admin_secret TO 'secret'",
+        )
+        .await
+        .unwrap();
+        let headers = match header {
+            Some(header) => serde_json::json!({ "x-admin-secret": header }),
+            None => serde_json::json!({}),
+        };
+        sqlx::query("SELECT set_config('request.headers', $1, true)")
+            .bind(headers.to_string())
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        test_db::as_web_anon(conn).await;
+        sqlx::query_scalar("SELECT run_pipeline($1)")
+            .bind(model_name)
+            .fetch_one(conn)
+            .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn run_is_requested() {
+        let mut tx = test_db::begin().await;
+        let id = run_pipeline(&mut tx, Some("secret"), "Test").await.unwrap();
+        let run: (String, bool, bool) = sqlx::query_as(
+            "SELECT model_name, started_at IS NULL, failed FROM pipeline_runs WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap();
+        assert_eq!(run, ("Test".to_string(), true, false));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn errors_are_not_exposed() {
+        let mut tx = test_db::begin().await;
+        test_db::as_web_anon(&mut tx).await;
+        let result = sqlx::query("SELECT error FROM pipeline_runs")
+            .execute(&mut *tx)
+            .await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("42703"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_pipeline_is_not_found() {
+        let mut tx = test_db::begin().await;
+        let result = run_pipeline(&mut tx, Some("secret"), "Unknown").await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("PT404"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn missing_or_wrong_secret_is_unauthorized() {
+        for header in [None, Some("wrong")] {
+            let mut tx = test_db::begin().await;
+            let result = run_pipeline(&mut tx, header, "Test").await;
+            assert_eq!(
+                test_db::sqlstate(result).as_deref(),
+                Some("PT401"),
+                "{header:?}"
+            );
+        }
+    }
+}
+
+mod market_daily_stats {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// A day of [`market_daily_stats`]: date, trade count, base and quote volumes, VWAP and unique
+    /// traders.
+    type Day = (String, i64, i64, i64, i64, i64);
+
+    /// Registers [`MARKET_ID`] and records fills of `(time, price, size, taker)`, each emitted to
+    /// the maker and the taker.
+    async fn seed(conn: &mut PgConnection, fills: &[(&str, i64, i64, &str)]) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        for (i, (time, price, size, taker)) in fills.iter().enumerate() {
+            for (event_idx, emit_address) in [(0, "0xa"), (1, *taker)] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": 100 + i,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "time": time,
+                        "market_id": MARKET_ID,
+                        "maker_address": "0xa",
+                        "maker_order_id": 1,
+                        "maker_side": true,
+                        "taker_address": taker,
+                        "taker_order_id": 2,
+                        "price": price,
+                        "size": size,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn market_daily_stats(
+        conn: &mut PgConnection,
+        start_time: &str,
+        end_time: &str,
+        fill_gaps: bool,
+        tz: &str,
+    ) -> Result<Vec<Day>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT \"date\"::text, trade_count, base_volume::int8, quote_volume::int8, \
+             vwap::int8, unique_traders \
+             FROM market_daily_stats($1, $2::timestamptz, $3::timestamptz, $4, $5)",
+        )
+        .bind(MARKET_ID)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(fill_gaps)
+        .bind(tz)
+        .fetch_all(conn)
+        .await
+    }
+
+    fn day(date: &str, [trades, base, quote, vwap, traders]: [i64; 5]) -> Day {
+        (date.to_string(), trades, base, quote, vwap, traders)
+    }
+
+    const FILLS: [(&str, i64, i64, &str); 3] = [
+        ("2024-01-01T10:00:00Z", 5, 2, "0xb"),
+        ("2024-01-01T11:00:00Z", 8, 1, "0xc"),
+        ("2024-01-03T10:00:00Z", 5, 4, "0xb"),
+    ];
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn only_days_with_fills_without_fill_gaps() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, &FILLS).await;
+        assert_eq!(
+            market_daily_stats(
+                &mut tx,
+                "2024-01-01T00:00:00Z",
+                "2024-01-04T00:00:00Z",
+                false,
+                "UTC"
+            )
+            .await
+            .unwrap(),
+            [
+                day("2024-01-01", [2, 3, 18, 6, 2]),
+                day("2024-01-03", [1, 4, 20, 5, 1]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn every_day_with_fill_gaps() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, &FILLS).await;
+        assert_eq!(
+            market_daily_stats(
+                &mut tx,
+                "2024-01-01T00:00:00Z",
+                "2024-01-04T00:00:00Z",
+                true,
+                "UTC"
+            )
+            .await
+            .unwrap(),
+            [
+                day("2024-01-01", [2, 3, 18, 6, 2]),
+                day("2024-01-02", [0, 0, 0, 0, 0]),
+                day("2024-01-03", [1, 4, 20, 5, 1]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn over_broad_window_is_unprocessable() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, &FILLS).await;
+        // 10001 days.
+        let result = market_daily_stats(&mut tx, "2000-01-01", "2027-05-20", false, "UTC").await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("PT422"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn days_follow_the_clock_of_the_time_zone_across_dst() {
+        let mut tx = test_db::begin().await;
+        // New York switched to daylight saving time at 07:00 UTC on 2024-03-10, which made that
+        // day last from 05:00 UTC to 04:00 UTC the next day.
+        seed(
+            &mut tx,
+            &[
+                ("2024-03-10T04:30:00Z", 5, 1, "0xb"),
+                ("2024-03-10T05:30:00Z", 5, 1, "0xb"),
+                ("2024-03-11T03:30:00Z", 5, 1, "0xb"),
+                ("2024-03-11T04:30:00Z", 5, 1, "0xb"),
+            ],
+        )
+        .await;
+        for (tz, expected) in [
+            (
+                "America/New_York",
+                vec![
+                    day("2024-03-09", [1, 1, 5, 5, 1]),
+                    day("2024-03-10", [2, 2, 10, 5, 1]),
+                    day("2024-03-11", [1, 1, 5, 5, 1]),
+                ],
+            ),
+            (
+                "UTC",
+                vec![
+                    day("2024-03-10", [2, 2, 10, 5, 1]),
+                    day("2024-03-11", [2, 2, 10, 5, 1]),
+                ],
+            ),
+        ] {
+            assert_eq!(
+                market_daily_stats(
+                    &mut tx,
+                    "2024-03-09T00:00:00Z",
+                    "2024-03-12T00:00:00Z",
+                    false,
+                    tz
+                )
+                .await
+                .unwrap(),
+                expected,
+                "{tz}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_time_zone_is_a_bad_request() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, &FILLS).await;
+        let result = market_daily_stats(
+            &mut tx,
+            "2024-01-01T00:00:00Z",
+            "2024-01-04T00:00:00Z",
+            false,
+            "Mars/Olympus_Mons",
+        )
+        .await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("22023"));
+    }
+}
+
+mod order_events {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// The transaction version, type, size, price and cancel reason of an event.
+    type Event = (i64, String, Option<i64>, Option<i64>, Option<i16>);
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn timeline_of_an_order() {
+        let mut tx = test_db::begin().await;
+        // Events of order 1, and a placement of order 2 which is left out.
+        for order_id in [1, 2] {
+            insert(
+                &mut tx,
+                "place_limit_order_events",
+                json!({
+                    "txn_version": 100,
+                    "event_idx": order_id,
+                    "market_id": MARKET_ID,
+                    "order_id": order_id,
+                    "user": "0xa",
+                    "side": false,
+                    "initial_size": 10,
+                    "size": 10,
+                    "price": 5,
+                }),
+            )
+            .await;
+        }
+        // Emitted to the maker and to the taker.
+        for (event_idx, emit_address) in [(0, "0xa"), (1, "0xb")] {
+            insert(
+                &mut tx,
+                "fill_events",
+                json!({
+                    "txn_version": 101,
+                    "event_idx": event_idx,
+                    "emit_address": emit_address,
+                    "market_id": MARKET_ID,
+                    "maker_address": "0xa",
+                    "maker_order_id": 1,
+                    "maker_side": false,
+                    "taker_address": "0xb",
+                    "taker_order_id": 3,
+                    "price": 5,
+                    "size": 3,
+                    "taker_quote_fees_paid": 0,
+                }),
+            )
+            .await;
+        }
+        insert(
+            &mut tx,
+            "change_order_size_events",
+            json!({
+                "txn_version": 102,
+                "market_id": MARKET_ID,
+                "order_id": 1,
+                "user": "0xa",
+                "side": false,
+                "new_size": 4,
+            }),
+        )
+        .await;
+        insert(
+            &mut tx,
+            "cancel_order_events",
+            json!({
+                "txn_version": 103,
+                "market_id": MARKET_ID,
+                "order_id": 1,
+                "user": "0xa",
+            }),
+        )
+        .await;
+
+        test_db::as_web_anon(&mut tx).await;
+        let events: Vec<Event> = sqlx::query_as(
+            "SELECT txn_version::int8, event_type, \"size\"::int8, price::int8, cancel_reason \
+             FROM order_events($1, 1)",
+        )
+        .bind(MARKET_ID)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+        assert_eq!(
+            events,
+            [
+                (100, "placed".to_string(), Some(10), Some(5), None),
+                (101, "filled".to_string(), Some(3), Some(5), None),
+                (102, "size_changed".to_string(), Some(4), None, None),
+                (103, "cancelled".to_string(), None, None, Some(3)),
+            ]
+        );
+    }
+}
+
+mod market_candlesticks {
+    use serde_json::json;
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Registers [`MARKET_ID`] and records `count` minute candlesticks from 2024-01-01, with a
+    /// volume of their index.
+    async fn seed(conn: &mut PgConnection, count: i64) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        conn.execute(
+            format!(
+                "INSERT INTO aggregator.candlesticks \
+                 SELECT {MARKET_ID}, 60, '2024-01-01'::timestamptz + i * interval '1 minute', \
+                 1, 1, 1, 1, i \
+                 FROM generate_series(0, {count} - 1) AS i"
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Returns the volumes of the candlesticks between the `start` and `end` minutes of
+    /// 2024-01-01.
+    async fn volumes(
+        conn: &mut PgConnection,
+        resolution: i32,
+        start: i32,
+        end: i32,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_scalar(
+            "SELECT volume::int8 FROM market_candlesticks( \
+                 $1, $2, \
+                 '2024-01-01'::timestamptz + make_interval(mins => $3), \
+                 '2024-01-01'::timestamptz + make_interval(mins => $4) \
+             )",
+        )
+        .bind(MARKET_ID)
+        .bind(resolution)
+        .bind(start)
+        .bind(end)
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn candlesticks_of_the_time_range() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, 10).await;
+        assert_eq!(volumes(&mut tx, 60, 2, 5).await.unwrap(), [2, 3, 4]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unsupported_resolution_is_rejected() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, 10).await;
+        let result = volumes(&mut tx, 61, 0, 10).await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("22023"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn at_most_1000_candlesticks() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, 1001).await;
+        let volumes = volumes(&mut tx, 60, 0, 2000).await.unwrap();
+        assert_eq!(volumes.len(), 1000);
+        assert_eq!(volumes.last(), Some(&999));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn over_broad_window_is_unprocessable() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, 1).await;
+        let result = volumes(&mut tx, 60, 0, 10_001).await;
+        let error = result.unwrap_err();
+        let error = error.as_database_error().unwrap();
+        assert_eq!(error.code().as_deref(), Some("PT422"));
+        assert_eq!(
+            error.message(),
+            "The time range spans 10001 periods, more than the maximum of 10000"
+        );
+        let hint = error
+            .try_downcast_ref::<sqlx::postgres::PgDatabaseError>()
+            .and_then(|e| e.hint());
+        assert_eq!(
+            hint,
+            Some("Use a coarser resolution or a narrower time range")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn window_at_the_limit_is_accepted() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, 1).await;
+        assert_eq!(volumes(&mut tx, 60, 0, 10_000).await.unwrap(), [0]);
+        // The broader range is few periods of a coarser resolution, which has no candlesticks.
+        assert!(volumes(&mut tx, 3600, 0, 10_001).await.unwrap().is_empty());
+    }
+}
+
+mod markets {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    type Market = (i64, i64, i64, String);
+
+    /// Returns the `(market_id, lot_size, tick_size, base_type)` of the test markets whose base
+    /// type is `base`, if set, as `/markets?base_type=eq.<base>&order=market_id` does.
+    async fn markets(conn: &mut PgConnection, base: Option<&str>) -> Vec<Market> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT m.market_id::int8, m.lot_size::int8, m.tick_size::int8, m.base_type \
+             FROM markets AS m \
+             WHERE m.market_id IN ($1, $1 + 1) AND ($2::text IS NULL OR m.base_type = $2) \
+             ORDER BY m.market_id",
+        )
+        .bind(MARKET_ID)
+        .bind(base)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn registered_markets_are_listed_and_filtered_by_base() {
+        let mut tx = test_db::begin().await;
+        for (market_id, lot_size, tick_size, base) in
+            [(MARKET_ID, 10, 2, "BASE"), (MARKET_ID + 1, 100, 5, "OTHER")]
+        {
+            insert(
+                &mut tx,
+                "market_registration_events",
+                json!({
+                    "txn_version": market_id,
+                    "market_id": market_id,
+                    "lot_size": lot_size,
+                    "tick_size": tick_size,
+                    "base_struct_name": base,
+                }),
+            )
+            .await;
+        }
+        let base = (MARKET_ID, 10, 2, "0x1::coin::BASE".into());
+        let other = (MARKET_ID + 1, 100, 5, "0x1::coin::OTHER".into());
+        assert_eq!(markets(&mut tx, None).await, [base, other.clone()]);
+        assert_eq!(markets(&mut tx, Some("0x1::coin::OTHER")).await, [other]);
+    }
+}
+
+mod user_trading_volume {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Maker and taker fill counts, base and quote volumes, then combined ones and fees paid.
+    type Volume = (i64, i64, i64, i64, i64, i64, i64, i64, i64, i64);
+
+    /// Registers [`MARKET_ID`] and records fills of `(maker, taker, price, size, fees)`, each
+    /// emitted to the maker and the taker.
+    async fn seed(conn: &mut PgConnection, fills: &[(&str, &str, i64, i64, i64)]) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        for (i, (maker, taker, price, size, fees)) in fills.iter().enumerate() {
+            for (event_idx, emit_address) in [(0, maker), (1, taker)] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": 100 + i,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "market_id": MARKET_ID,
+                        "maker_address": maker,
+                        "maker_order_id": 1,
+                        "maker_side": true,
+                        "taker_address": taker,
+                        "taker_order_id": 2,
+                        "price": price,
+                        "size": size,
+                        "taker_quote_fees_paid": fees,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn user_trading_volume(conn: &mut PgConnection, address: &str) -> Volume {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT maker_fill_count, maker_base_volume::int8, maker_quote_volume::int8, \
+             taker_fill_count, taker_base_volume::int8, taker_quote_volume::int8, \
+             fill_count, base_volume::int8, quote_volume::int8, fees_paid::int8 \
+             FROM user_trading_volume($1, '2023-12-31', '2024-01-02', $2)",
+        )
+        .bind(address)
+        .bind(MARKET_ID)
+        .fetch_one(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn maker_and_taker_volumes_are_split_and_combined() {
+        let mut tx = test_db::begin().await;
+        seed(
+            &mut tx,
+            &[
+                ("0xa", "0xb", 10, 2, 1),
+                ("0xc", "0xa", 20, 3, 5),
+                ("0xb", "0xc", 30, 4, 7),
+            ],
+        )
+        .await;
+        assert_eq!(
+            user_trading_volume(&mut tx, "0xa").await,
+            (1, 2, 20, 1, 3, 60, 2, 5, 80, 5)
+        );
+        // Users without fills have zero volumes.
+        assert_eq!(
+            user_trading_volume(&mut tx, "0xd").await,
+            Default::default()
+        );
+    }
+}
+
+mod market_ticker {
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Last price, price 24 hours ago, absolute and percent change, base and quote volumes, high
+    /// and low.
+    type Ticker = (
+        Option<i64>,
+        Option<i64>,
+        Option<i64>,
+        Option<f64>,
+        i64,
+        i64,
+        Option<i64>,
+        Option<i64>,
+    );
+
+    /// Registers [`MARKET_ID`] and records fills of `(hours ago, price, size)`, each emitted to
+    /// the maker and the taker.
+    async fn seed(conn: &mut PgConnection, fills: &[(i64, i64, i64)]) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        for (i, (hours_ago, price, size)) in fills.iter().enumerate() {
+            for (event_idx, emit_address) in [(0, "0xa"), (1, "0xb")] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": 100 + i,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "time": Utc::now() - Duration::hours(*hours_ago),
+                        "market_id": MARKET_ID,
+                        "maker_address": "0xa",
+                        "maker_order_id": 1,
+                        "maker_side": true,
+                        "taker_address": "0xb",
+                        "taker_order_id": 2,
+                        "price": price,
+                        "size": size,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Refreshes the statistics the ticker is read from, and returns the ticker of [`MARKET_ID`].
+    async fn market_ticker(conn: &mut PgConnection) -> Ticker {
+        crate::pipelines::market_stats_24h::refresh(conn)
+            .await
+            .unwrap();
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT last_price::int8, price_24h_ago::int8, price_change_24h::int8, \
+             price_change_percent_24h::float8, base_volume_24h::int8, quote_volume_24h::int8, \
+             high_24h::int8, low_24h::int8 \
+             FROM market_ticker($1)",
+        )
+        .bind(MARKET_ID)
+        .fetch_one(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn change_since_the_last_fill_before_24_hours() {
+        let mut tx = test_db::begin().await;
+        seed(
+            &mut tx,
+            &[(26, 90, 1), (25, 100, 1), (23, 120, 2), (1, 110, 3)],
+        )
+        .await;
+        assert_eq!(
+            market_ticker(&mut tx).await,
+            (
+                Some(110),
+                Some(100),
+                Some(10),
+                Some(10.0),
+                5,
+                570,
+                Some(120),
+                Some(110)
+            )
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn market_without_trades_has_no_prices() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, &[]).await;
+        assert_eq!(
+            market_ticker(&mut tx).await,
+            (None, None, None, None, 0, 0, None, None)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn market_without_recent_trades_has_no_high_and_low() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, &[(25, 100, 1)]).await;
+        assert_eq!(
+            market_ticker(&mut tx).await,
+            (Some(100), Some(100), Some(0), Some(0.0), 0, 0, None, None)
+        );
+    }
+}
+
+mod aggregation_watermark {
+    use sqlx::Executor;
+
+    use super::*;
+
+    async fn aggregation_watermark(
+        conn: &mut PgConnection,
+        model_name: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_scalar("SELECT aggregation_watermark($1)::int8")
+            .bind(model_name)
+            .fetch_one(conn)
+            .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn last_aggregated_version_is_returned() {
+        let mut tx = test_db::begin().await;
+        let last = i64::MAX - 10;
+        tx.execute(
+            format!("INSERT INTO aggregator.prices_last_indexed_txn VALUES ({last})").as_str(),
+        )
+        .await
+        .unwrap();
+        let watermark = aggregation_watermark(&mut tx, "Prices").await.unwrap();
+        assert_eq!(watermark, Some(last));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_pipeline_is_not_found() {
+        let mut tx = test_db::begin().await;
+        let result = aggregation_watermark(&mut tx, "Unknown").await;
+        assert_eq!(test_db::sqlstate(result).as_deref(), Some("PT404"));
+    }
+}
+
+mod all_trades {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Newer than any other fill, so that the trades of the tests come first.
+    const LAST: i64 = i64::MAX - 10;
+
+    /// A trade of [`all_trades`]: market ID, transaction version and event index.
+    type Trade = (i64, i64, i64);
+
+    /// Records trades of `(market_id, txn_version, event_idx)`, each emitted to the maker at that
+    /// event index and to the taker at the next one.
+    async fn seed(conn: &mut PgConnection, trades: &[Trade]) {
+        for (market_id, txn_version, event_idx) in trades {
+            for (event_idx, emit_address) in [(event_idx, "0xa"), (&(event_idx + 1), "0xb")] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": txn_version,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "market_id": market_id,
+                        "maker_address": "0xa",
+                        "maker_order_id": 1,
+                        "maker_side": true,
+                        "taker_address": "0xb",
+                        "taker_order_id": 2,
+                        "price": 10,
+                        "size": 1,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Returns at most `limit` trades after the cursor, newest first.
+    async fn all_trades(
+        conn: &mut PgConnection,
+        after: Option<(i64, i64)>,
+        limit: i64,
+    ) -> Vec<Trade> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT market_id::int8, txn_version::int8, event_idx::int8 \
+             FROM all_trades($1::numeric, $2::numeric) \
+             LIMIT $3",
+        )
+        .bind(after.map(|(txn_version, _)| txn_version))
+        .bind(after.map(|(_, event_idx)| event_idx))
+        .bind(limit)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn trades_of_all_markets_are_paged_newest_first() {
+        let mut tx = test_db::begin().await;
+        let other = MARKET_ID + 1;
+        seed(
+            &mut tx,
+            &[
+                (MARKET_ID, LAST - 3, 0),
+                (other, LAST - 2, 0),
+                (MARKET_ID, LAST - 1, 0),
+                (other, LAST - 1, 2),
+                (MARKET_ID, LAST, 0),
+            ],
+        )
+        .await;
+        let mut pages = vec![];
+        let mut after = None;
+        while pages.len() < 3 {
+            let page = all_trades(&mut tx, after, 2).await;
+            after = page
+                .last()
+                .map(|&(_, txn_version, event_idx)| (txn_version, event_idx));
+            pages.push(page);
+        }
+        assert_eq!(pages[0], [(MARKET_ID, LAST, 0), (other, LAST - 1, 2)]);
+        assert_eq!(pages[1], [(MARKET_ID, LAST - 1, 0), (other, LAST - 2, 0)]);
+        // Older trades of the database may follow.
+        assert_eq!(pages[2][0], (MARKET_ID, LAST - 3, 0));
+    }
+}
+
+mod data_as_of {
+    use serde_json::{json, Value};
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Newer than any other event, so that the events of the tests are the last ones.
+    const LAST: i64 = i64::MAX - 10;
+
+    /// Records order placements at `LAST - 2` and `LAST - 1`, the processor at `LAST`, and the
+    /// user history aggregated up to `watermark`, as after an aggregation cycle.
+    async fn seed(conn: &mut PgConnection, watermark: i64) {
+        for (txn_version, time) in [
+            (LAST - 2, "2024-01-01T00:00:00Z"),
+            (LAST - 1, "2024-01-02T00:00:00Z"),
+        ] {
+            insert(
+                conn,
+                "place_limit_order_events",
+                json!({
+                    "txn_version": txn_version,
+                    "time": time,
+                    "market_id": MARKET_ID,
+                    "user": "0xa",
+                    "order_id": txn_version,
+                    "side": false,
+                    "initial_size": 1,
+                    "price": 1,
+                    "size": 1,
+                }),
+            )
+            .await;
+        }
+        conn.execute(
+            format!(
+                "DELETE FROM processor_status WHERE processor = 'econia_processor'; \
+                 INSERT INTO processor_status \
+                 VALUES ('econia_processor', {LAST}, now(), '2024-01-03 00:00:00'); \
+                 INSERT INTO aggregator.user_history_last_indexed_txn VALUES ({watermark})"
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Runs the PostgREST pre-request for a request to `path`, and returns its
+    /// `x-data-as-of-txn-version` and `x-data-as-of` headers.
+    async fn data_as_of(conn: &mut PgConnection, path: &str) -> (String, String) {
+        test_db::as_web_anon(conn).await;
+        sqlx::query("SELECT set_config('request.path', $1, true)")
+            .bind(path)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        conn.execute("SELECT set_data_as_of()").await.unwrap();
+        let headers: String = sqlx::query_scalar("SELECT current_setting('response.headers')")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        let headers: Vec<Value> = serde_json::from_str(&headers).unwrap();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find_map(|h| h[name].as_str())
+                .unwrap_or_else(|| panic!("no {name} header in {headers:?}"))
+                .to_string()
+        };
+        (header("x-data-as-of-txn-version"), header("x-data-as-of"))
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn behind_pipeline_reports_its_watermark() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, LAST - 2).await;
+        assert_eq!(
+            data_as_of(&mut tx, "/orders").await,
+            ((LAST - 2).to_string(), "2024-01-01T00:00:00.000000Z".into())
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn caught_up_pipeline_reports_the_processor_progress() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, LAST - 1).await;
+        assert_eq!(
+            data_as_of(&mut tx, "/orders").await,
+            (LAST.to_string(), "2024-01-03T00:00:00.000000Z".into())
+        );
+    }
+
+    /// Runs the PostgREST pre-request for a request to `/orders` with an `X-Min-Txn-Version`
+    /// header of `min_txn_version`.
+    async fn read_at_least(
+        conn: &mut PgConnection,
+        min_txn_version: i64,
+    ) -> Result<(), sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query(
+            "SELECT set_config('request.path', '/orders', true), set_config(\
+                 'request.headers', json_build_object('x-min-txn-version', $1::text)::text, true\
+             )",
+        )
+        .bind(min_txn_version)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        conn.execute("SELECT set_data_as_of()").await.map(|_| ())
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn reads_wait_for_the_requested_version() {
+        for (min_txn_version, expected) in [
+            (LAST - 3, None),
+            (LAST - 2, None),
+            (LAST - 1, Some("PT503")),
+        ] {
+            let mut tx = test_db::begin().await;
+            seed(&mut tx, LAST - 2).await;
+            assert_eq!(
+                test_db::sqlstate(read_at_least(&mut tx, min_txn_version).await).as_deref(),
+                expected,
+                "{min_txn_version}"
+            );
+        }
+    }
+}
+
+mod order_priority {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, insert_order, MARKET_ID};
+
+    /// Newer than any other event, so that the placements of the tests do not collide.
+    const FIRST: i64 = i64::MAX - 10;
+
+    /// Places orders 1, 2 and 3 in that order at a price of 5, with remaining sizes of their ID,
+    /// order 4 at another price, and cancelled order 5 at the same price.
+    async fn seed(conn: &mut PgConnection) {
+        for (order_id, price, order_status) in [
+            (1, 5, "open"),
+            (2, 5, "open"),
+            (3, 5, "open"),
+            (4, 6, "open"),
+            (5, 5, "cancelled"),
+        ] {
+            insert(
+                conn,
+                "place_limit_order_events",
+                json!({
+                    "txn_version": FIRST + order_id,
+                    "market_id": MARKET_ID,
+                    "user": "0xa",
+                    "order_id": order_id,
+                    "side": false,
+                    "initial_size": order_id,
+                    "price": price,
+                    "size": order_id,
+                }),
+            )
+            .await;
+            insert_order(
+                conn,
+                json!({
+                    "order_id": order_id,
+                    "price": price,
+                    "remaining_size": order_id,
+                    "order_status": order_status,
+                }),
+            )
+            .await;
+        }
+    }
+
+    /// Returns the rank, size ahead and size of the price level of the order.
+    async fn order_priority(
+        conn: &mut PgConnection,
+        order_id: i64,
+    ) -> Result<(i64, i64, i64), sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT rank, size_ahead::int8, level_size::int8 FROM order_priority($1, $2)",
+        )
+        .bind(MARKET_ID)
+        .bind(order_id)
+        .fetch_one(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn orders_are_ranked_by_placement() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        let mut ranks = vec![];
+        for order_id in [1, 2, 3] {
+            ranks.push(order_priority(&mut tx, order_id).await.unwrap());
+        }
+        assert_eq!(ranks, [(1, 0, 6), (2, 1, 6), (3, 3, 6)]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn size_increase_sends_the_order_to_the_back() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        sqlx::query(
+            "UPDATE aggregator.user_history \
+             SET last_increase_stamp = ($1::numeric + 10) * 18446744073709551616 \
+             WHERE market_id = $2 AND order_id = 1",
+        )
+        .bind(FIRST)
+        .bind(MARKET_ID)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        let mut ranks = vec![];
+        for order_id in [2, 3, 1] {
+            ranks.push(order_priority(&mut tx, order_id).await.unwrap());
+        }
+        assert_eq!(ranks, [(1, 0, 6), (2, 2, 6), (3, 5, 6)]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn closed_and_unknown_orders_are_rejected() {
+        for (order_id, sqlstate) in [(5, "PT409"), (6, "PT404")] {
+            let mut tx = test_db::begin().await;
+            seed(&mut tx).await;
+            let result = order_priority(&mut tx, order_id).await;
+            assert_eq!(test_db::sqlstate(result).as_deref(), Some(sqlstate));
+        }
+    }
+}
+
+mod fill_latency {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, insert_order, MARKET_ID};
+
+    /// Orders placed, filled and fully filled, then the median, 90th percentile and mean seconds
+    /// to the first fill, and to the full fill.
+    type Latency = (i64, i64, i64, f64, f64, f64, f64, f64, f64);
+
+    /// Registers [`MARKET_ID`] and places orders at midnight on 2024-01-01, filled as makers
+    /// after the given seconds.
+    async fn seed(conn: &mut PgConnection, orders: &[(i64, &str, &[i64])]) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        for (order_id, order_status, fills) in orders {
+            insert_order(
+                conn,
+                json!({
+                    "order_id": order_id,
+                    "order_status": order_status,
+                    "close_reason": (*order_status == "closed").then_some("filled"),
+                }),
+            )
+            .await;
+            for (event_idx, seconds) in fills.iter().enumerate() {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": 100 + order_id,
+                        "event_idx": event_idx,
+                        "emit_address": "0xa",
+                        "time": format!("2024-01-01T00:00:{seconds:02}Z"),
+                        "market_id": MARKET_ID,
+                        "maker_address": "0xa",
+                        "maker_order_id": order_id,
+                        "maker_side": false,
+                        "taker_address": "0xb",
+                        "taker_order_id": 100,
+                        "price": 1,
+                        "size": 1,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn fill_latency(conn: &mut PgConnection) -> Latency {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT orders_placed, orders_filled, orders_fully_filled, \
+             median_seconds_to_first_fill::float8, p90_seconds_to_first_fill::float8, \
+             mean_seconds_to_first_fill::float8, median_seconds_to_full_fill::float8, \
+             p90_seconds_to_full_fill::float8, mean_seconds_to_full_fill::float8 \
+             FROM fill_latency($1, '2024-01-01', '2024-01-02')",
+        )
+        .bind(MARKET_ID)
+        .fetch_one(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn open_orders_only_count_towards_the_first_fill() {
+        let mut tx = test_db::begin().await;
+        seed(
+            &mut tx,
+            &[
+                (1, "closed", &[10, 50]),
+                (2, "closed", &[30]),
+                // Partially filled.
+                (3, "open", &[20]),
+                (4, "open", &[]),
+                (5, "closed", &[40, 58]),
+            ],
+        )
+        .await;
+        assert_eq!(
+            fill_latency(&mut tx).await,
+            (5, 4, 3, 25.0, 37.0, 25.0, 50.0, 56.4, 46.0)
+        );
+    }
+}
+
+mod user_taker_volume {
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, sqlstate, MARKET_ID};
+
+    /// Number of fills, base and quote volumes.
+    type Volume = (i64, i64, i64);
+
+    /// Registers [`MARKET_ID`] and records fills of `(hours ago, maker, taker, size)` at a price
+    /// of 10, each emitted to the maker and the taker.
+    async fn seed(conn: &mut PgConnection, fills: &[(i64, &str, &str, i64)]) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        for (i, (hours_ago, maker, taker, size)) in fills.iter().enumerate() {
+            for (event_idx, emit_address) in [(0, maker), (1, taker)] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": 100 + i,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "time": Utc::now() - Duration::hours(*hours_ago),
+                        "market_id": MARKET_ID,
+                        "maker_address": maker,
+                        "maker_order_id": 1,
+                        "maker_side": true,
+                        "taker_address": taker,
+                        "taker_order_id": 2,
+                        "price": 10,
+                        "size": size,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Returns the length of the window used, and the taker volume of `address` on
+    /// [`MARKET_ID`] over the last `duration`, if any.
+    async fn user_taker_volume(
+        conn: &mut PgConnection,
+        address: &str,
+        duration: &str,
+    ) -> Result<(String, Option<Volume>), sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        let (window, markets): (String, serde_json::Value) = sqlx::query_as(
+            "SELECT \
+             ((v->>'window_end')::timestamptz - (v->>'window_start')::timestamptz)::text, \
+             (v->'markets')::jsonb \
+             FROM user_taker_volume($1, $2::interval) AS v",
+        )
+        .bind(address)
+        .bind(duration)
+        .fetch_one(conn)
+        .await?;
+        let volume = markets
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|market| market["market_id"] == MARKET_ID)
+            .map(|market| {
+                (
+                    market["fill_count"].as_i64().unwrap(),
+                    market["base_volume"].as_i64().unwrap(),
+                    market["quote_volume"].as_i64().unwrap(),
+                )
+            });
+        Ok((window, volume))
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn only_taker_fills_within_the_window_count() {
+        let mut tx = test_db::begin().await;
+        seed(
+            &mut tx,
+            &[
+                (31 * 24, "0xa", "0xb", 1),
+                (29 * 24, "0xa", "0xb", 2),
+                (1, "0xa", "0xb", 3),
+                // 0xb is only the maker.
+                (1, "0xb", "0xc", 4),
+            ],
+        )
+        .await;
+        assert_eq!(
+            user_taker_volume(&mut tx, "0xb", "30 days").await.unwrap(),
+            (String::from("30 days"), Some((2, 5, 50)))
+        );
+        assert_eq!(
+            user_taker_volume(&mut tx, "0xb", "7 days").await.unwrap(),
+            (String::from("7 days"), Some((1, 3, 30)))
+        );
+        assert_eq!(
+            user_taker_volume(&mut tx, "0xa", "30 days").await.unwrap(),
+            (String::from("30 days"), None)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn window_is_capped() {
+        for duration in ["91 days", "0 days", "-1 day"] {
+            let mut tx = test_db::begin().await;
+            assert_eq!(
+                sqlstate(user_taker_volume(&mut tx, "0xb", duration).await),
+                Some(String::from("22023")),
+                "{duration}"
+            );
+        }
+        let mut tx = test_db::begin().await;
+        assert!(user_taker_volume(&mut tx, "0xb", "90 days").await.is_ok());
+    }
+}
+
+mod market_overview {
+    use serde_json::json;
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::test_db::{insert, insert_order, sqlstate, MARKET_ID};
+
+    /// Registers [`MARKET_ID`] with an open bid at a price of 5 and an open ask at 7.
+    async fn seed(conn: &mut PgConnection) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        insert_order(conn, json!({ "order_id": 1, "price": 5 })).await;
+        insert_order(
+            conn,
+            json!({ "order_id": 2, "price": 7, "direction": "ask" }),
+        )
+        .await;
+    }
+
+    async fn market_overview(
+        conn: &mut PgConnection,
+        market_id: i64,
+    ) -> Result<serde_json::Value, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_scalar("SELECT market_overview($1)::jsonb")
+            .bind(market_id)
+            .fetch_one(conn)
+            .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn lagging_pipeline_is_warned_about() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        tx.execute(
+            "INSERT INTO aggregator.lagging_pipelines (pipeline, lag) VALUES ('Prices', 500)",
+        )
+        .await
+        .unwrap();
+        let overview = market_overview(&mut tx, MARKET_ID).await.unwrap();
+        assert_eq!(overview["data"]["best_bid"], 5);
+        assert_eq!(overview["data"]["best_ask"], 7);
+        let warnings = overview["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert_eq!(warnings[0]["pipeline"], "Prices");
+        assert_eq!(warnings[0]["lag"], 500);
+        assert_eq!(
+            warnings[0]["message"],
+            "Prices is 500 versions behind, its data may be stale"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn caught_up_pipelines_are_not_warned_about() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        // Lagging, but the overview does not use it.
+        tx.execute(
+            "INSERT INTO aggregator.lagging_pipelines (pipeline, lag) \
+             VALUES ('Candlesticks(60)', 500)",
+        )
+        .await
+        .unwrap();
+        let overview = market_overview(&mut tx, MARKET_ID).await.unwrap();
+        assert_eq!(overview["warnings"], json!([]));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_market_is_not_found() {
+        let mut tx = test_db::begin().await;
+        assert_eq!(
+            sqlstate(market_overview(&mut tx, MARKET_ID).await).as_deref(),
+            Some("PT404")
+        );
+    }
+}
+
+mod cancelled_orders {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, insert_order, sqlstate, MARKET_ID};
+
+    /// Registers [`MARKET_ID`] and places orders of `(order_id, user, order_status,
+    /// close_reason, last_updated_at)`.
+    async fn seed(conn: &mut PgConnection) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        for (order_id, user, order_status, close_reason, last_updated_at) in [
+            (
+                1,
+                "0xa",
+                "cancelled",
+                Some("cancelled"),
+                "2024-01-01T01:00:00Z",
+            ),
+            (
+                2,
+                "0xb",
+                "cancelled",
+                Some("ioc_expired"),
+                "2024-01-01T02:00:00Z",
+            ),
+            (
+                3,
+                "0xa",
+                "cancelled",
+                Some("cancelled"),
+                "2024-01-01T02:00:00Z",
+            ),
+            (4, "0xa", "open", None, "2024-01-01T03:00:00Z"),
+            // Cancelled after the time range.
+            (
+                5,
+                "0xa",
+                "cancelled",
+                Some("cancelled"),
+                "2024-01-03T00:00:00Z",
+            ),
+        ] {
+            insert_order(
+                conn,
+                json!({
+                    "order_id": order_id,
+                    "user": user,
+                    "order_status": order_status,
+                    "close_reason": close_reason,
+                    "last_updated_at": last_updated_at,
+                }),
+            )
+            .await;
+        }
+    }
+
+    /// Returns the `(order_id, close_reason)` of the orders of [`MARKET_ID`] cancelled on
+    /// 2024-01-01, after the cursor and of the user if given.
+    async fn cancelled_orders(
+        conn: &mut PgConnection,
+        after: Option<(&str, i64)>,
+        user: Option<&str>,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT order_id::int8, close_reason::text \
+             FROM cancelled_orders($1, '2024-01-01', '2024-01-02', $2::timestamptz, \
+             $3::numeric, $4)",
+        )
+        .bind(MARKET_ID)
+        .bind(after.map(|(time, _)| time))
+        .bind(after.map(|(_, order_id)| order_id))
+        .bind(user)
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn cancels_and_ioc_expiries_are_listed_by_cancel_time() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            cancelled_orders(&mut tx, None, None).await.unwrap(),
+            [
+                (1, "cancelled".into()),
+                (2, "ioc_expired".into()),
+                (3, "cancelled".into()),
+            ]
+        );
+        assert_eq!(
+            cancelled_orders(&mut tx, None, Some("0xa")).await.unwrap(),
+            [(1, "cancelled".into()), (3, "cancelled".into())]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn cursor_resumes_within_a_cancel_time() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            cancelled_orders(&mut tx, Some(("2024-01-01T02:00:00Z", 2)), None)
+                .await
+                .unwrap(),
+            [(3, "cancelled".into())]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn cursor_needs_both_parameters() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        test_db::as_web_anon(&mut tx).await;
+        let result = sqlx::query(
+            "SELECT * FROM cancelled_orders($1, '2024-01-01', '2024-01-02', \
+             after_time => '2024-01-01T02:00:00Z')",
+        )
+        .bind(MARKET_ID)
+        .execute(&mut *tx)
+        .await;
+        assert_eq!(sqlstate(result).as_deref(), Some("22023"));
+    }
+}
+
+mod integrator_orders {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert_order, sqlstate, MARKET_ID};
+
+    /// Places orders of [`MARKET_ID`] of `(order_id, integrator, order_status, created_at)`.
+    async fn seed(conn: &mut PgConnection) {
+        for (order_id, integrator, order_status, created_at) in [
+            (1, "0xabc1", "open", "2024-01-01T00:00:00Z"),
+            (2, "0xabc1", "closed", "2024-01-01T01:00:00Z"),
+            (3, "0x0", "open", "2024-01-01T02:00:00Z"),
+            (4, "0xabc1", "open", "2024-01-01T03:00:00Z"),
+        ] {
+            insert_order(
+                conn,
+                json!({
+                    "order_id": order_id,
+                    "integrator": integrator,
+                    "order_status": order_status,
+                    "created_at": created_at,
+                }),
+            )
+            .await;
+        }
+    }
+
+    /// Returns the IDs of the orders of [`MARKET_ID`] placed through `integrator`, of `status`
+    /// if given, after the order created at `after` if given.
+    async fn integrator_orders(
+        conn: &mut PgConnection,
+        integrator: &str,
+        status: Option<&str>,
+        after: Option<(&str, i64)>,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_scalar(
+            "SELECT order_id::int8 FROM integrator_orders($1, $2, $3, $4::timestamptz, \
+             $5::numeric, $6::numeric)",
+        )
+        .bind(integrator)
+        .bind(MARKET_ID)
+        .bind(status)
+        .bind(after.map(|(time, _)| time))
+        .bind(after.map(|_| MARKET_ID))
+        .bind(after.map(|(_, order_id)| order_id))
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn status_composes_with_the_integrator() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            integrator_orders(&mut tx, "0x000ABC1", None, None)
+                .await
+                .unwrap(),
+            [1, 2, 4]
+        );
+        assert_eq!(
+            integrator_orders(&mut tx, "0xabc1", Some("open"), None)
+                .await
+                .unwrap(),
+            [1, 4]
+        );
+        assert_eq!(
+            integrator_orders(&mut tx, "0xabc1", Some("cancelled"), None)
+                .await
+                .unwrap(),
+            Vec::<i64>::new()
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn orders_without_an_integrator_are_queryable() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        for integrator in ["0x0", "0x0000"] {
+            assert_eq!(
+                integrator_orders(&mut tx, integrator, None, None)
+                    .await
+                    .unwrap(),
+                [3],
+                "{integrator}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn cursor_resumes_after_the_last_order() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            integrator_orders(&mut tx, "0xabc1", None, Some(("2024-01-01T01:00:00Z", 2)))
+                .await
+                .unwrap(),
+            [4]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_status_is_rejected() {
+        let mut tx = test_db::begin().await;
+        let result = integrator_orders(&mut tx, "0xabc1", Some("filled"), None).await;
+        assert_eq!(sqlstate(result).as_deref(), Some("22023"));
+    }
+}
+
+mod order_state {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert_order, sqlstate, MARKET_ID};
+
+    /// Fields of the current state of every order, whatever its type.
+    const COMMON: [&str; 14] = [
+        "market_id",
+        "order_id",
+        "user",
+        "custodian_id",
+        "integrator",
+        "order_status",
+        "order_type",
+        "close_reason",
+        "remaining_size",
+        "total_filled",
+        "average_execution_price",
+        "total_fees_paid_in_quote_subunits",
+        "created_at",
+        "last_updated_at",
+    ];
+
+    /// Places limit order 1, market order 2 and swap 3 on [`MARKET_ID`].
+    async fn seed(conn: &mut PgConnection) {
+        insert_order(
+            conn,
+            json!({ "order_id": 1, "price": 7, "restriction": 3, "self_match_behavior": 1 }),
+        )
+        .await;
+        insert_order(
+            conn,
+            json!({
+                "order_id": 2,
+                "order_type": "market",
+                "direction": "sell",
+                "price": null,
+                "self_match_behavior": 2,
+            }),
+        )
+        .await;
+        insert_order(
+            conn,
+            json!({
+                "order_id": 3,
+                "order_type": "swap",
+                "direction": "buy",
+                "price": null,
+                "min_base": 1,
+                "max_base": 5,
+                "min_quote": 0,
+                "max_quote": null,
+            }),
+        )
+        .await;
+    }
+
+    /// Returns the state of `order_id` of [`MARKET_ID`], without the fields all orders have.
+    async fn placement(
+        conn: &mut PgConnection,
+        order_id: i64,
+    ) -> Result<serde_json::Value, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        let mut state: serde_json::Value = sqlx::query_scalar("SELECT order_state($1, $2)::jsonb")
+            .bind(MARKET_ID)
+            .bind(order_id)
+            .fetch_one(conn)
+            .await?;
+        let state = state.as_object_mut().unwrap();
+        for field in COMMON {
+            assert!(state.remove(field).is_some(), "{field} is missing");
+        }
+        Ok(json!(state))
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn only_the_placement_fields_of_the_type_are_returned() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            placement(&mut tx, 1).await.unwrap(),
+            json!({ "direction": "bid", "price": 7, "restriction": 3, "self_match_behavior": 1 })
+        );
+        assert_eq!(
+            placement(&mut tx, 2).await.unwrap(),
+            json!({ "direction": "sell", "self_match_behavior": 2 })
+        );
+        assert_eq!(
+            placement(&mut tx, 3).await.unwrap(),
+            json!({
+                "direction": "buy",
+                "limit_price": null,
+                "min_base": 1,
+                "max_base": 5,
+                "min_quote": 0,
+                "max_quote": null,
+            })
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn missing_and_pruned_orders_are_not_found() {
+        let mut tx = test_db::begin().await;
+        assert_eq!(
+            sqlstate(placement(&mut tx, 1).await).as_deref(),
+            Some("PT404")
+        );
+
+        let mut tx = test_db::begin().await;
+        insert_order(
+            &mut tx,
+            json!({ "order_id": 1, "order_status": "closed", "remaining_size": 0 }),
+        )
+        .await;
+        sqlx::query("SELECT aggregator.prune_user_history('2024-01-02T00:00:00+00:00')")
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(
+            sqlstate(placement(&mut tx, 1).await).as_deref(),
+            Some("PT410")
+        );
+    }
+}
+
+mod nominal_amounts {
+    use std::str::FromStr;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, insert_order, MARKET_ID};
+
+    /// Registers [`MARKET_ID`] with lots of 1000 base subunits and ticks of 5 quote subunits per
+    /// lot, between a base coin of 8 decimals and a quote coin of 6.
+    async fn seed(conn: &mut PgConnection) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({
+                "txn_version": 1,
+                "market_id": MARKET_ID,
+                "lot_size": 1000,
+                "tick_size": 5,
+            }),
+        )
+        .await;
+        for (name, decimals) in [("BASE", 8), ("QUOTE", 6)] {
+            insert(
+                conn,
+                "aggregator.coins",
+                json!({
+                    "name": name,
+                    "symbol": name,
+                    "decimals": decimals,
+                    "address": "0x1",
+                    "module": "coin",
+                    "struct": name,
+                }),
+            )
+            .await;
+        }
+    }
+
+    fn decimal(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn order_amounts_match_a_hand_computation() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        insert_order(
+            &mut tx,
+            json!({
+                "order_id": 1,
+                "price": 20,
+                "remaining_size": 3,
+                "total_filled": 2,
+                "average_execution_price": 20,
+            }),
+        )
+        .await;
+        test_db::as_web_anon(&mut tx).await;
+        let amounts: (BigDecimal, BigDecimal, BigDecimal, BigDecimal) = sqlx::query_as(
+            "SELECT price_nominal(o), remaining_base_nominal(o), filled_base_nominal(o), \
+             filled_quote_nominal(o) FROM orders AS o WHERE market_id = $1 AND order_id = 1",
+        )
+        .bind(MARKET_ID)
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap();
+        assert_eq!(
+            amounts,
+            (
+                // 20 ticks of 5 quote subunits per 1000 base subunits, is 10^7 quote subunits
+                // per 10^8 base subunits.
+                decimal("10"),
+                // 3 lots of 1000 base subunits.
+                decimal("0.00003"),
+                decimal("0.00002"),
+                // 2 lots at 20 ticks of 5 quote subunits.
+                decimal("0.0002"),
+            )
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn prices_match_a_hand_computation() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        insert(
+            &mut tx,
+            "aggregator.prices",
+            json!({
+                "market_id": MARKET_ID,
+                "start_time_1m_period": "2024-01-01T00:00:00Z",
+                "price": 20,
+                "sum_fill_size_1m_period": 1,
+                "open": 10,
+                "high": 30,
+                "low": 1,
+                "close": 20,
+            }),
+        )
+        .await;
+        test_db::as_web_anon(&mut tx).await;
+        let prices: (BigDecimal, BigDecimal, BigDecimal, BigDecimal, BigDecimal) = sqlx::query_as(
+            "SELECT price_nominal(p), open_nominal(p), high_nominal(p), low_nominal(p), \
+                 close_nominal(p) FROM prices AS p WHERE market_id = $1",
+        )
+        .bind(MARKET_ID)
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap();
+        // Each tick is 5 quote subunits per 1000 base subunits, or 0.5 quote coins per base coin.
+        assert_eq!(
+            prices,
+            (
+                decimal("10"),
+                decimal("5"),
+                decimal("15"),
+                decimal("0.5"),
+                decimal("10"),
+            )
+        );
+    }
+}
+
+mod flow_imbalance {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, sqlstate, MARKET_ID};
+
+    /// Buy and sell volumes and imbalance of a bucket.
+    type Bucket = (i64, i64, Option<f64>);
+
+    /// Registers [`MARKET_ID`] and records fills of `(seconds after midnight on 2024-01-01,
+    /// maker_side, size)`, each emitted to the maker and the taker.
+    async fn seed(conn: &mut PgConnection, fills: &[(i64, bool, i64)]) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        for (i, (seconds, maker_side, size)) in fills.iter().enumerate() {
+            for (event_idx, emit_address) in [(0, "0xa"), (1, "0xb")] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": 100 + i,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "time": format!("2024-01-01T00:{:02}:{:02}Z", seconds / 60, seconds % 60),
+                        "market_id": MARKET_ID,
+                        "maker_address": "0xa",
+                        "maker_order_id": 1,
+                        "maker_side": maker_side,
+                        "taker_address": "0xb",
+                        "taker_order_id": 2,
+                        "price": 10,
+                        "size": size,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Returns the buckets of `bucket` seconds of the first three minutes of 2024-01-01.
+    async fn flow_imbalance(
+        conn: &mut PgConnection,
+        bucket: i32,
+    ) -> Result<Vec<Bucket>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT buy_volume::int8, sell_volume::int8, imbalance::float8 \
+             FROM flow_imbalance($1, '2024-01-01T00:00:00Z', '2024-01-01T00:03:00Z', $2)",
+        )
+        .bind(MARKET_ID)
+        .bind(bucket)
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn empty_buckets_have_no_imbalance() {
+        let mut tx = test_db::begin().await;
+        seed(
+            &mut tx,
+            &[
+                // Takers buy from ask makers.
+                (10, true, 2),
+                (50, true, 1),
+                (59, false, 1),
+                (130, false, 2),
+            ],
+        )
+        .await;
+        assert_eq!(
+            flow_imbalance(&mut tx, 60).await.unwrap(),
+            [(3, 1, Some(0.5)), (0, 0, None), (0, 2, Some(-1.))]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn bucket_must_be_positive() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, &[]).await;
+        assert_eq!(
+            sqlstate(flow_imbalance(&mut tx, 0).await).as_deref(),
+            Some("22023")
+        );
+    }
+}
+
+mod fills_by_version {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, sqlstate, MARKET_ID};
+
+    /// Records a fill of [`MARKET_ID`] in each of the transactions 9 to 12, emitted to the
+    /// taker and then to the maker.
+    async fn seed(conn: &mut PgConnection) {
+        for txn_version in 9..=12 {
+            for (event_idx, emit_address) in [(0, "0xb"), (1, "0xa")] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": txn_version,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "market_id": MARKET_ID,
+                        "maker_address": "0xa",
+                        "maker_order_id": 1,
+                        "maker_side": true,
+                        "taker_address": "0xb",
+                        "taker_order_id": 2,
+                        "price": 10,
+                        "size": 1,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Returns the `(txn_version, event_idx)` of the fills of the range.
+    async fn fills_by_version(
+        conn: &mut PgConnection,
+        from_version: i64,
+        to_version: i64,
+    ) -> Result<Vec<(i64, i64)>, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT txn_version::int8, event_idx::int8 FROM fills_by_version($1::int8, $2::int8)",
+        )
+        .bind(from_version)
+        .bind(to_version)
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn both_ends_of_the_range_are_included_once() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            fills_by_version(&mut tx, 10, 11).await.unwrap(),
+            [(10, 1), (11, 1)]
+        );
+        assert_eq!(fills_by_version(&mut tx, 12, 12).await.unwrap(), [(12, 1)]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn reversed_and_overly_large_ranges_are_rejected() {
+        for (from_version, to_version, expected) in [
+            (11, 10, Some("22023")),
+            (0, 100_000, Some("22023")),
+            (0, 99_999, None),
+        ] {
+            let mut tx = test_db::begin().await;
+            seed(&mut tx).await;
+            assert_eq!(
+                sqlstate(fills_by_version(&mut tx, from_version, to_version).await).as_deref(),
+                expected,
+                "{from_version}..={to_version}"
+            );
+        }
+    }
+}
+
+mod pipeline_schemas {
+    use std::time::Duration;
+
+    use aggregator::Pipeline;
+
+    use super::*;
+    use crate::pipelines::UserHistory;
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn columns_of_the_written_tables_are_listed_with_their_types() {
+        let pool = test_db::connect().await;
+        let pipeline = UserHistory::new(
+            pool.clone(),
+            false,
+            Duration::from_secs(60),
+            None,
+            None,
+            None,
+            None,
+        );
+        let mut tx = pool.begin().await.unwrap();
+        sqlx::query("INSERT INTO aggregator.pipelines (model_name, writes) VALUES ($1, $2)")
+            .bind("SchemaTest")
+            .bind(pipeline.writes())
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        test_db::as_web_anon(&mut tx).await;
+        let columns: Vec<(String, String, bool)> = sqlx::query_as(
+            "SELECT column_name, data_type, nullable FROM pipeline_schemas \
+             WHERE model_name = 'SchemaTest' AND table_name = 'aggregator.user_history' \
+             ORDER BY position",
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+        let column = |name: &str| columns.iter().find(|column| column.0 == name).cloned();
+        assert_eq!(
+            columns[..2],
+            [
+                (
+                    String::from("market_id"),
+                    String::from("numeric(20,0)"),
+                    false
+                ),
+                (
+                    String::from("order_id"),
+                    String::from("numeric(39,0)"),
+                    false
+                ),
+            ]
+        );
+        for (name, data_type, nullable) in [
+            ("created_at", "timestamp with time zone", false),
+            ("order_status", "order_status", false),
+            ("price", "numeric(20,0)", true),
+            ("close_reason", "order_close_reason", true),
+        ] {
+            assert_eq!(
+                column(name),
+                Some((name.to_string(), data_type.to_string(), nullable))
+            );
+        }
+    }
+}
+
+mod market_tickers {
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Registers three markets from [`MARKET_ID`] on, records fills in the first two, each
+    /// emitted to the maker and the taker, and refreshes the statistics of the tickers.
+    async fn seed(conn: &mut PgConnection) {
+        for i in 0..3 {
+            insert(
+                conn,
+                "market_registration_events",
+                json!({ "txn_version": 1 + i, "market_id": MARKET_ID + i }),
+            )
+            .await;
+        }
+        let fills = [
+            (MARKET_ID, 2, 100, 1),
+            (MARKET_ID, 1, 110, 2),
+            (MARKET_ID + 1, 1, 50, 3),
+        ];
+        for (i, (market_id, hours_ago, price, size)) in fills.into_iter().enumerate() {
+            for (event_idx, emit_address) in [(0, "0xa"), (1, "0xb")] {
+                insert(
+                    conn,
+                    "fill_events",
+                    json!({
+                        "txn_version": 100 + i,
+                        "event_idx": event_idx,
+                        "emit_address": emit_address,
+                        "time": Utc::now() - Duration::hours(hours_ago),
+                        "market_id": market_id,
+                        "maker_address": "0xa",
+                        "maker_order_id": 1,
+                        "maker_side": true,
+                        "taker_address": "0xb",
+                        "taker_order_id": 2,
+                        "price": price,
+                        "size": size,
+                        "taker_quote_fees_paid": 0,
+                    }),
+                )
+                .await;
+            }
+        }
+        crate::pipelines::market_stats_24h::refresh(conn)
+            .await
+            .unwrap();
+    }
+
+    /// Returns the market ID, last price and base volume of the tickers of `market_ids`.
+    async fn market_tickers(
+        conn: &mut PgConnection,
+        market_ids: &[i64],
+    ) -> Vec<(i64, Option<i64>, i64)> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_as(
+            "SELECT market_id::int8, last_price::int8, base_volume_24h::int8 \
+             FROM market_tickers($1::numeric[])",
+        )
+        .bind(market_ids)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn every_market_is_listed_with_its_last_price() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            market_tickers(&mut tx, &[MARKET_ID, MARKET_ID + 1, MARKET_ID + 2]).await,
+            [
+                (MARKET_ID, Some(110), 3),
+                (MARKET_ID + 1, Some(50), 3),
+                // Markets without fills are still listed.
+                (MARKET_ID + 2, None, 0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn only_the_requested_markets_are_listed() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx).await;
+        assert_eq!(
+            market_tickers(&mut tx, &[MARKET_ID + 1]).await,
+            [(MARKET_ID + 1, Some(50), 3)]
+        );
+    }
+}
+
+mod aggregation_lag {
+    use serde_json::json;
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::test_db::{insert, MARKET_ID};
+
+    /// Newer than any other event, so that the events of the tests are the last ones.
+    const LAST: i64 = i64::MAX - 10;
+
+    /// Records an order placement at `txn_version`, on 2024-01-01.
+    async fn place(conn: &mut PgConnection, txn_version: i64) {
+        insert(
+            conn,
+            "place_limit_order_events",
+            json!({
+                "txn_version": txn_version,
+                "market_id": MARKET_ID,
+                "user": "0xa",
+                "order_id": txn_version,
+                "side": false,
+                "initial_size": 1,
+                "price": 1,
+                "size": 1,
+            }),
+        )
+        .await;
+    }
+
+    /// Returns the aggregated and source transaction versions, the lag and the age in seconds of
+    /// the user history.
+    async fn user_history_lag(conn: &mut PgConnection) -> (i64, i64, i64, f64) {
+        sqlx::query_as(
+            "SELECT txn_version::int8, source_txn_version::int8, lag::int8, age_seconds::float8 \
+             FROM aggregation_lag WHERE model_name = 'UserHistory'",
+        )
+        .fetch_one(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn lag_is_the_gap_to_the_last_source_event() {
+        let mut tx = test_db::begin().await;
+        place(&mut tx, LAST - 2).await;
+        tx.execute(
+            format!(
+                "DELETE FROM aggregator.user_history_last_indexed_txn; \
+                 INSERT INTO aggregator.user_history_last_indexed_txn VALUES ({})",
+                LAST - 2
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+        test_db::as_web_anon(&mut tx).await;
+        let (txn_version, source_txn_version, lag, age_seconds) = user_history_lag(&mut tx).await;
+        assert_eq!(
+            (txn_version, source_txn_version, lag),
+            (LAST - 2, LAST - 2, 0)
+        );
+        // The placement happened on 2024-01-01.
+        assert!(age_seconds > 86400. * 365., "{age_seconds}");
+
+        tx.execute("RESET ROLE").await.unwrap();
+        place(&mut tx, LAST).await;
+        test_db::as_web_anon(&mut tx).await;
+        let (txn_version, source_txn_version, lag, _) = user_history_lag(&mut tx).await;
+        assert_eq!((txn_version, source_txn_version, lag), (LAST - 2, LAST, 2));
+    }
+}
+
+mod orderbook_snapshot {
+    use serde_json::{json, Value};
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::test_db::{insert, insert_order, sqlstate, MARKET_ID};
+
+    async fn orderbook_snapshot(
+        conn: &mut PgConnection,
+        market_id: i64,
+    ) -> Result<Value, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_scalar("SELECT orderbook_snapshot($1)")
+            .bind(market_id)
+            .fetch_one(conn)
+            .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn levels_are_tagged_with_the_aggregated_version() {
+        let mut tx = test_db::begin().await;
+        insert(
+            &mut tx,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        tx.execute(
+            "DELETE FROM aggregator.user_history_last_indexed_txn; \
+             INSERT INTO aggregator.user_history_last_indexed_txn VALUES (1234)",
+        )
+        .await
+        .unwrap();
+        for (order_id, direction, price, remaining_size, order_status) in [
+            (1, "bid", 10, 2, "open"),
+            (2, "bid", 10, 3, "open"),
+            (3, "bid", 9, 1, "open"),
+            (4, "bid", 11, 5, "closed"),
+            (5, "ask", 13, 1, "open"),
+            (6, "ask", 12, 4, "open"),
+        ] {
+            insert_order(
+                &mut tx,
+                json!({
+                    "order_id": order_id,
+                    "direction": direction,
+                    "price": price,
+                    "remaining_size": remaining_size,
+                    "order_status": order_status,
+                }),
+            )
+            .await;
+        }
+        assert_eq!(
+            orderbook_snapshot(&mut tx, MARKET_ID).await.unwrap(),
+            json!({
+                "market_id": MARKET_ID,
+                "txn_version": 1234,
+                "bids": [{ "price": 10, "size": 5 }, { "price": 9, "size": 1 }],
+                "asks": [{ "price": 12, "size": 4 }, { "price": 13, "size": 1 }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_market_is_not_found() {
+        let mut tx = test_db::begin().await;
+        assert_eq!(
+            sqlstate(orderbook_snapshot(&mut tx, MARKET_ID).await).as_deref(),
+            Some("PT404")
+        );
+    }
+}
+
+mod market_info {
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde_json::{json, Value};
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::test_db::{insert, sqlstate, MARKET_ID};
+
+    /// Returns the `data` of the info of [`MARKET_ID`].
+    async fn market_info(conn: &mut PgConnection) -> Result<Value, sqlx::Error> {
+        test_db::as_web_anon(conn).await;
+        sqlx::query_scalar("SELECT market_info($1) -> 'data'")
+            .bind(MARKET_ID)
+            .fetch_one(conn)
+            .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn parameters_come_with_the_freshness_of_the_market() {
+        let mut tx = test_db::begin().await;
+        insert(
+            &mut tx,
+            "market_registration_events",
+            json!({
+                "txn_version": 1,
+                "market_id": MARKET_ID,
+                "lot_size": 1000,
+                "tick_size": 5,
+                "min_size": 3,
+            }),
+        )
+        .await;
+        tx.execute(
+            "DELETE FROM aggregator.user_history_last_indexed_txn; \
+             INSERT INTO aggregator.user_history_last_indexed_txn VALUES (1234)",
+        )
+        .await
+        .unwrap();
+        insert(
+            &mut tx,
+            "fill_events",
+            json!({
+                "txn_version": 100,
+                "emit_address": "0xa",
+                "time": "2024-01-01T00:00:00Z",
+                "market_id": MARKET_ID,
+                "maker_address": "0xa",
+                "maker_order_id": 1,
+                "maker_side": true,
+                "taker_address": "0xb",
+                "taker_order_id": 2,
+                "price": 10,
+                "size": 1,
+                "taker_quote_fees_paid": 0,
+            }),
+        )
+        .await;
+        let data = market_info(&mut tx).await.unwrap();
+        for (field, value) in [
+            ("market_id", MARKET_ID),
+            ("lot_size", 1000),
+            ("tick_size", 5),
+            ("min_size", 3),
+            ("aggregated_txn_version", 1234),
+        ] {
+            assert_eq!(data[field], json!(value), "{field}");
+        }
+        let last_fill_time: DateTime<Utc> =
+            serde_json::from_value(data["last_fill_time"].clone()).unwrap();
+        assert_eq!(
+            last_fill_time,
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert!(data["last_fill_age_seconds"].as_f64().unwrap() > 0.);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn market_without_fills_has_no_freshness() {
+        let mut tx = test_db::begin().await;
+        insert(
+            &mut tx,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID }),
+        )
+        .await;
+        let data = market_info(&mut tx).await.unwrap();
+        assert_eq!(data["last_fill_time"], Value::Null);
+        assert_eq!(data["last_fill_age_seconds"], Value::Null);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unknown_market_is_not_found() {
+        let mut tx = test_db::begin().await;
+        assert_eq!(
+            sqlstate(market_info(&mut tx).await).as_deref(),
+            Some("PT404")
+        );
+    }
+}
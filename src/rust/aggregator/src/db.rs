@@ -0,0 +1,296 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use sqlx::{Executor, PgPool};
+use sqlx_postgres::PgPoolOptions;
+
+/// Default maximum number of connections in the pool.
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+/// Default time to wait for a connection to be available in the pool.
+pub const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Version of the latest diesel migration the aggregator relies on, to bump whenever a migration
+/// adds a table or column the aggregator reads or writes.
+pub const REQUIRED_MIGRATION: &str = "20261016105300";
+
+/// Configuration of the database connection pool.
+#[derive(Clone, Debug)]
+pub struct DbConfig {
+    pub database_url: String,
+    /// Maximum number of connections the pool keeps open.
+    pub max_connections: u32,
+    /// How long to wait for a free connection before giving up with
+    /// [`sqlx::Error::PoolTimedOut`].
+    pub acquire_timeout: Duration,
+    /// Statement timeout set on every connection, if any.
+    pub statement_timeout: Option<Duration>,
+}
+
+impl DbConfig {
+    pub fn new(database_url: String) -> Self {
+        Self {
+            database_url,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
+            statement_timeout: None,
+        }
+    }
+}
+
+/// Creates the connection pool shared by all pipelines.
+///
+/// Every connection defaults to the repeatable read isolation level.
+pub async fn connect(config: &DbConfig) -> Result<PgPool, sqlx::Error> {
+    let statement_timeout = config.statement_timeout;
+    PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .acquire_timeout(config.acquire_timeout)
+        .after_connect(move |conn, _| {
+            Box::pin(async move {
+                conn.execute("SET default_transaction_isolation TO 'repeatable read'")
+                    .await?;
+                if let Some(timeout) = statement_timeout {
+                    conn.execute(
+                        format!("SET statement_timeout TO {}", timeout.as_millis()).as_str(),
+                    )
+                    .await?;
+                }
+                Ok(())
+            })
+        })
+        .connect(&config.database_url)
+        .await
+}
+
+/// Fails with an error naming the missing migration if [`REQUIRED_MIGRATION`] has not been run on
+/// the database yet.
+///
+/// Meant to be called once at startup, so that an aggregator deployed before its migrations
+/// refuses to start instead of failing every batch on a missing column.
+pub async fn check_schema(pool: &PgPool) -> anyhow::Result<()> {
+    let latest: Option<String> =
+        sqlx::query_scalar("SELECT MAX(version) FROM __diesel_schema_migrations")
+            .fetch_one(pool)
+            .await
+            .map_err(|e| anyhow!("Could not read the migrations run on the database: {e}"))?;
+    // Versions are timestamps of the same length, so they sort as strings.
+    match latest {
+        Some(latest) if latest.as_str() >= REQUIRED_MIGRATION => Ok(()),
+        latest => Err(anyhow!(
+            "The database schema is older than this aggregator: it requires migration {}, but the \
+             latest migration run is {}. Run the migrations first.",
+            REQUIRED_MIGRATION,
+            latest.as_deref().unwrap_or("none"),
+        )),
+    }
+}
+
+/// Fails with an error naming every column of `expected` (see
+/// [`crate::Pipeline::expected_columns`]) that the database lacks.
+///
+/// Tables are given as `schema.table`, or as `table` for the `public` schema. Columns the
+/// database has on top of the expected ones are fine, since migrations may add columns the
+/// pipeline does not use yet.
+pub async fn check_columns(
+    pool: &PgPool,
+    pipeline: &str,
+    expected: &[(&str, &[&str])],
+) -> anyhow::Result<()> {
+    let mut missing = vec![];
+    for (table, columns) in expected {
+        let (schema, name) = table.split_once('.').unwrap_or(("public", table));
+        let actual: Vec<String> = sqlx::query_scalar(
+            "SELECT column_name::text FROM information_schema.columns \
+             WHERE table_schema = $1 AND table_name = $2",
+        )
+        .bind(schema)
+        .bind(name)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| anyhow!("Could not read the columns of {table}: {e}"))?;
+        if actual.is_empty() {
+            missing.push(format!("{table} (the whole table)"));
+            continue;
+        }
+        missing.extend(
+            columns
+                .iter()
+                .filter(|column| !actual.iter().any(|actual| actual == *column))
+                .map(|column| format!("{table}.{column}")),
+        );
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "The database schema does not match the queries of {pipeline}, it lacks {}. Run the \
+             migrations first.",
+            missing.join(", "),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn acquire_times_out_when_the_pool_is_exhausted() {
+        let mut config = DbConfig::new(
+            std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests"),
+        );
+        config.max_connections = 1;
+        config.acquire_timeout = Duration::from_millis(200);
+        let pool = connect(&config).await.unwrap();
+        let _held = pool.acquire().await.unwrap();
+
+        let start = Instant::now();
+        let result = pool.acquire().await;
+        let waited = start.elapsed();
+        assert!(
+            matches!(result, Err(sqlx::Error::PoolTimedOut)),
+            "{:?}",
+            result.map(|_| ())
+        );
+        assert!(waited >= config.acquire_timeout, "{waited:?}");
+        assert!(waited < Duration::from_secs(5), "{waited:?}");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn slow_statement_aborts_the_transaction() {
+        let mut config = DbConfig::new(
+            std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests"),
+        );
+        config.statement_timeout = Some(Duration::from_millis(100));
+        let pool = connect(&config).await.unwrap();
+        let mut transaction = crate::util::create_repeatable_read_transaction(&pool)
+            .await
+            .unwrap();
+
+        let start = Instant::now();
+        let result = transaction.execute("SELECT pg_sleep(5)").await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let error = crate::util::to_pipeline_error(result.unwrap_err());
+        assert!(error.is_statement_timeout(), "{error:?}");
+        assert!(!error.is_connection_error(), "{error:?}");
+        // 25P02 is in_failed_sql_transaction.
+        let result = transaction.execute("SELECT 1").await;
+        assert_eq!(
+            result
+                .unwrap_err()
+                .as_database_error()
+                .unwrap()
+                .code()
+                .as_deref(),
+            Some("25P02")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn duplicate_insert_is_a_unique_violation() {
+        let config = DbConfig::new(
+            std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests"),
+        );
+        let pool = connect(&config).await.unwrap();
+        // Outside of a transaction, a failed statement leaves the connection usable.
+        let mut conn = pool.acquire().await.unwrap();
+        conn.execute("CREATE TEMPORARY TABLE t (id int PRIMARY KEY, n int NOT NULL)")
+            .await
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES (1, 1)").await.unwrap();
+        let result = conn.execute("INSERT INTO t VALUES (1, 2)").await;
+        let error = crate::util::to_pipeline_error(result.unwrap_err());
+        assert!(error.is_unique_violation(), "{error:?}");
+        // Other constraints are not unique violations.
+        let result = conn.execute("INSERT INTO t VALUES (2, NULL)").await;
+        let error = crate::util::to_pipeline_error(result.unwrap_err());
+        assert!(!error.is_unique_violation(), "{error:?}");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn missing_columns_and_tables_are_named() {
+        let config = DbConfig::new(
+            std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests"),
+        );
+        let pool = connect(&config).await.unwrap();
+        pool.execute(
+            "DROP SCHEMA IF EXISTS aggregator_check_columns CASCADE; \
+             CREATE SCHEMA aggregator_check_columns; \
+             CREATE TABLE aggregator_check_columns.t (a int, b int, c int)",
+        )
+        .await
+        .unwrap();
+        let expected: &[(&str, &[&str])] = &[
+            ("aggregator_check_columns.t", &["a", "b"]),
+            ("aggregator_check_columns.gone", &["a"]),
+        ];
+        let before = check_columns(&pool, "Test", &expected[..1]).await;
+        pool.execute("ALTER TABLE aggregator_check_columns.t DROP COLUMN b")
+            .await
+            .unwrap();
+        let after = check_columns(&pool, "Test", expected).await;
+        pool.execute("DROP SCHEMA aggregator_check_columns CASCADE")
+            .await
+            .unwrap();
+
+        // Extra columns are fine.
+        before.unwrap();
+        let error = after.unwrap_err().to_string();
+        assert!(
+            error.contains(
+                "queries of Test, it lacks aggregator_check_columns.t.b, \
+                 aggregator_check_columns.gone (the whole table)."
+            ),
+            "{error}"
+        );
+    }
+
+    /// Connects with a single connection, on which a temporary `__diesel_schema_migrations`
+    /// holding `versions` shadows the real one.
+    async fn with_migrations(versions: &[&str]) -> PgPool {
+        let mut config = DbConfig::new(
+            std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests"),
+        );
+        config.max_connections = 1;
+        let pool = connect(&config).await.unwrap();
+        pool.execute("CREATE TEMPORARY TABLE __diesel_schema_migrations (version VARCHAR(50))")
+            .await
+            .unwrap();
+        for version in versions {
+            sqlx::query("INSERT INTO __diesel_schema_migrations VALUES ($1)")
+                .bind(version)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        pool
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn outdated_schema_is_refused_with_the_missing_migration() {
+        let pool = with_migrations(&["20231106133000", "20240101000000"]).await;
+        let error = check_schema(&pool).await.unwrap_err().to_string();
+        assert!(error.contains(REQUIRED_MIGRATION), "{error}");
+        assert!(error.contains("20240101000000"), "{error}");
+
+        let pool = with_migrations(&[]).await;
+        let error = check_schema(&pool).await.unwrap_err().to_string();
+        assert!(error.contains("latest migration run is none"), "{error}");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn schema_at_or_past_the_required_migration_is_accepted() {
+        let pool = with_migrations(&[REQUIRED_MIGRATION]).await;
+        check_schema(&pool).await.unwrap();
+        let pool = with_migrations(&["20991231000000"]).await;
+        check_schema(&pool).await.unwrap();
+    }
+}
@@ -1,11 +1,12 @@
-#[derive(sqlx::Type, Debug)]
-#[sqlx(type_name = "order_direction", rename_all = "lowercase")]
-pub enum OrderDirection {
-    Ask,
-    Bid,
-    Sell,
-    Buy,
-}
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
 
 #[derive(sqlx::Type, Debug)]
 #[sqlx(type_name = "order_status", rename_all = "lowercase")]
@@ -22,3 +23,220 @@ pub enum OrderType {
     Market,
     Swap,
 }
+
+/// How a limit order matching against an order of the same user is handled, as stored in
+/// `aggregator.user_history` with Econia's on-chain encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelfMatchBehavior {
+    Abort,
+    CancelBoth,
+    CancelMaker,
+    CancelTaker,
+    /// A value Econia did not define when this was written.
+    Unknown(u8),
+}
+
+impl From<u8> for SelfMatchBehavior {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Abort,
+            1 => Self::CancelBoth,
+            2 => Self::CancelMaker,
+            3 => Self::CancelTaker,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<SelfMatchBehavior> for u8 {
+    fn from(value: SelfMatchBehavior) -> Self {
+        match value {
+            SelfMatchBehavior::Abort => 0,
+            SelfMatchBehavior::CancelBoth => 1,
+            SelfMatchBehavior::CancelMaker => 2,
+            SelfMatchBehavior::CancelTaker => 3,
+            SelfMatchBehavior::Unknown(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for SelfMatchBehavior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Abort => f.write_str("abort"),
+            Self::CancelBoth => f.write_str("cancel_both"),
+            Self::CancelMaker => f.write_str("cancel_maker"),
+            Self::CancelTaker => f.write_str("cancel_taker"),
+            Self::Unknown(value) => write!(f, "unknown({value})"),
+        }
+    }
+}
+
+/// The restriction of a limit order, as stored in `aggregator.user_history` with Econia's
+/// on-chain encoding.
+// `NoRestriction` is Econia's name for it.
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Restriction {
+    NoRestriction,
+    FillOrAbort,
+    ImmediateOrCancel,
+    PostOrAbort,
+    /// A value Econia did not define when this was written.
+    Unknown(u8),
+}
+
+impl From<u8> for Restriction {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::NoRestriction,
+            1 => Self::FillOrAbort,
+            2 => Self::ImmediateOrCancel,
+            3 => Self::PostOrAbort,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<Restriction> for u8 {
+    fn from(value: Restriction) -> Self {
+        match value {
+            Restriction::NoRestriction => 0,
+            Restriction::FillOrAbort => 1,
+            Restriction::ImmediateOrCancel => 2,
+            Restriction::PostOrAbort => 3,
+            Restriction::Unknown(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for Restriction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoRestriction => f.write_str("no_restriction"),
+            Self::FillOrAbort => f.write_str("fill_or_abort"),
+            Self::ImmediateOrCancel => f.write_str("immediate_or_cancel"),
+            Self::PostOrAbort => f.write_str("post_or_abort"),
+            Self::Unknown(value) => write!(f, "unknown({value})"),
+        }
+    }
+}
+
+/// Stores an enum with an on-chain `u8` encoding in an `int2` column, and serializes it as its
+/// name, so that unknown values are carried through instead of failing.
+macro_rules! int2_enum {
+    ($name:ident) => {
+        impl Type<Postgres> for $name {
+            fn type_info() -> PgTypeInfo {
+                <i16 as Type<Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &PgTypeInfo) -> bool {
+                <i16 as Type<Postgres>>::compatible(ty)
+            }
+        }
+
+        impl Encode<'_, Postgres> for $name {
+            fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+                <i16 as Encode<Postgres>>::encode(i16::from(u8::from(*self)), buf)
+            }
+        }
+
+        impl<'r> Decode<'r, Postgres> for $name {
+            fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+                let value = <i16 as Decode<Postgres>>::decode(value)?;
+                Ok(Self::from(u8::try_from(value)?))
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+    };
+}
+
+int2_enum!(SelfMatchBehavior);
+int2_enum!(Restriction);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+
+    const SELF_MATCH_BEHAVIORS: [(u8, SelfMatchBehavior, &str); 5] = [
+        (0, SelfMatchBehavior::Abort, "abort"),
+        (1, SelfMatchBehavior::CancelBoth, "cancel_both"),
+        (2, SelfMatchBehavior::CancelMaker, "cancel_maker"),
+        (3, SelfMatchBehavior::CancelTaker, "cancel_taker"),
+        (4, SelfMatchBehavior::Unknown(4), "unknown(4)"),
+    ];
+
+    const RESTRICTIONS: [(u8, Restriction, &str); 5] = [
+        (0, Restriction::NoRestriction, "no_restriction"),
+        (1, Restriction::FillOrAbort, "fill_or_abort"),
+        (2, Restriction::ImmediateOrCancel, "immediate_or_cancel"),
+        (3, Restriction::PostOrAbort, "post_or_abort"),
+        (4, Restriction::Unknown(4), "unknown(4)"),
+    ];
+
+    #[test]
+    fn self_match_behavior_from_u8() {
+        for (value, behavior, name) in SELF_MATCH_BEHAVIORS {
+            assert_eq!(SelfMatchBehavior::from(value), behavior);
+            assert_eq!(u8::from(behavior), value);
+            assert_eq!(serde_json::to_value(behavior).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn restriction_from_u8() {
+        for (value, restriction, name) in RESTRICTIONS {
+            assert_eq!(Restriction::from(value), restriction);
+            assert_eq!(u8::from(restriction), value);
+            assert_eq!(serde_json::to_value(restriction).unwrap(), name);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn decode_from_int2() {
+        let mut tx = test_db::begin().await;
+        for (value, behavior, _) in SELF_MATCH_BEHAVIORS {
+            let decoded: SelfMatchBehavior = sqlx::query_scalar("SELECT $1::int2")
+                .bind(i16::from(value))
+                .fetch_one(&mut *tx)
+                .await
+                .unwrap();
+            assert_eq!(decoded, behavior);
+            let encoded: i16 = sqlx::query_scalar("SELECT $1")
+                .bind(behavior)
+                .fetch_one(&mut *tx)
+                .await
+                .unwrap();
+            assert_eq!(encoded, i16::from(value));
+        }
+        for (value, restriction, _) in RESTRICTIONS {
+            let decoded: Restriction = sqlx::query_scalar("SELECT $1::int2")
+                .bind(i16::from(value))
+                .fetch_one(&mut *tx)
+                .await
+                .unwrap();
+            assert_eq!(decoded, restriction);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn decode_rejects_values_outside_u8() {
+        let mut tx = test_db::begin().await;
+        for value in [-1_i16, 256] {
+            let decoded = sqlx::query_scalar::<_, Restriction>("SELECT $1::int2")
+                .bind(value)
+                .fetch_one(&mut *tx)
+                .await;
+            assert!(decoded.is_err(), "{value}");
+        }
+    }
+}
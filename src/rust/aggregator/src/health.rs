@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::alert::Alerter;
+
+/// The outcome of the last runs of a pipeline.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Health {
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Number of runs that failed since the last successful one.
+    pub consecutive_errors: u32,
+}
+
+/// The [`Health`] of every pipeline, keyed by model name, shared between the pipeline tasks.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineHealth {
+    pipelines: Arc<Mutex<HashMap<String, Health>>>,
+    /// Notified of every run, to alert when a pipeline keeps failing.
+    alerter: Option<Alerter>,
+}
+
+impl PipelineHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends alerts through `alerter` when a pipeline keeps failing and when it recovers.
+    pub fn with_alerter(mut self, alerter: Alerter) -> Self {
+        self.alerter = Some(alerter);
+        self
+    }
+
+    /// Records that a run of `pipeline` succeeded at `now`.
+    pub fn record_success(&self, pipeline: &str, now: DateTime<Utc>) {
+        {
+            let mut pipelines = self.pipelines.lock().unwrap();
+            let health = pipelines.entry(pipeline.to_string()).or_default();
+            health.last_success_at = Some(now);
+            health.consecutive_errors = 0;
+        }
+        if let Some(alerter) = &self.alerter {
+            alerter.record_success(pipeline);
+        }
+    }
+
+    /// Records that a run of `pipeline` failed with `error` at `now`.
+    pub fn record_error(&self, pipeline: &str, error: &str, now: DateTime<Utc>) {
+        let consecutive_errors = {
+            let mut pipelines = self.pipelines.lock().unwrap();
+            let health = pipelines.entry(pipeline.to_string()).or_default();
+            health.last_error_at = Some(now);
+            health.last_error = Some(error.to_string());
+            health.consecutive_errors += 1;
+            health.consecutive_errors
+        };
+        if let Some(alerter) = &self.alerter {
+            alerter.record_error(pipeline, error, consecutive_errors);
+        }
+    }
+
+    /// Returns the health of every pipeline that ran at least once.
+    pub fn snapshot(&self) -> Vec<(String, Health)> {
+        self.pipelines
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(pipeline, health)| (pipeline.clone(), health.clone()))
+            .collect()
+    }
+}
+
+/// Writes `health` to `aggregator.pipeline_health` every `interval`, which backs the
+/// `/pipeline_health` endpoint of the REST API.
+///
+/// Errors are logged and the write is retried at the next interval, so that a monitoring hiccup
+/// never stops the aggregator.
+pub async fn publish(pool: PgPool, health: PipelineHealth, interval: Duration) -> ! {
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(e) = save(&pool, &health).await {
+            tracing::warn!(error = %e, "Could not save pipeline health.");
+        }
+    }
+}
+
+async fn save(pool: &PgPool, health: &PipelineHealth) -> Result<(), sqlx::Error> {
+    let snapshot = health.snapshot();
+    if snapshot.is_empty() {
+        return Ok(());
+    }
+    let mut pipelines = vec![];
+    let mut last_success_at = vec![];
+    let mut last_error_at = vec![];
+    let mut last_error = vec![];
+    let mut consecutive_errors = vec![];
+    for (pipeline, health) in snapshot {
+        pipelines.push(pipeline);
+        last_success_at.push(health.last_success_at);
+        last_error_at.push(health.last_error_at);
+        last_error.push(health.last_error);
+        consecutive_errors.push(health.consecutive_errors as i64);
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO aggregator.pipeline_health (
+            pipeline,
+            last_success_at,
+            last_error_at,
+            last_error,
+            consecutive_errors
+        )
+        SELECT * FROM UNNEST($1::text[], $2::timestamptz[], $3::timestamptz[], $4::text[], $5::bigint[])
+        ON CONFLICT (pipeline) DO UPDATE SET
+            last_success_at = EXCLUDED.last_success_at,
+            last_error_at = EXCLUDED.last_error_at,
+            last_error = EXCLUDED.last_error,
+            consecutive_errors = EXCLUDED.consecutive_errors
+        "#,
+    )
+    .bind(&pipelines)
+    .bind(&last_success_at)
+    .bind(&last_error_at)
+    .bind(&last_error)
+    .bind(&consecutive_errors)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, second).unwrap()
+    }
+
+    #[test]
+    fn errors_are_counted_until_the_next_success() {
+        let health = PipelineHealth::new();
+        health.record_success("Prices", at(1));
+        health.record_error("Prices", "timeout", at(2));
+        health.record_error("Prices", "deadlock", at(3));
+        assert_eq!(
+            health.snapshot(),
+            [(
+                String::from("Prices"),
+                Health {
+                    last_success_at: Some(at(1)),
+                    last_error_at: Some(at(3)),
+                    last_error: Some(String::from("deadlock")),
+                    consecutive_errors: 2,
+                }
+            )]
+        );
+
+        // The last error is kept for operators to look at.
+        health.record_success("Prices", at(4));
+        assert_eq!(
+            health.snapshot(),
+            [(
+                String::from("Prices"),
+                Health {
+                    last_success_at: Some(at(4)),
+                    last_error_at: Some(at(3)),
+                    last_error: Some(String::from("deadlock")),
+                    consecutive_errors: 0,
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn pipelines_are_tracked_separately() {
+        let health = PipelineHealth::new();
+        health.record_success("Prices", at(1));
+        health.record_error("UserHistory", "timeout", at(2));
+        let mut snapshot = health.snapshot();
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+        let consecutive_errors: Vec<_> = snapshot
+            .iter()
+            .map(|(pipeline, health)| (pipeline.as_str(), health.consecutive_errors))
+            .collect();
+        assert_eq!(consecutive_errors, [("Prices", 0), ("UserHistory", 1)]);
+    }
+}
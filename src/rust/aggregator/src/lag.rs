@@ -0,0 +1,444 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use sqlx::PgPool;
+
+/// Tracks how far behind its source events each pipeline is, and trips when one of them stays
+/// more than `max_lag` transaction versions behind for longer than `max_duration`.
+///
+/// A tripped pipeline recovers as soon as its lag drops back under `max_lag`.
+#[derive(Debug)]
+pub struct LagBreaker {
+    max_lag: u64,
+    max_duration: Duration,
+    exceeded_since: HashMap<String, Instant>,
+    tripped: HashSet<String>,
+}
+
+impl LagBreaker {
+    pub fn new(max_lag: u64, max_duration: Duration) -> Self {
+        Self {
+            max_lag,
+            max_duration,
+            exceeded_since: HashMap::new(),
+            tripped: HashSet::new(),
+        }
+    }
+
+    /// Records the lag of `pipeline` as observed at `now`.
+    pub fn observe(&mut self, pipeline: &str, lag: u64, now: Instant) {
+        if lag > self.max_lag {
+            let since = *self
+                .exceeded_since
+                .entry(pipeline.to_string())
+                .or_insert(now);
+            if now.duration_since(since) >= self.max_duration
+                && self.tripped.insert(pipeline.to_string())
+            {
+                tracing::warn!(
+                    pipeline,
+                    lag,
+                    max_lag = self.max_lag,
+                    lagging_for_ms = now.duration_since(since).as_millis(),
+                    "Pipeline is lagging behind, tripping the lag breaker."
+                );
+            }
+        } else {
+            self.exceeded_since.remove(pipeline);
+            if self.tripped.remove(pipeline) {
+                tracing::info!(
+                    pipeline,
+                    lag,
+                    "Pipeline caught up, clearing the lag breaker."
+                );
+            }
+        }
+    }
+
+    /// Returns `true` if at least one pipeline is lagging.
+    pub fn is_tripped(&self) -> bool {
+        !self.tripped.is_empty()
+    }
+
+    /// Returns the pipelines that are currently lagging.
+    pub fn tripped(&self) -> impl Iterator<Item = &String> {
+        self.tripped.iter()
+    }
+}
+
+/// Decides which pipelines are far enough behind to run their batches back to back instead of
+/// waiting for their poll interval, e.g. during the initial backfill.
+///
+/// A pipeline enters catch-up mode when its lag goes over `enter_lag` and leaves it once its lag
+/// drops under `exit_lag`, so that it does not flap around a single threshold. Shared between the
+/// lag monitor and the pipeline tasks.
+#[derive(Clone, Debug)]
+pub struct CatchUp {
+    enter_lag: u64,
+    exit_lag: u64,
+    catching_up: Arc<Mutex<HashSet<String>>>,
+}
+
+impl CatchUp {
+    pub fn new(enter_lag: u64, exit_lag: u64) -> Self {
+        Self {
+            enter_lag,
+            exit_lag: exit_lag.min(enter_lag),
+            catching_up: Default::default(),
+        }
+    }
+
+    /// Records the lag of `pipeline`.
+    pub fn observe(&self, pipeline: &str, lag: u64) {
+        let mut catching_up = self.catching_up.lock().unwrap();
+        if lag > self.enter_lag {
+            if catching_up.insert(pipeline.to_string()) {
+                tracing::info!(
+                    pipeline,
+                    lag,
+                    "Pipeline is far behind, entering catch-up mode."
+                );
+            }
+        } else if lag < self.exit_lag && catching_up.remove(pipeline) {
+            tracing::info!(
+                pipeline,
+                lag,
+                "Pipeline caught up, resuming normal polling."
+            );
+        }
+    }
+
+    /// Returns `true` if `pipeline` should run its next batch right away.
+    pub fn is_active(&self, pipeline: &str) -> bool {
+        self.catching_up.lock().unwrap().contains(pipeline)
+    }
+}
+
+/// Makes pipelines yield to lagging pipelines of a higher [`Pipeline::priority`], so that the
+/// most important data stays fresh when the database cannot keep up with every pipeline.
+///
+/// A pipeline lags while its lag is over `max_lag`, and pipelines of a lower priority skip their
+/// batches until it caught up. A pipeline that skipped its batches for `max_yield` runs one
+/// anyway, so that it is never starved. Shared between the lag monitor and the pipeline tasks.
+///
+/// [`Pipeline::priority`]: crate::Pipeline::priority
+#[derive(Clone, Debug)]
+pub struct Priorities {
+    max_lag: u64,
+    max_yield: Duration,
+    priorities: Arc<HashMap<String, u8>>,
+    lagging: Arc<Mutex<HashSet<String>>>,
+    yielding_since: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl Priorities {
+    /// `priorities` maps the model name of every pipeline to its priority.
+    pub fn new(max_lag: u64, max_yield: Duration, priorities: HashMap<String, u8>) -> Self {
+        Self {
+            max_lag,
+            max_yield,
+            priorities: Arc::new(priorities),
+            lagging: Default::default(),
+            yielding_since: Default::default(),
+        }
+    }
+
+    /// Records the lag of `pipeline`.
+    pub fn observe(&self, pipeline: &str, lag: u64) {
+        let mut lagging = self.lagging.lock().unwrap();
+        if lag > self.max_lag {
+            if lagging.insert(pipeline.to_string()) {
+                tracing::warn!(
+                    pipeline,
+                    lag,
+                    "Pipeline is lagging, pipelines of a lower priority yield to it."
+                );
+            }
+        } else if lagging.remove(pipeline) {
+            tracing::info!(
+                pipeline,
+                lag,
+                "Pipeline caught up, pipelines of a lower priority resume."
+            );
+        }
+    }
+
+    /// Returns `true` if `pipeline` should skip its next batch at `now`, because a pipeline of a
+    /// higher priority is lagging and `pipeline` did not skip its batches for `max_yield` yet.
+    pub fn should_yield(&self, pipeline: &str, now: Instant) -> bool {
+        let priority = |pipeline: &str| self.priorities.get(pipeline).copied().unwrap_or(0);
+        let own = priority(pipeline);
+        let behind_lagging = self
+            .lagging
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|lagging| priority(lagging) > own);
+        let mut yielding_since = self.yielding_since.lock().unwrap();
+        if !behind_lagging {
+            yielding_since.remove(pipeline);
+            return false;
+        }
+        let since = *yielding_since.entry(pipeline.to_string()).or_insert(now);
+        if now.duration_since(since) < self.max_yield {
+            return true;
+        }
+        tracing::warn!(
+            pipeline,
+            yielding_for_ms = now.duration_since(since).as_millis(),
+            "Pipeline yielded for too long, running a batch anyway."
+        );
+        // Yields again for up to `max_yield` after this batch.
+        yielding_since.insert(pipeline.to_string(), now);
+        false
+    }
+}
+
+/// Polls `aggregator.pipeline_lag` every `interval` and feeds it to `catch_up`, `priorities` and
+/// `breaker`.
+///
+/// The state of `breaker` is mirrored into `aggregator.lagging_pipelines`, which backs the
+/// readiness check of the REST API.
+///
+/// Errors are logged and the poll is retried at the next interval, so that a monitoring hiccup
+/// never stops the aggregator.
+pub async fn monitor(
+    pool: PgPool,
+    mut breaker: Option<LagBreaker>,
+    catch_up: Option<CatchUp>,
+    priorities: Option<Priorities>,
+    interval: Duration,
+) -> ! {
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(e) = poll(
+            &pool,
+            breaker.as_mut(),
+            catch_up.as_ref(),
+            priorities.as_ref(),
+        )
+        .await
+        {
+            tracing::warn!(error = %e, "Could not check pipeline lag.");
+        }
+    }
+}
+
+async fn poll(
+    pool: &PgPool,
+    breaker: Option<&mut LagBreaker>,
+    catch_up: Option<&CatchUp>,
+    priorities: Option<&Priorities>,
+) -> Result<(), sqlx::Error> {
+    let lags: Vec<(String, i64)> =
+        sqlx::query_as("SELECT pipeline, lag::bigint FROM aggregator.pipeline_lag")
+            .fetch_all(pool)
+            .await?;
+    if let Some(catch_up) = catch_up {
+        for (pipeline, lag) in &lags {
+            catch_up.observe(pipeline, (*lag).max(0) as u64);
+        }
+    }
+    if let Some(priorities) = priorities {
+        for (pipeline, lag) in &lags {
+            priorities.observe(pipeline, (*lag).max(0) as u64);
+        }
+    }
+    let Some(breaker) = breaker else {
+        return Ok(());
+    };
+    let now = Instant::now();
+    for (pipeline, lag) in &lags {
+        breaker.observe(pipeline, (*lag).max(0) as u64, now);
+    }
+
+    let tripped: Vec<String> = breaker.tripped().cloned().collect();
+    let tripped_lags: Vec<i64> = tripped
+        .iter()
+        .map(|pipeline| {
+            lags.iter()
+                .find(|(p, _)| p == pipeline)
+                .map(|(_, lag)| *lag)
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut transaction = pool.begin().await?;
+    sqlx::query("DELETE FROM aggregator.lagging_pipelines WHERE pipeline <> ALL($1)")
+        .bind(&tripped)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO aggregator.lagging_pipelines (pipeline, lag)
+        SELECT * FROM UNNEST($1::text[], $2::bigint[])
+        ON CONFLICT (pipeline) DO UPDATE SET lag = EXCLUDED.lag
+        "#,
+    )
+    .bind(&tripped)
+    .bind(&tripped_lags)
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaker_trips_after_max_duration_and_clears() {
+        let mut breaker = LagBreaker::new(100, Duration::from_secs(60));
+        let start = Instant::now();
+
+        breaker.observe("Fees", 1_000, start);
+        assert!(!breaker.is_tripped());
+        breaker.observe("Fees", 1_000, start + Duration::from_secs(59));
+        assert!(!breaker.is_tripped());
+        breaker.observe("Fees", 1_000, start + Duration::from_secs(60));
+        assert!(breaker.is_tripped());
+        assert_eq!(breaker.tripped().collect::<Vec<_>>(), ["Fees"]);
+
+        breaker.observe("Fees", 100, start + Duration::from_secs(61));
+        assert!(!breaker.is_tripped());
+    }
+
+    #[test]
+    fn breaker_restarts_duration_when_lag_drops() {
+        let mut breaker = LagBreaker::new(100, Duration::from_secs(60));
+        let start = Instant::now();
+
+        breaker.observe("Fees", 1_000, start);
+        breaker.observe("Fees", 0, start + Duration::from_secs(30));
+        breaker.observe("Fees", 1_000, start + Duration::from_secs(40));
+        breaker.observe("Fees", 1_000, start + Duration::from_secs(90));
+        assert!(!breaker.is_tripped());
+        breaker.observe("Fees", 1_000, start + Duration::from_secs(100));
+        assert!(breaker.is_tripped());
+    }
+
+    #[test]
+    fn breaker_tracks_pipelines_separately() {
+        let mut breaker = LagBreaker::new(100, Duration::ZERO);
+        let now = Instant::now();
+
+        breaker.observe("Fees", 1_000, now);
+        breaker.observe("Prices", 1_000, now);
+        breaker.observe("Fees", 0, now);
+        assert_eq!(breaker.tripped().collect::<Vec<_>>(), ["Prices"]);
+    }
+
+    #[test]
+    fn catch_up_enters_over_enter_lag_and_exits_under_exit_lag() {
+        let catch_up = CatchUp::new(1_000, 100);
+
+        catch_up.observe("UserHistory", 1_000);
+        assert!(!catch_up.is_active("UserHistory"));
+        catch_up.observe("UserHistory", 1_001);
+        assert!(catch_up.is_active("UserHistory"));
+        // Stays in catch-up mode between the two thresholds.
+        catch_up.observe("UserHistory", 500);
+        catch_up.observe("UserHistory", 100);
+        assert!(catch_up.is_active("UserHistory"));
+        catch_up.observe("UserHistory", 99);
+        assert!(!catch_up.is_active("UserHistory"));
+        catch_up.observe("UserHistory", 500);
+        assert!(!catch_up.is_active("UserHistory"));
+    }
+
+    #[test]
+    fn catch_up_exit_lag_is_capped_by_enter_lag() {
+        let catch_up = CatchUp::new(100, 1_000);
+
+        catch_up.observe("Fees", 101);
+        assert!(catch_up.is_active("Fees"));
+        catch_up.observe("Fees", 100);
+        assert!(catch_up.is_active("Fees"));
+        catch_up.observe("Fees", 99);
+        assert!(!catch_up.is_active("Fees"));
+    }
+
+    #[test]
+    fn catch_up_is_shared_between_clones() {
+        let catch_up = CatchUp::new(100, 10);
+        let runner = catch_up.clone();
+
+        catch_up.observe("Fees", 1_000);
+        assert!(runner.is_active("Fees"));
+        assert!(!runner.is_active("Prices"));
+        catch_up.observe("Fees", 0);
+        assert!(!runner.is_active("Fees"));
+    }
+
+    fn priorities(max_yield: Duration) -> Priorities {
+        Priorities::new(
+            100,
+            max_yield,
+            HashMap::from([
+                (String::from("UserHistory"), 1),
+                (String::from("Fees"), 0),
+                (String::from("Prices"), 0),
+            ]),
+        )
+    }
+
+    #[test]
+    fn lower_priorities_yield_while_a_higher_one_lags() {
+        let priorities = priorities(Duration::from_secs(300));
+        let now = Instant::now();
+
+        assert!(!priorities.should_yield("Fees", now));
+        priorities.observe("UserHistory", 1_000);
+        assert!(priorities.should_yield("Fees", now));
+        assert!(priorities.should_yield("Prices", now));
+        assert!(!priorities.should_yield("UserHistory", now));
+
+        priorities.observe("UserHistory", 100);
+        assert!(!priorities.should_yield("Fees", now));
+        assert!(!priorities.should_yield("Prices", now));
+    }
+
+    #[test]
+    fn same_priority_never_yields() {
+        let priorities = priorities(Duration::from_secs(300));
+        let now = Instant::now();
+
+        priorities.observe("Fees", 1_000);
+        assert!(!priorities.should_yield("Prices", now));
+        assert!(!priorities.should_yield("UserHistory", now));
+    }
+
+    #[test]
+    fn yielding_is_capped_by_max_yield() {
+        let priorities = priorities(Duration::from_secs(300));
+        let start = Instant::now();
+
+        priorities.observe("UserHistory", 1_000);
+        assert!(priorities.should_yield("Fees", start));
+        assert!(priorities.should_yield("Fees", start + Duration::from_secs(299)));
+        // Runs one batch, then yields for up to another `max_yield`.
+        assert!(!priorities.should_yield("Fees", start + Duration::from_secs(300)));
+        assert!(priorities.should_yield("Fees", start + Duration::from_secs(301)));
+        assert!(priorities.should_yield("Fees", start + Duration::from_secs(599)));
+        assert!(!priorities.should_yield("Fees", start + Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn max_yield_restarts_once_caught_up() {
+        let priorities = priorities(Duration::from_secs(300));
+        let start = Instant::now();
+
+        priorities.observe("UserHistory", 1_000);
+        assert!(priorities.should_yield("Fees", start));
+        priorities.observe("UserHistory", 0);
+        assert!(!priorities.should_yield("Fees", start + Duration::from_secs(200)));
+        priorities.observe("UserHistory", 1_000);
+        assert!(priorities.should_yield("Fees", start + Duration::from_secs(300)));
+        assert!(priorities.should_yield("Fees", start + Duration::from_secs(599)));
+        assert!(!priorities.should_yield("Fees", start + Duration::from_secs(600)));
+    }
+}
@@ -1,4 +1,12 @@
+pub mod alert;
+pub mod amount;
+pub mod db;
+pub mod health;
+pub mod lag;
 pub mod pipeline;
+pub mod profile;
+pub mod schedule;
+pub mod trigger;
 pub mod util;
 
-pub use pipeline::{Pipeline, PipelineAggregationResult, PipelineError};
+pub use pipeline::{Pipeline, PipelineAggregationResult, PipelineError, ProcessSummary};
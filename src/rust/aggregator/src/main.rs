@@ -1,26 +1,43 @@
 use std::{
+    collections::HashMap,
+    path::PathBuf,
     str::FromStr,
     sync::Arc,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
-use aggregator::Pipeline;
+use aggregator::{
+    alert::{self, Alerter},
+    db::{self, DbConfig},
+    health::{self, PipelineHealth},
+    lag::{self, CatchUp, LagBreaker, Priorities},
+    profile::Sampler,
+    schedule::{self, TableLocks},
+    trigger::{self, Triggerable},
+    util::{self, wait_for_database, Backoff},
+    Pipeline, ProcessSummary,
+};
 use anyhow::{anyhow, Result};
 use aptos_sdk::rest_client::AptosBaseUrl;
 use bigdecimal::BigDecimal;
-use clap::{Parser, ValueEnum};
+use chrono::Utc;
+use clap::{Parser, Subcommand, ValueEnum};
 use pipelines::{
-    Candlesticks, Coins, EnumeratedVolume, Fees, Leaderboards, OrderHistoryPipelines, Prices,
-    RefreshMaterializedView, RollingVolume, UserBalances, UserHistory,
+    user_history::EventKind, Candlesticks, Coins, EnumeratedVolume, Fees, IntegratorVolume,
+    Leaderboards, MarketStats24h, OrderHistoryPipelines, Prices, RefreshMaterializedView,
+    RollingVolume, Trades, UserBalances, UserHistory,
 };
-use sqlx::Executor;
-use sqlx_postgres::PgPoolOptions;
 use tokio::{sync::Mutex, task::JoinSet};
 use tracing::Instrument;
 use url::Url;
 
+#[cfg(test)]
+mod api_tests;
 mod dbtypes;
 mod pipelines;
+mod replay;
+#[cfg(test)]
+mod test_db;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -44,6 +61,168 @@ struct Args {
     /// Aptos network.
     #[arg(short, long)]
     aptos_network: Option<AptosNetwork>,
+
+    /// Initial delay in milliseconds before probing the database after losing the connection.
+    #[arg(long)]
+    backoff_initial_ms: Option<u64>,
+
+    /// Maximum delay in milliseconds between two database probes.
+    #[arg(long)]
+    backoff_max_ms: Option<u64>,
+
+    /// Each pipeline waits a random delay of up to this many milliseconds before its first run,
+    /// so that pipelines do not all start at once. 0 by default.
+    #[arg(long)]
+    warmup_ms: Option<u64>,
+
+    /// Fraction of its poll interval, between 0 and 1, that a pipeline may randomly wait on top
+    /// of it before each poll, so that pipelines with the same interval drift apart. 0 by
+    /// default.
+    #[arg(long)]
+    poll_jitter: Option<f64>,
+
+    /// If set, a fill larger than the remaining size of its order fails the batch instead of
+    /// being clamped.
+    #[arg(long)]
+    strict_fills: bool,
+
+    /// At startup, recompute from their events the totals of the user history orders updated
+    /// within this many hours, and log those that drifted. Unset by default, which skips it.
+    #[arg(long)]
+    reconcile_hours: Option<u64>,
+
+    /// Correct the drifted orders found by --reconcile-hours instead of only logging them.
+    #[arg(long)]
+    reconcile_fix: bool,
+
+    /// Maximum number of transaction versions a pipeline may lag behind its source events before
+    /// the REST API reports as not ready. Unset by default, which disables the check.
+    #[arg(long)]
+    max_lag: Option<u64>,
+
+    /// How long in milliseconds a pipeline must stay over --max-lag before it is reported.
+    #[arg(long)]
+    max_lag_duration_ms: Option<u64>,
+
+    /// URL to POST a JSON alert to when a pipeline keeps failing, and when it recovers. Unset by
+    /// default, which disables alerts.
+    #[arg(long)]
+    alert_webhook_url: Option<String>,
+
+    /// Number of consecutive failed runs after which a pipeline is alerted about. 3 by default.
+    #[arg(long)]
+    alert_threshold: Option<u32>,
+
+    /// Pipelines lagging more than this many transaction versions behind their source events run
+    /// their batches back to back, without waiting for their poll interval. Unset by default,
+    /// which disables catch-up mode.
+    #[arg(long)]
+    catch_up_lag: Option<u64>,
+
+    /// Lag under which a pipeline leaves catch-up mode. Defaults to a tenth of --catch-up-lag.
+    #[arg(long)]
+    catch_up_exit_lag: Option<u64>,
+
+    /// While a pipeline lags more than this many transaction versions behind its source events,
+    /// pipelines of a lower priority skip their batches so that it can catch up. Unset by
+    /// default, which never skips a batch.
+    #[arg(long)]
+    priority_lag: Option<u64>,
+
+    /// Longest time in milliseconds a pipeline skips its batches for --priority-lag before it
+    /// runs one anyway. 300000 by default.
+    #[arg(long)]
+    max_yield_ms: Option<u64>,
+
+    /// Steps of an aggregation run taking longer than this many milliseconds are logged.
+    #[arg(long)]
+    slow_step_ms: Option<u64>,
+
+    /// Fraction of runs, between 0 and 1, whose SQL statements are counted and timed by kind,
+    /// with the breakdown logged at the end of the run. 0 by default, which disables profiling.
+    #[arg(long)]
+    profile_sample_rate: Option<f64>,
+
+    /// Schema of the event tables aggregated into the user history, for databases holding
+    /// several deployments. The event tables of the public schema are used by default.
+    #[arg(long)]
+    source_schema: Option<String>,
+
+    /// Comma-separated IDs of the markets aggregated into the user history. Every market is
+    /// aggregated by default.
+    #[arg(long, value_delimiter = ',')]
+    markets: Vec<BigDecimal>,
+
+    /// Comma-separated kinds of events aggregated into the user history. Events of every kind
+    /// are aggregated by default.
+    #[arg(long, value_delimiter = ',')]
+    user_history_events: Vec<EventKind>,
+
+    /// Maximum number of transaction versions aggregated into the user history by one run, so
+    /// that a failed run does not roll back a whole backlog. Unbounded by default.
+    #[arg(long)]
+    user_history_max_versions: Option<u64>,
+
+    /// Maximum number of connections of the database pool. 10 by default.
+    #[arg(long)]
+    db_max_connections: Option<u32>,
+
+    /// How long in milliseconds a pipeline waits for a free database connection before giving
+    /// up on the batch. 30000 by default.
+    #[arg(long)]
+    db_acquire_timeout_ms: Option<u64>,
+
+    /// Statement timeout in milliseconds of every database connection. Unset by default, which
+    /// lets statements run for as long as they need.
+    #[arg(long)]
+    db_statement_timeout_ms: Option<u64>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Recompute the user history of a single order from its events, then exit.
+    ReaggregateOrder {
+        /// Market ID of the order.
+        #[arg(long)]
+        market_id: BigDecimal,
+
+        /// Order ID of the order.
+        #[arg(long)]
+        order_id: BigDecimal,
+    },
+    /// Check that every placed order is in the user history and every order in it was placed,
+    /// then exit. Fails if it is not the case, unless --repair is passed.
+    CheckOrders {
+        /// Replay missing orders from their events and move orders without a place event to
+        /// aggregator.orphaned_orders.
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Wipe the aggregated state of a pipeline and aggregate it again until caught up, then exit.
+    ///
+    /// Stop the running aggregators first. Only supported for user-history.
+    Rebuild {
+        /// Pipeline to rebuild.
+        #[arg(long, value_enum)]
+        model: Pipelines,
+
+        /// Keep the state aggregated up to this transaction version and only rebuild after it.
+        #[arg(long)]
+        from_version: Option<BigDecimal>,
+    },
+    /// Aggregate the events of fixture files into the user history from scratch and compare the
+    /// result to the one they expect, then exit. Fails if any result differs.
+    ///
+    /// Meant for a scratch database: fails if the user history is not empty, and empties it
+    /// afterwards.
+    Replay {
+        /// Fixture files, see fixtures/user_history.
+        #[arg(required = true)]
+        fixtures: Vec<PathBuf>,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -52,11 +231,14 @@ pub enum Pipelines {
     Coins,
     EnumeratedVolume,
     Fees,
+    IntegratorVolume,
     Leaderboards,
     Market24hData,
+    MarketStats24h,
     Prices,
     RollingVolume,
     OrderHistoryPipelines,
+    Trades,
     TvlPerAsset,
     TvlPerMarket,
     UserBalances,
@@ -97,6 +279,30 @@ struct EnvConfig {
     include: Vec<Pipelines>,
     database_url: Option<String>,
     aptos_network: Option<AptosNetwork>,
+    backoff_initial_ms: Option<u64>,
+    backoff_max_ms: Option<u64>,
+    warmup_ms: Option<u64>,
+    poll_jitter: Option<f64>,
+    strict_fills: bool,
+    reconcile_hours: Option<u64>,
+    reconcile_fix: bool,
+    max_lag: Option<u64>,
+    max_lag_duration_ms: Option<u64>,
+    alert_webhook_url: Option<String>,
+    alert_threshold: Option<u32>,
+    catch_up_lag: Option<u64>,
+    catch_up_exit_lag: Option<u64>,
+    priority_lag: Option<u64>,
+    max_yield_ms: Option<u64>,
+    slow_step_ms: Option<u64>,
+    profile_sample_rate: Option<f64>,
+    source_schema: Option<String>,
+    markets: Vec<BigDecimal>,
+    user_history_events: Vec<EventKind>,
+    user_history_max_versions: Option<u64>,
+    db_max_connections: Option<u32>,
+    db_acquire_timeout_ms: Option<u64>,
+    db_statement_timeout_ms: Option<u64>,
 }
 
 impl EnvConfig {
@@ -109,38 +315,181 @@ impl EnvConfig {
             exclude: std::env::var("AGGREGATOR_EXCLUDE")
                 .ok()
                 .map(|s|
-                    s.split('+')
+                    parse_pipelines("AGGREGATOR_EXCLUDE", &s).unwrap_or_else(|e| {
+                        tracing::error!("{e}");
+                        panic!()
+                    })
+                )
+                .unwrap_or_default(),
+            include: std::env::var("AGGREGATOR_INCLUDE")
+                .ok()
+                .map(|s|
+                    parse_pipelines("AGGREGATOR_INCLUDE", &s).unwrap_or_else(|e| {
+                        tracing::error!("{e}");
+                        panic!()
+                    })
+                )
+                .unwrap_or_default(),
+            database_url: std::env::var("DATABASE_URL").ok(),
+            aptos_network: std::env::var("APTOS_NETWORK").ok().map(|s|
+                AptosNetwork::from_str(&s).unwrap_or_else(|_| {
+                    tracing::error!("Invalid Aptos network.");
+                    panic!()
+                })
+            ),
+            backoff_initial_ms: std::env::var("AGGREGATOR_BACKOFF_INITIAL_MS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_BACKOFF_INITIAL_MS, must be a number of milliseconds.");
+                    panic!()
+                })
+            ),
+            backoff_max_ms: std::env::var("AGGREGATOR_BACKOFF_MAX_MS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_BACKOFF_MAX_MS, must be a number of milliseconds.");
+                    panic!()
+                })
+            ),
+            warmup_ms: std::env::var("AGGREGATOR_WARMUP_MS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_WARMUP_MS, must be a number of milliseconds.");
+                    panic!()
+                })
+            ),
+            poll_jitter: std::env::var("AGGREGATOR_POLL_JITTER").ok().map(|s|
+                s.parse()
+                    .ok()
+                    .filter(|jitter| (0. ..=1.).contains(jitter))
+                    .unwrap_or_else(|| {
+                        tracing::error!("Invalid value for AGGREGATOR_POLL_JITTER, must be a number between 0 and 1.");
+                        panic!()
+                    })
+            ),
+            strict_fills: std::env::var("AGGREGATOR_STRICT_FILLS").unwrap_or(String::from("false")).parse().unwrap_or_else(|_| {
+                tracing::error!("Invalid value for AGGREGATOR_STRICT_FILLS, must be either true or false.");
+                panic!()
+            }),
+            reconcile_hours: std::env::var("AGGREGATOR_RECONCILE_HOURS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_RECONCILE_HOURS, must be a number of hours.");
+                    panic!()
+                })
+            ),
+            reconcile_fix: std::env::var("AGGREGATOR_RECONCILE_FIX").unwrap_or(String::from("false")).parse().unwrap_or_else(|_| {
+                tracing::error!("Invalid value for AGGREGATOR_RECONCILE_FIX, must be either true or false.");
+                panic!()
+            }),
+            max_lag: std::env::var("AGGREGATOR_MAX_LAG").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_MAX_LAG, must be a number of transaction versions.");
+                    panic!()
+                })
+            ),
+            max_lag_duration_ms: std::env::var("AGGREGATOR_MAX_LAG_DURATION_MS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_MAX_LAG_DURATION_MS, must be a number of milliseconds.");
+                    panic!()
+                })
+            ),
+            alert_webhook_url: std::env::var("AGGREGATOR_ALERT_WEBHOOK_URL").ok(),
+            alert_threshold: std::env::var("AGGREGATOR_ALERT_THRESHOLD").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_ALERT_THRESHOLD, must be a number of runs.");
+                    panic!()
+                })
+            ),
+            catch_up_lag: std::env::var("AGGREGATOR_CATCH_UP_LAG").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_CATCH_UP_LAG, must be a number of transaction versions.");
+                    panic!()
+                })
+            ),
+            catch_up_exit_lag: std::env::var("AGGREGATOR_CATCH_UP_EXIT_LAG").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_CATCH_UP_EXIT_LAG, must be a number of transaction versions.");
+                    panic!()
+                })
+            ),
+            priority_lag: std::env::var("AGGREGATOR_PRIORITY_LAG").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_PRIORITY_LAG, must be a number of transaction versions.");
+                    panic!()
+                })
+            ),
+            max_yield_ms: std::env::var("AGGREGATOR_MAX_YIELD_MS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_MAX_YIELD_MS, must be a number of milliseconds.");
+                    panic!()
+                })
+            ),
+            slow_step_ms: std::env::var("AGGREGATOR_SLOW_STEP_MS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_SLOW_STEP_MS, must be a number of milliseconds.");
+                    panic!()
+                })
+            ),
+            profile_sample_rate: std::env::var("AGGREGATOR_PROFILE_SAMPLE_RATE").ok().map(|s|
+                s.parse()
+                    .ok()
+                    .filter(|rate| (0. ..=1.).contains(rate))
+                    .unwrap_or_else(|| {
+                        tracing::error!("Invalid value for AGGREGATOR_PROFILE_SAMPLE_RATE, must be a number between 0 and 1.");
+                        panic!()
+                    })
+            ),
+            source_schema: std::env::var("AGGREGATOR_SOURCE_SCHEMA").ok(),
+            markets: std::env::var("AGGREGATOR_MARKETS")
+                .ok()
+                .map(|s|
+                    s.split(',')
                         .map(|s|
-                            ValueEnum::from_str(s, true)
-                                .unwrap_or_else(|_| {
-                                    tracing::error!("Invalid value for AGGREGATOR_EXCLUDE. Run the aggregator with --help to list possible values.");
+                            BigDecimal::from_str(s.trim())
+                                .ok()
+                                .filter(|market_id| market_id.is_integer())
+                                .unwrap_or_else(|| {
+                                    tracing::error!("Invalid value for AGGREGATOR_MARKETS, must be comma-separated market IDs.");
                                     panic!()
                                 })
                         )
                         .collect()
                 )
                 .unwrap_or_default(),
-            include: std::env::var("AGGREGATOR_INCLUDE")
+            user_history_events: std::env::var("AGGREGATOR_USER_HISTORY_EVENTS")
                 .ok()
                 .map(|s|
-                    s.split('+')
+                    s.split(',')
                         .map(|s|
-                            ValueEnum::from_str(s, true)
-                                .unwrap_or_else(|_| {
-                                    tracing::error!("Invalid value for AGGREGATOR_INCLUDE. Run the aggregator with --help to list possible values.");
-                                    panic!()
-                                })
+                            ValueEnum::from_str(s.trim(), true).unwrap_or_else(|_| {
+                                tracing::error!("Invalid value for AGGREGATOR_USER_HISTORY_EVENTS, must be comma-separated kinds of events among limit, market, swap, fill, change and cancel.");
+                                panic!()
+                            })
                         )
                         .collect()
                 )
                 .unwrap_or_default(),
-            database_url: std::env::var("DATABASE_URL").ok(),
-            aptos_network: std::env::var("APTOS_NETWORK").ok().map(|s|
-                AptosNetwork::from_str(&s).unwrap_or_else(|_| {
-                    tracing::error!("Invalid Aptos network.");
+            user_history_max_versions: std::env::var("AGGREGATOR_USER_HISTORY_MAX_VERSIONS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_USER_HISTORY_MAX_VERSIONS, must be a number of transaction versions.");
                     panic!()
                 })
-            )
+            ),
+            db_max_connections: std::env::var("AGGREGATOR_DB_MAX_CONNECTIONS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_DB_MAX_CONNECTIONS, must be a number of connections.");
+                    panic!()
+                })
+            ),
+            db_acquire_timeout_ms: std::env::var("AGGREGATOR_DB_ACQUIRE_TIMEOUT_MS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_DB_ACQUIRE_TIMEOUT_MS, must be a number of milliseconds.");
+                    panic!()
+                })
+            ),
+            db_statement_timeout_ms: std::env::var("AGGREGATOR_DB_STATEMENT_TIMEOUT_MS").ok().map(|s|
+                s.parse().unwrap_or_else(|_| {
+                    tracing::error!("Invalid value for AGGREGATOR_DB_STATEMENT_TIMEOUT_MS, must be a number of milliseconds.");
+                    panic!()
+                })
+            ),
         }
     }
 }
@@ -163,13 +512,12 @@ impl AptosNetwork {
 async fn main() -> Result<()> {
     let mut args: Args = Args::parse();
 
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .init();
-    tracing::info!("Started up.");
-
+    // Loaded first, so that LOG_FORMAT can be set in .env.
     dotenvy::dotenv().ok();
 
+    util::init_logging(tracing::Level::INFO);
+    tracing::info!("Started up.");
+
     let env_config: EnvConfig = EnvConfig::new();
 
     let network = env_config.aptos_network.unwrap_or_else(|| {
@@ -186,57 +534,287 @@ async fn main() -> Result<()> {
         })
     });
 
-    let pipelines = if env_config.no_default || args.no_default {
-        let mut include = env_config.include.clone();
-        include.append(&mut args.include);
-        include.sort();
-        include.dedup();
-        if include.is_empty() {
-            tracing::error!("No pipelines are included and --no-default is set.");
+    let backoff = Backoff::new(
+        Duration::from_millis(
+            env_config
+                .backoff_initial_ms
+                .or(args.backoff_initial_ms)
+                .unwrap_or(DEFAULT_BACKOFF_INITIAL_MS),
+        ),
+        Duration::from_millis(
+            env_config
+                .backoff_max_ms
+                .or(args.backoff_max_ms)
+                .unwrap_or(DEFAULT_BACKOFF_MAX_MS),
+        ),
+    );
+
+    let warmup = Duration::from_millis(env_config.warmup_ms.or(args.warmup_ms).unwrap_or(0));
+    let poll_jitter = env_config.poll_jitter.or(args.poll_jitter).unwrap_or(0.);
+    if !(0. ..=1.).contains(&poll_jitter) {
+        return Err(anyhow!(
+            "Invalid --poll-jitter {poll_jitter}, must be between 0 and 1."
+        ));
+    }
+
+    let strict_fills = env_config.strict_fills || args.strict_fills;
+
+    let slow_step_threshold = Duration::from_millis(
+        env_config
+            .slow_step_ms
+            .or(args.slow_step_ms)
+            .unwrap_or(DEFAULT_SLOW_STEP_MS),
+    );
+
+    let profile_sample_rate = env_config
+        .profile_sample_rate
+        .or(args.profile_sample_rate)
+        .unwrap_or(0.);
+    if !(0. ..=1.).contains(&profile_sample_rate) {
+        return Err(anyhow!(
+            "Invalid --profile-sample-rate {profile_sample_rate}, must be between 0 and 1."
+        ));
+    }
+
+    let source_schema = env_config.source_schema.or(args.source_schema);
+    if let Some(schema) = &source_schema {
+        if !util::is_valid_schema_name(schema) {
+            tracing::error!("Invalid source schema, must be a lowercase identifier.");
             panic!();
         }
-        include
+        tracing::info!(schema, "Reading events from a custom schema.");
+    }
+
+    let mut market_ids = if env_config.markets.is_empty() {
+        args.markets.clone()
     } else {
-        let mut x = vec![
-            Pipelines::Candlesticks,
-            Pipelines::Coins,
-            Pipelines::EnumeratedVolume,
-            Pipelines::Fees,
-            Pipelines::Market24hData,
-            Pipelines::Prices,
-            Pipelines::RollingVolume,
-            Pipelines::UserBalances,
-            Pipelines::UserHistory,
-            Pipelines::OrderHistoryPipelines,
-            Pipelines::TvlPerAsset,
-            Pipelines::TvlPerMarket,
-        ];
-        let mut exclude = env_config.exclude.clone();
-        let mut include = env_config.include.clone();
-        exclude.append(&mut args.exclude);
-        include.append(&mut args.include);
-        x = x.into_iter().filter(|a| !exclude.contains(a)).collect();
-        x.append(&mut include);
-        x.sort();
-        x.dedup();
-        x
+        env_config.markets.clone()
+    };
+    market_ids.sort();
+    market_ids.dedup();
+    let market_ids = (!market_ids.is_empty()).then_some(market_ids);
+    if let Some(market_ids) = &market_ids {
+        tracing::info!(
+            ?market_ids,
+            "Only aggregating the user history of some markets."
+        );
+    }
+
+    let mut event_kinds = if env_config.user_history_events.is_empty() {
+        args.user_history_events.clone()
+    } else {
+        env_config.user_history_events.clone()
     };
+    event_kinds.sort();
+    event_kinds.dedup();
+    let event_kinds = (!event_kinds.is_empty()).then_some(event_kinds);
+    if let Some(event_kinds) = &event_kinds {
+        tracing::info!(
+            ?event_kinds,
+            "Only aggregating some kinds of events into the user history."
+        );
+    }
+
+    let max_txn_versions = env_config
+        .user_history_max_versions
+        .or(args.user_history_max_versions)
+        .map(BigDecimal::from);
+    if let Some(max_txn_versions) = &max_txn_versions {
+        tracing::info!(
+            %max_txn_versions,
+            "Limiting the transaction versions aggregated into the user history per run."
+        );
+    }
+
+    let lag_breaker = env_config.max_lag.or(args.max_lag).map(|max_lag| {
+        LagBreaker::new(
+            max_lag,
+            Duration::from_millis(
+                env_config
+                    .max_lag_duration_ms
+                    .or(args.max_lag_duration_ms)
+                    .unwrap_or(DEFAULT_MAX_LAG_DURATION_MS),
+            ),
+        )
+    });
+
+    let catch_up = env_config
+        .catch_up_lag
+        .or(args.catch_up_lag)
+        .map(|catch_up_lag| {
+            CatchUp::new(
+                catch_up_lag,
+                env_config
+                    .catch_up_exit_lag
+                    .or(args.catch_up_exit_lag)
+                    .unwrap_or(catch_up_lag / 10),
+            )
+        });
+
+    let no_default = env_config.no_default || args.no_default;
+    let mut include = env_config.include.clone();
+    include.append(&mut args.include);
+    let mut exclude = env_config.exclude.clone();
+    exclude.append(&mut args.exclude);
+    if no_default && include.is_empty() {
+        tracing::error!("No pipelines are included and --no-default is set.");
+        panic!();
+    }
+    let pipelines = select_pipelines(no_default, include, exclude);
     tracing::info!("Using pipelines {pipelines:?}.");
     tracing::info!("Using network {network:?}.");
 
-    let pool = PgPoolOptions::new()
-        .after_connect(|conn, _| {
-            Box::pin(async move {
-                conn.execute("SET default_transaction_isolation TO 'repeatable read'")
-                    .await?;
-                Ok(())
-            })
-        })
-        .connect(&database_url)
-        .await?;
+    let mut db_config = DbConfig::new(database_url);
+    if let Some(max_connections) = env_config.db_max_connections.or(args.db_max_connections) {
+        db_config.max_connections = max_connections;
+    }
+    if let Some(acquire_timeout_ms) = env_config
+        .db_acquire_timeout_ms
+        .or(args.db_acquire_timeout_ms)
+    {
+        db_config.acquire_timeout = Duration::from_millis(acquire_timeout_ms);
+    }
+    db_config.statement_timeout = env_config
+        .db_statement_timeout_ms
+        .or(args.db_statement_timeout_ms)
+        .map(Duration::from_millis);
+    tracing::info!(
+        max_connections = db_config.max_connections,
+        acquire_timeout_ms = db_config.acquire_timeout.as_millis(),
+        "Using database pool configuration."
+    );
+
+    let pool = db::connect(&db_config).await?;
 
     tracing::info!("Connected to DB.");
 
+    if let Err(e) = db::check_schema(&pool).await {
+        tracing::error!(error = %e, "Incompatible database schema.");
+        return Err(e);
+    }
+
+    match args.command {
+        Some(Command::ReaggregateOrder {
+            market_id,
+            order_id,
+        }) => {
+            pipelines::user_history::reaggregate_order(
+                &pool,
+                &market_id,
+                &order_id,
+                strict_fills,
+                source_schema.as_deref(),
+            )
+            .await?;
+            tracing::info!(%market_id, %order_id, "Reaggregated order.");
+            return Ok(());
+        }
+        Some(Command::CheckOrders { repair }) => {
+            let check = pipelines::user_history::check_orders(
+                &pool,
+                repair,
+                strict_fills,
+                source_schema.as_deref(),
+                market_ids.as_deref(),
+            )
+            .await?;
+            tracing::info!(
+                missing = check.missing.len(),
+                orphaned = check.orphaned.len(),
+                unbalanced = check.unbalanced.len(),
+                repaired = repair,
+                "Checked orders."
+            );
+            // Unbalanced orders are not repaired.
+            if (!check.is_consistent() && !repair) || !check.unbalanced.is_empty() {
+                return Err(anyhow!(
+                    "The user history is inconsistent with the order events."
+                ));
+            }
+            return Ok(());
+        }
+        Some(Command::Replay { fixtures }) => {
+            let mut failed = 0;
+            for fixture in &fixtures {
+                let differences =
+                    replay::replay(&pool, fixture, strict_fills, slow_step_threshold).await?;
+                for difference in &differences {
+                    tracing::error!(fixture = %fixture.display(), "{difference}");
+                }
+                if differences.is_empty() {
+                    tracing::info!(fixture = %fixture.display(), "Fixture passed.");
+                } else {
+                    failed += 1;
+                }
+            }
+            if failed > 0 {
+                return Err(anyhow!("{failed} of {} fixtures failed.", fixtures.len()));
+            }
+            return Ok(());
+        }
+        Some(Command::Rebuild {
+            model,
+            from_version,
+        }) => {
+            if model != Pipelines::UserHistory {
+                return Err(anyhow!("Rebuilding {model:?} is not supported."));
+            }
+            pipelines::user_history::rewind(
+                &pool,
+                from_version.as_ref(),
+                strict_fills,
+                source_schema.as_deref(),
+            )
+            .await?;
+            let mut pipeline = UserHistory::new(
+                pool.clone(),
+                strict_fills,
+                slow_step_threshold,
+                source_schema,
+                market_ids,
+                event_kinds,
+                max_txn_versions,
+            );
+            pipeline.process_and_save_historical_data().await?;
+            while pipeline.has_work().await? {
+                pipeline.process_and_save_internal().await?;
+            }
+            tracing::info!(?model, "Rebuilt pipeline.");
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if let Some(hours) = env_config.reconcile_hours.or(args.reconcile_hours) {
+        if pipelines.contains(&Pipelines::UserHistory) {
+            let fix = env_config.reconcile_fix || args.reconcile_fix;
+            let since = Utc::now() - chrono::Duration::hours(hours as i64);
+            tracing::info!(%since, fix, "Reconciling recently updated orders.");
+            let drifts = pipelines::user_history::reconcile_orders(
+                &pool,
+                since,
+                fix,
+                strict_fills,
+                source_schema.as_deref(),
+                market_ids.as_deref(),
+            )
+            .await?;
+            for drift in &drifts {
+                tracing::warn!(
+                    market_id = %drift.market_id,
+                    order_id = %drift.order_id,
+                    total_filled = %drift.total_filled,
+                    expected_total_filled = %drift.expected_total_filled,
+                    remaining_size = %drift.remaining_size,
+                    expected_remaining_size = %drift.expected_remaining_size,
+                    fixed = fix,
+                    "Order totals drifted from its events."
+                );
+            }
+            tracing::info!(drifted = drifts.len(), fixed = fix, "Reconciled orders.");
+        }
+    }
+
     let default_interval = Duration::from_secs(5);
 
     let mut data: Vec<Arc<Mutex<dyn Pipeline + Send + Sync>>> = vec![];
@@ -284,6 +862,9 @@ async fn main() -> Result<()> {
                 data.push(Arc::new(Mutex::new(EnumeratedVolume::new(pool.clone()))))
             }
             Pipelines::Fees => data.push(Arc::new(Mutex::new(Fees::new(pool.clone())))),
+            Pipelines::IntegratorVolume => {
+                data.push(Arc::new(Mutex::new(IntegratorVolume::new(pool.clone()))))
+            }
             Pipelines::Leaderboards => {
                 data.push(Arc::new(Mutex::new(Leaderboards::new(pool.clone()))));
             }
@@ -294,6 +875,9 @@ async fn main() -> Result<()> {
                     Duration::from_secs(5 * 60),
                 ))))
             }
+            Pipelines::MarketStats24h => {
+                data.push(Arc::new(Mutex::new(MarketStats24h::new(pool.clone()))))
+            }
             Pipelines::Prices => data.push(Arc::new(Mutex::new(Prices::new(pool.clone())))),
             Pipelines::RollingVolume => {
                 data.push(Arc::new(Mutex::new(RollingVolume::new(pool.clone()))))
@@ -303,6 +887,7 @@ async fn main() -> Result<()> {
                     pool.clone(),
                 ))));
             }
+            Pipelines::Trades => data.push(Arc::new(Mutex::new(Trades::new(pool.clone())))),
             Pipelines::TvlPerAsset => {
                 data.push(Arc::new(Mutex::new(RefreshMaterializedView::new(
                     pool.clone(),
@@ -321,34 +906,171 @@ async fn main() -> Result<()> {
                 data.push(Arc::new(Mutex::new(UserBalances::new(pool.clone()))));
             }
             Pipelines::UserHistory => {
-                data.push(Arc::new(Mutex::new(UserHistory::new(pool.clone()))));
+                data.push(Arc::new(Mutex::new(UserHistory::new(
+                    pool.clone(),
+                    strict_fills,
+                    slow_step_threshold,
+                    source_schema.clone(),
+                    market_ids.clone(),
+                    event_kinds.clone(),
+                    max_txn_versions.clone(),
+                ))));
             }
         }
     }
 
+    for (i, a) in data.iter().enumerate() {
+        for b in &data[i + 1..] {
+            let (a, b) = (a.lock().await, b.lock().await);
+            if schedule::conflicts(&*a, &*b) {
+                tracing::info!(
+                    a = a.model_name(),
+                    b = b.model_name(),
+                    "Pipelines touch the same tables, they will not run concurrently."
+                );
+            }
+        }
+    }
+
+    for data in &data {
+        let locked = data.lock().await;
+        let name = locked.model_name();
+        if let Err(e) = db::check_columns(&pool, &name, locked.expected_columns()).await {
+            tracing::error!(error = %e, "Incompatible database schema.");
+            return Err(e);
+        }
+    }
+
+    let mut table_access = vec![];
+    for data in &data {
+        let locked = data.lock().await;
+        table_access.push((locked.reads().to_vec(), locked.writes().to_vec()));
+    }
+    let table_locks = TableLocks::new(table_access.iter().map(|(_, writes)| writes.as_slice()));
+
+    let priorities = match env_config.priority_lag.or(args.priority_lag) {
+        Some(priority_lag) => {
+            let mut by_name = HashMap::new();
+            for data in &data {
+                let locked = data.lock().await;
+                by_name.insert(locked.model_name(), locked.priority());
+            }
+            let max_yield = Duration::from_millis(
+                env_config
+                    .max_yield_ms
+                    .or(args.max_yield_ms)
+                    .unwrap_or(DEFAULT_MAX_YIELD_MS),
+            );
+            Some(Priorities::new(priority_lag, max_yield, by_name))
+        }
+        None => None,
+    };
+
+    let mut triggerables = HashMap::new();
+    for (data, (reads, writes)) in data.iter().zip(&table_access) {
+        let name = data.lock().await.model_name();
+        triggerables.insert(
+            name,
+            Triggerable {
+                pipeline: data.clone(),
+                reads: reads.clone(),
+                writes: writes.clone(),
+            },
+        );
+    }
+
     let mut handles = JoinSet::new();
 
-    for data in data {
+    if lag_breaker.is_some() || catch_up.is_some() || priorities.is_some() {
+        tracing::info!(
+            ?lag_breaker,
+            ?catch_up,
+            ?priorities,
+            "Monitoring pipeline lag."
+        );
+        let pool = pool.clone();
+        let catch_up = catch_up.clone();
+        let priorities = priorities.clone();
+        handles.spawn(
+            async move {
+                lag::monitor(pool, lag_breaker, catch_up, priorities, LAG_POLL_INTERVAL).await;
+                #[allow(unreachable_code)]
+                Ok::<(), anyhow::Error>(())
+            }
+            .instrument(tracing::info_span!("lag_monitor")),
+        );
+    }
+
+    let mut pipeline_health = PipelineHealth::new();
+    if let Some(url) = env_config.alert_webhook_url.or(args.alert_webhook_url) {
+        let threshold = env_config
+            .alert_threshold
+            .or(args.alert_threshold)
+            .unwrap_or(alert::DEFAULT_THRESHOLD);
+        pipeline_health = pipeline_health.with_alerter(Alerter::new(url, threshold));
+    }
+    {
+        let pool = pool.clone();
+        let pipeline_health = pipeline_health.clone();
+        handles.spawn(
+            async move {
+                health::publish(pool, pipeline_health, HEALTH_PUBLISH_INTERVAL).await;
+                #[allow(unreachable_code)]
+                Ok::<(), anyhow::Error>(())
+            }
+            .instrument(tracing::info_span!("health")),
+        );
+    }
+
+    {
+        let pool = pool.clone();
+        let table_locks = table_locks.clone();
+        handles.spawn(
+            async move {
+                trigger::listen(pool, triggerables, table_locks).await;
+                #[allow(unreachable_code)]
+                Ok::<(), anyhow::Error>(())
+            }
+            .instrument(tracing::info_span!("trigger")),
+        );
+    }
+
+    for (data, (reads, writes)) in data.into_iter().zip(table_access) {
         let name = {
             let locked = data.lock().await;
             locked.model_name()
         };
         let span = tracing::info_span!("pipeline", name);
+        let pool = pool.clone();
+        let mut backoff = backoff.clone();
+        let table_locks = table_locks.clone();
+        let pipeline_health = pipeline_health.clone();
+        let catch_up = catch_up.clone();
+        let priorities = priorities.clone();
         handles.spawn(async move {
 
+            tokio::time::sleep(schedule::jitter(warmup)).await;
+
             let span_hist = tracing::info_span!("historical");
             let data_hist = data.clone();
+            let table_locks_hist = table_locks.clone();
+            let (reads_hist, writes_hist) = (reads.clone(), writes.clone());
+            let (name_hist, health_hist) = (name.clone(), pipeline_health.clone());
             async move {
                 let mut data = data_hist.lock().await;
+                let _guards = table_locks_hist.acquire(&reads_hist, &writes_hist).await;
                 tracing::info!("Starting processing batch.");
                 let start = SystemTime::now();
                 let result = data.process_and_save_historical_data()
                     .await;
-                let time = start
-                    .elapsed()
-                    .unwrap_or(Duration::from_secs(0))
-                    .as_millis();
+                let elapsed = start.elapsed().unwrap_or(Duration::from_secs(0));
+                let time = elapsed.as_millis();
+                if let Err(aggregator::PipelineError::Locked) = result {
+                    // The other instance processes the historical data instead.
+                    return anyhow::Result::<()>::Ok(());
+                }
                 if let Err(e) = result {
+                    health_hist.record_error(&name_hist, &e.to_string(), Utc::now());
                     match &e {
                         aggregator::PipelineError::ProcessingError(e) => {
                             tracing::error!(elapsed_ms = time, error = %e, backtrace = %e.backtrace(), "Could not process batch.");
@@ -362,30 +1084,89 @@ async fn main() -> Result<()> {
                     }
                     Err(e)?;
                 };
+                let events = data.processed_events();
+                data.after_commit(ProcessSummary { events, elapsed }).await;
+                health_hist.record_success(&name_hist, Utc::now());
                 tracing::info!(elapsed_ms = time, "Finished processing batch.");
                 anyhow::Result::<()>::Ok(())
             }.instrument(span_hist).await?;
 
-            let mut data = data.lock().await;
-
             let mut retries = 0;
             let max_retries = 3;
+            let mut idle_polls = 0;
+            // Set after a successful batch in catch-up mode, to run the next one right away.
+            let mut back_to_back = false;
+            let mut sampler = Sampler::new(profile_sample_rate);
 
             loop {
-                let interval = data.poll_interval().unwrap_or(default_interval);
+                if !back_to_back {
+                    let interval = data.lock().await.poll_interval().unwrap_or(default_interval);
 
-                tokio::time::sleep(interval).await;
+                    let jitter = schedule::jitter(interval.mul_f64(poll_jitter));
+                    tokio::time::sleep(idle_interval(interval, idle_polls) + jitter).await;
+                }
+                back_to_back = false;
+                if priorities.as_ref().is_some_and(|p| p.should_yield(&name, Instant::now())) {
+                    tracing::debug!("Yielding to a lagging pipeline of a higher priority.");
+                    continue;
+                }
+                let catching_up = catch_up.as_ref().is_some_and(|c| c.is_active(&name));
+
+                // Only held for one cycle, so that requested runs can take their turn.
+                let mut data = data.lock().await;
 
-                if data.ready() {
+                if catching_up || data.ready() {
+                    match data.has_work().await {
+                        Ok(false) => {
+                            idle_polls += 1;
+                            tracing::debug!(idle_polls, "Nothing to process.");
+                            continue;
+                        }
+                        Ok(true) => idle_polls = 0,
+                        Err(e) => {
+                            idle_polls = 0;
+                            tracing::warn!(error = %e, "Could not check for new data, processing anyway.");
+                        }
+                    }
+                    let _guards = table_locks.acquire(&reads, &writes).await;
                     tracing::info!("Starting processing batch.");
                     let start = SystemTime::now();
-                    let result = data.process_and_save().await;
-                    let time = start
-                        .elapsed()
-                        .unwrap_or(Duration::from_secs(0))
-                        .as_millis();
+                    let result = sampler.run(async {
+                        if catching_up {
+                            data.process_and_save_internal().await
+                        } else {
+                            data.process_and_save().await
+                        }
+                    }).await;
+                    let elapsed = start.elapsed().unwrap_or(Duration::from_secs(0));
+                    let time = elapsed.as_millis();
+                    if let Err(aggregator::PipelineError::Locked) = result {
+                        // Not a failure, but no progress either: wait for the next poll instead
+                        // of retrying or running back to back.
+                        continue;
+                    }
                     if let Err(e) = result {
+                        pipeline_health.record_error(&name, &e.to_string(), Utc::now());
+                        if e.is_pool_timeout() {
+                            tracing::warn!(elapsed_ms = time, error = %e, "Timed out waiting for a database connection, consider raising AGGREGATOR_DB_MAX_CONNECTIONS.");
+                            continue;
+                        }
+                        if e.is_connection_error() {
+                            tracing::warn!(elapsed_ms = time, error = %e, "Lost connection to the database, backing off.");
+                            wait_for_database(&pool, &mut backoff).await;
+                            tracing::info!("Reconnected to the database, resuming.");
+                            continue;
+                        }
                         match &e {
+                            _ if e.is_statement_timeout() => {
+                                tracing::warn!(elapsed_ms = time, error = %e, "A statement timed out and the batch was rolled back, consider raising AGGREGATOR_DB_STATEMENT_TIMEOUT_MS.");
+                            }
+                            _ if e.is_deadlock() => {
+                                tracing::warn!(elapsed_ms = time, error = %e, "The batch was rolled back to break a deadlock, the Postgres logs name the statements involved.");
+                            }
+                            _ if e.is_unique_violation() => {
+                                tracing::error!(elapsed_ms = time, error = %e, "A batch inserted a row that already exists, an event is likely aggregated twice.");
+                            }
                             aggregator::PipelineError::ProcessingError(e) => {
                                 tracing::error!(elapsed_ms = time, error = %e, backtrace = %e.backtrace(), "Could not process batch.");
                             },
@@ -404,7 +1185,11 @@ async fn main() -> Result<()> {
                         }
                     } else {
                         retries = 0;
-                        tracing::info!(elapsed_ms = time, "Finished processing batch.");
+                        back_to_back = catching_up;
+                        let events = data.processed_events();
+                        data.after_commit(ProcessSummary { events, elapsed }).await;
+                        pipeline_health.record_success(&name, Utc::now());
+                        tracing::info!(elapsed_ms = time, catching_up, "Finished processing batch.");
                     }
                 } else {
                     tracing::warn!("Data is not ready.");
@@ -423,6 +1208,88 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Parses the `+`-separated pipeline names of the environment variable `var`, e.g.
+/// `prices+user_history`.
+fn parse_pipelines(var: &str, value: &str) -> Result<Vec<Pipelines>, String> {
+    value
+        .split('+')
+        .map(|s| {
+            Pipelines::from_str(s, true).map_err(|_| {
+                format!(
+                    "Invalid value {s:?} in {var}. Run the aggregator with --help to list possible \
+                     values."
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the pipelines to run: the `include`d ones if `no_default` is set, and otherwise the
+/// default ones minus the `exclude`d ones, plus the `include`d ones.
+fn select_pipelines(
+    no_default: bool,
+    mut include: Vec<Pipelines>,
+    exclude: Vec<Pipelines>,
+) -> Vec<Pipelines> {
+    let mut pipelines = if no_default {
+        include
+    } else {
+        let mut x = vec![
+            Pipelines::Candlesticks,
+            Pipelines::Coins,
+            Pipelines::EnumeratedVolume,
+            Pipelines::Fees,
+            Pipelines::IntegratorVolume,
+            Pipelines::Market24hData,
+            Pipelines::MarketStats24h,
+            Pipelines::Prices,
+            Pipelines::RollingVolume,
+            Pipelines::UserBalances,
+            Pipelines::UserHistory,
+            Pipelines::OrderHistoryPipelines,
+            Pipelines::Trades,
+            Pipelines::TvlPerAsset,
+            Pipelines::TvlPerMarket,
+        ];
+        x.retain(|a| !exclude.contains(a));
+        x.append(&mut include);
+        x
+    };
+    pipelines.sort();
+    pipelines.dedup();
+    pipelines
+}
+
+/// The default initial delay before probing the database after losing the connection.
+const DEFAULT_BACKOFF_INITIAL_MS: u64 = 1_000;
+/// The default maximum delay between two database probes.
+const DEFAULT_BACKOFF_MAX_MS: u64 = 60_000;
+
+/// The default duration above which a step of an aggregation run is logged.
+const DEFAULT_SLOW_STEP_MS: u64 = 1_000;
+
+/// The default time a pipeline must stay over the maximum lag before it is reported.
+const DEFAULT_MAX_LAG_DURATION_MS: u64 = 60_000;
+/// The default longest time a pipeline yields to a lagging pipeline of a higher priority.
+const DEFAULT_MAX_YIELD_MS: u64 = 300_000;
+/// The interval at which pipeline lag is checked.
+const LAG_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// The interval at which pipeline health is saved to the database.
+const HEALTH_PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The longest a pipeline with nothing to process waits between two polls, unless its own poll
+/// interval is longer.
+const MAX_IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Doubles the poll interval for every consecutive poll that found nothing to process, up to
+/// [`MAX_IDLE_INTERVAL`].
+fn idle_interval(interval: Duration, idle_polls: u32) -> Duration {
+    if idle_polls == 0 {
+        return interval;
+    }
+    (interval * 2u32.pow(idle_polls.min(16))).min(MAX_IDLE_INTERVAL.max(interval))
+}
+
 /// The maximum number of transactions processed in one batch.
 const MAX_BATCH_SIZE: u64 = 1_000_000;
 /// The minimum number of transactions processed in one batch.
@@ -462,3 +1329,68 @@ fn update_batch_size(current_batch_size: &mut BigDecimal, n_events: usize) {
         tracing::debug!("Batch size increased to {}", current_batch_size);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_interval_doubles_up_to_max() {
+        let interval = Duration::from_millis(100);
+        let intervals: Vec<u128> = (0..8)
+            .map(|idle_polls| idle_interval(interval, idle_polls).as_millis())
+            .collect();
+        assert_eq!(intervals, [100, 200, 400, 800, 1600, 3200, 5000, 5000]);
+    }
+
+    #[test]
+    fn idle_interval_does_not_overflow() {
+        let interval = Duration::from_millis(100);
+        assert_eq!(idle_interval(interval, u32::MAX), MAX_IDLE_INTERVAL);
+    }
+
+    #[test]
+    fn idle_interval_keeps_longer_poll_intervals() {
+        let interval = MAX_IDLE_INTERVAL * 2;
+        assert_eq!(idle_interval(interval, 0), interval);
+        assert_eq!(idle_interval(interval, 3), interval);
+    }
+
+    #[test]
+    fn idle_interval_of_zero() {
+        assert_eq!(idle_interval(Duration::ZERO, 3), Duration::ZERO);
+    }
+
+    #[test]
+    fn only_included_pipelines_run_without_the_defaults() {
+        let include = parse_pipelines("AGGREGATOR_INCLUDE", "user-history+PRICES").unwrap();
+        assert_eq!(
+            select_pipelines(true, include, vec![Pipelines::Prices]),
+            [Pipelines::Prices, Pipelines::UserHistory]
+        );
+    }
+
+    #[test]
+    fn excluded_pipelines_are_removed_from_the_defaults() {
+        let pipelines = select_pipelines(
+            false,
+            vec![Pipelines::Leaderboards],
+            vec![Pipelines::Prices, Pipelines::UserHistory],
+        );
+        assert!(pipelines.contains(&Pipelines::Leaderboards));
+        assert!(pipelines.contains(&Pipelines::Trades));
+        assert!(!pipelines.contains(&Pipelines::Prices));
+        assert!(!pipelines.contains(&Pipelines::UserHistory));
+    }
+
+    #[test]
+    fn unknown_pipeline_is_named() {
+        assert_eq!(
+            parse_pipelines("AGGREGATOR_INCLUDE", "prices+candles"),
+            Err(String::from(
+                "Invalid value \"candles\" in AGGREGATOR_INCLUDE. Run the aggregator with --help \
+                 to list possible values."
+            ))
+        );
+    }
+}
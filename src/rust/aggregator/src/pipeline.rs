@@ -1,7 +1,18 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
 pub type PipelineAggregationResult = Result<(), PipelineError>;
 
+/// What a successful run did, passed to [`Pipeline::after_commit`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessSummary {
+    /// Number of events the run aggregated, as reported by [`Pipeline::processed_events`].
+    pub events: Option<u64>,
+    /// How long the run took.
+    pub elapsed: Duration,
+}
+
 /// This trait represents a data pipeline.
 #[async_trait::async_trait]
 pub trait Pipeline {
@@ -27,7 +38,8 @@ pub trait Pipeline {
     /// Processes the data and saves the result.
     ///
     /// This is an internal function. It should never be called, except in
-    /// [`Pipeline::process_and_save`].
+    /// [`Pipeline::process_and_save`] and for runs requested through `api.run_pipeline`, which
+    /// skip [`Pipeline::ready`].
     async fn process_and_save_internal(&mut self) -> PipelineAggregationResult;
 
     /// Process and save historical data that is missing in the database.
@@ -39,6 +51,60 @@ pub trait Pipeline {
     /// sure that the data is up to date.
     async fn process_and_save_historical_data(&mut self) -> PipelineAggregationResult;
 
+    /// Returns `false` if there is cheaply known to be nothing new to process, so that the caller
+    /// can skip [`Pipeline::process_and_save`] and poll less often.
+    ///
+    /// Defaults to `true`, for pipelines that cannot tell without doing the work.
+    async fn has_work(&self) -> Result<bool, PipelineError> {
+        Ok(true)
+    }
+
+    /// Called after a run succeeded, that is once its transaction is committed, and never after
+    /// a failed run. Meant for side effects that must not happen if the run is rolled back, e.g.
+    /// updating in-memory state mirroring the database.
+    ///
+    /// Only called by the runner, not by one-off commands driving the pipeline themselves.
+    /// Defaults to doing nothing.
+    async fn after_commit(&mut self, _summary: ProcessSummary) {}
+
+    /// Returns the number of events aggregated by the last run, reported in the
+    /// [`ProcessSummary`] given to [`Pipeline::after_commit`].
+    ///
+    /// Defaults to `None`, for pipelines that do not count them.
+    fn processed_events(&self) -> Option<u64> {
+        None
+    }
+
+    /// The tables the pipeline reads from.
+    ///
+    /// Used with [`Pipeline::writes`] to keep pipelines touching the same tables from running
+    /// concurrently. Defaults to none.
+    fn reads(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// The tables the pipeline writes to. Defaults to none.
+    fn writes(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// The columns the queries of the pipeline rely on, by table (e.g.
+    /// `("aggregator.user_history", &["market_id", "order_id"])`).
+    ///
+    /// Checked against the database at startup by [`crate::db::check_columns`], so that a missing
+    /// column is reported by name instead of failing every batch. Defaults to none.
+    fn expected_columns(&self) -> &[(&'static str, &'static [&'static str])] {
+        &[]
+    }
+
+    /// How important it is to keep the data of the pipeline fresh, higher is more important.
+    ///
+    /// While a pipeline lags behind (see [`crate::lag::Priorities`]), pipelines of a lower
+    /// priority skip their batches so that it can catch up. Defaults to 0.
+    fn priority(&self) -> u8 {
+        0
+    }
+
     /// The interval at which the [`Pipeline::ready`] function should be polled.
     ///
     /// If `None` is returned, it is up to the caller to decide when to poll.
@@ -79,4 +145,164 @@ pub enum PipelineError {
     /// to save.
     #[error("Data is not processable, reason: {0}")]
     NotProcessable(String),
+
+    /// Another aggregator instance holds the lock of the pipeline (see
+    /// [`crate::util::create_locked_transaction`]) and processes the data instead.
+    ///
+    /// Nothing was done. Wait for the next poll rather than calling
+    /// [`Pipeline::process_and_save`] again right away.
+    #[error("Data is being processed by another aggregator instance")]
+    Locked,
+}
+
+impl PipelineError {
+    /// Returns `true` if the error was caused by the database connection rather than by the
+    /// data, e.g. when Postgres restarts or fails over.
+    ///
+    /// Such errors are not worth retrying right away, the database should be given time to
+    /// recover first.
+    pub fn is_connection_error(&self) -> bool {
+        self.sqlx_errors().any(|e| match e {
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => true,
+            // Class 08 is connection exceptions, 57P01 to 57P03 are emitted while the
+            // server is shutting down or starting up.
+            sqlx::Error::Database(e) => e.code().is_some_and(|code| {
+                code.starts_with("08") || matches!(&*code, "57P01" | "57P02" | "57P03")
+            }),
+            _ => false,
+        })
+    }
+
+    /// Returns `true` if no connection could be acquired from the pool in time.
+    ///
+    /// The database is reachable but every connection is busy, so the pool is too small for the
+    /// load rather than the database being down.
+    pub fn is_pool_timeout(&self) -> bool {
+        self.sqlx_errors()
+            .any(|e| matches!(e, sqlx::Error::PoolTimedOut))
+    }
+
+    /// Returns `true` if a statement was cancelled for running longer than the statement timeout
+    /// (see [`crate::db::DbConfig::statement_timeout`]).
+    ///
+    /// The transaction is rolled back, and the batch can be retried.
+    pub fn is_statement_timeout(&self) -> bool {
+        self.sqlx_errors().any(|e| match e {
+            // 57014 is query_canceled, which statement_timeout raises.
+            sqlx::Error::Database(e) => e.code().is_some_and(|code| code == "57014"),
+            _ => false,
+        })
+    }
+
+    /// Returns `true` if the transaction was aborted to break a deadlock with another
+    /// transaction.
+    ///
+    /// The batch can be retried, but a deadlock means two transactions lock the same rows in
+    /// different orders, which is worth fixing. Postgres logs the statements involved.
+    pub fn is_deadlock(&self) -> bool {
+        self.sqlx_errors().any(|e| match e {
+            // 40P01 is deadlock_detected, as opposed to 40001, serialization_failure.
+            sqlx::Error::Database(e) => e.code().is_some_and(|code| code == "40P01"),
+            _ => false,
+        })
+    }
+
+    /// Returns `true` if a write hit a unique constraint, that is the batch inserted a row that
+    /// already exists.
+    ///
+    /// Batches are atomic and their watermark moves with them, so this points at a pipeline
+    /// aggregating the same event twice rather than at a transient failure, and retrying the
+    /// batch will most likely fail the same way.
+    pub fn is_unique_violation(&self) -> bool {
+        self.sqlx_errors().any(|e| match e {
+            // 23505 is unique_violation.
+            sqlx::Error::Database(e) => e.code().is_some_and(|code| code == "23505"),
+            _ => false,
+        })
+    }
+
+    fn sqlx_errors(&self) -> impl Iterator<Item = &sqlx::Error> {
+        let e = match self {
+            PipelineError::ProcessingError(e) | PipelineError::SavingError(e) => Some(e),
+            _ => None,
+        };
+        e.into_iter()
+            .flat_map(|e| e.chain())
+            .filter_map(|e| e.downcast_ref::<sqlx::Error>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, error::Error as StdError, fmt};
+
+    use anyhow::anyhow;
+    use sqlx::error::{DatabaseError, ErrorKind};
+
+    use super::*;
+
+    /// A database error with SQLSTATE `.0`, as Postgres would report it.
+    #[derive(Debug)]
+    struct Sqlstate(&'static str);
+
+    impl fmt::Display for Sqlstate {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "error {}", self.0)
+        }
+    }
+
+    impl StdError for Sqlstate {}
+
+    impl DatabaseError for Sqlstate {
+        fn message(&self) -> &str {
+            "synthetic error"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.0))
+        }
+
+        fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    /// Returns a failed batch caused by a database error with SQLSTATE `code`.
+    fn failed_batch(code: &'static str) -> PipelineError {
+        let e = sqlx::Error::Database(Box::new(Sqlstate(code)));
+        PipelineError::ProcessingError(anyhow!(e).context("Could not aggregate fills"))
+    }
+
+    #[test]
+    fn deadlock_is_told_apart_from_serialization_failures() {
+        assert!(failed_batch("40P01").is_deadlock());
+        assert!(!failed_batch("40001").is_deadlock());
+        assert!(!failed_batch("57014").is_deadlock());
+    }
+
+    #[test]
+    fn deadlock_is_retried_like_a_failed_batch() {
+        // The runner backs off on connection errors and waits on pool timeouts, and retries
+        // other failed batches.
+        let e = failed_batch("40P01");
+        assert!(!e.is_connection_error());
+        assert!(!e.is_pool_timeout());
+        assert!(!e.is_statement_timeout());
+        assert!(!e.is_unique_violation());
+    }
 }
@@ -2,11 +2,14 @@ pub mod candlesticks;
 pub mod coins;
 pub mod enumerated_volume;
 pub mod fees;
+pub mod integrator_volume;
 pub mod leaderboards;
+pub mod market_stats_24h;
 pub mod order_history_pipelines;
 pub mod prices;
 pub mod refresh_materialized_view;
 pub mod rolling_volume;
+pub mod trades;
 pub mod user_balances;
 pub mod user_history;
 
@@ -14,10 +17,13 @@ pub use candlesticks::Candlesticks;
 pub use coins::Coins;
 pub use enumerated_volume::EnumeratedVolume;
 pub use fees::Fees;
+pub use integrator_volume::IntegratorVolume;
 pub use leaderboards::Leaderboards;
+pub use market_stats_24h::MarketStats24h;
 pub use order_history_pipelines::OrderHistoryPipelines;
 pub use prices::Prices;
 pub use refresh_materialized_view::RefreshMaterializedView;
 pub use rolling_volume::RollingVolume;
+pub use trades::Trades;
 pub use user_balances::UserBalances;
 pub use user_history::UserHistory;
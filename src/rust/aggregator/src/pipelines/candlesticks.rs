@@ -4,7 +4,7 @@ use sqlx::PgPool;
 use sqlx_postgres::PgConnection;
 
 use aggregator::{
-    util::{commit_transaction, create_repeatable_read_transaction},
+    util::{commit_transaction, create_locked_transaction},
     Pipeline, PipelineAggregationResult, PipelineError,
 };
 
@@ -55,7 +55,11 @@ impl Pipeline for Candlesticks {
     }
 
     async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
-        let mut transaction = create_repeatable_read_transaction(&self.pool).await?;
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
 
         sqlx::query_file!("sqlx_queries/candlesticks/insert_data.sql", self.resolution,)
             .execute(&mut transaction as &mut PgConnection)
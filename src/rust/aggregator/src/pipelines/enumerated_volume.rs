@@ -1,7 +1,7 @@
 use chrono::{DateTime, Duration, Utc};
 use sqlx::{PgConnection, PgPool};
 
-use aggregator::{util::*, Pipeline, PipelineAggregationResult};
+use aggregator::{util::*, Pipeline, PipelineAggregationResult, PipelineError};
 
 pub const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
 
@@ -40,7 +40,11 @@ impl Pipeline for EnumeratedVolume {
     }
 
     async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
-        let mut transaction = create_repeatable_read_transaction(&self.pool).await?;
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
         sqlx::query_file!("sqlx_queries/enumerated_volume/update.sql",)
             .execute(&mut transaction as &mut PgConnection)
             .await
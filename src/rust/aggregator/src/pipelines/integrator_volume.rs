@@ -0,0 +1,268 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgConnection, PgPool};
+
+use aggregator::{util::*, Pipeline, PipelineAggregationResult, PipelineError};
+
+pub const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Daily maker volume, taker volume and taker fees attributed to the integrator of each order.
+///
+/// Reads the integrator of an order from its placement. Never aggregates past the user history
+/// watermark, nor past a fill still pending there, whose placement has not been indexed yet: such
+/// a fill holds the pipeline back until it is resolved.
+pub struct IntegratorVolume {
+    pool: PgPool,
+    last_indexed_timestamp: Option<DateTime<Utc>>,
+}
+
+impl IntegratorVolume {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            last_indexed_timestamp: None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Pipeline for IntegratorVolume {
+    fn model_name(&self) -> String {
+        String::from("IntegratorVolume")
+    }
+
+    fn ready(&self) -> bool {
+        self.last_indexed_timestamp.is_none()
+            || self.last_indexed_timestamp.unwrap() + Duration::from_std(TIMEOUT).unwrap()
+                < Utc::now()
+    }
+
+    async fn process_and_save_historical_data(&mut self) -> PipelineAggregationResult {
+        self.process_and_save_internal().await
+    }
+
+    fn poll_interval(&self) -> Option<std::time::Duration> {
+        Some(TIMEOUT)
+    }
+
+    fn reads(&self) -> &[&'static str] {
+        &[
+            "fill_events",
+            "market_registration_events",
+            "place_limit_order_events",
+            "place_market_order_events",
+            "place_swap_order_events",
+            "aggregator.user_history_last_indexed_txn",
+            "aggregator.pending_fills",
+        ]
+    }
+
+    fn writes(&self) -> &[&'static str] {
+        &[
+            "aggregator.integrator_volume",
+            "aggregator.integrator_volume_last_indexed_txn",
+        ]
+    }
+
+    async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
+        aggregate(&mut transaction)
+            .await
+            .map_err(to_pipeline_error)?;
+        commit_transaction(transaction).await?;
+        self.last_indexed_timestamp = Some(Utc::now());
+        Ok(())
+    }
+}
+
+/// Aggregates the fills up to the new watermark and moves the watermark there. Does nothing before
+/// the user history pipeline set its own watermark.
+async fn aggregate(conn: &mut PgConnection) -> sqlx::Result<()> {
+    let Some(txn_version) =
+        sqlx::query_file!("sqlx_queries/integrator_volume/get_new_last_indexed_txn_version.sql",)
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(|r| r.txn_version)
+    else {
+        return Ok(());
+    };
+    sqlx::query_file!("sqlx_queries/integrator_volume/update.sql", txn_version)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query_file!(
+        "sqlx_queries/integrator_volume/update_last_indexed_txn.sql",
+        txn_version
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::{Executor, PgConnection};
+
+    use super::aggregate;
+    use crate::test_db::{self, insert, MARKET_ID};
+
+    /// Versions past any fixture, at which the test fills are recorded.
+    const START: i64 = i64::MAX - 100;
+
+    /// Registers [`MARKET_ID`] with a tick size of 10, places limit order 1 through integrator
+    /// `0x1` and market order 2 through integrator `0x2`, and moves the watermarks to [`START`] for
+    /// this pipeline and `user_history` for the user history.
+    async fn seed(conn: &mut PgConnection, user_history: i64) {
+        insert(
+            conn,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID, "tick_size": 10 }),
+        )
+        .await;
+        place(conn, "place_limit_order_events", 1, "0x1").await;
+        place(conn, "place_market_order_events", 2, "0x2").await;
+        conn.execute(
+            format!(
+                "DELETE FROM aggregator.user_history_last_indexed_txn; \
+                 INSERT INTO aggregator.user_history_last_indexed_txn VALUES ({user_history}); \
+                 UPDATE aggregator.integrator_volume_last_indexed_txn SET txn_version = {START}"
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Records the placement of `order_id` through `integrator` in `table`.
+    async fn place(conn: &mut PgConnection, table: &str, order_id: i64, integrator: &str) {
+        insert(
+            conn,
+            table,
+            json!({
+                "txn_version": START - order_id,
+                "market_id": MARKET_ID,
+                "order_id": order_id,
+                "integrator": integrator,
+                // The other columns of the placements of any kind, each table taking its own.
+                "user": "0xa",
+                "signing_account": "0xa",
+                "side": false,
+                "direction": false,
+                "initial_size": 1,
+                "size": 1,
+                "price": 1,
+                "limit_price": 1,
+                "min_base": 0,
+                "max_base": 1,
+                "min_quote": 0,
+                "max_quote": 1,
+            }),
+        )
+        .await;
+    }
+
+    /// Records a fill of `size` at price 2 between maker order 1 and taker order
+    /// `taker_order_id`, for which the taker paid a fee of 3.
+    async fn fill(conn: &mut PgConnection, txn_version: i64, taker_order_id: i64, size: i64) {
+        insert(
+            conn,
+            "fill_events",
+            json!({
+                "txn_version": txn_version,
+                "event_idx": 0,
+                "emit_address": "0xa",
+                "time": "2024-01-01T12:00:00+00:00",
+                "market_id": MARKET_ID,
+                "maker_address": "0xa",
+                "maker_order_id": 1,
+                "maker_side": true,
+                "taker_address": "0xb",
+                "taker_order_id": taker_order_id,
+                "price": 2,
+                "size": size,
+                "taker_quote_fees_paid": 3,
+            }),
+        )
+        .await;
+    }
+
+    /// Runs the pipeline and returns its watermark and the `(integrator, maker_volume,
+    /// taker_volume, taker_fees)` of [`MARKET_ID`].
+    async fn run(conn: &mut PgConnection) -> (i64, Vec<(String, i64, i64, i64)>) {
+        aggregate(conn).await.unwrap();
+        let watermark = sqlx::query_scalar(
+            "SELECT txn_version::int8 FROM aggregator.integrator_volume_last_indexed_txn",
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        let volumes = sqlx::query_as(
+            "SELECT integrator, maker_volume_in_quote_subunits::int8, \
+             taker_volume_in_quote_subunits::int8, taker_fees_in_quote_subunits::int8 \
+             FROM aggregator.integrator_volume WHERE market_id = $1 ORDER BY integrator",
+        )
+        .bind(MARKET_ID)
+        .fetch_all(conn)
+        .await
+        .unwrap();
+        (watermark, volumes)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn volume_is_rolled_up_per_integrator_and_side() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, START + 10).await;
+        fill(&mut tx, START + 1, 2, 1).await;
+        fill(&mut tx, START + 2, 2, 4).await;
+        // Past the user history watermark, left to the next run.
+        fill(&mut tx, START + 11, 2, 100).await;
+        assert_eq!(
+            run(&mut tx).await,
+            (
+                START + 10,
+                vec![("0x1".into(), 100, 0, 0), ("0x2".into(), 0, 100, 6)]
+            )
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn pending_fills_hold_the_watermark_back() {
+        let mut tx = test_db::begin().await;
+        seed(&mut tx, START + 10).await;
+        fill(&mut tx, START + 1, 2, 1).await;
+        // The placement of order 3 is not indexed yet.
+        fill(&mut tx, START + 2, 3, 4).await;
+        sqlx::query("INSERT INTO aggregator.pending_fills VALUES ($1, 0, $2, 3)")
+            .bind(START + 2)
+            .bind(MARKET_ID)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(
+            run(&mut tx).await,
+            (
+                START + 1,
+                vec![("0x1".into(), 20, 0, 0), ("0x2".into(), 0, 20, 3)]
+            )
+        );
+        // Once the placement is indexed, the user history resolves the fill.
+        place(&mut tx, "place_swap_order_events", 3, "0x2").await;
+        sqlx::query("DELETE FROM aggregator.pending_fills WHERE market_id = $1")
+            .bind(MARKET_ID)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(
+            run(&mut tx).await,
+            (
+                START + 10,
+                vec![("0x1".into(), 100, 0, 0), ("0x2".into(), 0, 100, 6)]
+            )
+        );
+    }
+}
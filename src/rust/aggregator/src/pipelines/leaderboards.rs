@@ -4,7 +4,7 @@ use chrono::{DateTime, Duration, Utc};
 use sqlx::{PgConnection, PgPool, Postgres, Transaction};
 
 use aggregator::{
-    util::{commit_transaction, create_repeatable_read_transaction},
+    util::{commit_transaction, create_locked_transaction},
     Pipeline, PipelineAggregationResult, PipelineError,
 };
 
@@ -50,7 +50,11 @@ impl Pipeline for Leaderboards {
     }
 
     async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
-        let mut transaction = create_repeatable_read_transaction(&self.pool).await?;
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
 
         // Get all competitions having created markets
         let competitions = sqlx::query_as!(
@@ -0,0 +1,166 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgConnection, PgPool};
+
+use aggregator::{util::*, Pipeline, PipelineAggregationResult, PipelineError};
+
+pub const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Maintains the rolling 24 hour statistics of every market in `aggregator.market_stats_24h`.
+///
+/// Only fills of the last 24 hours are scanned. Since the window moves even when no fill comes
+/// in, the statistics are recomputed every cycle rather than when there are new events.
+pub struct MarketStats24h {
+    pool: PgPool,
+    last_indexed_timestamp: Option<DateTime<Utc>>,
+}
+
+impl MarketStats24h {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            last_indexed_timestamp: None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Pipeline for MarketStats24h {
+    fn model_name(&self) -> String {
+        String::from("MarketStats24h")
+    }
+
+    fn ready(&self) -> bool {
+        self.last_indexed_timestamp.is_none()
+            || self.last_indexed_timestamp.unwrap() + Duration::from_std(TIMEOUT).unwrap()
+                < Utc::now()
+    }
+
+    async fn process_and_save_historical_data(&mut self) -> PipelineAggregationResult {
+        self.process_and_save_internal().await
+    }
+
+    fn poll_interval(&self) -> Option<std::time::Duration> {
+        Some(TIMEOUT)
+    }
+
+    fn reads(&self) -> &[&'static str] {
+        &["fill_events", "market_registration_events"]
+    }
+
+    fn writes(&self) -> &[&'static str] {
+        &["aggregator.market_stats_24h"]
+    }
+
+    async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
+        refresh(&mut transaction).await.map_err(to_pipeline_error)?;
+        commit_transaction(transaction).await?;
+        self.last_indexed_timestamp = Some(Utc::now());
+        Ok(())
+    }
+}
+
+/// Recomputes the statistics of every market over the 24 hours before the start of the
+/// transaction of `conn`.
+pub(crate) async fn refresh(conn: &mut PgConnection) -> sqlx::Result<()> {
+    sqlx::query_file!("sqlx_queries/market_stats_24h/refresh.sql")
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::{Executor, PgConnection};
+
+    use super::refresh;
+    use crate::test_db::{self, insert, MARKET_ID};
+
+    /// Records a fill of `size` at `price`, `hours_ago` hours before the start of the transaction.
+    async fn fill(
+        conn: &mut PgConnection,
+        txn_version: i64,
+        hours_ago: i64,
+        price: i64,
+        size: i64,
+    ) {
+        let time: String = sqlx::query_scalar(
+            "SELECT (CURRENT_TIMESTAMP - make_interval(hours => $1::int))::text",
+        )
+        .bind(hours_ago)
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        insert(
+            conn,
+            "fill_events",
+            json!({
+                "txn_version": txn_version,
+                "event_idx": 0,
+                "emit_address": "0xa",
+                "time": time,
+                "market_id": MARKET_ID,
+                "maker_address": "0xa",
+                "maker_order_id": 1,
+                "maker_side": true,
+                "taker_address": "0xb",
+                "taker_order_id": 2,
+                "price": price,
+                "size": size,
+                "taker_quote_fees_paid": 0,
+            }),
+        )
+        .await;
+    }
+
+    /// Runs the pipeline and returns the `(last_price, price_24h_ago, open, high, low,
+    /// base_volume, quote_volume)` of [`MARKET_ID`].
+    async fn run(conn: &mut PgConnection) -> (i64, i64, i64, i64, i64, i64, i64) {
+        refresh(conn).await.unwrap();
+        sqlx::query_as(
+            "SELECT last_price::int8, price_24h_ago::int8, \"open\"::int8, high::int8, low::int8, \
+             base_volume::int8, quote_volume::int8 \
+             FROM aggregator.market_stats_24h WHERE market_id = $1",
+        )
+        .bind(MARKET_ID)
+        .fetch_one(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn fills_leave_the_window_after_24_hours() {
+        let mut tx = test_db::begin().await;
+        insert(
+            &mut tx,
+            "market_registration_events",
+            json!({ "txn_version": 1, "market_id": MARKET_ID, "tick_size": 10 }),
+        )
+        .await;
+        let start = i64::MAX - 100;
+        fill(&mut tx, start, 25, 5, 1).await;
+        fill(&mut tx, start + 1, 2, 7, 2).await;
+        fill(&mut tx, start + 2, 1, 9, 3).await;
+        // Volumes are in lots and ticks, the tick size is left out.
+        assert_eq!(run(&mut tx).await, (9, 5, 7, 9, 7, 5, 7 * 2 + 9 * 3));
+
+        // The window moves without any new fill.
+        tx.execute(
+            format!(
+                "UPDATE fill_events SET \"time\" = \"time\" - interval '23 hours' \
+                 WHERE market_id = {MARKET_ID} AND txn_version = {}",
+                start + 1
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(run(&mut tx).await, (9, 7, 9, 9, 9, 3, 9 * 3));
+    }
+}
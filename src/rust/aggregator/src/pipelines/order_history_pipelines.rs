@@ -454,7 +454,11 @@ impl Pipeline for OrderHistoryPipelines {
     }
 
     async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
-        let mut transaction = create_repeatable_read_transaction(&self.pool).await?;
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
         let address_to_group =
             sqlx::query_file!("sqlx_queries/order_history_pipelines/get_liquidity_groups.sql")
                 .fetch_all(&mut transaction as &mut PgConnection)
@@ -1,7 +1,7 @@
 use chrono::{DateTime, Duration, Utc};
 use sqlx::{PgConnection, PgPool};
 
-use aggregator::{util::*, Pipeline, PipelineAggregationResult};
+use aggregator::{util::*, Pipeline, PipelineAggregationResult, PipelineError};
 
 pub const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
 
@@ -39,24 +39,114 @@ impl Pipeline for Prices {
         Some(TIMEOUT)
     }
 
-    async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
-        let mut transaction = create_repeatable_read_transaction(&self.pool).await?;
-        sqlx::query_file!("sqlx_queries/prices/backfill.sql",)
-            .execute(&mut transaction as &mut PgConnection)
-            .await
-            .map_err(to_pipeline_error)?;
+    fn reads(&self) -> &[&'static str] {
+        &["fill_events"]
+    }
+
+    fn writes(&self) -> &[&'static str] {
+        &["aggregator.prices", "aggregator.prices_last_indexed_txn"]
+    }
 
-        let res = sqlx::query_file!("sqlx_queries/prices/update_last_indexed_timestamp.sql",)
-            .execute(&mut transaction as &mut PgConnection)
+    async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
+        aggregate(&mut transaction)
             .await
             .map_err(to_pipeline_error)?;
-        if res.rows_affected() == 0 {
-            sqlx::query_file!("sqlx_queries/prices/insert_last_indexed_timestamp.sql",)
-                .execute(&mut transaction as &mut PgConnection)
-                .await
-                .map_err(to_pipeline_error)?;
-        }
         commit_transaction(transaction).await?;
         Ok(())
     }
 }
+
+/// Aggregates the fills after the watermark into the prices of their minute, and moves the
+/// watermark to the last fill.
+async fn aggregate(conn: &mut PgConnection) -> sqlx::Result<()> {
+    sqlx::query_file!("sqlx_queries/prices/backfill.sql")
+        .execute(&mut *conn)
+        .await?;
+    let res = sqlx::query_file!("sqlx_queries/prices/update_last_indexed_timestamp.sql")
+        .execute(&mut *conn)
+        .await?;
+    if res.rows_affected() == 0 {
+        sqlx::query_file!("sqlx_queries/prices/insert_last_indexed_timestamp.sql")
+            .execute(conn)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::{Executor, PgConnection};
+
+    use super::aggregate;
+    use crate::test_db::{self, insert, MARKET_ID};
+
+    /// Versions past any fixture, at which the test fills are recorded.
+    const START: i64 = i64::MAX - 100;
+
+    /// Records a fill of size 1 at `price` within the same minute as the other test fills.
+    async fn fill(conn: &mut PgConnection, txn_version: i64, price: i64) {
+        insert(
+            conn,
+            "fill_events",
+            json!({
+                "txn_version": txn_version,
+                "event_idx": 0,
+                "emit_address": "0xa",
+                "time": format!("2024-01-01T12:00:{:02}+00:00", txn_version - START),
+                "market_id": MARKET_ID,
+                "maker_address": "0xa",
+                "maker_order_id": 1,
+                "maker_side": true,
+                "taker_address": "0xb",
+                "taker_order_id": 2,
+                "price": price,
+                "size": 1,
+                "taker_quote_fees_paid": 0,
+            }),
+        )
+        .await;
+    }
+
+    /// Runs the pipeline and returns the `(open, high, low, close)` of the minute of the test
+    /// fills.
+    async fn run(conn: &mut PgConnection) -> (i64, i64, i64, i64) {
+        aggregate(conn).await.unwrap();
+        sqlx::query_as(
+            "SELECT \"open\"::int8, high::int8, low::int8, \"close\"::int8 \
+             FROM aggregator.prices WHERE market_id = $1",
+        )
+        .bind(MARKET_ID)
+        .fetch_one(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn ohlc_of_fills_within_a_minute() {
+        let mut tx = test_db::begin().await;
+        tx.execute(
+            format!(
+                "DELETE FROM aggregator.prices_last_indexed_txn; \
+                 INSERT INTO aggregator.prices_last_indexed_txn VALUES ({START})"
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+        for (i, price) in [5, 9, 3, 6].into_iter().enumerate() {
+            fill(&mut tx, START + 1 + i as i64, price).await;
+        }
+        assert_eq!(run(&mut tx).await, (5, 9, 3, 6));
+        // Fills of the same minute aggregated by a later run extend its prices.
+        fill(&mut tx, START + 5, 10).await;
+        fill(&mut tx, START + 6, 4).await;
+        assert_eq!(run(&mut tx).await, (5, 10, 3, 4));
+    }
+}
@@ -0,0 +1,156 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgConnection, PgPool};
+
+use aggregator::{util::*, Pipeline, PipelineAggregationResult, PipelineError};
+
+pub const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Aggregates fills into `aggregator.trades`, one row per trade, identified by the first fill
+/// emitted for it.
+pub struct Trades {
+    pool: PgPool,
+    last_indexed_timestamp: Option<DateTime<Utc>>,
+}
+
+impl Trades {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            last_indexed_timestamp: None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Pipeline for Trades {
+    fn model_name(&self) -> String {
+        String::from("Trades")
+    }
+
+    fn ready(&self) -> bool {
+        self.last_indexed_timestamp.is_none()
+            || self.last_indexed_timestamp.unwrap() + Duration::from_std(TIMEOUT).unwrap()
+                < Utc::now()
+    }
+
+    async fn process_and_save_historical_data(&mut self) -> PipelineAggregationResult {
+        self.process_and_save_internal().await
+    }
+
+    fn poll_interval(&self) -> Option<std::time::Duration> {
+        Some(TIMEOUT)
+    }
+
+    fn reads(&self) -> &[&'static str] {
+        &["fill_events"]
+    }
+
+    fn writes(&self) -> &[&'static str] {
+        &["aggregator.trades", "aggregator.trades_last_indexed_txn"]
+    }
+
+    async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
+        // Trades already aggregated are skipped, so a batch interrupted before its commit is
+        // simply aggregated again.
+        sqlx::query_file!("sqlx_queries/trades/backfill.sql",)
+            .execute(&mut transaction as &mut PgConnection)
+            .await
+            .map_err(to_pipeline_error)?;
+
+        let res = sqlx::query_file!("sqlx_queries/trades/update_last_indexed_txn.sql",)
+            .execute(&mut transaction as &mut PgConnection)
+            .await
+            .map_err(to_pipeline_error)?;
+        if res.rows_affected() == 0 {
+            sqlx::query_file!("sqlx_queries/trades/insert_last_indexed_txn.sql",)
+                .execute(&mut transaction as &mut PgConnection)
+                .await
+                .map_err(to_pipeline_error)?;
+        }
+        commit_transaction(transaction).await?;
+        self.last_indexed_timestamp = Some(Utc::now());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgConnection;
+
+    use crate::test_db::{self, insert, MARKET_ID};
+
+    /// Records the fills emitted to the maker and the taker when `taker_order_id` takes `size`
+    /// from order 1 at `txn_version`, as the first fill of its match.
+    async fn trade(conn: &mut PgConnection, txn_version: i64, taker_order_id: i64, size: i64) {
+        for (event_idx, emit_address) in [(0, "0xa"), (1, "0xb")] {
+            insert(
+                conn,
+                "fill_events",
+                json!({
+                    "txn_version": txn_version,
+                    "event_idx": event_idx,
+                    "emit_address": emit_address,
+                    "market_id": MARKET_ID,
+                    "maker_address": "0xa",
+                    "maker_order_id": 1,
+                    "maker_side": true,
+                    "taker_address": "0xb",
+                    "taker_order_id": taker_order_id,
+                    "price": 10,
+                    "size": size,
+                    "taker_quote_fees_paid": 0,
+                }),
+            )
+            .await;
+        }
+    }
+
+    /// Runs the backfill of the pipeline and returns the trades of [`MARKET_ID`], oldest first.
+    async fn backfill(conn: &mut PgConnection) -> Vec<(String, i64, i64)> {
+        sqlx::query(include_str!("../../sqlx_queries/trades/backfill.sql"))
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query_as(
+            "SELECT trade_id, taker_order_id::int8, size::int8 FROM aggregator.trades \
+             WHERE market_id = $1 ORDER BY txn_version, event_idx",
+        )
+        .bind(MARKET_ID)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn one_trade_per_maker_and_taker_fill_pair() {
+        let mut tx = test_db::begin().await;
+        trade(&mut tx, i64::MAX - 10, 3, 4).await;
+        let trades = backfill(&mut tx).await;
+        assert_eq!(trades, [(format!("{}-0", i64::MAX - 10), 3, 4)]);
+        // The trade ID does not change when the fills are read again.
+        assert_eq!(backfill(&mut tx).await, trades);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn takers_filling_the_same_maker_order_are_separate_trades() {
+        let mut tx = test_db::begin().await;
+        // Both fills are the first of their match, so they have the same sequence number.
+        trade(&mut tx, i64::MAX - 20, 3, 4).await;
+        trade(&mut tx, i64::MAX - 10, 4, 6).await;
+        assert_eq!(
+            backfill(&mut tx).await,
+            [
+                (format!("{}-0", i64::MAX - 20), 3, 4),
+                (format!("{}-0", i64::MAX - 10), 4, 6),
+            ]
+        );
+    }
+}
@@ -1,11 +1,17 @@
 use anyhow::anyhow;
-use bigdecimal::{num_bigint::ToBigInt, BigDecimal, Zero};
+use bigdecimal::{num_bigint::BigInt, BigDecimal, Zero};
 use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
 use sqlx::{PgConnection, PgPool, Postgres, Transaction};
 
 use aggregator::{
-    util::{commit_transaction, create_repeatable_read_transaction},
-    Pipeline, PipelineAggregationResult, PipelineError,
+    amount,
+    profile::{timed, Statement},
+    util::{
+        commit_transaction, create_locked_transaction, decimal_to_u128, use_source_schema,
+        StepTimer,
+    },
+    Pipeline, PipelineAggregationResult, PipelineError, ProcessSummary,
 };
 
 use crate::{dbtypes::OrderType, TARGET_EVENTS, MAX_BATCH_SIZE, update_batch_size, DEFAULT_BATCH_SIZE};
@@ -15,21 +21,83 @@ const SHIFT_TXN_VERSION: u8 = 64;
 
 pub const TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
 
+/// A kind of event aggregated into the user history.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum EventKind {
+    /// Limit order placements.
+    Limit,
+    /// Market order placements.
+    Market,
+    /// Swap placements.
+    Swap,
+    /// Fills.
+    Fill,
+    /// Order size changes.
+    Change,
+    /// Cancels.
+    Cancel,
+}
+
 pub struct UserHistory {
     pool: PgPool,
     last_indexed_timestamp: Option<DateTime<Utc>>,
+    /// Last transaction version aggregated, as persisted in
+    /// `aggregator.user_history_last_indexed_txn`.
+    last_indexed_txn_version: Option<BigDecimal>,
+    /// Number of placements, fills, size changes and cancels aggregated by the last run.
+    processed_events: u64,
     batch_size: BigDecimal,
+    /// If `true`, a fill larger than the remaining size of its order fails the batch instead of
+    /// being clamped to the remaining size.
+    strict_fills: bool,
+    /// Steps of an aggregation run taking longer than this are logged.
+    slow_step_threshold: std::time::Duration,
+    /// Schema the event tables are read from, `public` if `None`.
+    source_schema: Option<String>,
+    /// Markets to aggregate, every market if `None`. Events of other markets are skipped, but
+    /// still count as aggregated.
+    market_ids: Option<Vec<BigDecimal>>,
+    /// Kinds of events to aggregate, every kind if `None`. Events of other kinds are skipped, but
+    /// still count as aggregated.
+    event_kinds: Option<Vec<EventKind>>,
+    /// Maximum number of transaction versions aggregated by a run, unbounded if `None`. The rest
+    /// is left to the next runs, so that a failed run only rolls back its own versions.
+    max_txn_versions: Option<BigDecimal>,
 }
 
 impl UserHistory {
-    pub fn new(pool: PgPool) -> Self {
+    pub fn new(
+        pool: PgPool,
+        strict_fills: bool,
+        slow_step_threshold: std::time::Duration,
+        source_schema: Option<String>,
+        market_ids: Option<Vec<BigDecimal>>,
+        event_kinds: Option<Vec<EventKind>>,
+        max_txn_versions: Option<BigDecimal>,
+    ) -> Self {
         Self {
             pool,
             last_indexed_timestamp: None,
+            last_indexed_txn_version: None,
+            processed_events: 0,
             // Start with a very small batch size.
             // This way, if the aggregator is restarting after a crash due to too many events in
             // ram, it will not just crash again.
             batch_size: BigDecimal::from(DEFAULT_BATCH_SIZE),
+            strict_fills,
+            slow_step_threshold,
+            source_schema,
+            market_ids,
+            event_kinds,
+            max_txn_versions,
+        }
+    }
+
+    /// Returns `true` if events of `kind` are aggregated.
+    fn aggregates(&self, kind: EventKind) -> bool {
+        match &self.event_kinds {
+            Some(kinds) => kinds.contains(&kind),
+            None => true,
         }
     }
 }
@@ -46,7 +114,21 @@ impl Pipeline for UserHistory {
                 < Utc::now()
     }
 
+    /// Resumes from the position persisted in the database, so that a restart does not rescan
+    /// already aggregated history.
     async fn process_and_save_historical_data(&mut self) -> PipelineAggregationResult {
+        self.last_indexed_txn_version = timed(
+            Statement::Select,
+            sqlx::query_file!("sqlx_queries/user_history/get_last_indexed_txn_version.sql",)
+                .fetch_optional(&self.pool),
+        )
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        .map(|r| r.txn_version);
+        match &self.last_indexed_txn_version {
+            Some(txn_version) => tracing::info!(%txn_version, "Resuming aggregation."),
+            None => tracing::info!("No aggregated history found, starting from scratch."),
+        }
         self.process_and_save_internal().await
     }
 
@@ -54,19 +136,134 @@ impl Pipeline for UserHistory {
         Some(TIMEOUT)
     }
 
+    /// Orders are what traders act on, so the other pipelines yield to this one when it lags.
+    fn priority(&self) -> u8 {
+        1
+    }
+
+    fn reads(&self) -> &[&'static str] {
+        &[
+            "place_limit_order_events",
+            "place_market_order_events",
+            "place_swap_order_events",
+            "fill_events",
+            "change_order_size_events",
+            "cancel_order_events",
+        ]
+    }
+
+    fn writes(&self) -> &[&'static str] {
+        &[
+            "aggregator.user_history",
+            "aggregator.user_history_last_indexed_txn",
+            "aggregator.pending_cancels",
+            "aggregator.order_size_changes",
+            "aggregator.pending_fills",
+            "aggregator.pending_size_changes",
+        ]
+    }
+
+    fn expected_columns(&self) -> &[(&'static str, &'static [&'static str])] {
+        &[
+            (
+                "aggregator.user_history",
+                &[
+                    "market_id",
+                    "order_id",
+                    "created_at",
+                    "last_updated_at",
+                    "integrator",
+                    "total_filled",
+                    "remaining_size",
+                    "order_status",
+                    "order_type",
+                    "user",
+                    "direction",
+                    "price",
+                    "average_execution_price",
+                    "custodian_id",
+                    "self_match_behavior",
+                    "restriction",
+                    "last_increase_stamp",
+                    "min_base",
+                    "max_base",
+                    "min_quote",
+                    "max_quote",
+                    "total_fees_paid_in_quote_subunits",
+                    "close_reason",
+                ],
+            ),
+            ("aggregator.user_history_last_indexed_txn", &["txn_version"]),
+            (
+                "aggregator.pending_cancels",
+                &["txn_version", "event_idx", "market_id", "order_id"],
+            ),
+            (
+                "aggregator.order_size_changes",
+                &[
+                    "market_id",
+                    "order_id",
+                    "old_size",
+                    "new_size",
+                    "txn_version",
+                    "event_idx",
+                    "time",
+                ],
+            ),
+            (
+                "aggregator.pending_fills",
+                &["txn_version", "event_idx", "market_id", "order_id"],
+            ),
+            (
+                "aggregator.pending_size_changes",
+                &["txn_version", "event_idx", "market_id", "order_id"],
+            ),
+        ]
+    }
+
+    async fn has_work(&self) -> Result<bool, PipelineError> {
+        let Some(last_indexed_txn_version) = &self.last_indexed_txn_version else {
+            return Ok(true);
+        };
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+        use_source_schema(&mut transaction, self.source_schema.as_deref()).await?;
+        Ok(sqlx::query_file!(
+            "sqlx_queries/user_history/has_new_events.sql",
+            last_indexed_txn_version,
+            self.market_ids.as_deref(),
+        )
+        .fetch_one(&mut transaction as &mut PgConnection)
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        .has_new_events)
+    }
+
     /// All database interactions are handled in a single atomic transaction. Processor insertions
     /// are also handled in a single atomic transaction for each batch of transactions, such that
     /// user history aggregation logic is effectively serialized across historical chain state.
     async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
-        let mut transaction = create_repeatable_read_transaction(&self.pool).await?;
+        self.processed_events = 0;
+        let Some(mut transaction) =
+            create_locked_transaction(&self.pool, &self.model_name()).await?
+        else {
+            return Err(PipelineError::Locked);
+        };
+        use_source_schema(&mut transaction, self.source_schema.as_deref()).await?;
         struct TxnVersion {
             txn_version: BigDecimal,
         }
-        let last_indexed_txn_version = sqlx::query_file_as!(
-            TxnVersion,
-            "sqlx_queries/user_history/get_last_indexed_txn_version.sql",
+        let last_indexed_txn_version = timed(
+            Statement::Select,
+            sqlx::query_file_as!(
+                TxnVersion,
+                "sqlx_queries/user_history/get_last_indexed_txn_version.sql",
+            )
+            .fetch_optional(&mut transaction as &mut PgConnection),
         )
-        .fetch_optional(&mut transaction as &mut PgConnection)
         .await
         .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
         let txnv_exists = last_indexed_txn_version.is_some();
@@ -75,87 +272,314 @@ impl Pipeline for UserHistory {
                 txn_version: BigDecimal::zero(),
             })
             .txn_version;
-        sqlx::query_file!(
-            "sqlx_queries/user_history/insert_user_history_limit.sql",
-            last_indexed_txn_version,
+        let mut txn_version_stop = timed(
+            Statement::Select,
+            sqlx::query_file!("sqlx_queries/user_history/get_new_last_indexed_txn_version.sql",)
+                .fetch_one(&mut transaction as &mut PgConnection),
         )
-        .execute(&mut transaction as &mut PgConnection)
         .await
-        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
-        sqlx::query_file!(
-            "sqlx_queries/user_history/insert_user_history_market.sql",
-            last_indexed_txn_version,
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        .max
+        .unwrap_or(BigDecimal::zero());
+        if let Some(max_txn_versions) = &self.max_txn_versions {
+            txn_version_stop = txn_version_stop.min(&last_indexed_txn_version + max_txn_versions);
+        }
+        if let Some(negative) = timed(
+            Statement::Select,
+            sqlx::query_file!(
+                "sqlx_queries/user_history/get_negative_amount.sql",
+                last_indexed_txn_version,
+                txn_version_stop,
+                self.market_ids.as_deref(),
+            )
+            .fetch_optional(&mut transaction as &mut PgConnection),
         )
-        .execute(&mut transaction as &mut PgConnection)
         .await
-        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
-        sqlx::query_file!(
-            "sqlx_queries/user_history/insert_user_history_swap.sql",
-            last_indexed_txn_version,
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        {
+            return Err(PipelineError::ProcessingError(anyhow!(
+                "negative {} in {} event at txn version {} index {}",
+                negative.field,
+                negative.table,
+                negative.txn_version,
+                negative.event_idx,
+            )));
+        }
+        let timer = StepTimer::start("insert placements");
+        let mut placements = 0;
+        if self.aggregates(EventKind::Limit) {
+            placements += timed(
+                Statement::Insert,
+                sqlx::query_file!(
+                    "sqlx_queries/user_history/insert_user_history_limit.sql",
+                    last_indexed_txn_version,
+                    self.market_ids.as_deref(),
+                    txn_version_stop,
+                )
+                .execute(&mut transaction as &mut PgConnection),
+            )
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+            .rows_affected();
+        }
+        if self.aggregates(EventKind::Market) {
+            placements += timed(
+                Statement::Insert,
+                sqlx::query_file!(
+                    "sqlx_queries/user_history/insert_user_history_market.sql",
+                    last_indexed_txn_version,
+                    self.market_ids.as_deref(),
+                    txn_version_stop,
+                )
+                .execute(&mut transaction as &mut PgConnection),
+            )
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+            .rows_affected();
+        }
+        if self.aggregates(EventKind::Swap) {
+            placements += timed(
+                Statement::Insert,
+                sqlx::query_file!(
+                    "sqlx_queries/user_history/insert_user_history_swap.sql",
+                    last_indexed_txn_version,
+                    self.market_ids.as_deref(),
+                    txn_version_stop,
+                )
+                .execute(&mut transaction as &mut PgConnection),
+            )
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+            .rows_affected();
+        }
+        timer.finish(self.slow_step_threshold, placements as usize);
+
+        // Fills and size changes kept by earlier runs for orders that were not aggregated yet,
+        // applied before any newer event of their order.
+        let timer = StepTimer::start("pending fills and size changes");
+        let resolved = timed(
+            Statement::Select,
+            sqlx::query_file!("sqlx_queries/user_history/get_resolved_pending_orders.sql",)
+                .fetch_all(&mut transaction as &mut PgConnection),
         )
-        .execute(&mut transaction as &mut PgConnection)
         .await
         .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+        for order in &resolved {
+            let fill_events = timed(
+                Statement::Select,
+                sqlx::query_file_as!(
+                    FillEvent,
+                    "sqlx_queries/user_history/get_pending_fill_events.sql",
+                    order.market_id,
+                    order.order_id,
+                )
+                .fetch_all(&mut transaction as &mut PgConnection),
+            )
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+            let change_events = timed(
+                Statement::Select,
+                sqlx::query_file_as!(
+                    ChangeEvent,
+                    "sqlx_queries/user_history/get_pending_change_order_size_events.sql",
+                    order.market_id,
+                    order.order_id,
+                )
+                .fetch_all(&mut transaction as &mut PgConnection),
+            )
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+            aggregate_events(
+                &mut transaction,
+                &fill_events,
+                &change_events,
+                Some(&order.order_id),
+                self.strict_fills,
+                (&BigDecimal::zero(), &last_indexed_txn_version),
+            )
+            .await?;
+            timed(
+                Statement::Delete,
+                sqlx::query_file!(
+                    "sqlx_queries/user_history/delete_order_pending_events.sql",
+                    order.market_id,
+                    order.order_id,
+                )
+                .execute(&mut transaction as &mut PgConnection),
+            )
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+        }
+        timer.finish(self.slow_step_threshold, resolved.len());
 
+        let mut processed_events = placements;
         let mut txn_version_start = last_indexed_txn_version.clone();
-        let txn_version_stop =
-            sqlx::query_file!("sqlx_queries/user_history/get_new_last_indexed_txn_version.sql",)
-                .fetch_one(&mut transaction as &mut PgConnection)
-                .await
-                .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
-                .max
-                .unwrap_or(BigDecimal::zero());
 
         while txn_version_start < txn_version_stop {
             let txn_version_iter_stop = (txn_version_start.clone()
                 + &self.batch_size)
             .min(txn_version_stop.clone());
-            let fill_events = sqlx::query_file!(
-                "sqlx_queries/user_history/get_fill_events.sql",
-                txn_version_start,
-                txn_version_iter_stop,
+            let fill_events = if self.aggregates(EventKind::Fill) {
+                let timer = StepTimer::start("fill query");
+                let fill_events = timed(
+                    Statement::Select,
+                    sqlx::query_file_as!(
+                        FillEvent,
+                        "sqlx_queries/user_history/get_fill_events.sql",
+                        txn_version_start,
+                        txn_version_iter_stop,
+                        self.market_ids.as_deref(),
+                    )
+                    .fetch_all(&mut transaction as &mut PgConnection),
+                )
+                .await
+                .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+                timer.finish(self.slow_step_threshold, fill_events.len());
+                fill_events
+            } else {
+                vec![]
+            };
+            let change_events = if self.aggregates(EventKind::Change) {
+                let timer = StepTimer::start("change query");
+                let change_events = timed(
+                    Statement::Select,
+                    sqlx::query_file_as!(
+                        ChangeEvent,
+                        "sqlx_queries/user_history/get_change_order_size_events.sql",
+                        &txn_version_start,
+                        txn_version_iter_stop,
+                        self.market_ids.as_deref(),
+                    )
+                    .fetch_all(&mut transaction as &mut PgConnection),
+                )
+                .await
+                .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+                timer.finish(self.slow_step_threshold, change_events.len());
+                change_events
+            } else {
+                vec![]
+            };
+
+            let n_events = fill_events.len() + change_events.len();
+            update_batch_size(&mut self.batch_size, n_events);
+            processed_events += n_events as u64;
+
+            let timer = StepTimer::start("merge");
+            aggregate_events(
+                &mut transaction,
+                &fill_events,
+                &change_events,
+                None,
+                self.strict_fills,
+                (&txn_version_start, &txn_version_iter_stop),
             )
-            .fetch_all(&mut transaction as &mut PgConnection)
-            .await
-            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
-            let change_events = sqlx::query_file!(
-                "sqlx_queries/user_history/get_change_order_size_events.sql",
-                &txn_version_start,
-                txn_version_iter_stop,
+            .await?;
+            timer.finish(self.slow_step_threshold, n_events);
+            txn_version_start = txn_version_iter_stop;
+        }
+        let mut cancels = 0;
+        if self.aggregates(EventKind::Cancel) {
+            let timer = StepTimer::start("cancels");
+            cancels = timed(
+                Statement::Update,
+                sqlx::query_file!(
+                    "sqlx_queries/user_history/mark_cancelled.sql",
+                    last_indexed_txn_version,
+                    self.market_ids.as_deref(),
+                    self.aggregates(EventKind::Limit),
+                    self.aggregates(EventKind::Market),
+                    self.aggregates(EventKind::Swap),
+                    txn_version_stop,
+                )
+                .fetch_one(&mut transaction as &mut PgConnection),
             )
-            .fetch_all(&mut transaction as &mut PgConnection)
             .await
-            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+            .cancelled;
+            timer.finish(self.slow_step_threshold, cancels as usize);
+        }
+        update_max_txn_version(&mut transaction, txnv_exists, txn_version_stop.clone()).await?;
+        commit_transaction(transaction).await?;
+        self.last_indexed_txn_version = Some(txn_version_stop);
+        self.processed_events = processed_events + cancels as u64;
+        Ok(())
+    }
 
-            let n_events = fill_events.len() + change_events.len();
-            update_batch_size(&mut self.batch_size, n_events);
+    async fn after_commit(&mut self, _summary: ProcessSummary) {
+        self.last_indexed_timestamp = Some(Utc::now());
+    }
 
-            // Step through fill and change events in total order.
-            let mut fill_index = 0;
-            let mut change_index = 0;
-            for _ in 0..(fill_events.len() + change_events.len()) {
-                let (fill_event_to_aggregate, change_event_to_aggregate) =
-                    match (fill_events.get(fill_index), change_events.get(change_index)) {
-                        (Some(fill), Some(change)) => {
-                            if fill.txn_version < change.txn_version
-                                || (fill.txn_version == change.txn_version
-                                    && fill.event_idx < change.event_idx)
-                            {
-                                (Some(fill), None)
-                            } else {
-                                (None, Some(change))
-                            }
-                        }
-                        (Some(fill), None) => (Some(fill), None),
-                        (None, Some(change)) => (None, Some(change)),
-                        (None, None) => unreachable!(),
-                    };
-                match (fill_event_to_aggregate, change_event_to_aggregate) {
-                    (Some(fill), None) => {
-                        // Dedupe if needed by only aggregating events emitted to maker handle.
-                        if fill.maker_address == fill.emit_address {
+    fn processed_events(&self) -> Option<u64> {
+        Some(self.processed_events)
+    }
+}
+
+/// A row of `fill_events`, with the columns needed for aggregation.
+struct FillEvent {
+    txn_version: BigDecimal,
+    event_idx: BigDecimal,
+    emit_address: String,
+    time: DateTime<Utc>,
+    maker_address: String,
+    maker_order_id: BigDecimal,
+    market_id: BigDecimal,
+    price: BigDecimal,
+    size: BigDecimal,
+    taker_order_id: BigDecimal,
+    taker_quote_fees_paid: BigDecimal,
+}
+
+/// A row of `change_order_size_events`, with the columns needed for aggregation.
+struct ChangeEvent {
+    txn_version: BigDecimal,
+    event_idx: BigDecimal,
+    time: DateTime<Utc>,
+    market_id: BigDecimal,
+    order_id: BigDecimal,
+    new_size: BigDecimal,
+}
+
+/// Aggregates fill and change events in total order.
+///
+/// Both slices must be sorted by `(txn_version, event_idx)`. If `only_order_id` is set, fills are
+/// only aggregated for that order and not for its counterparty. `txn_versions` is the range the
+/// events were taken from, only used for error reporting.
+async fn aggregate_events<'a>(
+    tx: &mut Transaction<'a, Postgres>,
+    fill_events: &[FillEvent],
+    change_events: &[ChangeEvent],
+    only_order_id: Option<&BigDecimal>,
+    strict_fills: bool,
+    txn_versions: (&BigDecimal, &BigDecimal),
+) -> PipelineAggregationResult {
+    let mut fill_index = 0;
+    let mut change_index = 0;
+    for _ in 0..(fill_events.len() + change_events.len()) {
+        match next_event(
+            fill_events,
+            change_events,
+            fill_index,
+            change_index,
+            txn_versions,
+        )? {
+            NextEvent::Fill(fill) => {
+                // Dedupe if needed by only aggregating events emitted to maker handle.
+                if fill.maker_address == fill.emit_address && fill.size.is_zero() {
+                    // Nothing changed hands, and it would divide by zero when averaging the
+                    // execution price of an order without previous fills.
+                    tracing::warn!(
+                        market_id = %fill.market_id,
+                        maker_order_id = %fill.maker_order_id,
+                        taker_order_id = %fill.taker_order_id,
+                        txn_version = %fill.txn_version,
+                        event_idx = %fill.event_idx,
+                        "Skipping fill of size zero."
+                    );
+                } else if fill.maker_address == fill.emit_address {
+                    match only_order_id {
+                        None => {
                             aggregate_fill_for_maker_and_taker(
-                                &mut transaction,
+                                tx,
                                 &fill.size,
                                 &fill.maker_order_id,
                                 &fill.taker_order_id,
@@ -163,172 +587,1840 @@ impl Pipeline for UserHistory {
                                 &fill.time,
                                 &fill.price,
                                 &fill.taker_quote_fees_paid,
+                                strict_fills,
+                                &fill.txn_version,
+                                &fill.event_idx,
                             )
-                            .await?;
+                            .await?
                         }
-                        fill_index += 1;
-                    }
-                    (None, Some(change)) => {
-                        aggregate_change(
-                            &mut transaction,
-                            &change.new_size,
-                            &change.order_id,
-                            &change.market_id,
-                            &change.time,
-                            &change.txn_version,
-                            &change.event_idx,
-                        )
-                        .await?;
-                        change_index += 1;
+                        Some(order_id) if order_id == &fill.maker_order_id => {
+                            aggregate_fill(
+                                tx,
+                                &fill.size,
+                                order_id,
+                                &fill.market_id,
+                                &fill.time,
+                                &fill.price,
+                                &BigDecimal::zero(),
+                                strict_fills,
+                                &fill.txn_version,
+                                &fill.event_idx,
+                            )
+                            .await?
+                        }
+                        Some(order_id) if order_id == &fill.taker_order_id => {
+                            aggregate_fill(
+                                tx,
+                                &fill.size,
+                                order_id,
+                                &fill.market_id,
+                                &fill.time,
+                                &fill.price,
+                                &fill.taker_quote_fees_paid,
+                                strict_fills,
+                                &fill.txn_version,
+                                &fill.event_idx,
+                            )
+                            .await?
+                        }
+                        Some(_) => {}
                     }
-                    _ => unreachable!(),
-                };
+                }
+                fill_index += 1;
+            }
+            NextEvent::Change(change) => {
+                aggregate_change(
+                    tx,
+                    &change.new_size,
+                    &change.order_id,
+                    &change.market_id,
+                    &change.time,
+                    &change.txn_version,
+                    &change.event_idx,
+                )
+                .await?;
+                change_index += 1;
+            }
+        };
+    }
+    Ok(())
+}
+
+/// The next event to aggregate when merging fill and change events.
+enum NextEvent<'a> {
+    Fill(&'a FillEvent),
+    Change(&'a ChangeEvent),
+}
+
+/// Returns whichever of the fill at `fill_index` and the change at `change_index` comes first by
+/// `(txn_version, event_idx)`.
+///
+/// Fails if both are past the end of their slice, which means the merge went wrong, rather than
+/// crashing the aggregator. `txn_versions` is only used for error reporting.
+fn next_event<'a>(
+    fill_events: &'a [FillEvent],
+    change_events: &'a [ChangeEvent],
+    fill_index: usize,
+    change_index: usize,
+    txn_versions: (&BigDecimal, &BigDecimal),
+) -> Result<NextEvent<'a>, PipelineError> {
+    match (fill_events.get(fill_index), change_events.get(change_index)) {
+        (Some(fill), Some(change)) => {
+            if (&fill.txn_version, &fill.event_idx) < (&change.txn_version, &change.event_idx) {
+                Ok(NextEvent::Fill(fill))
+            } else {
+                Ok(NextEvent::Change(change))
             }
-            txn_version_start = txn_version_iter_stop;
         }
-        sqlx::query_file!(
-            "sqlx_queries/user_history/mark_cancelled.sql",
-            last_indexed_txn_version,
-        )
-        .execute(&mut transaction as &mut PgConnection)
-        .await
-        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
-        update_max_txn_version(&mut transaction, txnv_exists, txn_version_stop).await?;
-        commit_transaction(transaction).await?;
-        Ok(())
+        (Some(fill), None) => Ok(NextEvent::Fill(fill)),
+        (None, Some(change)) => Ok(NextEvent::Change(change)),
+        (None, None) => {
+            let (txn_version_start, txn_version_stop) = txn_versions;
+            Err(PipelineError::ProcessingError(anyhow!(
+                "merge invariant violated: no event left at fill index {fill_index}/{} and change index {change_index}/{} (txn versions {txn_version_start} to {txn_version_stop})",
+                fill_events.len(),
+                change_events.len(),
+            )))
+        }
     }
 }
 
-async fn aggregate_fill_for_maker_and_taker<'a>(
-    tx: &mut Transaction<'a, Postgres>,
-    size: &BigDecimal,
-    maker_order_id: &BigDecimal,
-    taker_order_id: &BigDecimal,
+/// Recomputes the user history of a single order from its events, in one transaction.
+///
+/// The order is deleted from `aggregator.user_history` and its place, fill, change and cancel
+/// events up to the last aggregated transaction are replayed. Other orders, including the
+/// counterparties of its fills, are left untouched, so running it again yields the same result.
+pub async fn reaggregate_order(
+    pool: &PgPool,
     market_id: &BigDecimal,
-    time: &DateTime<Utc>,
-    price: &BigDecimal,
-    fees: &BigDecimal,
+    order_id: &BigDecimal,
+    strict_fills: bool,
+    source_schema: Option<&str>,
 ) -> PipelineAggregationResult {
-    aggregate_fill(
-        tx,
-        size,
-        maker_order_id,
+    // Hold the lock of the pipeline so that it does not aggregate concurrently.
+    let Some(mut transaction) = create_locked_transaction(pool, "UserHistory").await? else {
+        return Err(PipelineError::NotProcessable(String::from(
+            "UserHistory is being aggregated, try again later",
+        )));
+    };
+    use_source_schema(&mut transaction, source_schema).await?;
+    let Some(last_indexed_txn_version) = timed(
+        Statement::Select,
+        sqlx::query_file!("sqlx_queries/user_history/get_last_indexed_txn_version.sql",)
+            .fetch_optional(&mut transaction as &mut PgConnection),
+    )
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+    .map(|r| r.txn_version) else {
+        return Err(PipelineError::NotProcessable(String::from(
+            "user history has not been aggregated yet",
+        )));
+    };
+    let replayed = replay_order(
+        &mut transaction,
         market_id,
-        time,
-        price,
-        &BigDecimal::zero(),
+        order_id,
+        &last_indexed_txn_version,
+        strict_fills,
     )
     .await?;
-    aggregate_fill(tx, size, taker_order_id, market_id, time, price, fees).await?;
+    if !replayed {
+        return Err(PipelineError::NotProcessable(format!(
+            "order {order_id} on market {market_id} has not been aggregated yet"
+        )));
+    }
+    commit_transaction(transaction).await?;
     Ok(())
 }
 
-async fn aggregate_fill<'a>(
-    tx: &mut Transaction<'a, Postgres>,
-    size: &BigDecimal,
-    order_id: &BigDecimal,
-    market_id: &BigDecimal,
-    time: &DateTime<Utc>,
-    price: &BigDecimal,
-    fees: &BigDecimal,
+/// Rewinds the user history so that the pipeline aggregates again every event after
+/// `from_txn_version`, or every event if it is `None`.
+///
+/// Without a version, the user history tables are truncated. Otherwise, orders placed after the
+/// version are deleted and orders changed after it are replayed up to it, leaving the state an
+/// incremental run had when it reached that version. Either way the pipeline then catches up as
+/// usual. Fails if `from_txn_version` is after the last aggregated transaction.
+pub async fn rewind(
+    pool: &PgPool,
+    from_txn_version: Option<&BigDecimal>,
+    strict_fills: bool,
+    source_schema: Option<&str>,
 ) -> PipelineAggregationResult {
-    // Only limit orders can remain open after a transaction during which they are filled against,
-    // so flag market orders and swaps as closed by default: if they end up being cancelled instead
-    // of closed, the cancel event emitted during the same transaction (aggregated after fills) will
-    // clean up the order status to cancelled.
+    let Some(mut transaction) = create_locked_transaction(pool, "UserHistory").await? else {
+        return Err(PipelineError::NotProcessable(String::from(
+            "UserHistory is being aggregated, stop the aggregator and try again",
+        )));
+    };
+    use_source_schema(&mut transaction, source_schema).await?;
+    let Some(from_txn_version) = from_txn_version else {
+        sqlx::query_file!("sqlx_queries/user_history/truncate.sql",)
+            .execute(&mut transaction as &mut PgConnection)
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+        commit_transaction(transaction).await?;
+        tracing::info!("Cleared user history.");
+        return Ok(());
+    };
+    let last_indexed_txn_version = timed(
+        Statement::Select,
+        sqlx::query_file!("sqlx_queries/user_history/get_last_indexed_txn_version.sql",)
+            .fetch_optional(&mut transaction as &mut PgConnection),
+    )
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+    .map(|r| r.txn_version);
+    match &last_indexed_txn_version {
+        Some(txn_version) if txn_version >= from_txn_version => {}
+        _ => {
+            return Err(PipelineError::NotProcessable(format!(
+                "user history has not been aggregated up to transaction {from_txn_version} yet"
+            )))
+        }
+    }
+    let deleted = sqlx::query_file!(
+        "sqlx_queries/user_history/delete_orders_placed_after.sql",
+        from_txn_version,
+    )
+    .execute(&mut transaction as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+    .rows_affected();
+    let changed = sqlx::query_file!(
+        "sqlx_queries/user_history/get_orders_changed_after.sql",
+        from_txn_version,
+    )
+    .fetch_all(&mut transaction as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    for order in &changed {
+        replay_order(
+            &mut transaction,
+            &order.market_id,
+            &order.order_id,
+            from_txn_version,
+            strict_fills,
+        )
+        .await?;
+    }
     sqlx::query_file!(
-        "sqlx_queries/user_history/aggregate_fill.sql",
-        size,
-        order_id,
-        market_id,
-        time,
-        price,
-        fees,
+        "sqlx_queries/user_history/delete_pending_cancels_after.sql",
+        from_txn_version,
     )
-    .execute(tx as &mut PgConnection)
+    .execute(&mut transaction as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    sqlx::query_file!(
+        "sqlx_queries/user_history/delete_order_size_changes_after.sql",
+        from_txn_version,
+    )
+    .execute(&mut transaction as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    sqlx::query_file!(
+        "sqlx_queries/user_history/delete_pending_events_after.sql",
+        from_txn_version,
+    )
+    .execute(&mut transaction as &mut PgConnection)
     .await
     .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    update_max_txn_version(&mut transaction, true, from_txn_version.clone()).await?;
+    commit_transaction(transaction).await?;
+    tracing::info!(
+        %from_txn_version,
+        deleted,
+        replayed = changed.len(),
+        "Rewound user history."
+    );
     Ok(())
 }
 
-async fn aggregate_change<'a>(
-    tx: &mut Transaction<'a, Postgres>,
-    new_size: &BigDecimal,
-    order_id: &BigDecimal,
-    market_id: &BigDecimal,
-    time: &DateTime<Utc>,
-    txn_version: &BigDecimal,
-    event_idx: &BigDecimal,
-) -> PipelineAggregationResult {
-    // Get some info
-    let record = sqlx::query_file!(
-        "sqlx_queries/user_history/get_order_type_with_remaining_size.sql",
-        market_id,
-        order_id,
+/// Inconsistencies between `aggregator.user_history` and the place events, as found by
+/// [`check_orders`]. Orders are identified by `(market_id, order_id)`.
+#[derive(Debug, Default)]
+pub struct OrderCheck {
+    /// Orders placed up to the last aggregated transaction that are neither in the user history
+    /// nor pruned.
+    pub missing: Vec<(BigDecimal, BigDecimal)>,
+    /// Orders in the user history without a place event.
+    pub orphaned: Vec<(BigDecimal, BigDecimal)>,
+    /// Limit orders whose filled and remaining sizes do not add up to their size.
+    pub unbalanced: Vec<SizeMismatch>,
+}
+
+impl OrderCheck {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty() && self.unbalanced.is_empty()
+    }
+}
+
+/// A limit order of `aggregator.user_history` whose `total_filled + remaining_size` differs from
+/// its size, as found by [`check_orders`].
+///
+/// The expected total is the size set by its place event or last size change, plus what was
+/// filled before that change.
+#[derive(Debug)]
+pub struct SizeMismatch {
+    pub market_id: BigDecimal,
+    pub order_id: BigDecimal,
+    pub total_filled: BigDecimal,
+    pub remaining_size: BigDecimal,
+    pub expected_total_size: BigDecimal,
+}
+
+/// Finds orders missing from `aggregator.user_history`, orders in it without a place event, and
+/// limit orders whose filled and remaining sizes do not add up to their size.
+///
+/// If `repair` is set, missing orders are replayed from their events and orphaned orders are
+/// moved to `aggregator.orphaned_orders`, in one transaction. Otherwise nothing is changed.
+/// Unbalanced orders are only reported: replaying them would yield the same sizes unless the
+/// aggregation bug behind them is fixed, after which [`reaggregate_order`] corrects them.
+///
+/// If `market_ids` is set, orders of other markets are not reported as missing nor checked.
+pub async fn check_orders(
+    pool: &PgPool,
+    repair: bool,
+    strict_fills: bool,
+    source_schema: Option<&str>,
+    market_ids: Option<&[BigDecimal]>,
+) -> Result<OrderCheck, PipelineError> {
+    let Some(mut transaction) = create_locked_transaction(pool, "UserHistory").await? else {
+        return Err(PipelineError::NotProcessable(String::from(
+            "UserHistory is being aggregated, try again later",
+        )));
+    };
+    use_source_schema(&mut transaction, source_schema).await?;
+    let check = check_orders_in(&mut transaction, repair, strict_fills, market_ids).await?;
+    if repair {
+        commit_transaction(transaction).await?;
+    }
+    Ok(check)
+}
+
+/// Does the work of [`check_orders`] in `transaction`, which is left to commit if `repair` is set.
+async fn check_orders_in<'a>(
+    transaction: &mut Transaction<'a, Postgres>,
+    repair: bool,
+    strict_fills: bool,
+    market_ids: Option<&[BigDecimal]>,
+) -> Result<OrderCheck, PipelineError> {
+    let Some(last_indexed_txn_version) = timed(
+        Statement::Select,
+        sqlx::query_file!("sqlx_queries/user_history/get_last_indexed_txn_version.sql",)
+            .fetch_optional(transaction as &mut PgConnection),
     )
-    .fetch_one(tx as &mut PgConnection)
     .await
-    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
-    let (order_type, original_size): (OrderType, BigDecimal) =
-        (record.order_type, record.remaining_size);
-    // If it's a limit order and needs reordering
-    if matches!(order_type, OrderType::Limit) && &original_size < new_size {
-        let txn = txn_version
-            .to_bigint()
-            .ok_or(PipelineError::ProcessingError(anyhow!(
-                "txn_version not integer"
-            )))?
-            << SHIFT_TXN_VERSION;
-        let event = event_idx
-            .to_bigint()
-            .ok_or(PipelineError::ProcessingError(anyhow!(
-                "event_idx not integer"
-            )))?;
-        let txn_event: BigDecimal = BigDecimal::from(txn | event);
-        sqlx::query_file!(
-            "sqlx_queries/user_history/update_last_increase_stamp.sql",
-            market_id,
-            order_id,
-            txn_event,
-        )
-        .execute(tx as &mut PgConnection)
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+    .map(|r| r.txn_version) else {
+        return Err(PipelineError::NotProcessable(String::from(
+            "user history has not been aggregated yet",
+        )));
+    };
+    let missing: Vec<_> = sqlx::query_file!(
+        "sqlx_queries/user_history/get_missing_orders.sql",
+        last_indexed_txn_version,
+        market_ids,
+    )
+    .fetch_all(transaction as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+    .into_iter()
+    .map(|r| (r.market_id, r.order_id))
+    .collect();
+    let orphaned: Vec<_> = sqlx::query_file!("sqlx_queries/user_history/get_orphaned_orders.sql",)
+        .fetch_all(transaction as &mut PgConnection)
         .await
-        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        .into_iter()
+        .map(|r| (r.market_id, r.order_id))
+        .collect();
+    for (market_id, order_id) in &missing {
+        tracing::warn!(%market_id, %order_id, "Order is missing from the user history.");
     }
-    sqlx::query_file!(
-        "sqlx_queries/user_history/aggregate_size_change.sql",
-        new_size,
-        order_id,
-        market_id,
-        time,
+    for (market_id, order_id) in &orphaned {
+        tracing::warn!(%market_id, %order_id, "Order in the user history has no place event.");
+    }
+    let unbalanced: Vec<_> = sqlx::query_file_as!(
+        SizeMismatch,
+        "sqlx_queries/user_history/get_unbalanced_orders.sql",
+        last_indexed_txn_version,
+        market_ids,
     )
-    .execute(tx as &mut PgConnection)
+    .fetch_all(transaction as &mut PgConnection)
     .await
     .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
-    Ok(())
+    for order in &unbalanced {
+        tracing::warn!(
+            market_id = %order.market_id,
+            order_id = %order.order_id,
+            total_filled = %order.total_filled,
+            remaining_size = %order.remaining_size,
+            expected_total_size = %order.expected_total_size,
+            delta = %(&order.total_filled + &order.remaining_size - &order.expected_total_size),
+            "Filled and remaining sizes of order do not add up to its size."
+        );
+    }
+    if repair {
+        for (market_id, order_id) in &missing {
+            replay_order(
+                transaction,
+                market_id,
+                order_id,
+                &last_indexed_txn_version,
+                strict_fills,
+            )
+            .await?;
+        }
+        for (market_id, order_id) in &orphaned {
+            sqlx::query_file!(
+                "sqlx_queries/user_history/quarantine_order.sql",
+                market_id,
+                order_id,
+            )
+            .execute(transaction as &mut PgConnection)
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+        }
+    }
+    Ok(OrderCheck {
+        missing,
+        orphaned,
+        unbalanced,
+    })
 }
 
-async fn update_max_txn_version<'a>(
-    tx: &mut Transaction<'a, Postgres>,
-    already_exists: bool,
-    new_max_txn_version: BigDecimal,
-) -> PipelineAggregationResult {
-    if already_exists {
-        sqlx::query_file!(
-            "sqlx_queries/user_history/set_new_last_indexed_txn_version.sql",
-            new_max_txn_version,
+/// An order of `aggregator.user_history` whose totals differ from those its events add up to,
+/// as found by [`reconcile_orders`].
+#[derive(Debug)]
+pub struct OrderDrift {
+    pub market_id: BigDecimal,
+    pub order_id: BigDecimal,
+    pub total_filled: BigDecimal,
+    pub expected_total_filled: BigDecimal,
+    pub remaining_size: BigDecimal,
+    pub expected_remaining_size: BigDecimal,
+}
+
+/// Recomputes the totals of every order of `aggregator.user_history` updated since `since` from
+/// its events, and returns the orders whose filled or remaining size differs.
+///
+/// Orders are replayed like [`reaggregate_order`] does, in one transaction. If `fix` is set, the
+/// transaction is committed so that the drifting orders are corrected, otherwise it is rolled
+/// back and nothing is changed. Replaying every order is expensive, so keep `since` recent.
+///
+/// If `market_ids` is set, orders of other markets are not checked.
+pub async fn reconcile_orders(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    fix: bool,
+    strict_fills: bool,
+    source_schema: Option<&str>,
+    market_ids: Option<&[BigDecimal]>,
+) -> Result<Vec<OrderDrift>, PipelineError> {
+    let Some(mut transaction) = create_locked_transaction(pool, "UserHistory").await? else {
+        return Err(PipelineError::NotProcessable(String::from(
+            "UserHistory is being aggregated, try again later",
+        )));
+    };
+    use_source_schema(&mut transaction, source_schema).await?;
+    let Some(last_indexed_txn_version) =
+        sqlx::query_file!("sqlx_queries/user_history/get_last_indexed_txn_version.sql",)
+            .fetch_optional(&mut transaction as &mut PgConnection)
+            .await
+            .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+            .map(|r| r.txn_version)
+    else {
+        return Ok(vec![]);
+    };
+    let orders = sqlx::query_file!(
+        "sqlx_queries/user_history/get_recently_updated_orders.sql",
+        since,
+        market_ids,
+    )
+    .fetch_all(&mut transaction as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    let mut drifts = vec![];
+    for order in orders {
+        let replayed = replay_order(
+            &mut transaction,
+            &order.market_id,
+            &order.order_id,
+            &last_indexed_txn_version,
+            strict_fills,
         )
-        .execute(tx as &mut PgConnection)
-        .await
-        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
-    } else {
-        sqlx::query_file!(
-            "sqlx_queries/user_history/init_last_indexed_txn_version.sql",
-            new_max_txn_version,
+        .await?;
+        if !replayed {
+            // Orders without a place event are reported by `check_orders`.
+            continue;
+        }
+        let expected = sqlx::query_file!(
+            "sqlx_queries/user_history/get_order_totals.sql",
+            order.market_id,
+            order.order_id,
         )
-        .execute(tx as &mut PgConnection)
+        .fetch_one(&mut transaction as &mut PgConnection)
         .await
         .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+        if expected.total_filled == order.total_filled
+            && expected.remaining_size == order.remaining_size
+        {
+            continue;
+        }
+        drifts.push(OrderDrift {
+            market_id: order.market_id,
+            order_id: order.order_id,
+            total_filled: order.total_filled,
+            expected_total_filled: expected.total_filled,
+            remaining_size: order.remaining_size,
+            expected_remaining_size: expected.remaining_size,
+        });
+    }
+    if fix {
+        commit_transaction(transaction).await?;
+    }
+    Ok(drifts)
+}
+
+/// Deletes an order from `aggregator.user_history` and replays its events up to
+/// `txn_version_stop`.
+///
+/// Returns `false` if the order was not placed by then, in which case it is left deleted.
+async fn replay_order<'a>(
+    tx: &mut Transaction<'a, Postgres>,
+    market_id: &BigDecimal,
+    order_id: &BigDecimal,
+    txn_version_stop: &BigDecimal,
+    strict_fills: bool,
+) -> Result<bool, PipelineError> {
+    sqlx::query_file!(
+        "sqlx_queries/user_history/delete_order.sql",
+        market_id,
+        order_id,
+    )
+    .execute(tx as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    // The size changes are recorded again while replaying them.
+    sqlx::query_file!(
+        "sqlx_queries/user_history/delete_order_size_changes.sql",
+        market_id,
+        order_id,
+    )
+    .execute(tx as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    // Pending fills and size changes are among the events replayed below, so keeping them would
+    // apply them twice once resolved.
+    sqlx::query_file!(
+        "sqlx_queries/user_history/delete_order_pending_events.sql",
+        market_id,
+        order_id,
+    )
+    .execute(tx as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    let inserted = sqlx::query_file!(
+        "sqlx_queries/user_history/insert_order_limit.sql",
+        market_id,
+        order_id,
+        txn_version_stop,
+    )
+    .execute(tx as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+    .rows_affected()
+        + sqlx::query_file!(
+            "sqlx_queries/user_history/insert_order_market.sql",
+            market_id,
+            order_id,
+            txn_version_stop,
+        )
+        .execute(tx as &mut PgConnection)
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        .rows_affected()
+        + sqlx::query_file!(
+            "sqlx_queries/user_history/insert_order_swap.sql",
+            market_id,
+            order_id,
+            txn_version_stop,
+        )
+        .execute(tx as &mut PgConnection)
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        .rows_affected();
+    if inserted == 0 {
+        return Ok(false);
+    }
+    let fill_events = sqlx::query_file_as!(
+        FillEvent,
+        "sqlx_queries/user_history/get_order_fill_events.sql",
+        market_id,
+        order_id,
+        txn_version_stop,
+    )
+    .fetch_all(tx as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    let change_events = sqlx::query_file_as!(
+        ChangeEvent,
+        "sqlx_queries/user_history/get_order_change_order_size_events.sql",
+        market_id,
+        order_id,
+        txn_version_stop,
+    )
+    .fetch_all(tx as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    aggregate_events(
+        tx,
+        &fill_events,
+        &change_events,
+        Some(order_id),
+        strict_fills,
+        (&BigDecimal::zero(), txn_version_stop),
+    )
+    .await?;
+    sqlx::query_file!(
+        "sqlx_queries/user_history/mark_order_cancelled.sql",
+        market_id,
+        order_id,
+        txn_version_stop,
+    )
+    .execute(tx as &mut PgConnection)
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    Ok(true)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn aggregate_fill_for_maker_and_taker<'a>(
+    tx: &mut Transaction<'a, Postgres>,
+    size: &BigDecimal,
+    maker_order_id: &BigDecimal,
+    taker_order_id: &BigDecimal,
+    market_id: &BigDecimal,
+    time: &DateTime<Utc>,
+    price: &BigDecimal,
+    fees: &BigDecimal,
+    strict: bool,
+    txn_version: &BigDecimal,
+    event_idx: &BigDecimal,
+) -> PipelineAggregationResult {
+    aggregate_fill(
+        tx,
+        size,
+        maker_order_id,
+        market_id,
+        time,
+        price,
+        &BigDecimal::zero(),
+        strict,
+        txn_version,
+        event_idx,
+    )
+    .await?;
+    aggregate_fill(
+        tx,
+        size,
+        taker_order_id,
+        market_id,
+        time,
+        price,
+        fees,
+        strict,
+        txn_version,
+        event_idx,
+    )
+    .await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn aggregate_fill<'a>(
+    tx: &mut Transaction<'a, Postgres>,
+    size: &BigDecimal,
+    order_id: &BigDecimal,
+    market_id: &BigDecimal,
+    time: &DateTime<Utc>,
+    price: &BigDecimal,
+    fees: &BigDecimal,
+    strict: bool,
+    txn_version: &BigDecimal,
+    event_idx: &BigDecimal,
+) -> PipelineAggregationResult {
+    // Only limit orders can remain open after a transaction during which they are filled against,
+    // so flag market orders and swaps as closed by default: if they end up being cancelled instead
+    // of closed, the cancel event emitted during the same transaction (aggregated after fills) will
+    // clean up the order status to cancelled.
+    let record = timed(
+        Statement::Update,
+        sqlx::query_file!(
+            "sqlx_queries/user_history/aggregate_fill.sql",
+            size,
+            order_id,
+            market_id,
+            time,
+            price,
+            fees,
+        )
+        .fetch_optional(tx as &mut PgConnection),
+    )
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    let Some(record) = record else {
+        // The fill updated nothing, keep it for when the placement is aggregated.
+        let pending = timed(
+            Statement::Insert,
+            sqlx::query_file!(
+                "sqlx_queries/user_history/insert_pending_fill.sql",
+                txn_version,
+                event_idx,
+                market_id,
+                order_id,
+            )
+            .execute(tx as &mut PgConnection),
+        )
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        .rows_affected();
+        log_missing_order("fill", market_id, order_id, txn_version, event_idx, pending);
+        return Ok(());
+    };
+    // A fill larger than the remaining size means events were processed out of order or a
+    // duplicate got through dedupe. The update only applied the remaining size of the order, and
+    // failing rolls it back with the rest of the batch.
+    let remaining_size = record.previous_remaining_size;
+    if amount::checked_sub(&remaining_size, size).is_none() {
+        if strict {
+            return Err(PipelineError::ProcessingError(anyhow!(
+                "fill of size {size} exceeds remaining size {remaining_size} of order {order_id} on market {market_id}",
+            )));
+        }
+        tracing::warn!(
+            %market_id,
+            %order_id,
+            fill_size = %size,
+            %remaining_size,
+            "Fill exceeds remaining size, clamping it to the remaining size."
+        );
+    }
+    Ok(())
+}
+
+async fn aggregate_change<'a>(
+    tx: &mut Transaction<'a, Postgres>,
+    new_size: &BigDecimal,
+    order_id: &BigDecimal,
+    market_id: &BigDecimal,
+    time: &DateTime<Utc>,
+    txn_version: &BigDecimal,
+    event_idx: &BigDecimal,
+) -> PipelineAggregationResult {
+    // Get some info
+    let record = timed(
+        Statement::Select,
+        sqlx::query_file!(
+            "sqlx_queries/user_history/get_order_type_with_remaining_size.sql",
+            market_id,
+            order_id,
+        )
+        .fetch_optional(tx as &mut PgConnection),
+    )
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    let Some(record) = record else {
+        let pending = timed(
+            Statement::Insert,
+            sqlx::query_file!(
+                "sqlx_queries/user_history/insert_pending_size_change.sql",
+                txn_version,
+                event_idx,
+                market_id,
+                order_id,
+            )
+            .execute(tx as &mut PgConnection),
+        )
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?
+        .rows_affected();
+        log_missing_order(
+            "size change",
+            market_id,
+            order_id,
+            txn_version,
+            event_idx,
+            pending,
+        );
+        return Ok(());
+    };
+    let (order_type, remaining_size): (OrderType, BigDecimal) =
+        (record.order_type, record.remaining_size);
+    // Only limit orders rest on the book, so a size change of anything else means the events are
+    // inconsistent, and applying it would hide that.
+    if !matches!(order_type, OrderType::Limit) {
+        return Err(PipelineError::ProcessingError(anyhow!(
+            "size change at txn version {txn_version} event {event_idx} targets order {order_id} on market {market_id}, which is a {order_type:?} order and not a limit order",
+        )));
+    }
+    // On chain, `new_size` replaces the remaining size of the order (not its size at placement)
+    // and the order loses its priority only if `new_size` is larger than that remaining size (see
+    // `market::change_order_size`), so a partially filled order shrunk below its placement size
+    // keeps its priority.
+    if &remaining_size < new_size {
+        let txn_event = encode_txn_event(txn_version, event_idx)?;
+        timed(
+            Statement::Update,
+            sqlx::query_file!(
+                "sqlx_queries/user_history/update_last_increase_stamp.sql",
+                market_id,
+                order_id,
+                txn_event,
+            )
+            .execute(tx as &mut PgConnection),
+        )
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    }
+    timed(
+        Statement::Update,
+        sqlx::query_file!(
+            "sqlx_queries/user_history/aggregate_size_change.sql",
+            new_size,
+            order_id,
+            market_id,
+            time,
+        )
+        .execute(tx as &mut PgConnection),
+    )
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    // The update above overwrites the remaining size, so the change is recorded to keep the
+    // history of the size of the order.
+    timed(
+        Statement::Insert,
+        sqlx::query_file!(
+            "sqlx_queries/user_history/insert_order_size_change.sql",
+            market_id,
+            order_id,
+            remaining_size,
+            new_size,
+            txn_version,
+            event_idx,
+            time,
+        )
+        .execute(tx as &mut PgConnection),
+    )
+    .await
+    .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    Ok(())
+}
+
+/// Logs a fill or size change of an order missing from `aggregator.user_history`, `pending` being
+/// the number of rows it added to the pending events.
+fn log_missing_order(
+    event: &str,
+    market_id: &BigDecimal,
+    order_id: &BigDecimal,
+    txn_version: &BigDecimal,
+    event_idx: &BigDecimal,
+    pending: u64,
+) {
+    if pending > 0 {
+        tracing::info!(
+            event,
+            %market_id,
+            %order_id,
+            %txn_version,
+            %event_idx,
+            "Order not aggregated yet, keeping the event until its placement is."
+        );
+    } else {
+        tracing::debug!(
+            event,
+            %market_id,
+            %order_id,
+            %txn_version,
+            %event_idx,
+            "Skipping event of an order of a kind that is not aggregated."
+        );
+    }
+}
+
+/// Packs a transaction version and an event index into a single key ordering events by
+/// `(txn_version, event_idx)`.
+///
+/// Fails if `event_idx` does not fit in the low [`SHIFT_TXN_VERSION`] bits, where it would
+/// overlap with the transaction version and corrupt the ordering, or if `txn_version` does not fit
+/// in the remaining high bits.
+fn encode_txn_event(
+    txn_version: &BigDecimal,
+    event_idx: &BigDecimal,
+) -> Result<BigDecimal, PipelineError> {
+    let txn = decimal_to_u128("txn_version", txn_version)?;
+    let event = decimal_to_u128("event_idx", event_idx)?;
+    if event >> SHIFT_TXN_VERSION != 0 {
+        return Err(PipelineError::ProcessingError(anyhow!(
+            "event_idx {event_idx} of txn_version {txn_version} does not fit in {SHIFT_TXN_VERSION} bits"
+        )));
+    }
+    if txn >> (u128::BITS - u32::from(SHIFT_TXN_VERSION)) != 0 {
+        return Err(PipelineError::ProcessingError(anyhow!(
+            "txn_version {txn_version} does not fit in {} bits",
+            u128::BITS - u32::from(SHIFT_TXN_VERSION)
+        )));
+    }
+    Ok(BigDecimal::new(
+        BigInt::from((txn << SHIFT_TXN_VERSION) | event),
+        0,
+    ))
+}
+
+async fn update_max_txn_version<'a>(
+    tx: &mut Transaction<'a, Postgres>,
+    already_exists: bool,
+    new_max_txn_version: BigDecimal,
+) -> PipelineAggregationResult {
+    if already_exists {
+        timed(
+            Statement::Update,
+            sqlx::query_file!(
+                "sqlx_queries/user_history/set_new_last_indexed_txn_version.sql",
+                new_max_txn_version,
+            )
+            .execute(tx as &mut PgConnection),
+        )
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    } else {
+        timed(
+            Statement::Insert,
+            sqlx::query_file!(
+                "sqlx_queries/user_history/init_last_indexed_txn_version.sql",
+                new_max_txn_version,
+            )
+            .execute(tx as &mut PgConnection),
+        )
+        .await
+        .map_err(|e| PipelineError::ProcessingError(anyhow!(e)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn decimal(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    fn encode(txn_version: &str, event_idx: &str) -> Result<BigDecimal, PipelineError> {
+        encode_txn_event(&decimal(txn_version), &decimal(event_idx))
+    }
+
+    fn fill(txn_version: u64, event_idx: u64) -> FillEvent {
+        FillEvent {
+            txn_version: BigDecimal::from(txn_version),
+            event_idx: BigDecimal::from(event_idx),
+            emit_address: String::from("0x1"),
+            time: Utc::now(),
+            maker_address: String::from("0x1"),
+            maker_order_id: BigDecimal::from(1),
+            market_id: BigDecimal::from(1),
+            price: BigDecimal::from(1),
+            size: BigDecimal::from(1),
+            taker_order_id: BigDecimal::from(2),
+            taker_quote_fees_paid: BigDecimal::zero(),
+        }
+    }
+
+    fn change(txn_version: u64, event_idx: u64) -> ChangeEvent {
+        ChangeEvent {
+            txn_version: BigDecimal::from(txn_version),
+            event_idx: BigDecimal::from(event_idx),
+            time: Utc::now(),
+            market_id: BigDecimal::from(1),
+            order_id: BigDecimal::from(1),
+            new_size: BigDecimal::from(1),
+        }
+    }
+
+    /// Merges `fills` and `changes` like [`aggregate_events`], returning the `(txn_version,
+    /// event_idx)` of each event in the order they would be aggregated.
+    fn merge(fills: &[FillEvent], changes: &[ChangeEvent]) -> Vec<(BigDecimal, BigDecimal)> {
+        let range = (&BigDecimal::zero(), &BigDecimal::zero());
+        let (mut fill_index, mut change_index) = (0, 0);
+        (0..fills.len() + changes.len())
+            .map(
+                |_| match next_event(fills, changes, fill_index, change_index, range).unwrap() {
+                    NextEvent::Fill(fill) => {
+                        fill_index += 1;
+                        (fill.txn_version.clone(), fill.event_idx.clone())
+                    }
+                    NextEvent::Change(change) => {
+                        change_index += 1;
+                        (change.txn_version.clone(), change.event_idx.clone())
+                    }
+                },
+            )
+            .collect()
+    }
+
+    #[test]
+    fn merges_fills_and_changes_in_total_order() {
+        let fills = [fill(1, 0), fill(2, 1), fill(3, 0)];
+        let changes = [change(2, 0), change(2, 2), change(4, 0)];
+        let order: Vec<(BigDecimal, BigDecimal)> = [(1, 0), (2, 0), (2, 1), (2, 2), (3, 0), (4, 0)]
+            .into_iter()
+            .map(|(txn_version, event_idx)| {
+                (BigDecimal::from(txn_version), BigDecimal::from(event_idx))
+            })
+            .collect();
+        assert_eq!(merge(&fills, &changes), order);
+    }
+
+    #[test]
+    fn merge_drains_either_side() {
+        assert_eq!(merge(&[fill(1, 0)], &[]).len(), 1);
+        assert_eq!(merge(&[], &[change(1, 0)]).len(), 1);
+        assert!(merge(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn merge_past_the_end_is_an_error() {
+        let fills = [fill(1, 0)];
+        let changes = [change(2, 0)];
+        let range = (&BigDecimal::from(1), &BigDecimal::from(3));
+        let result = next_event(&fills, &changes, 1, 1, range);
+        match result {
+            Err(PipelineError::ProcessingError(e)) => {
+                assert!(e.to_string().contains("merge invariant violated"), "{e}")
+            }
+            Err(e) => panic!("unexpected error {e}"),
+            Ok(_) => panic!("merged an event past the end"),
+        }
+    }
+
+    #[test]
+    fn packs_txn_version_above_event_idx() {
+        assert_eq!(encode("0", "0").unwrap(), decimal("0"));
+        assert_eq!(encode("0", "7").unwrap(), decimal("7"));
+        // 2^64
+        assert_eq!(encode("1", "0").unwrap(), decimal("18446744073709551616"));
+        assert_eq!(encode("1", "2").unwrap(), decimal("18446744073709551618"));
+    }
+
+    #[test]
+    fn largest_values() {
+        let u64_max = u64::MAX.to_string();
+        assert_eq!(
+            encode("0", &u64_max).unwrap(),
+            decimal("18446744073709551615")
+        );
+        // 2^128 - 1
+        assert_eq!(
+            encode(&u64_max, &u64_max).unwrap(),
+            decimal(&u128::MAX.to_string())
+        );
+    }
+
+    #[test]
+    fn orders_by_txn_version_then_event_idx() {
+        let u64_max = u64::MAX.to_string();
+        assert!(encode("1", &u64_max).unwrap() < encode("2", "0").unwrap());
+        assert!(encode("2", "0").unwrap() < encode("2", "1").unwrap());
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        // 2^64
+        let too_large = "18446744073709551616";
+        assert!(matches!(
+            encode("1", too_large),
+            Err(PipelineError::ProcessingError(_))
+        ));
+        assert!(matches!(
+            encode(too_large, "0"),
+            Err(PipelineError::ProcessingError(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_negative_and_fractional_values() {
+        for (txn_version, event_idx) in [("-1", "0"), ("1", "-1"), ("1.5", "0"), ("1", "0.5")] {
+            assert!(
+                matches!(
+                    encode(txn_version, event_idx),
+                    Err(PipelineError::ProcessingError(_))
+                ),
+                "{txn_version} {event_idx}"
+            );
+        }
+    }
+
+    /// Fills order 1 of [`MARKET_ID`](crate::test_db::MARKET_ID), of a remaining size of 10, by
+    /// `size` at price 3, and returns the result with the `(total_filled, remaining_size,
+    /// order_status, close_reason, average_execution_price)` of the order.
+    async fn fill_order_of_10(
+        size: i64,
+        strict: bool,
+    ) -> (
+        PipelineAggregationResult,
+        (i64, i64, String, Option<String>, Option<i64>),
+    ) {
+        let mut tx = crate::test_db::begin().await;
+        crate::test_db::insert_order(
+            &mut tx,
+            serde_json::json!({ "order_id": 1, "remaining_size": 10 }),
+        )
+        .await;
+        let market_id = BigDecimal::from(crate::test_db::MARKET_ID);
+        let result = aggregate_fill(
+            &mut tx,
+            &BigDecimal::from(size),
+            &BigDecimal::from(1),
+            &market_id,
+            &Utc::now(),
+            &BigDecimal::from(3),
+            &BigDecimal::zero(),
+            strict,
+            &BigDecimal::from(1),
+            &BigDecimal::zero(),
+        )
+        .await;
+        let order = sqlx::query_as(
+            "SELECT total_filled::int8, remaining_size::int8, order_status::text, \
+             close_reason::text, average_execution_price::int8 \
+             FROM aggregator.user_history WHERE market_id = $1 AND order_id = 1",
+        )
+        .bind(&market_id)
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap();
+        (result, order)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn partial_fill_leaves_the_order_open() {
+        let (result, order) = fill_order_of_10(4, true).await;
+        assert!(result.is_ok());
+        assert_eq!(order, (4, 6, "open".into(), None, Some(3)));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn over_fill_is_clamped_to_the_remaining_size() {
+        let (result, order) = fill_order_of_10(12, false).await;
+        assert!(result.is_ok());
+        assert_eq!(
+            order,
+            (10, 0, "closed".into(), Some("filled".into()), Some(3))
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn over_fill_fails_when_strict() {
+        let (result, _) = fill_order_of_10(12, true).await;
+        match result {
+            Err(PipelineError::ProcessingError(e)) => {
+                assert!(e.to_string().contains("exceeds remaining size 10"), "{e}")
+            }
+            Err(e) => panic!("unexpected error {e}"),
+            Ok(_) => panic!("aggregated an over-fill"),
+        }
+    }
+
+    /// Returns the `(order_type, order_status, close_reason, total_filled, remaining_size)` of
+    /// the orders of [`MARKET_ID`](crate::test_db::MARKET_ID), by order ID.
+    async fn orders(conn: &mut PgConnection) -> Vec<(String, String, Option<String>, i64, i64)> {
+        sqlx::query_as(
+            "SELECT order_type::text, order_status::text, close_reason::text, \
+             total_filled::int8, remaining_size::int8 \
+             FROM aggregator.user_history WHERE market_id = $1 ORDER BY order_id",
+        )
+        .bind(crate::test_db::MARKET_ID)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn zero_size_placements_are_closed() {
+        use crate::test_db::{insert, MARKET_ID};
+
+        let mut tx = crate::test_db::begin().await;
+        let txn_version = i64::MAX - 10;
+        insert(
+            &mut tx,
+            "place_limit_order_events",
+            serde_json::json!({
+                "txn_version": txn_version,
+                "market_id": MARKET_ID,
+                "user": "0xa",
+                "order_id": 1,
+                "side": false,
+                "initial_size": 0,
+                "price": 1,
+                "size": 0,
+            }),
+        )
+        .await;
+        insert(
+            &mut tx,
+            "place_market_order_events",
+            serde_json::json!({
+                "txn_version": txn_version,
+                "event_idx": 1,
+                "market_id": MARKET_ID,
+                "user": "0xa",
+                "order_id": 2,
+                "direction": false,
+                "size": 0,
+            }),
+        )
+        .await;
+        for query in [
+            include_str!("../../sqlx_queries/user_history/insert_user_history_limit.sql"),
+            include_str!("../../sqlx_queries/user_history/insert_user_history_market.sql"),
+        ] {
+            sqlx::query(query)
+                .bind(txn_version - 1)
+                .bind([MARKET_ID])
+                .bind(txn_version)
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+        }
+        let closed = |order_type: &str| {
+            (
+                order_type.into(),
+                "closed".into(),
+                Some("zero_size".into()),
+                0,
+                0,
+            )
+        };
+        assert_eq!(orders(&mut tx).await, [closed("limit"), closed("market")]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn zero_size_fills_are_skipped() {
+        use crate::test_db::MARKET_ID;
+
+        let mut tx = crate::test_db::begin().await;
+        for order_id in [1, 2] {
+            crate::test_db::insert_order(
+                &mut tx,
+                serde_json::json!({ "order_id": order_id, "remaining_size": 5 }),
+            )
+            .await;
+        }
+        let zero_size = FillEvent {
+            market_id: BigDecimal::from(MARKET_ID),
+            size: BigDecimal::zero(),
+            ..fill(1, 0)
+        };
+        let range = (&BigDecimal::zero(), &BigDecimal::from(2));
+        aggregate_events(&mut tx, &[zero_size], &[], None, true, range)
+            .await
+            .unwrap();
+        let open = ("limit".into(), "open".into(), None, 0, 5);
+        assert_eq!(orders(&mut tx).await, [open.clone(), open]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn no_work_past_the_last_event() {
+        use crate::test_db::{insert, MARKET_ID};
+
+        let mut tx = crate::test_db::begin().await;
+        let last = i64::MAX - 10;
+        insert(
+            &mut tx,
+            "cancel_order_events",
+            serde_json::json!({
+                "txn_version": last,
+                "market_id": MARKET_ID,
+                "order_id": 1,
+                "user": "0xa",
+            }),
+        )
+        .await;
+        for (after, market_ids, expected) in [
+            (last - 1, None, true),
+            (last - 1, Some(vec![MARKET_ID]), true),
+            (last, None, false),
+            // Events of markets that are not aggregated are no work.
+            (last - 1, Some(vec![MARKET_ID + 1]), false),
+        ] {
+            let has_new_events: bool = sqlx::query_scalar(include_str!(
+                "../../sqlx_queries/user_history/has_new_events.sql"
+            ))
+            .bind(after)
+            .bind(&market_ids)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+            assert_eq!(has_new_events, expected, "{after} {market_ids:?}");
+        }
+
+        // Nothing is known to be aggregated before the first run.
+        let pipeline = UserHistory::new(
+            crate::test_db::connect().await,
+            false,
+            std::time::Duration::from_secs(60),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(pipeline.has_work().await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn reaggregating_corrects_only_the_order() {
+        use crate::test_db::{insert, insert_order, MARKET_ID};
+
+        let mut tx = crate::test_db::begin().await;
+        let last = i64::MAX - 10;
+        // Order 2 is fully filled by order 1 on placement.
+        for (order_id, user, side, initial_size, size) in
+            [(1, "0xa", true, 10, 10), (2, "0xb", false, 4, 0)]
+        {
+            insert(
+                &mut tx,
+                "place_limit_order_events",
+                serde_json::json!({
+                    "txn_version": last,
+                    "event_idx": order_id,
+                    "market_id": MARKET_ID,
+                    "user": user,
+                    "order_id": order_id,
+                    "side": side,
+                    "initial_size": initial_size,
+                    "price": 3,
+                    "size": size,
+                }),
+            )
+            .await;
+        }
+        for (event_idx, emit_address) in [(3, "0xa"), (4, "0xb")] {
+            insert(
+                &mut tx,
+                "fill_events",
+                serde_json::json!({
+                    "txn_version": last,
+                    "event_idx": event_idx,
+                    "emit_address": emit_address,
+                    "maker_address": "0xa",
+                    "maker_order_id": 1,
+                    "maker_side": true,
+                    "market_id": MARKET_ID,
+                    "price": 3,
+                    "size": 4,
+                    "taker_address": "0xb",
+                    "taker_order_id": 2,
+                    "taker_quote_fees_paid": 0,
+                }),
+            )
+            .await;
+        }
+        // The total filled of order 1 is corrupted.
+        insert_order(
+            &mut tx,
+            serde_json::json!({
+                "order_id": 1,
+                "direction": "ask",
+                "price": 3,
+                "total_filled": 9,
+                "remaining_size": 1,
+            }),
+        )
+        .await;
+        insert_order(
+            &mut tx,
+            serde_json::json!({
+                "order_id": 2,
+                "user": "0xb",
+                "price": 3,
+                "total_filled": 4,
+                "remaining_size": 0,
+                "order_status": "closed",
+                "close_reason": "filled",
+            }),
+        )
+        .await;
+
+        let filled = ("limit".into(), "closed".into(), Some("filled".into()), 4, 0);
+        // Running it again yields the same result.
+        for _ in 0..2 {
+            let replayed = replay_order(
+                &mut tx,
+                &BigDecimal::from(MARKET_ID),
+                &BigDecimal::from(1),
+                &BigDecimal::from(last),
+                true,
+            )
+            .await
+            .unwrap();
+            assert!(replayed);
+            assert_eq!(
+                orders(&mut tx).await,
+                [("limit".into(), "open".into(), None, 4, 6), filled.clone()]
+            );
+        }
+
+        // Orders without a place event are not replayed.
+        let replayed = replay_order(
+            &mut tx,
+            &BigDecimal::from(MARKET_ID),
+            &BigDecimal::from(3),
+            &BigDecimal::from(last),
+            true,
+        )
+        .await
+        .unwrap();
+        assert!(!replayed);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn cancel_before_placement_is_kept_for_a_later_run() {
+        use crate::test_db::{insert, insert_order, MARKET_ID};
+
+        let mut tx = crate::test_db::begin().await;
+        let last = i64::MAX - 10;
+        insert(
+            &mut tx,
+            "cancel_order_events",
+            serde_json::json!({
+                "txn_version": last,
+                "market_id": MARKET_ID,
+                "order_id": 1,
+                "user": "0xa",
+            }),
+        )
+        .await;
+        let mark_cancelled = include_str!("../../sqlx_queries/user_history/mark_cancelled.sql");
+        let pending = "SELECT COUNT(*) FROM aggregator.pending_cancels WHERE market_id = $1";
+
+        let cancelled: i64 = sqlx::query_scalar(mark_cancelled)
+            .bind(last - 1)
+            .bind([MARKET_ID])
+            .bind(true)
+            .bind(true)
+            .bind(true)
+            .bind(last)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(cancelled, 0);
+        let pending_cancels: i64 = sqlx::query_scalar(pending)
+            .bind(MARKET_ID)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(pending_cancels, 1);
+
+        // The placement is aggregated, and the next run has no new cancel.
+        insert_order(&mut tx, serde_json::json!({ "order_id": 1 })).await;
+        let cancelled: i64 = sqlx::query_scalar(mark_cancelled)
+            .bind(last)
+            .bind([MARKET_ID])
+            .bind(true)
+            .bind(true)
+            .bind(true)
+            .bind(last + 1)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(cancelled, 1);
+        assert_eq!(
+            orders(&mut tx).await,
+            [(
+                "limit".into(),
+                "cancelled".into(),
+                Some("cancelled".into()),
+                0,
+                1
+            )]
+        );
+        let pending_cancels: i64 = sqlx::query_scalar(pending)
+            .bind(MARKET_ID)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(pending_cancels, 0);
+    }
+
+    /// Changes the size of order 1 of [`MARKET_ID`](crate::test_db::MARKET_ID), placed with a
+    /// size of 10 and filled by 6, to `new_size` at `(2, 1)`, and returns its remaining size and
+    /// `last_increase_stamp`, which was that of `(1, 0)`.
+    async fn resize_partially_filled_order(new_size: i64) -> (BigDecimal, BigDecimal) {
+        let mut tx = crate::test_db::begin().await;
+        crate::test_db::insert_order(
+            &mut tx,
+            serde_json::json!({
+                "order_id": 1,
+                "total_filled": 6,
+                "remaining_size": 4,
+                "last_increase_stamp": encode("1", "0").unwrap(),
+            }),
+        )
+        .await;
+        let market_id = BigDecimal::from(crate::test_db::MARKET_ID);
+        aggregate_change(
+            &mut tx,
+            &BigDecimal::from(new_size),
+            &BigDecimal::from(1),
+            &market_id,
+            &Utc::now(),
+            &BigDecimal::from(2),
+            &BigDecimal::from(1),
+        )
+        .await
+        .unwrap();
+        sqlx::query_as(
+            "SELECT remaining_size, last_increase_stamp FROM aggregator.user_history \
+             WHERE market_id = $1 AND order_id = 1",
+        )
+        .bind(&market_id)
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn growing_a_partially_filled_order_loses_priority() {
+        // Still below the size at placement, but above the remaining size.
+        assert_eq!(
+            resize_partially_filled_order(5).await,
+            (BigDecimal::from(5), encode("2", "1").unwrap())
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn shrinking_a_partially_filled_order_keeps_priority() {
+        for new_size in [3, 4] {
+            assert_eq!(
+                resize_partially_filled_order(new_size).await,
+                (BigDecimal::from(new_size), encode("1", "0").unwrap()),
+                "{new_size}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn size_changes_are_recorded_with_the_size_before() {
+        let mut tx = crate::test_db::begin().await;
+        crate::test_db::insert_order(
+            &mut tx,
+            serde_json::json!({ "order_id": 1, "remaining_size": 10 }),
+        )
+        .await;
+        let market_id = BigDecimal::from(crate::test_db::MARKET_ID);
+        let last = i64::MAX - 10;
+        for (txn_version, new_size) in [(last - 1, 4), (last, 7)] {
+            aggregate_change(
+                &mut tx,
+                &BigDecimal::from(new_size),
+                &BigDecimal::from(1),
+                &market_id,
+                &Utc::now(),
+                &BigDecimal::from(txn_version),
+                &BigDecimal::from(0),
+            )
+            .await
+            .unwrap();
+        }
+        let changes: Vec<(i64, i64, i64)> = sqlx::query_as(
+            "SELECT old_size::int8, new_size::int8, txn_version::int8 \
+             FROM aggregator.order_size_changes WHERE market_id = $1 AND order_id = 1 \
+             ORDER BY txn_version",
+        )
+        .bind(&market_id)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+        assert_eq!(changes, [(10, 4, last - 1), (4, 7, last)]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn expected_columns_exist() {
+        let pipeline = UserHistory::new(
+            crate::test_db::connect().await,
+            false,
+            std::time::Duration::from_secs(60),
+            None,
+            None,
+            None,
+            None,
+        );
+        aggregator::db::check_columns(
+            &crate::test_db::connect().await,
+            &pipeline.model_name(),
+            pipeline.expected_columns(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn initializing_the_watermark_twice_is_a_no_op() {
+        let mut tx = crate::test_db::begin().await;
+        let txn_version = BigDecimal::from(i64::MAX - 10);
+        for _ in 0..2 {
+            update_max_txn_version(&mut tx, false, txn_version.clone())
+                .await
+                .unwrap();
+        }
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM aggregator.user_history_last_indexed_txn WHERE txn_version = $1",
+        )
+        .bind(&txn_version)
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn size_changes_of_market_orders_and_swaps_fail() {
+        for order_type in ["market", "swap"] {
+            let mut tx = crate::test_db::begin().await;
+            crate::test_db::insert_order(
+                &mut tx,
+                serde_json::json!({ "order_id": 1, "order_type": order_type }),
+            )
+            .await;
+            let market_id = BigDecimal::from(crate::test_db::MARKET_ID);
+            let result = aggregate_change(
+                &mut tx,
+                &BigDecimal::from(5),
+                &BigDecimal::from(1),
+                &market_id,
+                &Utc::now(),
+                &BigDecimal::from(2),
+                &BigDecimal::from(1),
+            )
+            .await;
+            match result {
+                Err(PipelineError::ProcessingError(e)) => assert!(
+                    e.to_string().contains(&format!(
+                        "txn version 2 event 1 targets order 1 on market {market_id}, which is a"
+                    )) && e.to_string().contains("not a limit order"),
+                    "{e}"
+                ),
+                Err(e) => panic!("unexpected error {e}"),
+                Ok(_) => panic!("changed the size of a {order_type} order"),
+            }
+            let remaining_size: i64 = sqlx::query_scalar(
+                "SELECT remaining_size::int8 FROM aggregator.user_history \
+                 WHERE market_id = $1 AND order_id = 1",
+            )
+            .bind(&market_id)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+            assert_eq!(remaining_size, 1, "{order_type}");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn missing_and_orphaned_orders_are_repaired() {
+        use bigdecimal::ToPrimitive;
+        use sqlx::Executor;
+
+        use crate::test_db::{insert, insert_order, MARKET_ID};
+
+        let mut tx = crate::test_db::begin().await;
+        let last = i64::MAX - 10;
+        tx.execute(
+            format!(
+                "DELETE FROM aggregator.user_history_last_indexed_txn; \
+                 INSERT INTO aggregator.user_history_last_indexed_txn VALUES ({last})"
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+        // Order 1 was placed but not aggregated, order 2 was aggregated without a place event,
+        // and order 3 is consistent.
+        for order_id in [1, 3] {
+            insert(
+                &mut tx,
+                "place_limit_order_events",
+                serde_json::json!({
+                    "txn_version": last - 3 + order_id,
+                    "market_id": MARKET_ID,
+                    "user": "0xa",
+                    "order_id": order_id,
+                    "side": false,
+                    "initial_size": 1,
+                    "price": 1,
+                    "size": 1,
+                }),
+            )
+            .await;
+        }
+        for order_id in [2, 3] {
+            insert_order(&mut tx, serde_json::json!({ "order_id": order_id })).await;
+        }
+        let market_ids = [BigDecimal::from(MARKET_ID)];
+        let of_market = |orders: &[(BigDecimal, BigDecimal)]| -> Vec<i64> {
+            orders
+                .iter()
+                .filter(|(market_id, _)| market_id == &market_ids[0])
+                .map(|(_, order_id)| order_id.to_i64().unwrap())
+                .collect()
+        };
+
+        let check = check_orders_in(&mut tx, false, true, Some(&market_ids))
+            .await
+            .unwrap();
+        assert_eq!(of_market(&check.missing), [1]);
+        // Orphans are looked for in every market.
+        assert_eq!(of_market(&check.orphaned), [2]);
+        assert!(check.unbalanced.is_empty(), "{:?}", check.unbalanced);
+
+        check_orders_in(&mut tx, true, true, Some(&market_ids))
+            .await
+            .unwrap();
+        let open = ("limit".into(), "open".into(), None, 0, 1);
+        assert_eq!(orders(&mut tx).await, [open.clone(), open]);
+        let quarantined: Vec<i64> = sqlx::query_scalar(
+            "SELECT order_id::int8 FROM aggregator.orphaned_orders WHERE market_id = $1",
+        )
+        .bind(MARKET_ID)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+        assert_eq!(quarantined, [2]);
+        let check = check_orders_in(&mut tx, false, true, Some(&market_ids))
+            .await
+            .unwrap();
+        assert!(of_market(&check.missing).is_empty());
+        assert!(of_market(&check.orphaned).is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn unbalanced_limit_orders_are_reported() {
+        use bigdecimal::ToPrimitive;
+        use sqlx::Executor;
+
+        use crate::test_db::{insert, insert_order, MARKET_ID};
+
+        let mut tx = crate::test_db::begin().await;
+        let last = i64::MAX - 100;
+        tx.execute(
+            format!(
+                "DELETE FROM aggregator.user_history_last_indexed_txn; \
+                 INSERT INTO aggregator.user_history_last_indexed_txn VALUES ({last})"
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+        // Orders of size 10 with 3 filled. Order 2 lost a lot of its remaining size, and order 3
+        // was resized to 5 after the fill, then filled 1 more.
+        for order_id in [1, 2, 3] {
+            insert(
+                &mut tx,
+                "place_limit_order_events",
+                serde_json::json!({
+                    "txn_version": last - 50 + order_id,
+                    "market_id": MARKET_ID,
+                    "user": "0xa",
+                    "order_id": order_id,
+                    "side": false,
+                    "initial_size": 10,
+                    "price": 1,
+                    "size": 10,
+                }),
+            )
+            .await;
+        }
+        let fill = |txn_version: i64, order_id: i64, size: i64| {
+            serde_json::json!({
+                "txn_version": txn_version,
+                "emit_address": "0xa",
+                "market_id": MARKET_ID,
+                "maker_address": "0xa",
+                "maker_order_id": order_id,
+                "maker_side": false,
+                "taker_address": "0xb",
+                "taker_order_id": 100,
+                "price": 1,
+                "size": size,
+                "taker_quote_fees_paid": 0,
+            })
+        };
+        for order_id in [1, 2, 3] {
+            insert(
+                &mut tx,
+                "fill_events",
+                fill(last - 40 + order_id, order_id, 3),
+            )
+            .await;
+        }
+        insert(
+            &mut tx,
+            "change_order_size_events",
+            serde_json::json!({
+                "txn_version": last - 30,
+                "market_id": MARKET_ID,
+                "order_id": 3,
+                "user": "0xa",
+                "side": false,
+                "new_size": 5,
+            }),
+        )
+        .await;
+        insert(&mut tx, "fill_events", fill(last - 20, 3, 1)).await;
+        for (order_id, total_filled, remaining_size) in [(1, 3, 7), (2, 3, 6), (3, 4, 4)] {
+            insert_order(
+                &mut tx,
+                serde_json::json!({
+                    "order_id": order_id,
+                    "total_filled": total_filled,
+                    "remaining_size": remaining_size,
+                }),
+            )
+            .await;
+        }
+
+        let check = check_orders_in(&mut tx, false, true, Some(&[BigDecimal::from(MARKET_ID)]))
+            .await
+            .unwrap();
+        let unbalanced: Vec<_> = check
+            .unbalanced
+            .iter()
+            .map(|order| {
+                [
+                    &order.order_id,
+                    &order.total_filled,
+                    &order.remaining_size,
+                    &order.expected_total_size,
+                ]
+                .map(|n| n.to_i64().unwrap())
+            })
+            .collect();
+        assert_eq!(unbalanced, [[2, 3, 6, 10]]);
     }
-    Ok(())
 }
@@ -0,0 +1,202 @@
+//! Opt-in profiling of the SQL statements of pipeline runs, to find where a run spends its time.
+//!
+//! A run is wrapped in [`Sampler::run`], and the statements it times with [`timed`] are rolled
+//! up by [`Statement`] kind and logged when the run ends, if it was sampled. Outside of a sampled
+//! run, [`timed`] only checks a task-local, so instrumenting queries costs next to nothing while
+//! profiling is off.
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// Kind of a SQL statement, which statements are rolled up by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Statement {
+    Select = 0,
+    Insert = 1,
+    Update = 2,
+    Delete = 3,
+}
+
+/// Number and cumulative duration of the statements of each kind timed during a run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    statements: [(u32, Duration); 4],
+}
+
+impl Profile {
+    fn record(&mut self, statement: Statement, elapsed: Duration) {
+        let (count, total) = &mut self.statements[statement as usize];
+        *count += 1;
+        *total += elapsed;
+    }
+
+    /// Returns the number of statements of kind `statement`.
+    pub fn count(&self, statement: Statement) -> u32 {
+        self.statements[statement as usize].0
+    }
+
+    /// Returns the time spent running statements of kind `statement`.
+    pub fn total(&self, statement: Statement) -> Duration {
+        self.statements[statement as usize].1
+    }
+
+    /// Logs the breakdown of the run, which took `elapsed` in total. Time not spent in timed
+    /// statements (e.g. in Rust code, or in statements left uninstrumented) is logged as
+    /// `other_ms`.
+    fn log(&self, elapsed: Duration) {
+        let timed: Duration = self.statements.iter().map(|(_, total)| *total).sum();
+        let ms = |statement| self.total(statement).as_millis();
+        tracing::info!(
+            elapsed_ms = elapsed.as_millis(),
+            select_count = self.count(Statement::Select),
+            select_ms = ms(Statement::Select),
+            insert_count = self.count(Statement::Insert),
+            insert_ms = ms(Statement::Insert),
+            update_count = self.count(Statement::Update),
+            update_ms = ms(Statement::Update),
+            delete_count = self.count(Statement::Delete),
+            delete_ms = ms(Statement::Delete),
+            other_ms = elapsed.saturating_sub(timed).as_millis(),
+            "Statement profile of the run."
+        );
+    }
+}
+
+tokio::task_local! {
+    static PROFILE: RefCell<Profile>;
+}
+
+/// Awaits `query`, recording how long it took under `statement` if the run is sampled.
+pub async fn timed<F: Future>(statement: Statement, query: F) -> F::Output {
+    if PROFILE.try_with(|_| ()).is_err() {
+        return query.await;
+    }
+    let start = Instant::now();
+    let output = query.await;
+    let elapsed = start.elapsed();
+    let _ = PROFILE.try_with(|profile| profile.borrow_mut().record(statement, elapsed));
+    output
+}
+
+/// Picks the runs to profile, a `rate` fraction of them.
+///
+/// Runs are picked at regular intervals rather than at random, e.g. every fourth run for a rate
+/// of 0.25, so that the first sampled run is never far off.
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    rate: f64,
+    runs: u64,
+}
+
+impl Sampler {
+    /// Creates a sampler profiling a `rate` fraction of runs, clamped between 0 (never) and 1
+    /// (every run).
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate: rate.clamp(0., 1.),
+            runs: 0,
+        }
+    }
+
+    /// Returns `true` if the next run is to be profiled.
+    fn sample(&mut self) -> bool {
+        if self.rate == 0. {
+            return false;
+        }
+        let before = (self.runs as f64 * self.rate).floor();
+        self.runs += 1;
+        (self.runs as f64 * self.rate).floor() > before
+    }
+
+    /// Awaits `run`, and logs the breakdown of the statements it timed if it is sampled.
+    pub async fn run<F: Future>(&mut self, run: F) -> F::Output {
+        if !self.sample() {
+            return run.await;
+        }
+        let start = Instant::now();
+        let (output, profile) = PROFILE
+            .scope(RefCell::new(Profile::default()), async {
+                let output = run.await;
+                (output, PROFILE.with(|profile| profile.take()))
+            })
+            .await;
+        profile.log(start.elapsed());
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `runs` runs of a select taking 10 ms and two inserts through a sampler of `rate`,
+    /// and returns the fields of the logged profiles.
+    async fn profile(rate: f64, runs: usize) -> Vec<serde_json::Value> {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let mut sampler = Sampler::new(rate);
+        for _ in 0..runs {
+            sampler
+                .run(async {
+                    timed(
+                        Statement::Select,
+                        tokio::time::sleep(Duration::from_millis(10)),
+                    )
+                    .await;
+                    timed(Statement::Insert, async {}).await;
+                    timed(Statement::Insert, async {}).await;
+                })
+                .await;
+        }
+        let logs = logs.0.lock().unwrap();
+        serde_json::Deserializer::from_slice(&logs)
+            .into_iter()
+            .map(|event: serde_json::Result<serde_json::Value>| event.unwrap()["fields"].clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn sampled_run_logs_the_totals_of_each_kind() {
+        let profiles = profile(1., 1).await;
+        assert_eq!(profiles.len(), 1, "{profiles:?}");
+        let profile = &profiles[0];
+        assert_eq!(profile["message"], "Statement profile of the run.");
+        assert_eq!(profile["select_count"], 1);
+        // Durations are `u128`s, which are logged as strings.
+        let ms = |field: &str| profile[field].as_str().unwrap().parse::<u64>().unwrap();
+        assert!(ms("select_ms") >= 10, "{profile}");
+        assert_eq!(profile["insert_count"], 2);
+        assert_eq!(profile["update_count"], 0);
+        assert_eq!(profile["delete_count"], 0);
+        assert!(ms("elapsed_ms") >= ms("select_ms"), "{profile}");
+    }
+
+    #[tokio::test]
+    async fn only_a_fraction_of_runs_is_sampled() {
+        assert_eq!(profile(0.25, 8).await.len(), 2);
+        assert!(profile(0., 8).await.is_empty());
+    }
+}
@@ -0,0 +1,926 @@
+//! Replays a recorded event stream through [`UserHistory`] and compares the resulting user
+//! history to the expected one, to catch regressions in the aggregation logic.
+//!
+//! A fixture is a JSON file of the form:
+//!
+//! ```json
+//! {
+//!   "description": "What the fixture covers",
+//!   "events": {
+//!     "place_limit_order_events": [{ "txn_version": 10, "event_idx": 0, ... }],
+//!     "fill_events": [...]
+//!   },
+//!   "expected_user_history": [{ "market_id": 1, "order_id": 1, "remaining_size": 0, ... }]
+//! }
+//! ```
+//!
+//! Event rows may leave out the columns of [`EVENT_DEFAULTS`], and must hold every other non-null
+//! column of their table. Expected rows must hold
+//! `market_id` and `order_id`, and only the other columns they hold are compared, so fixtures
+//! keep passing when a column is added to the user history. The fixture is parsed by Postgres,
+//! so 128 bit order IDs do not lose precision.
+
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use bigdecimal::BigDecimal;
+use serde::{de::IgnoredAny, Deserialize};
+use sqlx::{Executor, PgPool};
+
+use aggregator::Pipeline;
+
+use crate::pipelines::{user_history, UserHistory};
+
+/// Schema the events of a fixture are loaded into, dropped after the replay.
+const REPLAY_SCHEMA: &str = "aggregator_replay";
+
+/// The event tables [`UserHistory`] reads, which a fixture can seed.
+const SOURCE_TABLES: [&str; 7] = [
+    "market_registration_events",
+    "place_limit_order_events",
+    "place_market_order_events",
+    "place_swap_order_events",
+    "fill_events",
+    "change_order_size_events",
+    "cancel_order_events",
+];
+
+/// Values of the event columns a fixture row leaves out, so that fixtures only spell out what
+/// they are about. Each table takes the columns it has: a single market with unit sizes,
+/// default custodians and integrator, and manual cancels.
+pub const EVENT_DEFAULTS: &str = r#"{
+    "event_idx": 0,
+    "time": "2024-01-01T00:00:00+00:00",
+    "base_account_address": "0x1",
+    "base_module_name": "coin",
+    "base_struct_name": "BASE",
+    "base_name_generic": "",
+    "quote_account_address": "0x1",
+    "quote_module_name": "coin",
+    "quote_struct_name": "QUOTE",
+    "lot_size": 1,
+    "tick_size": 1,
+    "min_size": 1,
+    "underwriter_id": 0,
+    "custodian_id": 0,
+    "maker_custodian_id": 0,
+    "taker_custodian_id": 0,
+    "sequence_number_for_trade": 0,
+    "integrator": "0x0",
+    "restriction": 0,
+    "self_match_behavior": 0,
+    "reason": 3
+}"#;
+
+/// The shape of a fixture, only used to check it before handing it to Postgres.
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    #[serde(default)]
+    events: BTreeMap<String, Vec<IgnoredAny>>,
+    #[allow(dead_code)]
+    expected_user_history: Vec<IgnoredAny>,
+}
+
+/// Aggregates the events of the fixture at `path` from scratch and returns the differences
+/// between the resulting user history and the expected one, empty if there are none.
+///
+/// The user history must be empty, so this is meant to run against a scratch database. It is
+/// truncated again afterwards, whatever the outcome.
+pub async fn replay(
+    pool: &PgPool,
+    path: &Path,
+    strict_fills: bool,
+    slow_step_threshold: Duration,
+) -> Result<Vec<String>> {
+    replay_in_runs(pool, path, strict_fills, slow_step_threshold, None).await
+}
+
+/// Like [`replay`], but if `run_versions` is set, aggregates at most that many transaction
+/// versions per run, see [`aggregate`].
+async fn replay_in_runs(
+    pool: &PgPool,
+    path: &Path,
+    strict_fills: bool,
+    slow_step_threshold: Duration,
+    run_versions: Option<u64>,
+) -> Result<Vec<String>> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read fixture {}", path.display()))?;
+    let fixture: Fixture = serde_json::from_str(&json)
+        .with_context(|| format!("Invalid fixture {}", path.display()))?;
+    if let Some(table) = fixture
+        .events
+        .keys()
+        .find(|table| !SOURCE_TABLES.contains(&table.as_str()))
+    {
+        return Err(anyhow!("Unknown event table {table} in fixture."));
+    }
+
+    if !user_history_is_empty(pool).await? {
+        return Err(anyhow!(
+            "The user history is not empty, replay fixtures against a scratch database."
+        ));
+    }
+
+    seed(pool, &json).await?;
+    let result = aggregate(pool, strict_fills, slow_step_threshold, run_versions).await;
+    let result = match result {
+        Ok(()) => compare(pool, &json).await,
+        Err(e) => Err(e),
+    };
+    user_history::rewind(pool, None, strict_fills, None).await?;
+    pool.execute(format!("DROP SCHEMA {REPLAY_SCHEMA} CASCADE").as_str())
+        .await?;
+    result
+}
+
+/// Returns whether nothing was aggregated into the user history, which replays start from.
+async fn user_history_is_empty(pool: &PgPool) -> Result<bool> {
+    let (not_empty,): (bool,) = sqlx::query_as(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM aggregator.user_history)
+            OR EXISTS (SELECT 1 FROM aggregator.user_history_last_indexed_txn)
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(!not_empty)
+}
+
+/// Creates empty copies of the event tables in [`REPLAY_SCHEMA`] and loads the events of the
+/// `fixture` into them.
+async fn seed(pool: &PgPool, fixture: &str) -> Result<()> {
+    let mut transaction = pool.begin().await?;
+    transaction
+        .execute(format!("DROP SCHEMA IF EXISTS {REPLAY_SCHEMA} CASCADE").as_str())
+        .await?;
+    transaction
+        .execute(format!("CREATE SCHEMA {REPLAY_SCHEMA}").as_str())
+        .await?;
+    for table in SOURCE_TABLES {
+        transaction
+            .execute(
+                format!(
+                    "CREATE TABLE {REPLAY_SCHEMA}.{table} (LIKE public.{table} INCLUDING DEFAULTS)"
+                )
+                .as_str(),
+            )
+            .await?;
+        // Tables missing from the fixture stay empty. Columns missing from a row are taken from
+        // the base record, built from the defaults.
+        sqlx::query(&format!(
+            "INSERT INTO {REPLAY_SCHEMA}.{table} \
+             SELECT * FROM jsonb_populate_recordset(\
+                 jsonb_populate_record(NULL::{REPLAY_SCHEMA}.{table}, $3::jsonb), \
+                 $1::jsonb -> 'events' -> $2\
+             )"
+        ))
+        .bind(fixture)
+        .bind(table)
+        .bind(EVENT_DEFAULTS)
+        .execute(&mut *transaction)
+        .await
+        .with_context(|| format!("Could not load {table}"))?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Runs [`UserHistory`] over [`REPLAY_SCHEMA`] until it caught up.
+///
+/// If `run_versions` is set, each run aggregates at most that many transaction versions, with a
+/// new pipeline resuming from the position the previous one saved, as after a restart.
+async fn aggregate(
+    pool: &PgPool,
+    strict_fills: bool,
+    slow_step_threshold: Duration,
+    run_versions: Option<u64>,
+) -> Result<()> {
+    let new_pipeline = || {
+        UserHistory::new(
+            pool.clone(),
+            strict_fills,
+            slow_step_threshold,
+            Some(String::from(REPLAY_SCHEMA)),
+            None,
+            None,
+            run_versions.map(BigDecimal::from),
+        )
+    };
+    let mut pipeline = new_pipeline();
+    pipeline.process_and_save_historical_data().await?;
+    while pipeline.has_work().await? {
+        if run_versions.is_some() {
+            pipeline = new_pipeline();
+            pipeline.process_and_save_historical_data().await?;
+        } else {
+            pipeline.process_and_save_internal().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns a description of every order the `fixture` expects that is missing or differs from the
+/// user history, and of every order in the user history that was not expected.
+async fn compare(pool: &PgPool, fixture: &str) -> Result<Vec<String>> {
+    let mut transaction = pool.begin().await?;
+    // Timestamps are compared as the strings they serialize to.
+    transaction.execute("SET LOCAL TIME ZONE 'UTC'").await?;
+    // jsonb compares numbers by value, so an expected 100 matches an actual 100.0000.
+    let rows: Vec<(Option<String>, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT
+            expected.value::text,
+            CASE
+                WHEN user_history.order_id IS NOT NULL THEN to_jsonb(user_history)::text
+            END
+        FROM
+            jsonb_array_elements($1::jsonb -> 'expected_user_history') AS expected
+            LEFT JOIN aggregator.user_history
+                ON user_history.market_id = (expected.value->>'market_id')::numeric
+                AND user_history.order_id = (expected.value->>'order_id')::numeric
+        WHERE
+            user_history.order_id IS NULL
+            OR NOT to_jsonb(user_history) @> expected.value
+        UNION ALL
+        SELECT
+            NULL,
+            to_jsonb(user_history)::text
+        FROM
+            aggregator.user_history
+        WHERE
+            NOT EXISTS (
+                SELECT 1 FROM jsonb_array_elements($1::jsonb -> 'expected_user_history') AS expected
+                WHERE user_history.market_id = (expected.value->>'market_id')::numeric
+                AND user_history.order_id = (expected.value->>'order_id')::numeric
+            )
+        "#,
+    )
+    .bind(fixture)
+    .fetch_all(&mut *transaction)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| match row {
+            (Some(expected), None) => format!("missing order, expected {expected}"),
+            (None, Some(actual)) => format!("unexpected order {actual}"),
+            (expected, actual) => format!(
+                "expected {}, got {}",
+                expected.unwrap_or_default(),
+                actual.unwrap_or_default()
+            ),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pipelines::user_history::EventKind, test_db};
+
+    /// Returns everything the user history pipeline wrote, as text.
+    async fn snapshot(pool: &PgPool) -> Result<String> {
+        let mut transaction = pool.begin().await?;
+        transaction.execute("SET LOCAL TIME ZONE 'UTC'").await?;
+        let table = |name: &str| {
+            format!("(SELECT jsonb_agg(to_jsonb(t) ORDER BY to_jsonb(t)::text) FROM {name} AS t)")
+        };
+        let tables = [
+            "aggregator.user_history",
+            "aggregator.user_history_last_indexed_txn",
+            "aggregator.order_size_changes",
+            "aggregator.pending_cancels",
+            "aggregator.pending_fills",
+            "aggregator.pending_size_changes",
+        ]
+        .map(|name| format!("'{name}', {}", table(name)));
+        let (snapshot,): (String,) = sqlx::query_as(&format!(
+            "SELECT jsonb_build_object({})::text",
+            tables.join(", ")
+        ))
+        .fetch_one(&mut *transaction)
+        .await?;
+        Ok(snapshot)
+    }
+
+    /// Aggregates the fixture at `path` one transaction version per run, then rebuilds the user
+    /// history from scratch and from each transaction version of the fixture, and returns the
+    /// versions whose rebuild differs from the incremental run.
+    async fn rebuilds_differing_from_incremental_run(
+        pool: &PgPool,
+        path: &Path,
+    ) -> Result<Vec<Option<BigDecimal>>> {
+        let json = std::fs::read_to_string(path)?;
+        seed(pool, &json).await?;
+        let threshold = Duration::from_secs(60);
+        let result = async {
+            aggregate(pool, false, threshold, Some(1)).await?;
+            let incremental = snapshot(pool).await?;
+            let versions: Vec<BigDecimal> = sqlx::query_scalar(
+                "SELECT DISTINCT (event.value->>'txn_version')::numeric \
+                 FROM jsonb_each($1::jsonb -> 'events') AS events, \
+                 jsonb_array_elements(events.value) AS event \
+                 ORDER BY 1",
+            )
+            .bind(&json)
+            .fetch_all(pool)
+            .await?;
+            let mut differing = vec![];
+            for from in std::iter::once(None).chain(versions.into_iter().map(Some)) {
+                user_history::rewind(pool, from.as_ref(), false, Some(REPLAY_SCHEMA)).await?;
+                aggregate(pool, false, threshold, None).await?;
+                if snapshot(pool).await? != incremental {
+                    differing.push(from);
+                }
+            }
+            Ok(differing)
+        }
+        .await;
+        user_history::rewind(pool, None, false, None).await?;
+        pool.execute(format!("DROP SCHEMA {REPLAY_SCHEMA} CASCADE").as_str())
+            .await?;
+        result
+    }
+
+    /// Rebuilding the user history, from scratch or from a transaction version, leaves the same
+    /// state as aggregating it incrementally.
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn rebuilding_matches_an_incremental_run() {
+        let (pool, _guard) = test_db::connect_exclusive().await;
+        if !user_history_is_empty(&pool).await.unwrap() {
+            eprintln!("The user history is not empty, skipping the rebuilds.");
+            return;
+        }
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/user_history");
+        for entry in std::fs::read_dir(fixtures).unwrap() {
+            let path = entry.unwrap().path();
+            let differing = rebuilds_differing_from_incremental_run(&pool, &path)
+                .await
+                .unwrap();
+            assert!(differing.is_empty(), "{}: {differing:?}", path.display());
+        }
+    }
+
+    /// Replays every fixture with a new pipeline for each transaction version, which would
+    /// aggregate fills and size changes twice if it did not resume from the position saved by
+    /// the previous one.
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn resuming_does_not_aggregate_twice() {
+        let (pool, _guard) = test_db::connect_exclusive().await;
+        if !user_history_is_empty(&pool).await.unwrap() {
+            eprintln!("The user history is not empty, skipping the fixture replays.");
+            return;
+        }
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/user_history");
+        for entry in std::fs::read_dir(fixtures).unwrap() {
+            let path = entry.unwrap().path();
+            let differences = replay_in_runs(&pool, &path, false, Duration::from_secs(60), Some(1))
+                .await
+                .unwrap();
+            assert!(
+                differences.is_empty(),
+                "{}: {differences:#?}",
+                path.display()
+            );
+        }
+    }
+
+    /// Only the markets of the allow-list are aggregated, swaps included, while the events of
+    /// the other markets still count as aggregated.
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn only_allowed_markets_are_aggregated() {
+        let (pool, _guard) = test_db::connect_exclusive().await;
+        if !user_history_is_empty(&pool).await.unwrap() {
+            eprintln!("The user history is not empty, skipping the aggregation.");
+            return;
+        }
+        let mut events = serde_json::json!({
+            "market_registration_events": [],
+            "place_limit_order_events": [],
+            "place_swap_order_events": [],
+        });
+        for market_id in [1, 2] {
+            events["market_registration_events"]
+                .as_array_mut()
+                .unwrap()
+                .push(serde_json::json!({ "txn_version": market_id, "market_id": market_id }));
+            events["place_limit_order_events"]
+                .as_array_mut()
+                .unwrap()
+                .push(serde_json::json!({
+                    "txn_version": 10 + market_id,
+                    "market_id": market_id,
+                    "user": "0xa",
+                    "order_id": 1,
+                    "side": false,
+                    "initial_size": 1,
+                    "price": 1,
+                    "size": 1,
+                }));
+            events["place_swap_order_events"]
+                .as_array_mut()
+                .unwrap()
+                .push(serde_json::json!({
+                    "txn_version": 20 + market_id,
+                    "market_id": market_id,
+                    "order_id": 2,
+                    "signing_account": "0xb",
+                    "direction": false,
+                    "min_base": 0,
+                    "max_base": 1,
+                    "min_quote": 0,
+                    "max_quote": 1,
+                    "limit_price": 1,
+                }));
+        }
+        let fixture = serde_json::json!({ "events": events }).to_string();
+        seed(&pool, &fixture).await.unwrap();
+
+        let result = async {
+            let mut pipeline = UserHistory::new(
+                pool.clone(),
+                false,
+                Duration::from_secs(60),
+                Some(String::from(REPLAY_SCHEMA)),
+                Some(vec![BigDecimal::from(1)]),
+                None,
+                None,
+            );
+            pipeline.process_and_save_historical_data().await?;
+            while pipeline.has_work().await? {
+                pipeline.process_and_save_internal().await?;
+            }
+            let orders: Vec<(i64, i64)> = sqlx::query_as(
+                "SELECT market_id::int8, order_id::int8 FROM aggregator.user_history \
+                 ORDER BY 1, 2",
+            )
+            .fetch_all(&pool)
+            .await?;
+            let watermark: BigDecimal = sqlx::query_scalar(
+                "SELECT txn_version FROM aggregator.user_history_last_indexed_txn",
+            )
+            .fetch_one(&pool)
+            .await?;
+            Ok::<_, anyhow::Error>((orders, watermark))
+        }
+        .await;
+        user_history::rewind(&pool, None, false, None)
+            .await
+            .unwrap();
+        pool.execute(format!("DROP SCHEMA {REPLAY_SCHEMA} CASCADE").as_str())
+            .await
+            .unwrap();
+
+        let (orders, watermark) = result.unwrap();
+        assert_eq!(orders, [(1, 1), (1, 2)]);
+        assert_eq!(watermark, BigDecimal::from(22));
+    }
+
+    /// Events of disabled kinds are skipped without blocking the others: with swaps disabled,
+    /// limit orders are still aggregated, and the cancel of a swap is not kept pending.
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn disabled_event_kinds_are_skipped() {
+        let (pool, _guard) = test_db::connect_exclusive().await;
+        if !user_history_is_empty(&pool).await.unwrap() {
+            eprintln!("The user history is not empty, skipping the aggregation.");
+            return;
+        }
+        let fixture = serde_json::json!({
+            "events": {
+                "market_registration_events": [{ "txn_version": 1, "market_id": 1 }],
+                "place_limit_order_events": [{
+                    "txn_version": 11,
+                    "market_id": 1,
+                    "user": "0xa",
+                    "order_id": 1,
+                    "side": false,
+                    "initial_size": 1,
+                    "price": 1,
+                    "size": 1,
+                }],
+                "place_swap_order_events": [{
+                    "txn_version": 21,
+                    "market_id": 1,
+                    "order_id": 2,
+                    "signing_account": "0xb",
+                    "direction": false,
+                    "min_base": 0,
+                    "max_base": 1,
+                    "min_quote": 0,
+                    "max_quote": 1,
+                    "limit_price": 1,
+                }],
+                "cancel_order_events": [{
+                    "txn_version": 21,
+                    "event_idx": 1,
+                    "market_id": 1,
+                    "user": "0xb",
+                    "order_id": 2,
+                    "reason": 2,
+                }],
+            },
+        })
+        .to_string();
+        seed(&pool, &fixture).await.unwrap();
+
+        let result = async {
+            let mut pipeline = UserHistory::new(
+                pool.clone(),
+                false,
+                Duration::from_secs(60),
+                Some(String::from(REPLAY_SCHEMA)),
+                None,
+                Some(vec![
+                    EventKind::Limit,
+                    EventKind::Market,
+                    EventKind::Fill,
+                    EventKind::Change,
+                    EventKind::Cancel,
+                ]),
+                None,
+            );
+            pipeline.process_and_save_historical_data().await?;
+            while pipeline.has_work().await? {
+                pipeline.process_and_save_internal().await?;
+            }
+            let orders: Vec<(i64, String)> = sqlx::query_as(
+                "SELECT order_id::int8, order_status::text FROM aggregator.user_history \
+                 ORDER BY 1",
+            )
+            .fetch_all(&pool)
+            .await?;
+            let pending_cancels: i64 =
+                sqlx::query_scalar("SELECT count(*) FROM aggregator.pending_cancels")
+                    .fetch_one(&pool)
+                    .await?;
+            let watermark: BigDecimal = sqlx::query_scalar(
+                "SELECT txn_version FROM aggregator.user_history_last_indexed_txn",
+            )
+            .fetch_one(&pool)
+            .await?;
+            Ok::<_, anyhow::Error>((orders, pending_cancels, watermark))
+        }
+        .await;
+        user_history::rewind(&pool, None, false, None)
+            .await
+            .unwrap();
+        pool.execute(format!("DROP SCHEMA {REPLAY_SCHEMA} CASCADE").as_str())
+            .await
+            .unwrap();
+
+        let (orders, pending_cancels, watermark) = result.unwrap();
+        assert_eq!(orders, [(1, String::from("open"))]);
+        assert_eq!(pending_cancels, 0);
+        assert_eq!(watermark, BigDecimal::from(21));
+    }
+
+    /// Waits until the rollback of a dropped transaction, which sqlx only sends after the run or
+    /// check that dropped it returned, released the lock of the user history.
+    async fn wait_for_rollback(pool: &PgPool) {
+        pool.execute(
+            "SELECT pg_advisory_lock(hashtext('UserHistory')); \
+             SELECT pg_advisory_unlock(hashtext('UserHistory'))",
+        )
+        .await
+        .unwrap();
+    }
+
+    /// With runs of bounded size, a run failing on an event only rolls back its own range: the
+    /// orders placed by the runs before it stay aggregated.
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn failed_run_keeps_the_runs_before_it() {
+        let (pool, _guard) = test_db::connect_exclusive().await;
+        if !user_history_is_empty(&pool).await.unwrap() {
+            eprintln!("The user history is not empty, skipping the aggregation.");
+            return;
+        }
+        let place = |txn_version: i64, order_id: i64| {
+            serde_json::json!({
+                "txn_version": txn_version,
+                "market_id": 1,
+                "user": "0xa",
+                "order_id": order_id,
+                "side": true,
+                "initial_size": 1,
+                "price": 1,
+                "size": 1,
+            })
+        };
+        // The fill of order 3 is larger than the order, which fails its run with strict fills.
+        let fill = |emit_address: &str, event_idx: i64| {
+            serde_json::json!({
+                "txn_version": 30,
+                "event_idx": event_idx,
+                "emit_address": emit_address,
+                "market_id": 1,
+                "maker_address": "0xa",
+                "maker_order_id": 3,
+                "maker_side": true,
+                "taker_address": "0xb",
+                "taker_order_id": 4,
+                "price": 1,
+                "size": 5,
+                "taker_quote_fees_paid": 0,
+            })
+        };
+        let fixture = serde_json::json!({
+            "events": {
+                "market_registration_events": [{ "txn_version": 1, "market_id": 1 }],
+                "place_limit_order_events": [place(10, 1), place(20, 2), place(25, 3)],
+                "fill_events": [fill("0xa", 0), fill("0xb", 1)],
+            },
+        })
+        .to_string();
+        seed(&pool, &fixture).await.unwrap();
+
+        let result = async {
+            let aggregated = aggregate(&pool, true, Duration::from_secs(60), Some(10)).await;
+            wait_for_rollback(&pool).await;
+            let orders: Vec<i64> =
+                sqlx::query_scalar("SELECT order_id::int8 FROM aggregator.user_history ORDER BY 1")
+                    .fetch_all(&pool)
+                    .await?;
+            let watermark: BigDecimal = sqlx::query_scalar(
+                "SELECT txn_version FROM aggregator.user_history_last_indexed_txn",
+            )
+            .fetch_one(&pool)
+            .await?;
+            Ok::<_, anyhow::Error>((aggregated, orders, watermark))
+        }
+        .await;
+        user_history::rewind(&pool, None, false, None)
+            .await
+            .unwrap();
+        pool.execute(format!("DROP SCHEMA {REPLAY_SCHEMA} CASCADE").as_str())
+            .await
+            .unwrap();
+
+        let (aggregated, orders, watermark) = result.unwrap();
+        assert!(aggregated.is_err());
+        assert_eq!(orders, [1, 2]);
+        assert_eq!(watermark, BigDecimal::from(20));
+    }
+
+    /// A negative amount fails the run with an error naming the event, before anything of the
+    /// run is written.
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn negative_amounts_are_rejected() {
+        let (pool, _guard) = test_db::connect_exclusive().await;
+        if !user_history_is_empty(&pool).await.unwrap() {
+            eprintln!("The user history is not empty, skipping the aggregation.");
+            return;
+        }
+        let place = |price: i64| {
+            serde_json::json!({
+                "txn_version": 10,
+                "market_id": 1,
+                "user": "0xa",
+                "order_id": 1,
+                "side": true,
+                "initial_size": 10,
+                "price": price,
+                "size": 10,
+            })
+        };
+        let fill = |emit_address: &str, event_idx: i64| {
+            serde_json::json!({
+                "txn_version": 20,
+                "event_idx": event_idx,
+                "emit_address": emit_address,
+                "market_id": 1,
+                "maker_address": "0xa",
+                "maker_order_id": 1,
+                "maker_side": true,
+                "taker_address": "0xb",
+                "taker_order_id": 2,
+                "price": 100,
+                "size": -3,
+                "taker_quote_fees_paid": 0,
+            })
+        };
+        for (events, expected) in [
+            (
+                serde_json::json!({ "place_limit_order_events": [place(-100)] }),
+                "negative price in place_limit_order_events event at txn version 10 index 0",
+            ),
+            (
+                serde_json::json!({
+                    "place_limit_order_events": [place(100)],
+                    "fill_events": [fill("0xa", 1), fill("0xb", 2)],
+                }),
+                "negative size in fill_events event at txn version 20 index 1",
+            ),
+        ] {
+            let mut events = events;
+            events["market_registration_events"] =
+                serde_json::json!([{ "txn_version": 1, "market_id": 1 }]);
+            let fixture = serde_json::json!({ "events": events }).to_string();
+            seed(&pool, &fixture).await.unwrap();
+
+            let result = async {
+                let aggregated = aggregate(&pool, false, Duration::from_secs(60), None).await;
+                wait_for_rollback(&pool).await;
+                let orders: i64 =
+                    sqlx::query_scalar("SELECT count(*) FROM aggregator.user_history")
+                        .fetch_one(&pool)
+                        .await?;
+                Ok::<_, anyhow::Error>((aggregated, orders))
+            }
+            .await;
+            user_history::rewind(&pool, None, false, None)
+                .await
+                .unwrap();
+            pool.execute(format!("DROP SCHEMA {REPLAY_SCHEMA} CASCADE").as_str())
+                .await
+                .unwrap();
+
+            let (aggregated, orders) = result.unwrap();
+            let error = aggregated.unwrap_err();
+            assert!(format!("{error:#}").contains(expected), "{error:#}");
+            assert_eq!(orders, 0, "{expected}");
+        }
+    }
+
+    /// A fill of an order whose placement is not aggregated yet is kept pending, and applied once
+    /// the placement is.
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn fill_before_its_placement_is_applied_later() {
+        let (pool, _guard) = test_db::connect_exclusive().await;
+        if !user_history_is_empty(&pool).await.unwrap() {
+            eprintln!("The user history is not empty, skipping the aggregation.");
+            return;
+        }
+        let fill = |emit_address: &str, event_idx: i64| {
+            serde_json::json!({
+                "txn_version": 20,
+                "event_idx": event_idx,
+                "emit_address": emit_address,
+                "market_id": 1,
+                "maker_address": "0xa",
+                "maker_order_id": 1,
+                "maker_side": true,
+                "taker_address": "0xb",
+                "taker_order_id": 2,
+                "price": 100,
+                "size": 3,
+                "taker_quote_fees_paid": 0,
+            })
+        };
+        let fixture = serde_json::json!({
+            "events": {
+                "market_registration_events": [{ "txn_version": 1, "market_id": 1 }],
+                "fill_events": [fill("0xa", 0), fill("0xb", 1)],
+            },
+        })
+        .to_string();
+        seed(&pool, &fixture).await.unwrap();
+
+        let result = async {
+            let pending = || async {
+                sqlx::query_scalar::<_, i64>(
+                    "SELECT order_id::int8 FROM aggregator.pending_fills ORDER BY 1",
+                )
+                .fetch_all(&pool)
+                .await
+            };
+            aggregate(&pool, false, Duration::from_secs(60), None).await?;
+            let pending_before = pending().await?;
+            let orders_before: i64 =
+                sqlx::query_scalar("SELECT count(*) FROM aggregator.user_history")
+                    .fetch_one(&pool)
+                    .await?;
+
+            // The placement of the maker order shows up.
+            test_db::insert(
+                &mut *pool.acquire().await?,
+                &format!("{REPLAY_SCHEMA}.place_limit_order_events"),
+                serde_json::json!({
+                    "txn_version": 30,
+                    "market_id": 1,
+                    "user": "0xa",
+                    "order_id": 1,
+                    "side": true,
+                    "initial_size": 10,
+                    "price": 100,
+                    "size": 10,
+                }),
+            )
+            .await;
+            aggregate(&pool, false, Duration::from_secs(60), None).await?;
+            Ok::<_, anyhow::Error>((
+                pending_before,
+                orders_before,
+                pending().await?,
+                order_1(&pool).await,
+            ))
+        }
+        .await;
+        user_history::rewind(&pool, None, false, None)
+            .await
+            .unwrap();
+        pool.execute(format!("DROP SCHEMA {REPLAY_SCHEMA} CASCADE").as_str())
+            .await
+            .unwrap();
+
+        let (pending_before, orders_before, pending_after, order) = result.unwrap();
+        // One for each side.
+        assert_eq!(pending_before, [1, 2]);
+        assert_eq!(orders_before, 0);
+        // The taker order is still not placed.
+        assert_eq!(pending_after, [2]);
+        assert_eq!(order, (BigDecimal::from(3), BigDecimal::from(7)));
+    }
+
+    /// Returns the `(total_filled, remaining_size)` of order 1 of market 1.
+    async fn order_1(pool: &PgPool) -> (BigDecimal, BigDecimal) {
+        sqlx::query_as(
+            "SELECT total_filled, remaining_size FROM aggregator.user_history \
+             WHERE market_id = 1 AND order_id = 1",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    /// Reconciliation reports an order whose totals drifted from its events, and only corrects
+    /// it when asked to.
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn reconciliation_reports_and_fixes_a_drifted_order() {
+        let (pool, _guard) = test_db::connect_exclusive().await;
+        if !user_history_is_empty(&pool).await.unwrap() {
+            eprintln!("The user history is not empty, skipping the reconciliation.");
+            return;
+        }
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/user_history/place_fill.json");
+        seed(&pool, &std::fs::read_to_string(path).unwrap())
+            .await
+            .unwrap();
+        let since = "2000-01-01T00:00:00Z".parse().unwrap();
+        let result = async {
+            aggregate(&pool, false, Duration::from_secs(60), None).await?;
+            // Order 1 was filled by 4 of its 10.
+            pool.execute(
+                "UPDATE aggregator.user_history SET total_filled = 5, remaining_size = 5 \
+                 WHERE market_id = 1 AND order_id = 1",
+            )
+            .await?;
+            let mut runs = vec![];
+            for fix in [false, true] {
+                let drifts = user_history::reconcile_orders(
+                    &pool,
+                    since,
+                    fix,
+                    false,
+                    Some(REPLAY_SCHEMA),
+                    None,
+                )
+                .await?;
+                // Only reporting rolls back.
+                wait_for_rollback(&pool).await;
+                let drifts: Vec<_> = drifts
+                    .into_iter()
+                    .map(|drift| {
+                        (
+                            drift.order_id,
+                            drift.total_filled,
+                            drift.expected_total_filled,
+                            drift.remaining_size,
+                            drift.expected_remaining_size,
+                        )
+                    })
+                    .collect();
+                runs.push((drifts, order_1(&pool).await));
+            }
+            Ok::<_, anyhow::Error>(runs)
+        }
+        .await;
+        user_history::rewind(&pool, None, false, None)
+            .await
+            .unwrap();
+        pool.execute(format!("DROP SCHEMA {REPLAY_SCHEMA} CASCADE").as_str())
+            .await
+            .unwrap();
+
+        let n = BigDecimal::from;
+        let drift = vec![(n(1), n(5), n(4), n(5), n(6))];
+        assert_eq!(
+            result.unwrap(),
+            [
+                // Only reported.
+                (drift.clone(), (n(5), n(5))),
+                // Corrected.
+                (drift, (n(4), n(6))),
+            ]
+        );
+    }
+}
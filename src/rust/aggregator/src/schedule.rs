@@ -0,0 +1,191 @@
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+
+use crate::Pipeline;
+
+/// Returns `true` if `a` and `b` must not run concurrently, that is if one of them writes a table
+/// the other reads or writes.
+pub fn conflicts(a: &(dyn Pipeline + Send + Sync), b: &(dyn Pipeline + Send + Sync)) -> bool {
+    let touches = |p: &(dyn Pipeline + Send + Sync), table: &str| {
+        p.reads().contains(&table) || p.writes().contains(&table)
+    };
+    a.writes().iter().any(|table| touches(b, table))
+        || b.writes().iter().any(|table| touches(a, table))
+}
+
+/// One lock per table written by at least one pipeline.
+///
+/// A pipeline holds a read lock on the tables it reads and a write lock on the tables it writes
+/// while it processes a batch, so that conflicting pipelines are serialized while the others
+/// keep running concurrently.
+#[derive(Clone, Debug, Default)]
+pub struct TableLocks {
+    // Ordered, so that locks are always acquired in the same order and cannot deadlock.
+    locks: BTreeMap<&'static str, Arc<RwLock<()>>>,
+}
+
+/// A lock held on a table, released when dropped.
+pub enum TableGuard {
+    Read(OwnedRwLockReadGuard<()>),
+    Write(OwnedRwLockWriteGuard<()>),
+}
+
+impl TableLocks {
+    /// Creates a lock for every table in `written`.
+    pub fn new<'a>(written: impl IntoIterator<Item = &'a [&'static str]>) -> Self {
+        let mut locks = BTreeMap::new();
+        for tables in written {
+            for table in tables {
+                locks.entry(*table).or_insert_with(Default::default);
+            }
+        }
+        Self { locks }
+    }
+
+    /// Waits until the given tables can be read and written without conflicting with another
+    /// pipeline, and returns the guards to hold while doing so.
+    pub async fn acquire(
+        &self,
+        reads: &[&'static str],
+        writes: &[&'static str],
+    ) -> Vec<TableGuard> {
+        let mut guards = vec![];
+        for (table, lock) in &self.locks {
+            if writes.contains(table) {
+                guards.push(TableGuard::Write(lock.clone().write_owned().await));
+            } else if reads.contains(table) {
+                guards.push(TableGuard::Read(lock.clone().read_owned().await));
+            }
+        }
+        guards
+    }
+}
+
+/// Returns a random duration between zero and `max`, added to the sleeps of pipelines so that
+/// pipelines polling at the same interval do not all hit the database at once.
+///
+/// Jitter only ever lengthens a sleep, so a pipeline is never woken up before its
+/// [`Pipeline::ready`] interval is over.
+pub fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    // Every `RandomState` hashes with different keys, which is random enough to spread polls.
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PipelineAggregationResult;
+
+    /// A pipeline that only declares the tables it touches.
+    struct Touches {
+        reads: &'static [&'static str],
+        writes: &'static [&'static str],
+    }
+
+    #[async_trait::async_trait]
+    impl Pipeline for Touches {
+        fn ready(&self) -> bool {
+            true
+        }
+
+        fn model_name(&self) -> String {
+            String::from("Touches")
+        }
+
+        async fn process_and_save_internal(&mut self) -> PipelineAggregationResult {
+            Ok(())
+        }
+
+        async fn process_and_save_historical_data(&mut self) -> PipelineAggregationResult {
+            Ok(())
+        }
+
+        fn reads(&self) -> &[&'static str] {
+            self.reads
+        }
+
+        fn writes(&self) -> &[&'static str] {
+            self.writes
+        }
+
+        fn poll_interval(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    const FILLS: &str = "fill_events";
+    const PRICES: &str = "aggregator.prices";
+    const USER_HISTORY: &str = "aggregator.user_history";
+
+    #[test]
+    fn pipelines_touching_a_written_table_conflict() {
+        let prices = Touches {
+            reads: &[FILLS],
+            writes: &[PRICES],
+        };
+        let also_prices = Touches {
+            reads: &[],
+            writes: &[PRICES],
+        };
+        let reads_prices = Touches {
+            reads: &[PRICES],
+            writes: &[USER_HISTORY],
+        };
+        let user_history = Touches {
+            reads: &[FILLS],
+            writes: &[USER_HISTORY],
+        };
+        assert!(conflicts(&prices, &also_prices));
+        assert!(conflicts(&prices, &reads_prices));
+        assert!(conflicts(&reads_prices, &prices));
+        // Reading the same table is no conflict.
+        assert!(!conflicts(&prices, &user_history));
+    }
+
+    #[tokio::test]
+    async fn conflicting_pipelines_are_serialized() {
+        let wait = Duration::from_millis(50);
+        let locks = TableLocks::new([&[PRICES][..], &[USER_HISTORY][..]]);
+        let guards = locks.acquire(&[FILLS], &[PRICES]).await;
+        // Another writer of the prices waits, while a pipeline writing another table proceeds,
+        // and so does one reading the fills.
+        let writer = tokio::time::timeout(wait, locks.acquire(&[], &[PRICES])).await;
+        assert!(writer.is_err());
+        let other = tokio::time::timeout(wait, locks.acquire(&[FILLS], &[USER_HISTORY])).await;
+        assert!(other.is_ok());
+
+        drop(guards);
+        let writer = tokio::time::timeout(wait, locks.acquire(&[], &[PRICES])).await;
+        assert!(writer.is_ok());
+    }
+
+    #[test]
+    fn jitter_stays_within_max() {
+        let max = Duration::from_millis(250);
+        for _ in 0..1000 {
+            assert!(jitter(max) <= max);
+        }
+    }
+
+    #[test]
+    fn jitter_varies() {
+        let max = Duration::from_secs(1);
+        let first = jitter(max);
+        assert!((0..100).any(|_| jitter(max) != first));
+    }
+
+    #[test]
+    fn no_jitter() {
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+    }
+}
@@ -0,0 +1,97 @@
+//! Access to a database for the tests exercising SQL, that of `DATABASE_URL`, which must have
+//! every migration run.
+//!
+//! These tests are ignored by default, so that `cargo test` does not need a database, and are run
+//! with `cargo test -- --ignored`. They then fail if `DATABASE_URL` is not set. They only touch
+//! the database inside transactions they roll back, apart from the fixture replays, which need an
+//! empty user history like the `replay` command and are skipped otherwise.
+
+use sqlx::{Executor, PgConnection, PgPool, Postgres, Transaction};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::replay::EVENT_DEFAULTS;
+
+/// A market no other data uses, so that the tests only see the rows they insert.
+pub const MARKET_ID: i64 = 999_999;
+
+/// Held by the tests that commit to the database, which would otherwise see each other's rows.
+static COMMITTING: Mutex<()> = Mutex::const_new(());
+
+/// Connects to the database of `DATABASE_URL`.
+pub async fn connect() -> PgPool {
+    let database_url =
+        std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests");
+    PgPool::connect(&database_url)
+        .await
+        .expect("Could not connect to DATABASE_URL")
+}
+
+/// Opens a transaction on the database of `DATABASE_URL`, rolled back when dropped.
+pub async fn begin() -> Transaction<'static, Postgres> {
+    connect()
+        .await
+        .begin()
+        .await
+        .expect("Could not open a transaction")
+}
+
+/// Connects to the database of `DATABASE_URL` for a test that commits, which no other such test
+/// runs alongside until the returned guard is dropped.
+pub async fn connect_exclusive() -> (PgPool, MutexGuard<'static, ()>) {
+    let guard = COMMITTING.lock().await;
+    (connect().await, guard)
+}
+
+/// Switches the transaction of `conn` to the role and search path the REST API runs queries with,
+/// so that tests of API functions also check their grants and name resolution.
+pub async fn as_web_anon(conn: &mut PgConnection) {
+    conn.execute("SET LOCAL ROLE web_anon; SET LOCAL search_path TO api, public")
+        .await
+        .expect("Could not switch to web_anon");
+}
+
+/// Inserts a row into `table`, with the columns it leaves out taken from [`EVENT_DEFAULTS`].
+pub async fn insert(conn: &mut PgConnection, table: &str, row: serde_json::Value) {
+    sqlx::query(&format!(
+        "INSERT INTO {table} \
+         SELECT * FROM jsonb_populate_record(\
+             jsonb_populate_record(NULL::{table}, $1::jsonb), $2::jsonb\
+         )"
+    ))
+    .bind(EVENT_DEFAULTS)
+    .bind(&row)
+    .execute(conn)
+    .await
+    .unwrap_or_else(|e| panic!("Could not insert {row} into {table}: {e}"));
+}
+
+/// Inserts an order into the user history, with the columns `row` leaves out set to those of an
+/// open limit order of [`MARKET_ID`] on which nothing happened yet.
+pub async fn insert_order(conn: &mut PgConnection, row: serde_json::Value) {
+    let mut order = serde_json::json!({
+        "market_id": MARKET_ID,
+        "created_at": "2024-01-01T00:00:00+00:00",
+        "integrator": "0x0",
+        "total_filled": 0,
+        "remaining_size": 1,
+        "order_status": "open",
+        "order_type": "limit",
+        "user": "0xa",
+        "direction": "bid",
+        "price": 1,
+        "custodian_id": 0,
+        "total_fees_paid_in_quote_subunits": 0,
+    });
+    order
+        .as_object_mut()
+        .unwrap()
+        .extend(row.as_object().expect("Orders are JSON objects").clone());
+    insert(conn, "aggregator.user_history", order).await;
+}
+
+/// Returns the SQLSTATE of the database error of `result`, if any.
+pub fn sqlstate<T>(result: Result<T, sqlx::Error>) -> Option<String> {
+    result
+        .err()
+        .and_then(|e| e.as_database_error()?.code().map(String::from))
+}
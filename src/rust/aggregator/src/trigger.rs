@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use sqlx::{PgConnection, PgPool};
+use sqlx_postgres::PgListener;
+use tokio::sync::Mutex;
+
+use crate::{schedule::TableLocks, Pipeline, ProcessSummary};
+
+/// The channel `api.run_pipeline` notifies with the ID of the requested run.
+pub const CHANNEL: &str = "aggregator_run";
+
+/// How long to wait before listening again after the database failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A pipeline that can be run out of band, with the tables it touches.
+pub struct Triggerable {
+    pub pipeline: Arc<Mutex<dyn Pipeline + Send + Sync>>,
+    pub reads: Vec<&'static str>,
+    pub writes: Vec<&'static str>,
+}
+
+/// Registers `pipelines` in `aggregator.pipelines`, with the tables they write (which
+/// `api.pipeline_schemas` describes), then runs each pipeline requested through
+/// `api.run_pipeline` once, recording the outcome in `aggregator.pipeline_runs`.
+///
+/// A run is claimed before it starts, so that only one aggregator instance processes it. Runs
+/// requested while no instance was listening, e.g. before startup, are claimed once listening.
+/// So are runs claimed by an instance that died before finishing them, which is told by the
+/// connection it claimed them with being gone.
+///
+/// Errors are logged and listening is retried, so that a database hiccup never stops the
+/// aggregator.
+pub async fn listen(
+    pool: PgPool,
+    pipelines: HashMap<String, Triggerable>,
+    table_locks: TableLocks,
+) -> ! {
+    loop {
+        if let Err(e) = serve(&pool, &pipelines, &table_locks).await {
+            tracing::warn!(error = %e, "Could not serve pipeline run requests, retrying.");
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+async fn serve(
+    pool: &PgPool,
+    pipelines: &HashMap<String, Triggerable>,
+    table_locks: &TableLocks,
+) -> Result<(), sqlx::Error> {
+    let names: Vec<String> = pipelines.keys().cloned().collect();
+    let writes: Vec<String> = names
+        .iter()
+        .map(|name| pipelines[name].writes.join(","))
+        .collect();
+    sqlx::query_file!(
+        "sqlx_queries/trigger/register_pipelines.sql",
+        &names,
+        &writes
+    )
+    .execute(pool)
+    .await?;
+
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANNEL).await?;
+    // Runs are claimed and finished on a connection of their own, which stays open for as long as
+    // this instance serves them.
+    let mut conn = pool.acquire().await?;
+    loop {
+        // Every pending run is claimed on each wake-up, which also catches up on notifications
+        // missed while the listener was not connected.
+        while let Some(run) = sqlx::query_file!("sqlx_queries/trigger/claim_next_run.sql", &names)
+            .fetch_optional(&mut *conn)
+            .await?
+        {
+            run_pipeline(
+                &mut conn,
+                run.id,
+                &run.model_name,
+                &pipelines[&run.model_name],
+                table_locks,
+            )
+            .await?;
+        }
+        // `None` means the connection was lost, it is reconnected by the next call.
+        if listener.try_recv().await?.is_none() {
+            tracing::warn!("Lost the pipeline run listener connection, reconnecting.");
+        }
+    }
+}
+
+async fn run_pipeline(
+    conn: &mut PgConnection,
+    id: i64,
+    name: &str,
+    triggerable: &Triggerable,
+    table_locks: &TableLocks,
+) -> Result<(), sqlx::Error> {
+    tracing::info!(id, pipeline = %name, "Running pipeline on request.");
+    let result = {
+        let mut pipeline = triggerable.pipeline.lock().await;
+        let _guards = table_locks
+            .acquire(&triggerable.reads, &triggerable.writes)
+            .await;
+        let start = Instant::now();
+        let result = pipeline.process_and_save_internal().await;
+        if result.is_ok() {
+            let events = pipeline.processed_events();
+            let elapsed = start.elapsed();
+            pipeline
+                .after_commit(ProcessSummary { events, elapsed })
+                .await;
+        }
+        result
+    };
+    let error = result.err().map(|e| e.to_string());
+    match &error {
+        Some(error) => tracing::error!(id, %error, "Requested pipeline run failed."),
+        None => tracing::info!(id, "Requested pipeline run finished."),
+    }
+    sqlx::query_file!("sqlx_queries/trigger/finish_run.sql", id, error)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Acquire, Executor};
+
+    use super::*;
+
+    /// Requests a run of the `Test` pipeline, started by the backend `claimed_by` (an SQL
+    /// expression, `NULL` for a run nothing started yet), and returns the run the next claim
+    /// picks.
+    async fn claim(claimed_by: &str) -> Option<i64> {
+        let pool = PgPool::connect(
+            &std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests"),
+        )
+        .await
+        .unwrap();
+        let mut tx = pool.begin().await.unwrap();
+        let conn = tx.acquire().await.unwrap();
+        conn.execute("INSERT INTO aggregator.pipelines (model_name) VALUES ('Test')")
+            .await
+            .unwrap();
+        let id: i64 = sqlx::query_scalar(&format!(
+            "INSERT INTO aggregator.pipeline_runs (model_name, started_at, claimed_by) \
+             VALUES ('Test', CASE WHEN {claimed_by} IS NOT NULL THEN CURRENT_TIMESTAMP END, \
+             {claimed_by}) RETURNING id"
+        ))
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        let claimed = sqlx::query_file!(
+            "sqlx_queries/trigger/claim_next_run.sql",
+            &[String::from("Test")]
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .unwrap()
+        .map(|run| run.id);
+        assert!(claimed.is_none() || claimed == Some(id));
+        claimed
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn requested_run_is_claimed() {
+        assert!(claim("NULL").await.is_some());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn run_of_a_live_instance_is_not_claimed_again() {
+        assert_eq!(claim("pg_backend_pid()").await, None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn run_of_a_dead_instance_is_claimed_again() {
+        // Backend PIDs are positive.
+        assert!(claim("0").await.is_some());
+    }
+
+    /// A pipeline whose runs fail if `fail` is set, recording what its
+    /// [`Pipeline::after_commit`] is called with.
+    struct Recording {
+        fail: bool,
+        summaries: Arc<std::sync::Mutex<Vec<ProcessSummary>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Pipeline for Recording {
+        fn ready(&self) -> bool {
+            true
+        }
+
+        fn model_name(&self) -> String {
+            String::from("Test")
+        }
+
+        async fn process_and_save_internal(&mut self) -> crate::PipelineAggregationResult {
+            if self.fail {
+                return Err(crate::PipelineError::ProcessingError(anyhow::anyhow!(
+                    "run failed"
+                )));
+            }
+            Ok(())
+        }
+
+        async fn process_and_save_historical_data(&mut self) -> crate::PipelineAggregationResult {
+            Ok(())
+        }
+
+        fn poll_interval(&self) -> Option<Duration> {
+            None
+        }
+
+        async fn after_commit(&mut self, summary: ProcessSummary) {
+            self.summaries.lock().unwrap().push(summary);
+        }
+
+        fn processed_events(&self) -> Option<u64> {
+            Some(7)
+        }
+    }
+
+    /// Runs a [`Recording`] pipeline on request, and returns the error recorded for the run and
+    /// the number of events of each call to [`Pipeline::after_commit`].
+    async fn run(fail: bool) -> (Option<String>, Vec<Option<u64>>) {
+        let pool = PgPool::connect(
+            &std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests"),
+        )
+        .await
+        .unwrap();
+        let mut tx = pool.begin().await.unwrap();
+        let conn = tx.acquire().await.unwrap();
+        conn.execute("INSERT INTO aggregator.pipelines (model_name) VALUES ('Test')")
+            .await
+            .unwrap();
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO aggregator.pipeline_runs (model_name) VALUES ('Test') RETURNING id",
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        let summaries = Arc::default();
+        let triggerable = Triggerable {
+            pipeline: Arc::new(Mutex::new(Recording {
+                fail,
+                summaries: Arc::clone(&summaries),
+            })),
+            reads: vec![],
+            writes: vec![],
+        };
+        run_pipeline(conn, id, "Test", &triggerable, &TableLocks::default())
+            .await
+            .unwrap();
+        let error: Option<String> =
+            sqlx::query_scalar("SELECT error FROM aggregator.pipeline_runs WHERE id = $1")
+                .bind(id)
+                .fetch_one(&mut *conn)
+                .await
+                .unwrap();
+        let events = summaries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|summary: &ProcessSummary| summary.events)
+            .collect();
+        (error, events)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn after_commit_follows_a_successful_run() {
+        assert_eq!(run(false).await, (None, vec![Some(7)]));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn after_commit_is_skipped_after_a_failed_run() {
+        let (error, events) = run(true).await;
+        assert!(error.unwrap().contains("run failed"));
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn finished_run_records_the_watermark() {
+        let pool = PgPool::connect(
+            &std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests"),
+        )
+        .await
+        .unwrap();
+        let mut tx = pool.begin().await.unwrap();
+        let conn = tx.acquire().await.unwrap();
+        // As committed by the run.
+        conn.execute(
+            "INSERT INTO aggregator.pipelines (model_name) VALUES ('UserHistory') \
+             ON CONFLICT DO NOTHING; \
+             DELETE FROM aggregator.user_history_last_indexed_txn; \
+             INSERT INTO aggregator.user_history_last_indexed_txn VALUES (1234)",
+        )
+        .await
+        .unwrap();
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO aggregator.pipeline_runs (model_name) VALUES ('UserHistory') \
+             RETURNING id",
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        let triggerable = Triggerable {
+            pipeline: Arc::new(Mutex::new(Recording {
+                fail: false,
+                summaries: Arc::default(),
+            })),
+            reads: vec![],
+            writes: vec![],
+        };
+        run_pipeline(
+            conn,
+            id,
+            "UserHistory",
+            &triggerable,
+            &TableLocks::default(),
+        )
+        .await
+        .unwrap();
+        let txn_version: Option<i64> = sqlx::query_scalar(
+            "SELECT txn_version::int8 FROM aggregator.pipeline_runs WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(txn_version, Some(1234));
+    }
+}
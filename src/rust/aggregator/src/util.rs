@@ -1,7 +1,10 @@
+use std::time::{Duration, Instant};
+
 use anyhow::anyhow;
-use bigdecimal::BigDecimal;
+use bigdecimal::{num_bigint::ToBigInt, BigDecimal, ToPrimitive};
 use sqlx::{Executor, Pool, Transaction};
 use sqlx_postgres::Postgres;
+use tracing_subscriber::util::SubscriberInitExt;
 
 use crate::{PipelineAggregationResult, PipelineError};
 
@@ -9,6 +12,55 @@ pub fn to_pipeline_error<T: Into<anyhow::Error>>(e: T) -> PipelineError {
     PipelineError::ProcessingError(anyhow!(e))
 }
 
+/// Converts `value` to a `u128`, failing with an error naming `field` and the offending value if
+/// it is fractional, negative or too large.
+pub fn decimal_to_u128(field: &str, value: &BigDecimal) -> Result<u128, PipelineError> {
+    if !value.is_integer() {
+        return Err(PipelineError::ProcessingError(anyhow!(
+            "{field} {value} is not an integer"
+        )));
+    }
+    value
+        .to_bigint()
+        .and_then(|value| value.to_u128())
+        .ok_or(PipelineError::ProcessingError(anyhow!(
+            "{field} {value} does not fit in an unsigned 128 bit integer"
+        )))
+}
+
+/// Returns `true` if `name` can be used as the schema of the event tables: a lowercase
+/// unquoted Postgres identifier.
+pub fn is_valid_schema_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some('a'..='z' | '_'))
+        && chars.all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_'))
+        && name.len() <= 63
+}
+
+/// Makes unqualified table names in `transaction` resolve to `schema` first, then to `public`,
+/// so that queries naming the event tables read those of another deployment.
+///
+/// Does nothing if `schema` is `None`. The setting ends with the transaction.
+pub async fn use_source_schema(
+    transaction: &mut Transaction<'_, Postgres>,
+    schema: Option<&str>,
+) -> PipelineAggregationResult {
+    let Some(schema) = schema else {
+        return Ok(());
+    };
+    if !is_valid_schema_name(schema) {
+        return Err(PipelineError::NotProcessable(format!(
+            "invalid source schema {schema:?}"
+        )));
+    }
+    sqlx::query("SELECT set_config('search_path', format('%I, public', $1::text), true)")
+        .bind(schema)
+        .execute(&mut **transaction)
+        .await
+        .map_err(to_pipeline_error)?;
+    Ok(())
+}
+
 pub async fn create_repeatable_read_transaction<'a>(
     pool: &Pool<Postgres>,
 ) -> Result<Transaction<'a, Postgres>, PipelineError> {
@@ -20,7 +72,375 @@ pub async fn create_repeatable_read_transaction<'a>(
     Ok(transaction)
 }
 
+/// Creates a repeatable read transaction holding the advisory lock of the pipeline `name`.
+///
+/// The lock is released when the transaction ends. If another aggregator instance holds it,
+/// `None` is returned and that instance is left to process the cycle, so that running duplicate
+/// instances (e.g. during a deploy) is safe. Pipelines then return [`PipelineError::Locked`].
+pub async fn create_locked_transaction<'a>(
+    pool: &Pool<Postgres>,
+    name: &str,
+) -> Result<Option<Transaction<'a, Postgres>>, PipelineError> {
+    let mut transaction = create_repeatable_read_transaction(pool).await?;
+    let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_xact_lock(hashtext($1))")
+        .bind(name)
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(to_pipeline_error)?;
+    if !locked {
+        tracing::info!(
+            pipeline = name,
+            "Pipeline is locked by another instance, skipping."
+        );
+        return Ok(None);
+    }
+    Ok(Some(transaction))
+}
+
 pub async fn commit_transaction<'a>(tx: Transaction<'a, Postgres>) -> PipelineAggregationResult {
     tx.commit().await.map_err(to_pipeline_error)?;
     Ok(())
 }
+
+/// Measures a step of a pipeline, to point at the culprit when aggregation slows down.
+pub struct StepTimer {
+    step: &'static str,
+    start: Instant,
+}
+
+impl StepTimer {
+    pub fn start(step: &'static str) -> Self {
+        Self {
+            step,
+            start: Instant::now(),
+        }
+    }
+
+    /// Logs a warning if the step took longer than `threshold`, along with the number of rows
+    /// it processed.
+    pub fn finish(self, threshold: Duration, rows: usize) {
+        let elapsed = self.start.elapsed();
+        if elapsed > threshold {
+            tracing::warn!(
+                step = self.step,
+                elapsed_ms = elapsed.as_millis(),
+                threshold_ms = threshold.as_millis(),
+                rows,
+                "Slow pipeline step."
+            );
+        }
+    }
+}
+
+/// Exponential backoff used while waiting for the database to come back.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            current: initial,
+        }
+    }
+
+    /// Returns the delay to wait before the next attempt, and doubles it for the one after,
+    /// up to the configured maximum.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    /// Starts over from the initial delay.
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+/// Pings the database until it answers, sleeping according to `backoff` between attempts.
+pub async fn wait_for_database(pool: &Pool<Postgres>, backoff: &mut Backoff) {
+    loop {
+        let delay = backoff.next_delay();
+        tracing::warn!(delay_ms = delay.as_millis(), "Waiting for the database.");
+        tokio::time::sleep(delay).await;
+        match pool.execute("SELECT 1").await {
+            Ok(_) => break,
+            Err(e) => tracing::warn!(error = %e, "Database is still unreachable."),
+        }
+    }
+    backoff.reset();
+}
+
+/// Installs the global log subscriber, logging events up to `max_level`.
+///
+/// Logs are human-readable by default. Set `LOG_FORMAT=json` to log one JSON object per event
+/// instead, with the fields of the current span and of its parents (e.g. the pipeline name),
+/// for log aggregators to index. Any other value is warned about and logs are human-readable, so
+/// that a typo in the deployment does not keep the aggregator from starting.
+pub fn init_logging(max_level: tracing::Level) {
+    let format = std::env::var("LOG_FORMAT").ok();
+    match format.as_deref() {
+        None | Some("pretty") => tracing_subscriber::fmt().with_max_level(max_level).init(),
+        Some("json") => json_logs(max_level, std::io::stdout).init(),
+        Some(format) => {
+            tracing_subscriber::fmt().with_max_level(max_level).init();
+            tracing::warn!(
+                format,
+                "Invalid value for LOG_FORMAT, must be either json or pretty. Logging in the \
+                 pretty format."
+            );
+        }
+    }
+}
+
+/// The subscriber of `LOG_FORMAT=json`, writing one JSON object per event to `writer`.
+fn json_logs<W>(
+    max_level: tracing::Level,
+    writer: W,
+) -> impl tracing::Subscriber + Send + Sync + 'static
+where
+    W: for<'w> tracing_subscriber::fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt()
+        .with_max_level(max_level)
+        .with_writer(writer)
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn decimal(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(500));
+        let delays: Vec<u128> = (0..5).map(|_| backoff.next_delay().as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+    }
+
+    #[test]
+    fn backoff_reset() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(10));
+        backoff.next_delay();
+        backoff.next_delay();
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn backoff_does_not_overflow() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        for _ in 0..100 {
+            assert!(backoff.next_delay() <= Duration::from_secs(60));
+        }
+    }
+
+    #[test]
+    fn decimal_to_u128_bounds() {
+        assert_eq!(decimal_to_u128("x", &decimal("0")).unwrap(), 0);
+        assert_eq!(
+            decimal_to_u128("x", &decimal(&u64::MAX.to_string())).unwrap(),
+            u128::from(u64::MAX)
+        );
+        assert_eq!(
+            decimal_to_u128("x", &decimal(&u128::MAX.to_string())).unwrap(),
+            u128::MAX
+        );
+    }
+
+    #[test]
+    fn decimal_to_u128_ignores_zero_fraction() {
+        assert_eq!(decimal_to_u128("x", &decimal("42.000")).unwrap(), 42);
+    }
+
+    #[test]
+    fn decimal_to_u128_rejects_invalid_values() {
+        // 2^128
+        let too_large = "340282366920938463463374607431768211456";
+        for value in ["-1", "0.5", "1.5", too_large] {
+            let error = decimal_to_u128("order_id", &decimal(value)).unwrap_err();
+            let message = error.to_string();
+            assert!(
+                message.contains("order_id") && message.contains(value),
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn schema_names_are_lowercase_identifiers() {
+        for name in ["staging", "_replay", "prod_2"] {
+            assert!(is_valid_schema_name(name), "{name}");
+        }
+        let too_long = "a".repeat(64);
+        for name in [
+            "",
+            "Staging",
+            "2prod",
+            "a b",
+            "a;drop schema public",
+            "\"a\"",
+            &too_long,
+        ] {
+            assert!(!is_valid_schema_name(name), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn event_tables_are_read_from_the_source_schema() {
+        let database_url =
+            std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests");
+        let pool = Pool::<Postgres>::connect(&database_url).await.unwrap();
+        let mut transaction = pool.begin().await.unwrap();
+        transaction
+            .execute(
+                "CREATE SCHEMA source_test; \
+                 CREATE TABLE source_test.fill_events (LIKE public.fill_events)",
+            )
+            .await
+            .unwrap();
+
+        use_source_schema(&mut transaction, Some("source_test"))
+            .await
+            .unwrap();
+        let mut schemas = vec![];
+        for table in ["fill_events", "cancel_order_events"] {
+            let schema: String = sqlx::query_scalar(
+                "SELECT relnamespace::regnamespace::text FROM pg_class WHERE oid = to_regclass($1)",
+            )
+            .bind(table)
+            .fetch_one(&mut *transaction)
+            .await
+            .unwrap();
+            schemas.push(schema);
+        }
+        // Tables missing from the schema are still read from `public`.
+        assert_eq!(schemas, ["source_test", "public"]);
+
+        let result = use_source_schema(&mut transaction, Some("source_test, public")).await;
+        assert!(matches!(result, Err(PipelineError::NotProcessable(_))));
+    }
+
+    /// Collects what a subscriber logs, one JSON object per line.
+    #[derive(Clone, Default)]
+    struct Logs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs a step taking `delay` with a slow step threshold of 20 ms, and returns the logged
+    /// events.
+    fn time_step(delay: Duration) -> Vec<serde_json::Value> {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let timer = StepTimer::start("fills");
+            std::thread::sleep(delay);
+            timer.finish(Duration::from_millis(20), 3);
+        });
+        let logs = logs.0.lock().unwrap();
+        serde_json::Deserializer::from_slice(&logs)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn slow_step_is_logged() {
+        let events = time_step(Duration::from_millis(50));
+        assert_eq!(events.len(), 1, "{events:?}");
+        let fields = &events[0]["fields"];
+        assert_eq!(events[0]["level"], "WARN");
+        assert_eq!(fields["message"], "Slow pipeline step.");
+        assert_eq!(fields["step"], "fills");
+        assert_eq!(fields["rows"], 3);
+        // Milliseconds are `u128`s, which tracing records as strings.
+        assert_eq!(fields["threshold_ms"], "20");
+        let elapsed_ms: u128 = fields["elapsed_ms"].as_str().unwrap().parse().unwrap();
+        assert!(elapsed_ms >= 50, "{fields}");
+    }
+
+    #[test]
+    fn fast_step_is_not_logged() {
+        assert!(time_step(Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn json_logs_hold_the_fields_of_the_event_and_its_spans() {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = json_logs(tracing::Level::INFO, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let _pipeline = tracing::info_span!("pipeline", model_name = "Prices").entered();
+            tracing::info!(rows = 3, "Processed a batch.");
+            tracing::debug!("Not logged.");
+        });
+        let logs = logs.0.lock().unwrap();
+        let events: Vec<serde_json::Value> = serde_json::Deserializer::from_slice(&logs)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(events.len(), 1, "{events:?}");
+        let event = &events[0];
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["fields"]["message"], "Processed a batch.");
+        assert_eq!(event["fields"]["rows"], 3);
+        assert_eq!(event["span"]["model_name"], "Prices");
+        assert_eq!(event["spans"][0]["name"], "pipeline");
+    }
+
+    /// Returns whether a new transaction takes the lock of the pipeline `name`, rolling it back.
+    async fn takes_lock(pool: &Pool<Postgres>, name: &str) -> bool {
+        create_locked_transaction(pool, name)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn only_one_instance_holds_the_lock_of_a_pipeline() {
+        let database_url =
+            std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for database tests");
+        let pool = Pool::<Postgres>::connect(&database_url).await.unwrap();
+        let name = "only_one_instance_holds_the_lock_of_a_pipeline";
+
+        let first = create_locked_transaction(&pool, name).await.unwrap();
+        assert!(first.is_some());
+        assert!(!takes_lock(&pool, name).await);
+        // Other pipelines proceed.
+        assert!(takes_lock(&pool, &format!("{name}_other")).await);
+
+        // The lock is released when the transaction ends.
+        commit_transaction(first.unwrap()).await.unwrap();
+        assert!(takes_lock(&pool, name).await);
+    }
+}
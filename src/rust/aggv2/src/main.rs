@@ -192,9 +192,17 @@ impl From<bool> for Direction {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter("aggv2=TRACE")
-        .init();
+    // Same as the aggregator: human-readable by default, LOG_FORMAT=json for log aggregators.
+    let subscriber = tracing_subscriber::fmt().with_env_filter("aggv2=TRACE");
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+        Ok("pretty") | Err(_) => subscriber.init(),
+        Ok(_) => panic!("Invalid value for LOG_FORMAT, must be either json or pretty."),
+    }
     feed::run().await?;
     Ok(())
 }
@@ -0,0 +1,42 @@
+-- Your SQL goes here
+
+-- Parameters:
+-- * `market_id`: The market ID that trades are queried for
+-- * `after_txn_version`: Optional cursor, only trades older than it are returned
+-- * `after_event_idx`: Optional cursor, used together with `after_txn_version`
+--
+-- Returns:
+-- * The trades of the market, newest first, where `side` is the side of the taker
+CREATE FUNCTION api.trades (
+    market_id numeric(20,0),
+    after_txn_version numeric(20,0) DEFAULT NULL,
+    after_event_idx numeric(20,0) DEFAULT NULL
+) RETURNS TABLE (
+    txn_version numeric(20,0),
+    event_idx numeric(20,0),
+    "time" timestamptz,
+    price numeric(20,0),
+    "size" numeric(20,0),
+    side text
+) AS $$
+    SELECT
+        txn_version,
+        event_idx,
+        "time",
+        price,
+        "size",
+        -- The taker buys from an ask maker and sells to a bid maker.
+        CASE
+            WHEN maker_side = true THEN 'buy'
+            ELSE 'sell'
+        END AS side
+    FROM fill_events
+    WHERE fill_events.market_id = $1
+    -- Fills are emitted to both the maker and the taker, keep only one of them.
+    AND emit_address = maker_address
+    AND (
+        $2 IS NULL
+        OR (txn_version, event_idx) < ($2, COALESCE($3, 0))
+    )
+    ORDER BY txn_version DESC, event_idx DESC;
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP VIEW api.integrator_volume;
+DROP TABLE aggregator.integrator_volume_last_indexed_txn;
+DROP TABLE aggregator.integrator_volume;
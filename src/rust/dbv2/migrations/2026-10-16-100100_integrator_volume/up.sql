@@ -0,0 +1,31 @@
+-- Your SQL goes here
+CREATE TABLE aggregator.integrator_volume (
+    "integrator" TEXT NOT NULL,
+    "market_id" NUMERIC(20,0) NOT NULL,
+    "day" DATE NOT NULL,
+    "maker_volume_in_quote_subunits" NUMERIC(39,0) NOT NULL,
+    "taker_volume_in_quote_subunits" NUMERIC(39,0) NOT NULL,
+    "taker_fees_in_quote_subunits" NUMERIC(39,0) NOT NULL,
+    PRIMARY KEY ("integrator", "market_id", "day")
+);
+
+
+CREATE TABLE aggregator.integrator_volume_last_indexed_txn (
+    "txn_version" NUMERIC(20,0) NOT NULL
+);
+
+
+INSERT INTO aggregator.integrator_volume_last_indexed_txn
+VALUES (0);
+
+
+CREATE VIEW api.integrator_volume AS
+SELECT * FROM aggregator.integrator_volume;
+
+
+GRANT SELECT ON api.integrator_volume TO web_anon;
+
+
+GRANT SELECT ON aggregator.integrator_volume TO grafana;
+GRANT SELECT ON aggregator.integrator_volume_last_indexed_txn TO grafana;
+GRANT SELECT ON api.integrator_volume TO grafana;
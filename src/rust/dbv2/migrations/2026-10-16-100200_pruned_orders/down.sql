@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.get_order;
+
+
+DROP FUNCTION aggregator.prune_user_history;
+
+
+DROP VIEW api.pruned_orders;
+
+
+DROP TABLE aggregator.pruned_orders;
@@ -0,0 +1,92 @@
+-- Your SQL goes here
+CREATE TABLE aggregator.pruned_orders (
+  market_id NUMERIC(20,0) NOT NULL,
+  order_id NUMERIC(39,0) NOT NULL,
+  pruned_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (market_id, order_id)
+);
+
+
+GRANT
+SELECT
+  ON aggregator.pruned_orders TO grafana;
+
+
+CREATE VIEW api.pruned_orders AS
+SELECT
+  *
+FROM
+  aggregator.pruned_orders;
+
+
+GRANT
+SELECT
+  ON api.pruned_orders TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.pruned_orders TO grafana;
+
+
+-- Parameters:
+-- * `before`: Orders closed or cancelled before this time are pruned
+--
+-- Returns:
+-- * The number of pruned orders
+--
+-- Open orders are never pruned. Every pruned order leaves a marker in
+-- `aggregator.pruned_orders` so that it can be told apart from an unknown one.
+CREATE FUNCTION aggregator.prune_user_history (
+  "before" timestamptz
+) RETURNS bigint AS $$
+DECLARE
+  pruned bigint;
+BEGIN
+  WITH deleted AS (
+    DELETE FROM aggregator.user_history
+    WHERE order_status != 'open'
+    AND COALESCE(last_updated_at, created_at) < $1
+    RETURNING market_id, order_id
+  ), inserted AS (
+    INSERT INTO aggregator.pruned_orders (market_id, order_id)
+    SELECT market_id, order_id FROM deleted
+    ON CONFLICT DO NOTHING
+  )
+  SELECT COUNT(*) INTO pruned FROM deleted;
+  RETURN pruned;
+END;
+$$ LANGUAGE plpgsql;
+
+
+-- Parameters:
+-- * `market_id`: The market ID of the order
+-- * `order_id`: The order ID of the order
+--
+-- Returns:
+-- * The order
+--
+-- Raises a 410 if the order has been pruned and a 404 if it never existed.
+CREATE FUNCTION api.get_order (
+  market_id numeric(20,0),
+  order_id numeric(39,0)
+) RETURNS SETOF api.orders AS $$
+BEGIN
+  RETURN QUERY
+  SELECT *
+  FROM api.orders
+  WHERE orders.market_id = $1
+  AND orders.order_id = $2;
+  IF NOT FOUND THEN
+    IF EXISTS (
+      SELECT
+      FROM api.pruned_orders
+      WHERE pruned_orders.market_id = $1
+      AND pruned_orders.order_id = $2
+    ) THEN
+      RAISE sqlstate 'PT410' USING message = 'Order has been pruned';
+    END IF;
+    RAISE sqlstate 'PT404' USING message = 'Order not found';
+  END IF;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,41 @@
+-- Your SQL goes here
+ALTER TABLE aggregator.prices
+ADD COLUMN "open" NUMERIC(20,0),
+ADD COLUMN "high" NUMERIC(20,0),
+ADD COLUMN "low" NUMERIC(20,0),
+ADD COLUMN "close" NUMERIC(20,0);
+
+
+WITH ohlc AS (
+    SELECT
+        market_id,
+        date_trunc('minute', "time") AS start_time_1m_period,
+        FIRST(price ORDER BY txn_version, event_idx) AS "open",
+        MAX(price) AS "high",
+        MIN(price) AS "low",
+        LAST(price ORDER BY txn_version, event_idx) AS "close"
+    FROM fill_events
+    WHERE emit_address = maker_address
+    AND txn_version <= COALESCE((SELECT * FROM aggregator.prices_last_indexed_txn), 0)
+    GROUP BY date_trunc('minute', "time"), market_id
+)
+UPDATE aggregator.prices
+SET
+    "open" = ohlc."open",
+    "high" = ohlc."high",
+    "low" = ohlc."low",
+    "close" = ohlc."close"
+FROM ohlc
+WHERE prices.market_id = ohlc.market_id
+AND prices.start_time_1m_period = ohlc.start_time_1m_period;
+
+
+ALTER TABLE aggregator.prices
+ALTER COLUMN "open" SET NOT NULL,
+ALTER COLUMN "high" SET NOT NULL,
+ALTER COLUMN "low" SET NOT NULL,
+ALTER COLUMN "close" SET NOT NULL;
+
+
+CREATE OR REPLACE VIEW api.prices AS
+SELECT * FROM aggregator.prices;
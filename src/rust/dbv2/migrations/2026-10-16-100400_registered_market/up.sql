@@ -0,0 +1,93 @@
+-- Your SQL goes here
+
+-- Parameters:
+-- * `market_id`: The market ID to look up
+--
+-- Returns:
+-- * The registration of the market, which carries its lot size and tick size
+--
+-- Raises a 404 if the market is not registered, so that every endpoint taking
+-- a market ID rejects unknown markets the same way.
+CREATE FUNCTION api.registered_market (
+    market_id numeric(20,0)
+) RETURNS SETOF api.market_registration_events AS $$
+BEGIN
+    RETURN QUERY
+    SELECT *
+    FROM api.market_registration_events AS m
+    WHERE m.market_id = $1;
+    IF NOT FOUND THEN
+        RAISE sqlstate 'PT404' USING message = 'Market not found';
+    END IF;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+
+-- Parameters:
+-- * `market_id`: The market ID to check
+--
+-- Returns:
+-- * True, raising a 404 like `api.registered_market` if the market is not
+--   registered
+--
+-- Meant for the `WHERE` clause of SQL functions, which stay inlinable unlike
+-- plpgsql ones. The call only depends on the parameters, so it is run once
+-- before any row is read.
+CREATE FUNCTION api.require_registered_market (
+    market_id numeric(20,0)
+) RETURNS boolean AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    RETURN true;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+
+DROP FUNCTION api.trades;
+
+
+-- Parameters:
+-- * `market_id`: The market ID that trades are queried for
+-- * `after_txn_version`: Optional cursor, only trades older than it are returned
+-- * `after_event_idx`: Optional cursor, used together with `after_txn_version`
+--
+-- Returns:
+-- * The trades of the market, newest first, where `side` is the side of the taker
+--
+-- Written in SQL rather than plpgsql so that it gets inlined: the `limit` of
+-- the request then stops the walk of the fills early instead of sorting all of
+-- them. The market is checked in the `WHERE` clause instead.
+CREATE FUNCTION api.trades (
+    market_id numeric(20,0),
+    after_txn_version numeric(20,0) DEFAULT NULL,
+    after_event_idx numeric(20,0) DEFAULT NULL
+) RETURNS TABLE (
+    txn_version numeric(20,0),
+    event_idx numeric(20,0),
+    "time" timestamptz,
+    price numeric(20,0),
+    "size" numeric(20,0),
+    side text
+) AS $$
+    SELECT
+        f.txn_version,
+        f.event_idx,
+        f."time",
+        f.price,
+        f."size",
+        -- The taker buys from an ask maker and sells to a bid maker.
+        CASE
+            WHEN f.maker_side = true THEN 'buy'
+            ELSE 'sell'
+        END AS side
+    FROM fill_events AS f
+    WHERE api.require_registered_market($1)
+    AND f.market_id = $1
+    -- Fills are emitted to both the maker and the taker, keep only one of them.
+    AND f.emit_address = f.maker_address
+    AND (
+        $2 IS NULL
+        OR (f.txn_version, f.event_idx) < ($2, COALESCE($3, 0))
+    )
+    ORDER BY f.txn_version DESC, f.event_idx DESC;
+$$ LANGUAGE SQL STABLE;
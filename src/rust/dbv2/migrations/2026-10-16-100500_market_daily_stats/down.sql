@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.market_daily_stats;
+
+
+DROP FUNCTION api.validate_time_range;
@@ -0,0 +1,84 @@
+-- Your SQL goes here
+
+-- Parameters:
+-- * `start_time`: Start of the time range
+-- * `end_time`: End of the time range
+--
+-- Raises a 400 if the time range is empty or reversed. Meant to be shared by
+-- every endpoint taking a time range.
+CREATE FUNCTION api.validate_time_range (
+    start_time timestamptz,
+    end_time timestamptz
+) RETURNS void AS $$
+BEGIN
+    IF $1 IS NULL OR $2 IS NULL THEN
+        RAISE sqlstate '22023' USING message = 'start_time and end_time are required';
+    END IF;
+    IF $1 >= $2 THEN
+        RAISE sqlstate '22023' USING message = 'start_time must be before end_time';
+    END IF;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+
+-- Parameters:
+-- * `market_id`: The market ID to compute the statistics of
+-- * `start_time`: Start of the time range (inclusive)
+-- * `end_time`: End of the time range (exclusive)
+-- * `fill_gaps`: If true, days without fills are returned with zeros
+--
+-- Returns:
+-- * One row per UTC day with fills, where volumes are in lots and ticks, and
+--   `unique_traders` is the number of distinct takers
+CREATE FUNCTION api.market_daily_stats (
+    market_id numeric(20,0),
+    start_time timestamptz,
+    end_time timestamptz,
+    fill_gaps boolean DEFAULT false
+) RETURNS TABLE (
+    "date" date,
+    trade_count bigint,
+    base_volume numeric,
+    quote_volume numeric,
+    vwap numeric,
+    unique_traders bigint
+) AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    PERFORM api.validate_time_range($2, $3);
+    RETURN QUERY
+    WITH stats AS (
+        SELECT
+            (f."time" AT TIME ZONE 'UTC')::date AS "date",
+            COUNT(*) AS trade_count,
+            SUM(f."size") AS base_volume,
+            SUM(f."size" * f.price) AS quote_volume,
+            COUNT(DISTINCT f.taker_address) AS unique_traders
+        FROM fill_events AS f
+        WHERE f.market_id = $1
+        -- Fills are emitted to both the maker and the taker, keep only one of them.
+        AND f.emit_address = f.maker_address
+        AND f."time" >= $2
+        AND f."time" < $3
+        GROUP BY 1
+    ), days AS (
+        SELECT d::date AS "date"
+        FROM generate_series(
+            ($2 AT TIME ZONE 'UTC')::date,
+            (($3 - interval '1 microsecond') AT TIME ZONE 'UTC')::date,
+            interval '1 day'
+        ) AS d
+        WHERE $4
+    )
+    SELECT
+        COALESCE(stats."date", days."date"),
+        COALESCE(stats.trade_count, 0),
+        COALESCE(stats.base_volume, 0),
+        COALESCE(stats.quote_volume, 0),
+        COALESCE(stats.quote_volume / stats.base_volume, 0),
+        COALESCE(stats.unique_traders, 0)
+    FROM stats
+    FULL JOIN days ON days."date" = stats."date"
+    ORDER BY 1;
+END;
+$$ LANGUAGE plpgsql STABLE;
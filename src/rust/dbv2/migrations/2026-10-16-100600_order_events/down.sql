@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.order_events;
+
+
+DROP INDEX cancel_order_events_market_id_order_id;
+DROP INDEX change_order_size_events_market_id_order_id;
+DROP INDEX place_swap_order_events_market_id_order_id;
+DROP INDEX place_market_order_events_market_id_order_id;
+DROP INDEX place_limit_order_events_market_id_order_id;
@@ -0,0 +1,61 @@
+-- Your SQL goes here
+CREATE INDEX place_limit_order_events_market_id_order_id ON place_limit_order_events (market_id, order_id);
+CREATE INDEX place_market_order_events_market_id_order_id ON place_market_order_events (market_id, order_id);
+CREATE INDEX place_swap_order_events_market_id_order_id ON place_swap_order_events (market_id, order_id);
+CREATE INDEX change_order_size_events_market_id_order_id ON change_order_size_events (market_id, order_id);
+CREATE INDEX cancel_order_events_market_id_order_id ON cancel_order_events (market_id, order_id);
+
+
+-- Parameters:
+-- * `market_id`: The market ID of the order
+-- * `order_id`: The order ID of the order
+--
+-- Returns:
+-- * The lifecycle events of the order in the order the aggregator applies them,
+--   that is by `(txn_version, event_idx)`, where:
+--   * `placed` carries the placed size (`max_base` for swaps) and price
+--   * `filled` carries the filled size and the fill price
+--   * `size_changed` carries the new size
+--   * `cancelled` carries the cancel reason
+CREATE FUNCTION api.order_events (
+    market_id numeric(20,0),
+    order_id numeric(39,0)
+) RETURNS TABLE (
+    txn_version numeric(20,0),
+    event_idx numeric(20,0),
+    "time" timestamptz,
+    event_type text,
+    "size" numeric(20,0),
+    price numeric(20,0),
+    cancel_reason smallint
+) AS $$
+    SELECT * FROM (
+        SELECT txn_version, event_idx, "time", 'placed', "size", price, NULL::smallint
+        FROM place_limit_order_events
+        WHERE place_limit_order_events.market_id = $1 AND place_limit_order_events.order_id = $2
+        UNION ALL
+        SELECT txn_version, event_idx, "time", 'placed', "size", NULL, NULL
+        FROM place_market_order_events
+        WHERE place_market_order_events.market_id = $1 AND place_market_order_events.order_id = $2
+        UNION ALL
+        SELECT txn_version, event_idx, "time", 'placed', max_base, limit_price, NULL
+        FROM place_swap_order_events
+        WHERE place_swap_order_events.market_id = $1 AND place_swap_order_events.order_id = $2
+        UNION ALL
+        SELECT txn_version, event_idx, "time", 'filled', "size", price, NULL
+        FROM fill_events
+        WHERE fill_events.market_id = $1
+        AND (maker_order_id = $2 OR taker_order_id = $2)
+        -- Fills are emitted to both the maker and the taker, keep only one of them.
+        AND emit_address = maker_address
+        UNION ALL
+        SELECT txn_version, event_idx, "time", 'size_changed', new_size, NULL, NULL
+        FROM change_order_size_events
+        WHERE change_order_size_events.market_id = $1 AND change_order_size_events.order_id = $2
+        UNION ALL
+        SELECT txn_version, event_idx, "time", 'cancelled', NULL, NULL, reason
+        FROM cancel_order_events
+        WHERE cancel_order_events.market_id = $1 AND cancel_order_events.order_id = $2
+    ) AS events
+    ORDER BY 1, 2;
+$$ LANGUAGE SQL STABLE;
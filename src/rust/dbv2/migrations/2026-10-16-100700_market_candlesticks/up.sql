@@ -0,0 +1,48 @@
+-- Your SQL goes here
+
+-- Parameters:
+-- * `market_id`: The market ID to get candlesticks for
+-- * `resolution`: The resolution in seconds, one of the resolutions computed by
+--   the aggregator (60, 300, 900, 1800, 3600, 14400, 43200 or 86400)
+-- * `start_time`: Start of the time range (inclusive)
+-- * `end_time`: End of the time range (exclusive)
+--
+-- Returns:
+-- * At most 1000 candlesticks, oldest first. Periods without fills have no
+--   candlestick, page forward using the start time of the last one returned.
+CREATE FUNCTION api.market_candlesticks (
+    market_id numeric(20,0),
+    resolution int,
+    start_time timestamptz,
+    end_time timestamptz
+) RETURNS TABLE (
+    period_start_time timestamptz,
+    "open" numeric,
+    "high" numeric,
+    "low" numeric,
+    "close" numeric,
+    volume numeric
+) AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    IF $2 IS NULL OR $2 NOT IN (60, 300, 900, 1800, 3600, 14400, 43200, 86400) THEN
+        RAISE sqlstate '22023' USING message = 'Unsupported resolution';
+    END IF;
+    PERFORM api.validate_time_range($3, $4);
+    RETURN QUERY
+    SELECT
+        c.start_time,
+        c."open",
+        c."high",
+        c."low",
+        c."close",
+        c.volume
+    FROM api.candlesticks AS c
+    WHERE c.market_id = $1
+    AND c.resolution = $2
+    AND c.start_time >= $3
+    AND c.start_time < $4
+    ORDER BY c.start_time
+    LIMIT 1000;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,64 @@
+-- Your SQL goes here
+DROP FUNCTION api.trades;
+
+
+-- Parameters:
+-- * `market_id`: The market ID that trades are queried for
+-- * `after_txn_version`: Optional cursor, only trades older than it are returned
+-- * `after_event_idx`: Optional cursor, used together with `after_txn_version`
+--
+-- Returns:
+-- * The trades of the market, newest first, where `side` is the side of the taker.
+--   Only the taker pays a fee, makers pay none and get no rebate.
+--
+-- Written in SQL rather than plpgsql so that it gets inlined: the `limit` of
+-- the request then stops the walk of the fills early instead of sorting all of
+-- them. The market is checked in the `WHERE` clause instead.
+CREATE FUNCTION api.trades (
+    market_id numeric(20,0),
+    after_txn_version numeric(20,0) DEFAULT NULL,
+    after_event_idx numeric(20,0) DEFAULT NULL
+) RETURNS TABLE (
+    txn_version numeric(20,0),
+    event_idx numeric(20,0),
+    "time" timestamptz,
+    price numeric(20,0),
+    "size" numeric(20,0),
+    side text,
+    maker_address varchar(70),
+    maker_custodian_id numeric(20,0),
+    maker_order_id numeric(39,0),
+    taker_address varchar(70),
+    taker_custodian_id numeric(20,0),
+    taker_order_id numeric(39,0),
+    taker_quote_fees_paid numeric(20,0)
+) AS $$
+    SELECT
+        f.txn_version,
+        f.event_idx,
+        f."time",
+        f.price,
+        f."size",
+        -- The taker buys from an ask maker and sells to a bid maker.
+        CASE
+            WHEN f.maker_side = true THEN 'buy'
+            ELSE 'sell'
+        END AS side,
+        f.maker_address,
+        f.maker_custodian_id,
+        f.maker_order_id,
+        f.taker_address,
+        f.taker_custodian_id,
+        f.taker_order_id,
+        f.taker_quote_fees_paid
+    FROM fill_events AS f
+    WHERE api.require_registered_market($1)
+    AND f.market_id = $1
+    -- Fills are emitted to both the maker and the taker, keep only one of them.
+    AND f.emit_address = f.maker_address
+    AND (
+        $2 IS NULL
+        OR (f.txn_version, f.event_idx) < ($2, COALESCE($3, 0))
+    )
+    ORDER BY f.txn_version DESC, f.event_idx DESC;
+$$ LANGUAGE SQL STABLE;
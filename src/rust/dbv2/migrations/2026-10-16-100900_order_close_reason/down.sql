@@ -0,0 +1,81 @@
+-- This file should undo anything in `up.sql`
+-- A replaced view cannot lose columns, so `api.orders` is recreated, along
+-- with `api.get_order` which returns its rows.
+DROP FUNCTION api.get_order;
+
+
+DROP VIEW api.orders;
+
+
+CREATE VIEW api.orders AS
+    SELECT
+        market_id,
+        order_id,
+        created_at,
+        last_updated_at,
+        integrator,
+        total_filled,
+        remaining_size,
+        order_status,
+        order_type,
+        "user",
+        direction,
+        price,
+        average_execution_price,
+        custodian_id,
+        self_match_behavior,
+        restriction,
+        last_increase_stamp,
+        min_base,
+        max_base,
+        min_quote,
+        max_quote,
+        total_fees_paid_in_quote_subunits
+    FROM
+        aggregator.user_history;
+
+
+GRANT SELECT ON api.orders TO web_anon;
+
+
+GRANT SELECT ON api.orders TO grafana;
+
+
+-- Parameters:
+-- * `market_id`: The market ID of the order
+-- * `order_id`: The order ID of the order
+--
+-- Returns:
+-- * The order
+--
+-- Raises a 410 if the order has been pruned and a 404 if it never existed.
+CREATE FUNCTION api.get_order (
+  market_id numeric(20,0),
+  order_id numeric(39,0)
+) RETURNS SETOF api.orders AS $$
+BEGIN
+  RETURN QUERY
+  SELECT *
+  FROM api.orders
+  WHERE orders.market_id = $1
+  AND orders.order_id = $2;
+  IF NOT FOUND THEN
+    IF EXISTS (
+      SELECT
+      FROM api.pruned_orders
+      WHERE pruned_orders.market_id = $1
+      AND pruned_orders.order_id = $2
+    ) THEN
+      RAISE sqlstate 'PT410' USING message = 'Order has been pruned';
+    END IF;
+    RAISE sqlstate 'PT404' USING message = 'Order not found';
+  END IF;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+
+ALTER TABLE aggregator.user_history
+DROP COLUMN close_reason;
+
+
+DROP TYPE order_close_reason;
@@ -0,0 +1,58 @@
+-- Your SQL goes here
+-- `zero_size` closes the orders placed with a size of zero, which nothing can
+-- fill.
+CREATE TYPE order_close_reason AS ENUM ('filled', 'ioc_expired', 'market_exhausted', 'cancelled', 'zero_size');
+
+
+ALTER TABLE aggregator.user_history
+ADD COLUMN close_reason order_close_reason;
+
+
+UPDATE aggregator.user_history
+SET close_reason = CASE order_type
+    WHEN 'limit' THEN 'filled'::order_close_reason
+    ELSE 'market_exhausted'::order_close_reason
+END
+WHERE order_status = 'closed';
+
+
+UPDATE aggregator.user_history AS user_history
+SET close_reason = CASE cancel_order_events.reason
+    -- CANCEL_REASON_IMMEDIATE_OR_CANCEL
+    WHEN 2 THEN 'ioc_expired'::order_close_reason
+    ELSE 'cancelled'::order_close_reason
+END
+FROM cancel_order_events
+WHERE user_history.order_status = 'cancelled'
+AND user_history.order_id = cancel_order_events.order_id
+AND user_history.market_id = cancel_order_events.market_id;
+
+
+-- The reason is exposed as text so that the view does not depend on the type.
+CREATE OR REPLACE VIEW api.orders AS
+    SELECT
+        market_id,
+        order_id,
+        created_at,
+        last_updated_at,
+        integrator,
+        total_filled,
+        remaining_size,
+        order_status,
+        order_type,
+        "user",
+        direction,
+        price,
+        average_execution_price,
+        custodian_id,
+        self_match_behavior,
+        restriction,
+        last_increase_stamp,
+        min_base,
+        max_base,
+        min_quote,
+        max_quote,
+        total_fees_paid_in_quote_subunits,
+        close_reason::text AS close_reason
+    FROM
+        aggregator.user_history;
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.ready;
+
+
+DROP VIEW api.pipeline_lag;
+
+
+DROP TABLE aggregator.lagging_pipelines;
+
+
+DROP VIEW aggregator.pipeline_lag;
@@ -0,0 +1,94 @@
+-- Your SQL goes here
+CREATE VIEW aggregator.pipeline_lag AS
+WITH processor AS (
+  SELECT last_success_version
+  FROM processor_status
+  WHERE processor = 'econia_processor'
+)
+SELECT
+  pipeline,
+  GREATEST((SELECT last_success_version FROM processor) - txn_version, 0) AS lag
+FROM (
+  SELECT 'UserHistory' AS pipeline, MAX(txn_version) AS txn_version
+  FROM aggregator.user_history_last_indexed_txn
+  UNION ALL
+  SELECT 'Candlesticks(' || resolution || ')', txn_version
+  FROM aggregator.candlesticks_last_indexed_txn
+  UNION ALL
+  SELECT 'Fees', MAX(txn_version)
+  FROM aggregator.fees_last_indexed_txn
+  UNION ALL
+  SELECT 'Prices', MAX(txn_version)
+  FROM aggregator.prices_last_indexed_txn
+  UNION ALL
+  SELECT 'UserBalances', MAX(txn_version)
+  FROM aggregator.user_balances_last_indexed_txn
+  UNION ALL
+  SELECT 'EnumeratedVolume', MAX(txn_version)
+  FROM aggregator.enumerated_volume_last_indexed_txn
+  UNION ALL
+  SELECT 'IntegratorVolume', MAX(txn_version)
+  FROM aggregator.integrator_volume_last_indexed_txn
+) AS last_indexed_txn
+WHERE txn_version IS NOT NULL;
+
+
+GRANT
+SELECT
+  ON aggregator.pipeline_lag TO grafana;
+
+
+-- Pipelines whose lag has been above the configured maximum for longer than
+-- the configured duration, as maintained by the aggregator lag monitor.
+CREATE TABLE aggregator.lagging_pipelines (
+  pipeline TEXT NOT NULL PRIMARY KEY,
+  lag NUMERIC(20,0) NOT NULL,
+  tripped_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+
+GRANT
+SELECT
+  ON aggregator.lagging_pipelines TO grafana;
+
+
+CREATE VIEW api.pipeline_lag AS
+SELECT
+  pipeline_lag.*,
+  lagging_pipelines.tripped_at
+FROM
+  aggregator.pipeline_lag
+  LEFT JOIN aggregator.lagging_pipelines USING (pipeline);
+
+
+GRANT
+SELECT
+  ON api.pipeline_lag TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.pipeline_lag TO grafana;
+
+
+-- Returns:
+-- * `true` if no pipeline is lagging
+--
+-- Raises a 503 listing the lagging pipelines otherwise.
+CREATE FUNCTION api.ready ()
+RETURNS boolean AS $$
+DECLARE
+  lagging text;
+BEGIN
+  SELECT string_agg(pipeline || ' (' || lag || ' versions behind)', ', ' ORDER BY pipeline)
+  INTO lagging
+  FROM api.pipeline_lag
+  WHERE tripped_at IS NOT NULL;
+  IF lagging IS NOT NULL THEN
+    RAISE sqlstate 'PT503' USING
+      message = 'Data is stale',
+      detail = 'Lagging pipelines: ' || lagging;
+  END IF;
+  RETURN true;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- Cancel events whose order was not in `aggregator.user_history` yet when they
+-- were aggregated. They are retried on every run of the user history pipeline
+-- until the placement of their order has been aggregated.
+CREATE TABLE aggregator.pending_cancels (
+  txn_version NUMERIC(20,0) NOT NULL,
+  event_idx NUMERIC(20,0) NOT NULL,
+  market_id NUMERIC(20,0) NOT NULL,
+  order_id NUMERIC(39,0) NOT NULL,
+  PRIMARY KEY (txn_version, event_idx)
+);
+
+
+GRANT
+SELECT
+  ON aggregator.pending_cancels TO grafana;
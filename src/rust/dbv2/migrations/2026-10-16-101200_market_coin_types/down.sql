@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.quote_type;
+
+
+DROP FUNCTION api.base_type;
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market`: A row of `api.markets`
+--
+-- Returns:
+-- * The fully qualified type of the base coin (e.g. `0x1::aptos_coin::AptosCoin`),
+--   or `NULL` for generic base assets
+--
+-- Exposed by PostgREST as a computed column, e.g. `/markets?base_type=eq.0x1::aptos_coin::AptosCoin`.
+CREATE FUNCTION api.base_type (market api.markets)
+RETURNS text AS $$
+    SELECT $1.base_account_address || '::' || $1.base_module_name || '::' || $1.base_struct_name;
+$$ LANGUAGE SQL IMMUTABLE;
+
+
+-- Parameters:
+-- * `market`: A row of `api.markets`
+--
+-- Returns:
+-- * The fully qualified type of the quote coin (e.g. `0x1::aptos_coin::AptosCoin`)
+--
+-- Exposed by PostgREST as a computed column, e.g. `/markets?quote_type=eq.0x1::aptos_coin::AptosCoin`.
+CREATE FUNCTION api.quote_type (market api.markets)
+RETURNS text AS $$
+    SELECT $1.quote_account_address || '::' || $1.quote_module_name || '::' || $1.quote_struct_name;
+$$ LANGUAGE SQL IMMUTABLE;
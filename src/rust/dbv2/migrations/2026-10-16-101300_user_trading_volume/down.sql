@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.user_trading_volume;
+
+
+DROP INDEX fill_events_taker_address_time;
+
+
+DROP INDEX fill_events_maker_address_time;
@@ -0,0 +1,76 @@
+-- Your SQL goes here
+CREATE INDEX fill_events_maker_address_time ON fill_events (maker_address, "time");
+
+
+CREATE INDEX fill_events_taker_address_time ON fill_events (taker_address, "time");
+
+
+-- Parameters:
+-- * `address`: The address of the user
+-- * `start_time`: Start of the time range (inclusive)
+-- * `end_time`: End of the time range (exclusive)
+-- * `market_id`: If set, only fills on this market are counted
+--
+-- Returns:
+-- * The number of fills, base volume (in lots) and quote volume (in ticks) of
+--   the user as a maker, as a taker and combined, and the quote fees they paid
+--
+-- A fill where the user was on both sides counts once as a maker and once as a
+-- taker.
+CREATE FUNCTION api.user_trading_volume (
+    address varchar(70),
+    start_time timestamptz,
+    end_time timestamptz,
+    market_id numeric(20,0) DEFAULT NULL
+) RETURNS TABLE (
+    maker_fill_count bigint,
+    maker_base_volume numeric,
+    maker_quote_volume numeric,
+    taker_fill_count bigint,
+    taker_base_volume numeric,
+    taker_quote_volume numeric,
+    fill_count bigint,
+    base_volume numeric,
+    quote_volume numeric,
+    fees_paid numeric
+) AS $$
+BEGIN
+    IF $4 IS NOT NULL THEN
+        PERFORM FROM api.registered_market($4);
+    END IF;
+    PERFORM api.validate_time_range($2, $3);
+    RETURN QUERY
+    WITH fills AS (
+        SELECT f.*
+        FROM fill_events AS f
+        WHERE (f.maker_address = $1 OR f.taker_address = $1)
+        -- Fills are emitted to both the maker and the taker, keep only one of them.
+        AND f.emit_address = f.maker_address
+        AND f."time" >= $2
+        AND f."time" < $3
+        AND ($4 IS NULL OR f.market_id = $4)
+    ), totals AS (
+        SELECT
+            COUNT(*) FILTER (WHERE f.maker_address = $1) AS maker_fill_count,
+            COALESCE(SUM(f."size") FILTER (WHERE f.maker_address = $1), 0) AS maker_base_volume,
+            COALESCE(SUM(f."size" * f.price) FILTER (WHERE f.maker_address = $1), 0) AS maker_quote_volume,
+            COUNT(*) FILTER (WHERE f.taker_address = $1) AS taker_fill_count,
+            COALESCE(SUM(f."size") FILTER (WHERE f.taker_address = $1), 0) AS taker_base_volume,
+            COALESCE(SUM(f."size" * f.price) FILTER (WHERE f.taker_address = $1), 0) AS taker_quote_volume,
+            COALESCE(SUM(f.taker_quote_fees_paid) FILTER (WHERE f.taker_address = $1), 0) AS fees_paid
+        FROM fills AS f
+    )
+    SELECT
+        t.maker_fill_count,
+        t.maker_base_volume,
+        t.maker_quote_volume,
+        t.taker_fill_count,
+        t.taker_base_volume,
+        t.taker_quote_volume,
+        t.maker_fill_count + t.taker_fill_count,
+        t.maker_base_volume + t.taker_base_volume,
+        t.maker_quote_volume + t.taker_quote_volume,
+        t.fees_paid
+    FROM totals AS t;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.run_pipeline;
+
+
+DROP VIEW api.pipeline_runs;
+
+
+DROP TABLE aggregator.pipeline_runs;
+
+
+DROP TABLE aggregator.pipelines;
@@ -0,0 +1,86 @@
+-- Your SQL goes here
+-- Pipelines run by an aggregator instance, registered when it starts.
+CREATE TABLE aggregator.pipelines (
+  model_name TEXT NOT NULL PRIMARY KEY,
+  registered_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+
+-- Out of band runs of a pipeline requested through `api.run_pipeline`.
+--
+-- `claimed_by` is the backend PID of the connection of the aggregator instance
+-- running the run. A started run whose connection is gone (e.g. the instance
+-- died) is claimed again by the next instance.
+CREATE TABLE aggregator.pipeline_runs (
+  id BIGSERIAL NOT NULL PRIMARY KEY,
+  model_name TEXT NOT NULL REFERENCES aggregator.pipelines (model_name) ON DELETE CASCADE,
+  requested_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  started_at TIMESTAMPTZ,
+  claimed_by INTEGER,
+  finished_at TIMESTAMPTZ,
+  error TEXT
+);
+
+
+GRANT
+SELECT
+  ON aggregator.pipelines, aggregator.pipeline_runs TO grafana;
+
+
+-- Errors may leak internals (table names, connection details), so the API only
+-- tells whether a run failed. They are logged and kept in
+-- `aggregator.pipeline_runs`.
+CREATE VIEW api.pipeline_runs AS
+SELECT
+  id,
+  model_name,
+  requested_at,
+  started_at,
+  finished_at,
+  error IS NOT NULL AS failed
+FROM
+  aggregator.pipeline_runs;
+
+
+GRANT
+SELECT
+  ON api.pipeline_runs TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.pipeline_runs TO grafana;
+
+
+-- Parameters:
+-- * `model_name`: The name of the pipeline to run (e.g. `UserHistory`)
+--
+-- Returns:
+-- * The ID of the run, to follow on `/pipeline_runs`
+--
+-- Requires the `X-Admin-Secret` header to match the `app.settings.admin_secret`
+-- setting of PostgREST, and raises a 401 otherwise or if no secret is set.
+-- Raises a 404 if no aggregator runs the pipeline. The aggregator is notified
+-- on the `aggregator_run` channel and runs the pipeline once, without waiting
+-- for its poll interval.
+CREATE FUNCTION api.run_pipeline (
+  model_name text
+) RETURNS bigint AS $$
+DECLARE
+  secret text := current_setting('app.settings.admin_secret', true);
+  run_id bigint;
+BEGIN
+  IF COALESCE(secret, '') = ''
+  OR current_setting('request.headers', true)::json->>'x-admin-secret' IS DISTINCT FROM secret THEN
+    RAISE sqlstate 'PT401' USING message = 'Unauthorized';
+  END IF;
+  IF NOT EXISTS (SELECT FROM aggregator.pipelines AS p WHERE p.model_name = $1) THEN
+    RAISE sqlstate 'PT404' USING message = 'Pipeline not found';
+  END IF;
+  INSERT INTO aggregator.pipeline_runs (model_name)
+  VALUES ($1)
+  RETURNING id INTO run_id;
+  PERFORM pg_notify('aggregator_run', run_id::text);
+  RETURN run_id;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = '';
@@ -0,0 +1,59 @@
+-- This file should undo anything in `up.sql`
+CREATE OR REPLACE FUNCTION api.market_ticker (
+    market_id numeric(20,0)
+) RETURNS TABLE (
+    last_price numeric,
+    price_24h_ago numeric,
+    price_change_24h numeric,
+    price_change_percent_24h numeric,
+    base_volume_24h numeric,
+    quote_volume_24h numeric,
+    high_24h numeric,
+    low_24h numeric
+) AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    RETURN QUERY
+    WITH fills AS NOT MATERIALIZED (
+        SELECT f.*
+        FROM fill_events AS f
+        WHERE f.market_id = $1
+        -- Fills are emitted to both the maker and the taker, keep only one of them.
+        AND f.emit_address = f.maker_address
+    ), prices AS (
+        SELECT
+            (
+                SELECT f.price FROM fills AS f
+                ORDER BY f.txn_version DESC, f.event_idx DESC
+                LIMIT 1
+            ) AS last_price,
+            (
+                SELECT f.price FROM fills AS f
+                WHERE f."time" <= CURRENT_TIMESTAMP - interval '24 hours'
+                ORDER BY f.txn_version DESC, f.event_idx DESC
+                LIMIT 1
+            ) AS price_24h_ago
+    ), last_24h AS (
+        SELECT
+            COALESCE(SUM(f."size"), 0) AS base_volume,
+            COALESCE(SUM(f."size" * f.price), 0) AS quote_volume,
+            MAX(f.price) AS high,
+            MIN(f.price) AS low
+        FROM fills AS f
+        WHERE f."time" > CURRENT_TIMESTAMP - interval '24 hours'
+    )
+    SELECT
+        p.last_price,
+        p.price_24h_ago,
+        p.last_price - p.price_24h_ago,
+        (p.last_price - p.price_24h_ago) / p.price_24h_ago * 100,
+        v.base_volume,
+        v.quote_volume,
+        v.high,
+        v.low
+    FROM prices AS p, last_24h AS v;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+
+DROP TABLE aggregator.market_stats_24h;
@@ -0,0 +1,51 @@
+-- Your SQL goes here
+-- Rolling 24 hour statistics per market, maintained by the `MarketStats24h`
+-- pipeline. Refreshed every cycle, so that fills older than 24 hours leave the
+-- window even when no new fill comes in. Volumes are in lots and ticks, like
+-- those of `api.market_ticker`.
+CREATE TABLE aggregator.market_stats_24h (
+    market_id NUMERIC(20,0) NOT NULL PRIMARY KEY,
+    last_price NUMERIC(20,0),
+    price_24h_ago NUMERIC(20,0),
+    "open" NUMERIC(20,0),
+    high NUMERIC(20,0),
+    low NUMERIC(20,0),
+    base_volume NUMERIC NOT NULL,
+    quote_volume NUMERIC NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+
+GRANT
+SELECT
+  ON aggregator.market_stats_24h TO grafana;
+
+
+CREATE OR REPLACE FUNCTION api.market_ticker (
+    market_id numeric(20,0)
+) RETURNS TABLE (
+    last_price numeric,
+    price_24h_ago numeric,
+    price_change_24h numeric,
+    price_change_percent_24h numeric,
+    base_volume_24h numeric,
+    quote_volume_24h numeric,
+    high_24h numeric,
+    low_24h numeric
+) AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    RETURN QUERY
+    SELECT
+        s.last_price,
+        s.price_24h_ago,
+        s.last_price - s.price_24h_ago,
+        (s.last_price - s.price_24h_ago) / s.price_24h_ago * 100,
+        s.base_volume,
+        s.quote_volume,
+        s.high,
+        s.low
+    FROM aggregator.market_stats_24h AS s
+    WHERE s.market_id = $1;
+END;
+$$ LANGUAGE plpgsql STABLE SECURITY DEFINER SET search_path = '';
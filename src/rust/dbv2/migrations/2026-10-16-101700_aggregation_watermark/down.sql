@@ -0,0 +1,66 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER integrator_volume_watermark ON aggregator.integrator_volume_last_indexed_txn;
+
+
+DROP TRIGGER enumerated_volume_watermark ON aggregator.enumerated_volume_last_indexed_txn;
+
+
+DROP TRIGGER user_balances_watermark ON aggregator.user_balances_last_indexed_txn;
+
+
+DROP TRIGGER prices_watermark ON aggregator.prices_last_indexed_txn;
+
+
+DROP TRIGGER fees_watermark ON aggregator.fees_last_indexed_txn;
+
+
+DROP TRIGGER candlesticks_watermark ON aggregator.candlesticks_last_indexed_txn;
+
+
+DROP TRIGGER user_history_watermark ON aggregator.user_history_last_indexed_txn;
+
+
+DROP FUNCTION aggregator.notify_watermark;
+
+
+DROP FUNCTION api.aggregation_watermark;
+
+
+DROP VIEW api.aggregation_watermarks;
+
+
+CREATE OR REPLACE VIEW aggregator.pipeline_lag AS
+WITH processor AS (
+  SELECT last_success_version
+  FROM processor_status
+  WHERE processor = 'econia_processor'
+)
+SELECT
+  pipeline,
+  GREATEST((SELECT last_success_version FROM processor) - txn_version, 0) AS lag
+FROM (
+  SELECT 'UserHistory' AS pipeline, MAX(txn_version) AS txn_version
+  FROM aggregator.user_history_last_indexed_txn
+  UNION ALL
+  SELECT 'Candlesticks(' || resolution || ')', txn_version
+  FROM aggregator.candlesticks_last_indexed_txn
+  UNION ALL
+  SELECT 'Fees', MAX(txn_version)
+  FROM aggregator.fees_last_indexed_txn
+  UNION ALL
+  SELECT 'Prices', MAX(txn_version)
+  FROM aggregator.prices_last_indexed_txn
+  UNION ALL
+  SELECT 'UserBalances', MAX(txn_version)
+  FROM aggregator.user_balances_last_indexed_txn
+  UNION ALL
+  SELECT 'EnumeratedVolume', MAX(txn_version)
+  FROM aggregator.enumerated_volume_last_indexed_txn
+  UNION ALL
+  SELECT 'IntegratorVolume', MAX(txn_version)
+  FROM aggregator.integrator_volume_last_indexed_txn
+) AS last_indexed_txn
+WHERE txn_version IS NOT NULL;
+
+
+DROP VIEW aggregator.pipeline_watermarks;
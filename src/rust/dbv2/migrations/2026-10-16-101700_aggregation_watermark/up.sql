@@ -0,0 +1,133 @@
+-- Your SQL goes here
+-- The last transaction version aggregated by each pipeline tracking one.
+-- Pipelines aggregate whole transactions, so every event of that transaction
+-- and the ones before it is aggregated.
+CREATE VIEW aggregator.pipeline_watermarks AS
+SELECT 'UserHistory' AS model_name, MAX(txn_version) AS txn_version
+FROM aggregator.user_history_last_indexed_txn
+UNION ALL
+SELECT 'Candlesticks(' || resolution || ')', txn_version
+FROM aggregator.candlesticks_last_indexed_txn
+UNION ALL
+SELECT 'Fees', MAX(txn_version)
+FROM aggregator.fees_last_indexed_txn
+UNION ALL
+SELECT 'Prices', MAX(txn_version)
+FROM aggregator.prices_last_indexed_txn
+UNION ALL
+SELECT 'UserBalances', MAX(txn_version)
+FROM aggregator.user_balances_last_indexed_txn
+UNION ALL
+SELECT 'EnumeratedVolume', MAX(txn_version)
+FROM aggregator.enumerated_volume_last_indexed_txn
+UNION ALL
+SELECT 'IntegratorVolume', MAX(txn_version)
+FROM aggregator.integrator_volume_last_indexed_txn;
+
+
+GRANT
+SELECT
+  ON aggregator.pipeline_watermarks TO grafana;
+
+
+CREATE OR REPLACE VIEW aggregator.pipeline_lag AS
+WITH processor AS (
+  SELECT last_success_version
+  FROM processor_status
+  WHERE processor = 'econia_processor'
+)
+SELECT
+  model_name AS pipeline,
+  GREATEST((SELECT last_success_version FROM processor) - txn_version, 0) AS lag
+FROM aggregator.pipeline_watermarks
+WHERE txn_version IS NOT NULL;
+
+
+CREATE VIEW api.aggregation_watermarks AS
+SELECT
+  *
+FROM
+  aggregator.pipeline_watermarks;
+
+
+GRANT
+SELECT
+  ON api.aggregation_watermarks TO web_anon;
+
+
+-- Parameters:
+-- * `model_name`: The name of the pipeline (e.g. `UserHistory`)
+--
+-- Returns:
+-- * The last transaction version aggregated by the pipeline, or null if it
+--   has not aggregated anything yet
+--
+-- Raises a 404 for pipelines that do not track a transaction version.
+CREATE FUNCTION api.aggregation_watermark (
+  model_name text
+) RETURNS numeric AS $$
+DECLARE
+  watermark numeric;
+BEGIN
+  SELECT w.txn_version INTO watermark
+  FROM api.aggregation_watermarks AS w
+  WHERE w.model_name = $1;
+  IF NOT FOUND THEN
+    RAISE sqlstate 'PT404' USING message = 'Pipeline not found';
+  END IF;
+  RETURN watermark;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+
+-- Notifies `aggregation_watermark` with the pipeline name given as argument
+-- and its new watermark, so that consumers can skip anything at or below the
+-- watermark they fetched before subscribing.
+CREATE FUNCTION aggregator.notify_watermark ()
+RETURNS TRIGGER AS $$
+BEGIN
+  PERFORM pg_notify('aggregation_watermark', json_build_object(
+    'model_name', CASE TG_ARGV[0]
+      WHEN 'Candlesticks' THEN 'Candlesticks(' || (to_jsonb(NEW)->>'resolution') || ')'
+      ELSE TG_ARGV[0]
+    END,
+    'txn_version', NEW.txn_version
+  )::text);
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+
+CREATE TRIGGER user_history_watermark
+AFTER INSERT OR UPDATE ON aggregator.user_history_last_indexed_txn
+FOR EACH ROW EXECUTE PROCEDURE aggregator.notify_watermark ('UserHistory');
+
+
+CREATE TRIGGER candlesticks_watermark
+AFTER INSERT OR UPDATE ON aggregator.candlesticks_last_indexed_txn
+FOR EACH ROW EXECUTE PROCEDURE aggregator.notify_watermark ('Candlesticks');
+
+
+CREATE TRIGGER fees_watermark
+AFTER INSERT OR UPDATE ON aggregator.fees_last_indexed_txn
+FOR EACH ROW EXECUTE PROCEDURE aggregator.notify_watermark ('Fees');
+
+
+CREATE TRIGGER prices_watermark
+AFTER INSERT OR UPDATE ON aggregator.prices_last_indexed_txn
+FOR EACH ROW EXECUTE PROCEDURE aggregator.notify_watermark ('Prices');
+
+
+CREATE TRIGGER user_balances_watermark
+AFTER INSERT OR UPDATE ON aggregator.user_balances_last_indexed_txn
+FOR EACH ROW EXECUTE PROCEDURE aggregator.notify_watermark ('UserBalances');
+
+
+CREATE TRIGGER enumerated_volume_watermark
+AFTER INSERT OR UPDATE ON aggregator.enumerated_volume_last_indexed_txn
+FOR EACH ROW EXECUTE PROCEDURE aggregator.notify_watermark ('EnumeratedVolume');
+
+
+CREATE TRIGGER integrator_volume_watermark
+AFTER INSERT OR UPDATE ON aggregator.integrator_volume_last_indexed_txn
+FOR EACH ROW EXECUTE PROCEDURE aggregator.notify_watermark ('IntegratorVolume');
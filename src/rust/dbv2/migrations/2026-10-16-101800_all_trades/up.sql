@@ -0,0 +1,59 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `after_txn_version`: Optional cursor, only trades older than it are returned
+-- * `after_event_idx`: Optional cursor, used together with `after_txn_version`
+--
+-- Returns:
+-- * The trades of all markets, newest first, with the same columns as
+--   `api.trades` plus `market_id`
+--
+-- Written in SQL rather than plpgsql so that it gets inlined: the `limit` of
+-- the request then stops the walk of the `(txn_version, event_idx)` primary
+-- key early instead of sorting every fill.
+CREATE FUNCTION api.all_trades (
+    after_txn_version numeric(20,0) DEFAULT NULL,
+    after_event_idx numeric(20,0) DEFAULT NULL
+) RETURNS TABLE (
+    market_id numeric(20,0),
+    txn_version numeric(20,0),
+    event_idx numeric(20,0),
+    "time" timestamptz,
+    price numeric(20,0),
+    "size" numeric(20,0),
+    side text,
+    maker_address varchar(70),
+    maker_custodian_id numeric(20,0),
+    maker_order_id numeric(39,0),
+    taker_address varchar(70),
+    taker_custodian_id numeric(20,0),
+    taker_order_id numeric(39,0),
+    taker_quote_fees_paid numeric(20,0)
+) AS $$
+    SELECT
+        f.market_id,
+        f.txn_version,
+        f.event_idx,
+        f."time",
+        f.price,
+        f."size",
+        -- The taker buys from an ask maker and sells to a bid maker.
+        CASE
+            WHEN f.maker_side = true THEN 'buy'
+            ELSE 'sell'
+        END AS side,
+        f.maker_address,
+        f.maker_custodian_id,
+        f.maker_order_id,
+        f.taker_address,
+        f.taker_custodian_id,
+        f.taker_order_id,
+        f.taker_quote_fees_paid
+    FROM fill_events AS f
+    -- Fills are emitted to both the maker and the taker, keep only one of them.
+    WHERE f.emit_address = f.maker_address
+    AND (
+        $1 IS NULL
+        OR (f.txn_version, f.event_idx) < ($1, COALESCE($2, 0))
+    )
+    ORDER BY f.txn_version DESC, f.event_idx DESC;
+$$ LANGUAGE SQL STABLE;
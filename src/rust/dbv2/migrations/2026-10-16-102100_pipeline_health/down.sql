@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP VIEW api.pipeline_health;
+
+
+DROP TABLE aggregator.pipeline_health;
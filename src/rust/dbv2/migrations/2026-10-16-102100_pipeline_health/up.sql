@@ -0,0 +1,33 @@
+-- Your SQL goes here
+-- The outcome of the last runs of each pipeline, as saved periodically by
+-- the aggregator.
+CREATE TABLE aggregator.pipeline_health (
+  pipeline TEXT NOT NULL PRIMARY KEY,
+  last_success_at TIMESTAMPTZ,
+  last_error_at TIMESTAMPTZ,
+  last_error TEXT,
+  -- Number of runs that failed since the last successful one.
+  consecutive_errors BIGINT NOT NULL DEFAULT 0
+);
+
+
+GRANT
+SELECT
+  ON aggregator.pipeline_health TO grafana;
+
+
+CREATE VIEW api.pipeline_health AS
+SELECT
+  *
+FROM
+  aggregator.pipeline_health;
+
+
+GRANT
+SELECT
+  ON api.pipeline_health TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.pipeline_health TO grafana;
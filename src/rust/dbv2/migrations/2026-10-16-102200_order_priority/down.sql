@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.order_priority;
+
+
+DROP INDEX aggregator.user_history_open_market_id_price;
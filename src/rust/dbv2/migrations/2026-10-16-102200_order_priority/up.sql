@@ -0,0 +1,76 @@
+-- Your SQL goes here
+CREATE INDEX user_history_open_market_id_price
+ON aggregator.user_history (market_id, price)
+WHERE order_status = 'open';
+
+
+-- Parameters:
+-- * `market_id`: The market ID of the order
+-- * `order_id`: The order ID of the order
+--
+-- Returns:
+-- * The price and direction of the order
+-- * `rank`: The position of the order in the queue of its price level,
+--   starting at 1 for the order matched first
+-- * `size_ahead`: The remaining size of the orders ahead of it
+-- * `level_size`: The remaining size of the whole price level
+--
+-- Within a price level, orders are matched in the order they entered the
+-- queue: at placement, or at their last size increase since an increase
+-- sends the order to the back of the queue. Both are encoded as
+-- `txn_version * 2^64 + event_idx`, like `last_increase_stamp`.
+--
+-- Raises a 404 if the order does not exist and a 409 if it is not open.
+CREATE FUNCTION api.order_priority (
+  market_id numeric(20,0),
+  order_id numeric(39,0)
+) RETURNS TABLE (
+  price numeric(20,0),
+  direction order_direction,
+  rank bigint,
+  size_ahead numeric,
+  level_size numeric
+) AS $$
+DECLARE
+  o record;
+BEGIN
+  SELECT orders.price, orders.direction, orders.order_status, orders.order_type INTO o
+  FROM api.orders
+  WHERE orders.market_id = $1
+  AND orders.order_id = $2;
+  IF NOT FOUND THEN
+    RAISE sqlstate 'PT404' USING message = 'Order not found';
+  END IF;
+  IF o.order_status <> 'open' OR o.order_type <> 'limit' THEN
+    RAISE sqlstate 'PT409' USING message = 'Order is not an open limit order';
+  END IF;
+  RETURN QUERY
+  WITH queue AS (
+    SELECT
+      orders.order_id,
+      orders.remaining_size,
+      COALESCE(
+        orders.last_increase_stamp,
+        p.txn_version * 18446744073709551616 + p.event_idx
+      ) AS stamp
+    FROM api.orders
+    INNER JOIN api.place_limit_order_events AS p
+    ON p.market_id = orders.market_id AND p.order_id = orders.order_id
+    WHERE orders.market_id = $1
+    AND orders.price = o.price
+    AND orders.direction = o.direction
+    AND orders.order_status = 'open'
+  ),
+  this AS (
+    SELECT stamp FROM queue WHERE queue.order_id = $2
+  )
+  SELECT
+    o.price,
+    o.direction,
+    COUNT(*) FILTER (WHERE queue.stamp <= this.stamp),
+    COALESCE(SUM(queue.remaining_size) FILTER (WHERE queue.stamp < this.stamp), 0),
+    SUM(queue.remaining_size)
+  FROM queue, this
+  GROUP BY this.stamp;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,75 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market_id`: The market ID that trades are queried for
+-- * `after_txn_version`: Transaction version of the last trade already received
+-- * `after_event_idx`: Event index of the last trade already received
+--
+-- Returns:
+-- * The trades of the market after the given one, oldest first, with the
+--   same columns as `api.trades`
+--
+-- Meant for clients of the `trade/MARKET_ID` MQTT topic catching up on the
+-- trades they missed while disconnected. Raises a 410 if more than 1000
+-- trades were missed, in which case the client should reload its state from
+-- `api.trades` instead.
+CREATE FUNCTION api.trades_since (
+    market_id numeric(20,0),
+    after_txn_version numeric(20,0),
+    after_event_idx numeric(20,0)
+) RETURNS TABLE (
+    txn_version numeric(20,0),
+    event_idx numeric(20,0),
+    "time" timestamptz,
+    price numeric(20,0),
+    "size" numeric(20,0),
+    side text,
+    maker_address varchar(70),
+    maker_custodian_id numeric(20,0),
+    maker_order_id numeric(39,0),
+    taker_address varchar(70),
+    taker_custodian_id numeric(20,0),
+    taker_order_id numeric(39,0),
+    taker_quote_fees_paid numeric(20,0)
+) AS $$
+DECLARE
+    missed bigint;
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    SELECT COUNT(*) INTO missed FROM (
+        SELECT
+        FROM fill_events AS f
+        WHERE f.market_id = $1
+        AND f.emit_address = f.maker_address
+        AND (f.txn_version, f.event_idx) > ($2, $3)
+        LIMIT 1001
+    ) AS m;
+    IF missed > 1000 THEN
+        RAISE sqlstate 'PT410' USING message = 'Too many trades missed, reload them from /rpc/trades';
+    END IF;
+    RETURN QUERY
+    SELECT
+        f.txn_version,
+        f.event_idx,
+        f."time",
+        f.price,
+        f."size",
+        -- The taker buys from an ask maker and sells to a bid maker.
+        CASE
+            WHEN f.maker_side = true THEN 'buy'
+            ELSE 'sell'
+        END AS side,
+        f.maker_address,
+        f.maker_custodian_id,
+        f.maker_order_id,
+        f.taker_address,
+        f.taker_custodian_id,
+        f.taker_order_id,
+        f.taker_quote_fees_paid
+    FROM fill_events AS f
+    WHERE f.market_id = $1
+    -- Fills are emitted to both the maker and the taker, keep only one of them.
+    AND f.emit_address = f.maker_address
+    AND (f.txn_version, f.event_idx) > ($2, $3)
+    ORDER BY f.txn_version, f.event_idx;
+END;
+$$ LANGUAGE plpgsql STABLE;
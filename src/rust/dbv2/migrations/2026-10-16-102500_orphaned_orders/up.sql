@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Rows of `aggregator.user_history` without a place event, moved out of it by
+-- `check-orders --repair` so that they can be inspected.
+CREATE TABLE aggregator.orphaned_orders (
+  LIKE aggregator.user_history,
+  quarantined_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (market_id, order_id)
+);
+
+
+GRANT
+SELECT
+  ON aggregator.orphaned_orders TO grafana;
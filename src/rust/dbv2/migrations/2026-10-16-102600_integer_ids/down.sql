@@ -0,0 +1,88 @@
+-- This file should undo anything in `up.sql`
+-- Parameters:
+-- * `market_id`: The market ID of the order
+-- * `order_id`: The order ID of the order
+--
+-- Returns:
+-- * The order
+--
+-- Raises a 410 if the order has been pruned and a 404 if it never existed.
+CREATE OR REPLACE FUNCTION api.get_order (
+  market_id numeric(20,0),
+  order_id numeric(39,0)
+) RETURNS SETOF api.orders AS $$
+BEGIN
+  RETURN QUERY
+  SELECT *
+  FROM api.orders
+  WHERE orders.market_id = $1
+  AND orders.order_id = $2;
+  IF NOT FOUND THEN
+    IF EXISTS (
+      SELECT
+      FROM api.pruned_orders
+      WHERE pruned_orders.market_id = $1
+      AND pruned_orders.order_id = $2
+    ) THEN
+      RAISE sqlstate 'PT410' USING message = 'Order has been pruned';
+    END IF;
+    RAISE sqlstate 'PT404' USING message = 'Order not found';
+  END IF;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+
+-- Parameters:
+-- * `market_id`: The market ID of the orders
+-- * `order_ids`: The order IDs of the orders, at most 200
+--
+-- Returns:
+-- * A JSON object with the found orders under `orders`, the IDs of the
+--   pruned orders under `pruned` and the other IDs under `not_found`
+--
+-- Raises a 400 if more than 200 order IDs are given.
+CREATE OR REPLACE FUNCTION api.get_orders (
+  market_id numeric(20,0),
+  order_ids numeric(39,0)[]
+) RETURNS json AS $$
+DECLARE
+  result json;
+BEGIN
+  IF cardinality($2) > 200 THEN
+    RAISE sqlstate '22023' USING message = 'At most 200 order IDs can be requested at once';
+  END IF;
+  WITH requested AS NOT MATERIALIZED (
+    SELECT DISTINCT id
+    FROM unnest($2) AS id
+  ),
+  present AS NOT MATERIALIZED (
+    SELECT *
+    FROM api.orders
+    WHERE orders.market_id = $1
+    AND orders.order_id = ANY($2)
+  ),
+  pruned AS NOT MATERIALIZED (
+    SELECT pruned_orders.order_id
+    FROM api.pruned_orders
+    WHERE pruned_orders.market_id = $1
+    AND pruned_orders.order_id = ANY($2)
+  )
+  SELECT json_build_object(
+    'orders',
+    COALESCE((SELECT json_agg(present ORDER BY present.order_id) FROM present), '[]'),
+    'pruned',
+    COALESCE((SELECT json_agg(pruned.order_id ORDER BY pruned.order_id) FROM pruned), '[]'),
+    'not_found',
+    COALESCE((
+      SELECT json_agg(requested.id ORDER BY requested.id)
+      FROM requested
+      WHERE requested.id NOT IN (SELECT present.order_id FROM present)
+      AND requested.id NOT IN (SELECT pruned.order_id FROM pruned)
+    ), '[]')
+  ) INTO result;
+  RETURN result;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+
+DROP FUNCTION api.validate_ids;
@@ -0,0 +1,75 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market_id`: The market ID to compute the statistics of
+-- * `start_time`: Start of the time range in which orders were placed (inclusive)
+-- * `end_time`: End of the time range in which orders were placed (exclusive)
+--
+-- Returns:
+-- * How long limit orders placed in the time range waited for their first
+--   fill, and for their last one if they were fully filled, in seconds.
+--   Orders that are still open or were cancelled count towards the first fill
+--   statistics if they were partially filled, but not towards the full fill
+--   ones. Statistics are null when no order was filled.
+--
+-- Raises a 400 if the time range is empty or reversed.
+CREATE FUNCTION api.fill_latency (
+    market_id numeric(20,0),
+    start_time timestamptz,
+    end_time timestamptz
+) RETURNS TABLE (
+    orders_placed bigint,
+    orders_filled bigint,
+    orders_fully_filled bigint,
+    median_seconds_to_first_fill numeric,
+    p90_seconds_to_first_fill numeric,
+    mean_seconds_to_first_fill numeric,
+    median_seconds_to_full_fill numeric,
+    p90_seconds_to_full_fill numeric,
+    mean_seconds_to_full_fill numeric
+) AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    PERFORM api.validate_time_range($2, $3);
+    RETURN QUERY
+    WITH latencies AS (
+        SELECT
+            o.close_reason = 'filled' AS fully_filled,
+            EXTRACT(EPOCH FROM f.first_fill_time - o.created_at) AS to_first_fill,
+            EXTRACT(EPOCH FROM f.last_fill_time - o.created_at) AS to_last_fill
+        FROM api.orders AS o
+        CROSS JOIN LATERAL (
+            -- A limit order is filled as a maker, or as a taker when it
+            -- crosses the spread on placement.
+            SELECT
+                MIN(fills."time") AS first_fill_time,
+                MAX(fills."time") AS last_fill_time
+            FROM (
+                SELECT e."time"
+                FROM api.fill_events AS e
+                WHERE e.market_id = $1
+                AND e.maker_order_id = o.order_id
+                UNION ALL
+                SELECT e."time"
+                FROM api.fill_events AS e
+                WHERE e.market_id = $1
+                AND e.taker_order_id = o.order_id
+            ) AS fills
+        ) AS f
+        WHERE o.market_id = $1
+        AND o.order_type = 'limit'
+        AND o.created_at >= $2
+        AND o.created_at < $3
+    )
+    SELECT
+        COUNT(*),
+        COUNT(*) FILTER (WHERE to_first_fill IS NOT NULL),
+        COUNT(*) FILTER (WHERE fully_filled),
+        (PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY to_first_fill))::numeric,
+        (PERCENTILE_CONT(0.9) WITHIN GROUP (ORDER BY to_first_fill))::numeric,
+        AVG(to_first_fill),
+        (PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY to_last_fill) FILTER (WHERE fully_filled))::numeric,
+        (PERCENTILE_CONT(0.9) WITHIN GROUP (ORDER BY to_last_fill) FILTER (WHERE fully_filled))::numeric,
+        AVG(to_last_fill) FILTER (WHERE fully_filled)
+    FROM latencies;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,51 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `address`: The address of the user
+-- * `duration`: The length of the rolling window ending now, e.g. `30 days`,
+--   at most 90 days
+--
+-- Returns:
+-- * `window_start` and `window_end`: The bounds of the window
+-- * `markets`: For each market the user took liquidity on during the window,
+--   the number of fills, the base volume (in lots) and the quote volume (in
+--   quote subunits) of the user as a taker. Fills where the user was only the
+--   maker are not counted.
+--
+-- Raises a 400 if the duration is not positive or longer than 90 days.
+CREATE FUNCTION api.user_taker_volume (
+    address varchar(70),
+    duration interval DEFAULT '30 days'
+) RETURNS json AS $$
+DECLARE
+    end_time timestamptz := now();
+BEGIN
+    IF $2 IS NULL OR $2 <= interval '0' THEN
+        RAISE sqlstate '22023' USING message = 'duration must be positive';
+    END IF;
+    IF $2 > interval '90 days' THEN
+        RAISE sqlstate '22023' USING message = 'duration must be at most 90 days';
+    END IF;
+    RETURN json_build_object(
+        'window_start', end_time - $2,
+        'window_end', end_time,
+        'markets', COALESCE((
+            SELECT json_agg(v ORDER BY v.market_id)
+            FROM (
+                SELECT
+                    f.market_id,
+                    COUNT(*) AS fill_count,
+                    SUM(f."size") AS base_volume,
+                    SUM(f."size" * f.price * m.tick_size) AS quote_volume
+                FROM fill_events AS f
+                INNER JOIN market_registration_events AS m ON m.market_id = f.market_id
+                WHERE f.taker_address = $1
+                -- Fills are emitted to both the maker and the taker, keep only one of them.
+                AND f.emit_address = f.maker_address
+                AND f."time" >= end_time - $2
+                AND f."time" < end_time
+                GROUP BY f.market_id
+            ) AS v
+        ), '[]'::json)
+    );
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,97 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `after_txn_version`, `after_event_idx`, `before_time`: The cursor of a
+--   request to `api.trades`
+--
+-- Returns:
+-- * True, raising a 400 if both a time cursor and a version cursor are given
+--
+-- Meant for the `WHERE` clause of `api.trades`, like
+-- `api.require_registered_market`.
+CREATE FUNCTION api.require_trades_cursor (
+    after_txn_version numeric(20,0),
+    after_event_idx numeric(20,0),
+    before_time timestamptz
+) RETURNS boolean AS $$
+BEGIN
+    IF $3 IS NOT NULL AND ($1 IS NOT NULL OR $2 IS NOT NULL) THEN
+        RAISE sqlstate '22023' USING
+            message = 'before_time cannot be combined with after_txn_version or after_event_idx';
+    END IF;
+    RETURN true;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+
+DROP FUNCTION api.trades;
+
+
+-- Parameters:
+-- * `market_id`: The market ID that trades are queried for
+-- * `after_txn_version`: Optional cursor, only trades older than it are returned
+-- * `after_event_idx`: Optional cursor, used together with `after_txn_version`
+-- * `before_time`: Optional cursor, only trades older than this time are
+--   returned. Trades of the same time are ordered by transaction version and
+--   event index, so page on from the last trade returned with
+--   `after_txn_version` and `after_event_idx` to not skip any of them.
+--
+-- Returns:
+-- * The trades of the market, newest first, where `side` is the side of the taker.
+--   Only the taker pays a fee, makers pay none and get no rebate.
+--
+-- Raises a 400 if both a time cursor and a version cursor are given.
+--
+-- Written in SQL rather than plpgsql so that it gets inlined: the `limit` of
+-- the request then stops the walk of the fills early instead of sorting all of
+-- them. The market and the cursor are checked in the `WHERE` clause instead.
+CREATE FUNCTION api.trades (
+    market_id numeric(20,0),
+    after_txn_version numeric(20,0) DEFAULT NULL,
+    after_event_idx numeric(20,0) DEFAULT NULL,
+    before_time timestamptz DEFAULT NULL
+) RETURNS TABLE (
+    txn_version numeric(20,0),
+    event_idx numeric(20,0),
+    "time" timestamptz,
+    price numeric(20,0),
+    "size" numeric(20,0),
+    side text,
+    maker_address varchar(70),
+    maker_custodian_id numeric(20,0),
+    maker_order_id numeric(39,0),
+    taker_address varchar(70),
+    taker_custodian_id numeric(20,0),
+    taker_order_id numeric(39,0),
+    taker_quote_fees_paid numeric(20,0)
+) AS $$
+    SELECT
+        f.txn_version,
+        f.event_idx,
+        f."time",
+        f.price,
+        f."size",
+        -- The taker buys from an ask maker and sells to a bid maker.
+        CASE
+            WHEN f.maker_side = true THEN 'buy'
+            ELSE 'sell'
+        END AS side,
+        f.maker_address,
+        f.maker_custodian_id,
+        f.maker_order_id,
+        f.taker_address,
+        f.taker_custodian_id,
+        f.taker_order_id,
+        f.taker_quote_fees_paid
+    FROM fill_events AS f
+    WHERE api.require_registered_market($1)
+    AND api.require_trades_cursor($2, $3, $4)
+    AND f.market_id = $1
+    -- Fills are emitted to both the maker and the taker, keep only one of them.
+    AND f.emit_address = f.maker_address
+    AND (
+        $2 IS NULL
+        OR (f.txn_version, f.event_idx) < ($2, COALESCE($3, 0))
+    )
+    AND ($4 IS NULL OR f."time" < $4)
+    ORDER BY f.txn_version DESC, f.event_idx DESC;
+$$ LANGUAGE SQL STABLE;
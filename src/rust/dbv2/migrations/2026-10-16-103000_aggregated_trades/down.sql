@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE aggregator.trades_last_indexed_txn;
+
+DROP TABLE aggregator.trades;
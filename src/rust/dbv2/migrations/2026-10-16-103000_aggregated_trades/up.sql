@@ -0,0 +1,45 @@
+-- Your SQL goes here
+-- One row per trade, deduplicated from the fills emitted to both the maker and
+-- the taker by the `Trades` pipeline of the aggregator.
+--
+-- `trade_id` is the transaction version and event index of the first fill
+-- emitted for the trade, so it never changes once the trade is aggregated.
+-- `side` is the side of the taker.
+CREATE TABLE aggregator.trades (
+  trade_id TEXT GENERATED ALWAYS AS (txn_version::text || '-' || event_idx::text) STORED NOT NULL UNIQUE,
+  txn_version NUMERIC(20,0) NOT NULL,
+  event_idx NUMERIC(20,0) NOT NULL,
+  market_id NUMERIC(20,0) NOT NULL,
+  sequence_number_for_trade NUMERIC(20,0) NOT NULL,
+  "time" TIMESTAMPTZ NOT NULL,
+  price NUMERIC(20,0) NOT NULL,
+  "size" NUMERIC(20,0) NOT NULL,
+  side TEXT NOT NULL,
+  maker_address VARCHAR(70) NOT NULL,
+  maker_custodian_id NUMERIC(20,0) NOT NULL,
+  maker_order_id NUMERIC(39,0) NOT NULL,
+  taker_address VARCHAR(70) NOT NULL,
+  taker_custodian_id NUMERIC(20,0) NOT NULL,
+  taker_order_id NUMERIC(39,0) NOT NULL,
+  taker_quote_fees_paid NUMERIC(20,0) NOT NULL,
+  PRIMARY KEY (txn_version, event_idx),
+  -- Identifies the trade whichever of its fills it is read from, which also
+  -- deduplicates self trades, whose two fills are both emitted to the maker.
+  -- Sequence numbers only count the fills of one taker order, so a maker
+  -- order filled by several takers has several trades of the same number.
+  UNIQUE (market_id, taker_order_id, sequence_number_for_trade)
+);
+
+
+CREATE INDEX trades_market_id_txn_version_event_idx ON aggregator.trades (market_id, txn_version DESC, event_idx DESC);
+
+
+CREATE TABLE aggregator.trades_last_indexed_txn (
+  txn_version NUMERIC(20,0) NOT NULL,
+  PRIMARY KEY (txn_version)
+);
+
+
+GRANT
+SELECT
+  ON aggregator.trades, aggregator.trades_last_indexed_txn TO grafana;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.market_overview;
+
+DROP FUNCTION api.stale_data_warnings;
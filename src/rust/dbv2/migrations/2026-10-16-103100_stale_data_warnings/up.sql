@@ -0,0 +1,80 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `pipelines`: The pipelines the data of an endpoint comes from, as `LIKE`
+--   patterns (e.g. `Candlesticks(%)`)
+--
+-- Returns:
+-- * One warning per pipeline among them that is lagging, as tripped by the
+--   aggregator lag monitor, with its lag in transaction versions and since
+--   when it lags, or an empty array
+--
+-- Meant to build the `warnings` of endpoints combining the data of several
+-- pipelines, which answer with the data they have instead of failing while
+-- one of them is lagging.
+CREATE FUNCTION api.stale_data_warnings (
+    pipelines text[]
+) RETURNS json AS $$
+    SELECT COALESCE(json_agg(json_build_object(
+        'pipeline', l.pipeline,
+        'lag', l.lag,
+        'stale_since', l.tripped_at,
+        'message', l.pipeline || ' is ' || l.lag || ' versions behind, its data may be stale'
+    ) ORDER BY l.pipeline), '[]'::json)
+    FROM aggregator.lagging_pipelines AS l
+    WHERE l.pipeline LIKE ANY ($1);
+$$ LANGUAGE SQL STABLE SECURITY DEFINER SET search_path = '';
+
+
+-- Parameters:
+-- * `market_id`: The market ID to get an overview of
+--
+-- Returns:
+-- * `data`: The best bid and ask of the market (from the `UserHistory`
+--   pipeline), the close price of its last minute with fills (from
+--   `Prices`) and the taker fees it collected over the last 24 hours (from
+--   `Fees`). Prices are in ticks and fees in quote subunits.
+-- * `warnings`: The pipelines among those that are lagging, as returned by
+--   `api.stale_data_warnings`
+--
+-- Answers with the data available even while a pipeline is lagging. Raises a
+-- 404 if the market is not registered.
+CREATE FUNCTION api.market_overview (
+    market_id numeric(20,0)
+) RETURNS json AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    RETURN json_build_object(
+        'data', json_build_object(
+            'market_id', $1,
+            'best_bid', (
+                SELECT MAX(u.price)
+                FROM aggregator.user_history AS u
+                WHERE u.market_id = $1
+                AND u.order_status = 'open'
+                AND u.direction = 'bid'
+            ),
+            'best_ask', (
+                SELECT MIN(u.price)
+                FROM aggregator.user_history AS u
+                WHERE u.market_id = $1
+                AND u.order_status = 'open'
+                AND u.direction = 'ask'
+            ),
+            'last_price', (
+                SELECT p."close"
+                FROM aggregator.prices AS p
+                WHERE p.market_id = $1
+                ORDER BY p.start_time_1m_period DESC
+                LIMIT 1
+            ),
+            'fees_24h', (
+                SELECT COALESCE(SUM(f.fees_in_quote_subunits), 0)
+                FROM aggregator.fees AS f
+                WHERE f.market_id = $1
+                AND f.start_time_1hr_period >= now() - interval '24 hours'
+            )
+        ),
+        'warnings', api.stale_data_warnings(ARRAY['UserHistory', 'Prices', 'Fees'])
+    );
+END;
+$$ LANGUAGE plpgsql STABLE SECURITY DEFINER SET search_path = '';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.cancelled_orders;
+
+DROP INDEX aggregator.user_history_cancelled;
@@ -0,0 +1,54 @@
+-- Your SQL goes here
+CREATE INDEX user_history_cancelled ON aggregator.user_history (market_id, last_updated_at, order_id)
+WHERE order_status = 'cancelled';
+
+
+-- Parameters:
+-- * `market_id`: The market ID of the orders
+-- * `start_time`: Start of the time range of the cancels (inclusive)
+-- * `end_time`: End of the time range of the cancels (exclusive)
+-- * `after_time`: Optional cursor, the `last_updated_at` of the last order
+--   already received
+-- * `after_order_id`: Optional cursor, the `order_id` of the last order
+--   already received, used together with `after_time`
+-- * `user`: Optional, only the orders of this address are returned
+-- * `custodian_id`: Optional, only the orders of this custodian are returned
+--
+-- Returns:
+-- * At most 1000 cancelled orders, oldest cancel first, with the columns of
+--   `api.orders`. `last_updated_at` is the time of the cancel, and
+--   `close_reason` is `ioc_expired` for immediate-or-cancel orders that did
+--   not fully fill and `cancelled` otherwise.
+--
+-- Raises a 400 if the time range is empty or reversed, or if only one of the
+-- cursor parameters is given.
+CREATE FUNCTION api.cancelled_orders (
+    market_id numeric(20,0),
+    start_time timestamptz,
+    end_time timestamptz,
+    after_time timestamptz DEFAULT NULL,
+    after_order_id numeric(39,0) DEFAULT NULL,
+    "user" varchar(70) DEFAULT NULL,
+    custodian_id numeric(20,0) DEFAULT NULL
+) RETURNS SETOF api.orders AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    PERFORM api.validate_time_range($2, $3);
+    IF ($4 IS NULL) <> ($5 IS NULL) THEN
+        RAISE sqlstate '22023' USING
+            message = 'after_time and after_order_id must be given together';
+    END IF;
+    RETURN QUERY
+    SELECT o.*
+    FROM api.orders AS o
+    WHERE o.market_id = $1
+    AND o.order_status = 'cancelled'
+    AND o.last_updated_at >= $2
+    AND o.last_updated_at < $3
+    AND ($4 IS NULL OR (o.last_updated_at, o.order_id) > ($4, $5))
+    AND ($6 IS NULL OR o."user" = $6)
+    AND ($7 IS NULL OR o.custodian_id = $7)
+    ORDER BY o.last_updated_at, o.order_id
+    LIMIT 1000;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,55 @@
+-- Your SQL goes here
+CREATE INDEX user_history_integrator_created_at ON aggregator.user_history (integrator, created_at, market_id, order_id);
+
+
+-- Parameters:
+-- * `integrator`: The integrator address the orders were placed through. Leading
+--   zeros are ignored, so orders placed without an integrator can be queried
+--   with `0x0`.
+-- * `market_id`: Optional, only the orders of this market are returned
+-- * `status`: Optional, one of `open`, `closed` or `cancelled`
+-- * `after_time`: Optional cursor, the `created_at` of the last order already
+--   received
+-- * `after_market_id`: Optional cursor, its `market_id`
+-- * `after_order_id`: Optional cursor, its `order_id`
+--
+-- Returns:
+-- * At most 1000 orders placed through the integrator, oldest first, with the
+--   columns of `api.orders`
+--
+-- Raises a 400 if the status is unknown, or if the cursor parameters are not
+-- all given together.
+CREATE FUNCTION api.integrator_orders (
+    integrator text,
+    market_id numeric(20,0) DEFAULT NULL,
+    status text DEFAULT NULL,
+    after_time timestamptz DEFAULT NULL,
+    after_market_id numeric(20,0) DEFAULT NULL,
+    after_order_id numeric(39,0) DEFAULT NULL
+) RETURNS SETOF api.orders AS $$
+DECLARE
+    -- Addresses are stored without leading zeros.
+    address text := regexp_replace(lower($1), '^0x0*', '0x');
+BEGIN
+    IF address = '0x' THEN
+        address := '0x0';
+    END IF;
+    IF $3 IS NOT NULL AND $3 NOT IN ('open', 'closed', 'cancelled') THEN
+        RAISE sqlstate '22023' USING
+            message = 'status must be one of open, closed or cancelled';
+    END IF;
+    IF ($4 IS NULL) <> ($5 IS NULL) OR ($4 IS NULL) <> ($6 IS NULL) THEN
+        RAISE sqlstate '22023' USING
+            message = 'after_time, after_market_id and after_order_id must be given together';
+    END IF;
+    RETURN QUERY
+    SELECT o.*
+    FROM api.orders AS o
+    WHERE o.integrator = address
+    AND ($2 IS NULL OR o.market_id = $2)
+    AND ($3 IS NULL OR o.order_status::text = $3)
+    AND ($4 IS NULL OR (o.created_at, o.market_id, o.order_id) > ($4, $5, $6))
+    ORDER BY o.created_at, o.market_id, o.order_id
+    LIMIT 1000;
+END;
+$$ LANGUAGE plpgsql STABLE;
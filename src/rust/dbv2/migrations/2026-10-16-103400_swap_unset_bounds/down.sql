@@ -0,0 +1,15 @@
+-- This file should undo anything in `up.sql`
+UPDATE aggregator.user_history
+SET price = CASE direction
+    WHEN 'sell' THEN 0
+    ELSE 4294967295
+END
+WHERE order_type = 'swap'
+AND price IS NULL;
+
+
+UPDATE aggregator.user_history
+SET max_base = COALESCE(max_base, 18446744073709551615),
+    max_quote = COALESCE(max_quote, 18446744073709551615)
+WHERE order_type = 'swap'
+AND (max_base IS NULL OR max_quote IS NULL);
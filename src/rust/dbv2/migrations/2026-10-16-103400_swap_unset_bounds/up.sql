@@ -0,0 +1,22 @@
+-- Your SQL goes here
+-- Swap orders placed without a price limit or without a maximum size carry
+-- sentinel values on chain: the highest price (`HI_PRICE`) for a buy and 0 for
+-- a sell, and `MAX_POSSIBLE` for `max_base` and `max_quote`. In
+-- `aggregator.user_history`, those are stored as NULL, so that `price`,
+-- `max_base` and `max_quote` are NULL exactly when the order is unbounded on
+-- that side. `min_base` and `min_quote` keep 0 for no minimum, which is what 0
+-- means for them.
+UPDATE aggregator.user_history
+SET price = NULL
+WHERE order_type = 'swap'
+AND (
+    (direction = 'sell' AND price = 0)
+    OR (direction = 'buy' AND price = 4294967295)
+);
+
+
+UPDATE aggregator.user_history
+SET max_base = NULLIF(max_base, 18446744073709551615),
+    max_quote = NULLIF(max_quote, 18446744073709551615)
+WHERE order_type = 'swap'
+AND (max_base = 18446744073709551615 OR max_quote = 18446744073709551615);
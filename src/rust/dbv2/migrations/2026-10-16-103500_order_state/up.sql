@@ -0,0 +1,79 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market_id`: The market ID of the order
+-- * `order_id`: The order ID of the order
+--
+-- Returns:
+-- * A JSON object with the current state of the order (`order_status`,
+--   `order_type`, `remaining_size`, `total_filled`, `created_at`,
+--   `last_updated_at`, ...) and the placement details of its type only:
+--   * limit orders: `direction`, `price`, `restriction` and
+--     `self_match_behavior`
+--   * market orders: `direction` and `self_match_behavior`
+--   * swap orders: `direction`, `limit_price`, `min_base`, `max_base`,
+--     `min_quote` and `max_quote`, where `limit_price`, `max_base` and
+--     `max_quote` are null when the swap is unbounded on that side
+--
+-- Raises a 410 if the order has been pruned and a 404 if it never existed.
+CREATE FUNCTION api.order_state (
+  market_id numeric(20,0),
+  order_id numeric(39,0)
+) RETURNS json AS $$
+DECLARE
+  o api.orders;
+BEGIN
+  SELECT * INTO o
+  FROM api.orders
+  WHERE orders.market_id = $1
+  AND orders.order_id = $2;
+  IF NOT FOUND THEN
+    IF EXISTS (
+      SELECT
+      FROM api.pruned_orders
+      WHERE pruned_orders.market_id = $1
+      AND pruned_orders.order_id = $2
+    ) THEN
+      RAISE sqlstate 'PT410' USING message = 'Order has been pruned';
+    END IF;
+    RAISE sqlstate 'PT404' USING message = 'Order not found';
+  END IF;
+  RETURN (
+    jsonb_build_object(
+      'market_id', o.market_id,
+      'order_id', o.order_id,
+      'user', o."user",
+      'custodian_id', o.custodian_id,
+      'integrator', o.integrator,
+      'order_status', o.order_status,
+      'order_type', o.order_type,
+      'close_reason', o.close_reason,
+      'remaining_size', o.remaining_size,
+      'total_filled', o.total_filled,
+      'average_execution_price', o.average_execution_price,
+      'total_fees_paid_in_quote_subunits', o.total_fees_paid_in_quote_subunits,
+      'created_at', o.created_at,
+      'last_updated_at', o.last_updated_at
+    )
+    || CASE o.order_type
+      WHEN 'limit' THEN jsonb_build_object(
+        'direction', o.direction,
+        'price', o.price,
+        'restriction', o.restriction,
+        'self_match_behavior', o.self_match_behavior
+      )
+      WHEN 'market' THEN jsonb_build_object(
+        'direction', o.direction,
+        'self_match_behavior', o.self_match_behavior
+      )
+      WHEN 'swap' THEN jsonb_build_object(
+        'direction', o.direction,
+        'limit_price', o.price,
+        'min_base', o.min_base,
+        'max_base', o.max_base,
+        'min_quote', o.min_quote,
+        'max_quote', o.max_quote
+      )
+    END
+  )::json;
+END;
+$$ LANGUAGE plpgsql STABLE;
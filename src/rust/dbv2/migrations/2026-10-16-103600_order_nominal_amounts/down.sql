@@ -0,0 +1,17 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.filled_quote_nominal;
+
+
+DROP FUNCTION api.filled_base_nominal;
+
+
+DROP FUNCTION api.remaining_base_nominal;
+
+
+DROP FUNCTION api.price_nominal;
+
+
+DROP FUNCTION size_and_price_to_quote_nominal;
+
+
+DROP FUNCTION size_to_base_nominal;
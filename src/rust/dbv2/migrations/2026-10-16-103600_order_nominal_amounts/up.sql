@@ -0,0 +1,69 @@
+-- Your SQL goes here
+CREATE FUNCTION size_to_base_nominal(market_id numeric, "size" numeric) RETURNS NUMERIC IMMUTABLE AS $$
+    SELECT $2 * lot_size / POW(10,COALESCE(base.decimals, 0))
+    FROM market_registration_events
+    LEFT JOIN api.coins base
+    ON base_account_address = base."address" AND base_module_name = base.module AND base_struct_name = base.struct
+    WHERE market_id = $1;
+$$ LANGUAGE sql;
+
+
+CREATE FUNCTION size_and_price_to_quote_nominal(market_id numeric, "size" numeric, price numeric) RETURNS NUMERIC IMMUTABLE AS $$
+    SELECT size_and_price_to_quote_indivisible_subunits($1, $2, $3) / POW(10,decimals)
+    FROM market_registration_events AS m
+    INNER JOIN api.coins AS c
+    ON m.quote_account_address = c."address"
+    AND m.quote_module_name = c.module
+    AND m.quote_struct_name = c.struct
+    WHERE market_id = $1;
+$$ LANGUAGE sql;
+
+
+-- The functions below are exposed by PostgREST as computed columns of
+-- `api.orders`. They are only computed when selected, e.g.
+-- `/orders?select=*,price_nominal,remaining_base_nominal`, so clients that
+-- only want the integer lots and ticks do not pay for them.
+
+-- Parameters:
+-- * `order`: A row of `api.orders`
+--
+-- Returns:
+-- * The price of the order in quote coins per base coin, taking the decimals
+--   of both coins into account
+CREATE FUNCTION api.price_nominal ("order" api.orders)
+RETURNS numeric AS $$
+    SELECT integer_price_to_quote_nominal($1.market_id, $1.price);
+$$ LANGUAGE SQL STABLE;
+
+
+-- Parameters:
+-- * `order`: A row of `api.orders`
+--
+-- Returns:
+-- * The remaining size of the order in base coins
+CREATE FUNCTION api.remaining_base_nominal ("order" api.orders)
+RETURNS numeric AS $$
+    SELECT size_to_base_nominal($1.market_id, $1.remaining_size);
+$$ LANGUAGE SQL STABLE;
+
+
+-- Parameters:
+-- * `order`: A row of `api.orders`
+--
+-- Returns:
+-- * The filled size of the order in base coins
+CREATE FUNCTION api.filled_base_nominal ("order" api.orders)
+RETURNS numeric AS $$
+    SELECT size_to_base_nominal($1.market_id, $1.total_filled);
+$$ LANGUAGE SQL STABLE;
+
+
+-- Parameters:
+-- * `order`: A row of `api.orders`
+--
+-- Returns:
+-- * The quote coins exchanged by the fills of the order, before fees
+CREATE FUNCTION api.filled_quote_nominal ("order" api.orders)
+RETURNS numeric AS $$
+    SELECT size_and_price_to_quote_nominal($1.market_id, $1.total_filled, $1.average_execution_price);
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,64 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market_id`: The market ID of the fills
+-- * `start_time`: Start of the time range (inclusive), and start of the first
+--   bucket
+-- * `end_time`: End of the time range (exclusive)
+-- * `bucket`: The duration of one bucket, in seconds
+--
+-- Returns:
+-- * One row per bucket of the time range, oldest first, including buckets
+--   without fills. `buy_volume` and `sell_volume` are the volumes (in lots)
+--   of the fills whose taker bought and sold, and `imbalance` is
+--   `(buy_volume - sell_volume) / (buy_volume + sell_volume)`, between -1
+--   and 1, or NULL for buckets without fills.
+--
+-- Raises a 400 if the bucket is not positive or the time range is empty or
+-- reversed, and a 422 if the time range spans too many buckets.
+CREATE FUNCTION api.flow_imbalance (
+    market_id numeric(20,0),
+    start_time timestamptz,
+    end_time timestamptz,
+    bucket int
+) RETURNS TABLE (
+    bucket_start_time timestamptz,
+    buy_volume numeric,
+    sell_volume numeric,
+    imbalance numeric
+) AS $$
+BEGIN
+    PERFORM FROM api.registered_market($1);
+    IF $4 IS NULL OR $4 <= 0 THEN
+        RAISE sqlstate '22023' USING message = 'bucket must be a positive number of seconds';
+    END IF;
+    PERFORM api.validate_time_range($2, $3);
+    PERFORM api.validate_result_window($2, $3, $4);
+    RETURN QUERY
+    WITH volumes AS (
+        SELECT
+            date_bin(make_interval(secs => $4), f."time", $2) AS bucket_start_time,
+            -- The maker of an ask is sold to, so the taker bought.
+            COALESCE(SUM(f."size") FILTER (WHERE f.maker_side = true), 0) AS buy_volume,
+            COALESCE(SUM(f."size") FILTER (WHERE f.maker_side = false), 0) AS sell_volume
+        FROM fill_events AS f
+        WHERE f.market_id = $1
+        -- Fills are emitted to both the maker and the taker, keep only one of them.
+        AND f.emit_address = f.maker_address
+        AND f."time" >= $2
+        AND f."time" < $3
+        GROUP BY 1
+    ), buckets AS (
+        SELECT b AS bucket_start_time
+        FROM generate_series($2, $3 - interval '1 microsecond', make_interval(secs => $4)) AS b
+    )
+    SELECT
+        buckets.bucket_start_time,
+        COALESCE(volumes.buy_volume, 0),
+        COALESCE(volumes.sell_volume, 0),
+        (volumes.buy_volume - volumes.sell_volume)
+            / NULLIF(volumes.buy_volume + volumes.sell_volume, 0)
+    FROM buckets
+    LEFT JOIN volumes ON volumes.bucket_start_time = buckets.bucket_start_time
+    ORDER BY 1;
+END;
+$$ LANGUAGE plpgsql STABLE;
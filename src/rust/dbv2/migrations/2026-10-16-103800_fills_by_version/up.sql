@@ -0,0 +1,41 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `from_version`: First transaction version of the range (inclusive)
+-- * `to_version`: Last transaction version of the range (inclusive)
+-- * `market_id`: Optional, only the fills of this market are returned
+--
+-- Returns:
+-- * The fills of the transactions of the range, once each (fills are emitted
+--   to both the maker and the taker), ordered by transaction version and
+--   event index
+--
+-- Meant for debugging and auditing, e.g. to see what happened in a given set
+-- of transactions. Raises a 400 if the range is reversed or spans more than
+-- 100000 versions.
+CREATE FUNCTION api.fills_by_version (
+    from_version numeric(20,0),
+    to_version numeric(20,0),
+    market_id numeric(20,0) DEFAULT NULL
+) RETURNS SETOF api.fill_events_deduped AS $$
+BEGIN
+    IF $1 IS NULL OR $2 IS NULL THEN
+        RAISE sqlstate '22023' USING message = 'from_version and to_version are required';
+    END IF;
+    IF $1 > $2 THEN
+        RAISE sqlstate '22023' USING message = 'from_version must not be after to_version';
+    END IF;
+    IF $2 - $1 >= 100000 THEN
+        RAISE sqlstate '22023' USING message = 'The range must span at most 100000 versions';
+    END IF;
+    IF $3 IS NOT NULL THEN
+        PERFORM FROM api.registered_market($3);
+    END IF;
+    RETURN QUERY
+    SELECT f.*
+    FROM api.fill_events_deduped AS f
+    WHERE f.txn_version >= $1
+    AND f.txn_version <= $2
+    AND ($3 IS NULL OR f.market_id = $3)
+    ORDER BY f.txn_version, f.event_idx;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.user_pnl;
+
+
+DROP FUNCTION match_fills_pnl;
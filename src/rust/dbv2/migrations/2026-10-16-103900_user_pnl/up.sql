@@ -0,0 +1,165 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `sizes`: The sizes of a sequence of fills, positive for buys and negative
+--   for sells
+-- * `prices`: The prices of the fills
+-- * `method`: How sells are matched against buys (and buys against short
+--   sells): `fifo` matches them with the oldest open fills first, `avg`
+--   with the average price of the open position
+--
+-- Returns:
+-- * `realized_pnl`: The sum of `size * (exit price - entry price)` over the
+--   matched sizes, negated for short positions
+-- * `position`: The size left open, negative for a short position
+-- * `cost_basis`: The sum of `size * price` over the size left open, always
+--   positive
+--
+-- A fill larger than the open position closes it and opens one in the other
+-- direction at its price. Pure, so that both methods can be checked on their
+-- own against hand computations.
+CREATE FUNCTION match_fills_pnl (
+    sizes numeric[],
+    prices numeric[],
+    method text
+) RETURNS TABLE (
+    realized_pnl numeric,
+    "position" numeric,
+    cost_basis numeric
+) AS $$
+DECLARE
+    -- Open fills, oldest first, for `fifo`.
+    open_sizes numeric[] := '{}';
+    open_prices numeric[] := '{}';
+    -- Average price of the open position, for `avg`.
+    average_price numeric := 0;
+    matched numeric;
+    remaining numeric;
+    i int;
+BEGIN
+    IF $3 IS NULL OR $3 NOT IN ('fifo', 'avg') THEN
+        RAISE sqlstate '22023' USING message = 'method must be fifo or avg';
+    END IF;
+    realized_pnl := 0;
+    "position" := 0;
+    FOR i IN 1 .. COALESCE(cardinality($1), 0) LOOP
+        remaining := $1[i];
+        IF $3 = 'fifo' THEN
+            WHILE remaining <> 0 AND cardinality(open_sizes) > 0
+            AND sign(open_sizes[1]) <> sign(remaining) LOOP
+                matched := least(abs(remaining), abs(open_sizes[1]));
+                realized_pnl := realized_pnl
+                    + matched * ($2[i] - open_prices[1]) * sign(open_sizes[1]);
+                open_sizes[1] := open_sizes[1] - matched * sign(open_sizes[1]);
+                remaining := remaining - matched * sign(remaining);
+                IF open_sizes[1] = 0 THEN
+                    open_sizes := open_sizes[2:];
+                    open_prices := open_prices[2:];
+                END IF;
+            END LOOP;
+            IF remaining <> 0 THEN
+                open_sizes := open_sizes || remaining;
+                open_prices := open_prices || $2[i];
+            END IF;
+        ELSE
+            IF "position" <> 0 AND sign("position") <> sign(remaining) THEN
+                matched := least(abs(remaining), abs("position"));
+                realized_pnl := realized_pnl
+                    + matched * ($2[i] - average_price) * sign("position");
+                "position" := "position" - matched * sign("position");
+                remaining := remaining - matched * sign(remaining);
+            END IF;
+            IF remaining <> 0 THEN
+                average_price := (abs("position") * average_price + abs(remaining) * $2[i])
+                    / (abs("position") + abs(remaining));
+                "position" := "position" + remaining;
+            END IF;
+        END IF;
+    END LOOP;
+    IF $3 = 'fifo' THEN
+        SELECT COALESCE(SUM(s), 0), COALESCE(SUM(abs(s) * p), 0)
+        INTO "position", cost_basis
+        FROM unnest(open_sizes, open_prices) AS o(s, p);
+    ELSE
+        cost_basis := abs("position") * average_price;
+    END IF;
+    RETURN NEXT;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+
+-- Parameters:
+-- * `address`: The address of the user
+-- * `market_id`: The market ID of the fills
+-- * `start_time`: Start of the time range (inclusive)
+-- * `end_time`: End of the time range (exclusive)
+-- * `method`: `fifo` (the default) or `avg`, see `match_fills_pnl`
+--
+-- Returns:
+-- * `realized_pnl`: The profit and loss of the positions closed by the fills
+--   of the user during the time range, as a maker or a taker, in quote
+--   subunits and before fees
+-- * `position`: The size (in lots) left open at the end of the time range,
+--   negative for a short position
+-- * `cost_basis`: The quote subunits the open position cost
+--
+-- The user is considered flat at the start of the time range, so fills
+-- before it are ignored. A time range without fills returns zeros.
+--
+-- Raises a 400 if the time range is empty or reversed, or if the method is
+-- unknown.
+CREATE FUNCTION api.user_pnl (
+    address varchar(70),
+    market_id numeric(20,0),
+    start_time timestamptz,
+    end_time timestamptz,
+    method text DEFAULT 'fifo'
+) RETURNS TABLE (
+    realized_pnl numeric,
+    "position" numeric,
+    cost_basis numeric
+) AS $$
+DECLARE
+    tick_size numeric;
+BEGIN
+    SELECT m.tick_size INTO tick_size FROM api.registered_market($2) AS m;
+    PERFORM api.validate_time_range($3, $4);
+    RETURN QUERY
+    WITH legs AS (
+        -- The maker of an ask sells, and its taker buys. A user filling their
+        -- own order gets both legs.
+        SELECT
+            f.txn_version,
+            f.event_idx,
+            f.price,
+            CASE WHEN f.maker_side = true THEN -f."size" ELSE f."size" END AS "size"
+        FROM fill_events AS f
+        WHERE f.maker_address = $1
+        AND f.market_id = $2
+        -- Fills are emitted to both the maker and the taker, keep only one of them.
+        AND f.emit_address = f.maker_address
+        AND f."time" >= $3
+        AND f."time" < $4
+        UNION ALL
+        SELECT
+            f.txn_version,
+            f.event_idx,
+            f.price,
+            CASE WHEN f.maker_side = true THEN f."size" ELSE -f."size" END
+        FROM fill_events AS f
+        WHERE f.taker_address = $1
+        AND f.market_id = $2
+        AND f.emit_address = f.maker_address
+        AND f."time" >= $3
+        AND f."time" < $4
+    )
+    SELECT
+        p.realized_pnl * tick_size,
+        p."position",
+        p.cost_basis * tick_size
+    FROM match_fills_pnl(
+        ARRAY(SELECT legs."size" FROM legs ORDER BY legs.txn_version, legs.event_idx),
+        ARRAY(SELECT legs.price FROM legs ORDER BY legs.txn_version, legs.event_idx),
+        $5
+    ) AS p;
+END;
+$$ LANGUAGE plpgsql STABLE;
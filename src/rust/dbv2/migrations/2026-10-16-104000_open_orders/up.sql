@@ -0,0 +1,68 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market_id`: The market ID of the orders
+-- * `after_price`: Optional cursor, the `price` of the last order already
+--   received
+-- * `after_stamp`: Optional cursor, the `priority_stamp` of the last order
+--   already received, used together with `after_price`
+-- * `max_orders`: The maximum number of orders returned, at most 1000
+--
+-- Returns:
+-- * The open limit orders of the market, one row per order, ordered by
+--   price and then by `priority_stamp`, the position of the order in the
+--   queue of its price level (see `api.order_priority`). Bids and asks are
+--   not separated, see `direction`.
+--
+-- Raises a 400 if only one of the cursor parameters is given, or if
+-- `max_orders` is not between 1 and 1000.
+CREATE FUNCTION api.open_orders (
+  market_id numeric(20,0),
+  after_price numeric(20,0) DEFAULT NULL,
+  after_stamp numeric DEFAULT NULL,
+  max_orders int DEFAULT 1000
+) RETURNS TABLE (
+  order_id numeric(39,0),
+  direction order_direction,
+  price numeric(20,0),
+  remaining_size numeric(20,0),
+  created_at timestamptz,
+  "user" text,
+  custodian_id numeric(20,0),
+  priority_stamp numeric
+) AS $$
+BEGIN
+  PERFORM FROM api.registered_market($1);
+  IF ($2 IS NULL) <> ($3 IS NULL) THEN
+    RAISE sqlstate '22023' USING
+      message = 'after_price and after_stamp must be given together';
+  END IF;
+  IF $4 IS NULL OR $4 < 1 OR $4 > 1000 THEN
+    RAISE sqlstate '22023' USING message = 'max_orders must be between 1 and 1000';
+  END IF;
+  RETURN QUERY
+  SELECT *
+  FROM (
+    SELECT
+      o.order_id,
+      o.direction,
+      o.price,
+      o.remaining_size,
+      o.created_at,
+      o."user",
+      o.custodian_id,
+      COALESCE(
+        o.last_increase_stamp,
+        p.txn_version * 18446744073709551616 + p.event_idx
+      ) AS priority_stamp
+    FROM api.orders AS o
+    INNER JOIN api.place_limit_order_events AS p
+    ON p.market_id = o.market_id AND p.order_id = o.order_id
+    WHERE o.market_id = $1
+    AND o.order_status = 'open'
+    AND o.order_type = 'limit'
+  ) AS queued
+  WHERE $2 IS NULL OR (queued.price, queued.priority_stamp) > ($2, $3)
+  ORDER BY queued.price, queued.priority_stamp
+  LIMIT $4;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP VIEW api.pipeline_schemas;
+
+
+ALTER TABLE aggregator.pipelines
+DROP COLUMN writes;
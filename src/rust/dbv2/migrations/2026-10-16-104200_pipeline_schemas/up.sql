@@ -0,0 +1,40 @@
+-- Your SQL goes here
+-- The tables each pipeline writes, as declared by `Pipeline::writes`.
+ALTER TABLE aggregator.pipelines
+ADD COLUMN writes TEXT[] NOT NULL DEFAULT '{}';
+
+
+-- The columns of the tables written by every registered pipeline, with their
+-- types as Postgres formats them (e.g. `numeric(20,0)`), in table order.
+--
+-- Read from the catalog rather than declared by the aggregator, so that it
+-- always matches the tables as migrated. Tables are looked up by name in
+-- pg_class, as to_regclass needs access to the schema of the table.
+CREATE VIEW api.pipeline_schemas AS
+SELECT
+  p.model_name,
+  w.table_name,
+  a.attnum AS "position",
+  a.attname::text AS column_name,
+  format_type(a.atttypid, a.atttypmod) AS data_type,
+  NOT a.attnotnull AS nullable
+FROM
+  aggregator.pipelines AS p
+  CROSS JOIN LATERAL unnest(p.writes) AS w(table_name)
+  INNER JOIN pg_namespace AS n ON n.nspname = split_part(w.table_name, '.', 1)
+  INNER JOIN pg_class AS c ON c.relnamespace = n.oid
+  AND c.relname = split_part(w.table_name, '.', 2)
+  INNER JOIN pg_attribute AS a ON a.attrelid = c.oid
+WHERE
+  a.attnum > 0
+  AND NOT a.attisdropped;
+
+
+GRANT
+SELECT
+  ON api.pipeline_schemas TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.pipeline_schemas TO grafana;
@@ -0,0 +1,39 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market_ids`: Optional, only the tickers of these markets are returned
+--
+-- Returns:
+-- * The ticker of every registered market (see `api.market_ticker`), by
+--   market ID. Markets the `MarketStats24h` pipeline has no statistics for
+--   yet have null prices and zero volumes.
+CREATE FUNCTION api.market_tickers (
+    market_ids numeric(20,0)[] DEFAULT NULL
+) RETURNS TABLE (
+    market_id numeric(20,0),
+    last_price numeric,
+    price_24h_ago numeric,
+    price_change_24h numeric,
+    price_change_percent_24h numeric,
+    base_volume_24h numeric,
+    quote_volume_24h numeric,
+    high_24h numeric,
+    low_24h numeric
+) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT
+        m.market_id,
+        s.last_price,
+        s.price_24h_ago,
+        s.last_price - s.price_24h_ago,
+        (s.last_price - s.price_24h_ago) / s.price_24h_ago * 100,
+        COALESCE(s.base_volume, 0),
+        COALESCE(s.quote_volume, 0),
+        s.high,
+        s.low
+    FROM api.market_registration_events AS m
+    LEFT JOIN aggregator.market_stats_24h AS s ON s.market_id = m.market_id
+    WHERE $1 IS NULL OR m.market_id = ANY($1)
+    ORDER BY m.market_id;
+END;
+$$ LANGUAGE plpgsql STABLE SECURITY DEFINER SET search_path = '';
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP VIEW api.aggregation_lag;
+
+
+DROP FUNCTION aggregator.last_event_time;
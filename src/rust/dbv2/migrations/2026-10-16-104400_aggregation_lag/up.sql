@@ -0,0 +1,57 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `version`: A transaction version
+--
+-- Returns:
+-- * The block time of the last order event at or before the version, as
+--   reported by `x-data-as-of` (see `api.set_data_as_of`), or null if there is
+--   none
+CREATE FUNCTION aggregator.last_event_time (
+  version numeric
+) RETURNS timestamptz AS $$
+  -- One backward scan of the primary key of each event table.
+  SELECT MAX(t) FROM (
+    (SELECT "time" AS t FROM public.fill_events WHERE txn_version <= $1 ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_limit_order_events WHERE txn_version <= $1 ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_market_order_events WHERE txn_version <= $1 ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_swap_order_events WHERE txn_version <= $1 ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.change_order_size_events WHERE txn_version <= $1 ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.cancel_order_events WHERE txn_version <= $1 ORDER BY txn_version DESC LIMIT 1)
+  ) AS last_events;
+$$ LANGUAGE sql STABLE SECURITY DEFINER SET search_path = '';
+
+
+-- How far each pipeline tracking a transaction version is behind the
+-- processor, in transaction versions and in time. `age_seconds` is how long
+-- ago the last event aggregated by the pipeline happened, and is null if it
+-- has not aggregated any.
+CREATE VIEW api.aggregation_lag AS
+WITH processor AS (
+  SELECT last_success_version
+  FROM public.processor_status
+  WHERE processor = 'econia_processor'
+)
+SELECT
+  w.model_name,
+  w.txn_version,
+  (SELECT last_success_version FROM processor) AS source_txn_version,
+  l.lag,
+  EXTRACT(EPOCH FROM CURRENT_TIMESTAMP - aggregator.last_event_time(w.txn_version)) AS age_seconds
+FROM
+  aggregator.pipeline_watermarks AS w
+  LEFT JOIN aggregator.pipeline_lag AS l ON l.pipeline = w.model_name;
+
+
+GRANT
+SELECT
+  ON api.aggregation_lag TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.aggregation_lag TO grafana;
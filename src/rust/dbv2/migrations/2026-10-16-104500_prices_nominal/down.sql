@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION api.close_nominal (api.prices);
+
+
+DROP FUNCTION api.low_nominal (api.prices);
+
+
+DROP FUNCTION api.high_nominal (api.prices);
+
+
+DROP FUNCTION api.open_nominal (api.prices);
+
+
+DROP FUNCTION api.price_nominal (api.prices);
@@ -0,0 +1,59 @@
+-- Your SQL goes here
+-- Computed columns of `api.prices`, like those of `api.orders`, e.g.
+-- `/prices?select=*,price_nominal`. Prices are converted with
+-- `integer_price_to_quote_nominal`, in numeric, so no precision is lost.
+
+-- Parameters:
+-- * `prices`: A row of `api.prices`
+--
+-- Returns:
+-- * The average fill price of the minute in quote coins per base coin, taking
+--   the decimals of both coins into account
+CREATE FUNCTION api.price_nominal (prices api.prices)
+RETURNS numeric AS $$
+    SELECT integer_price_to_quote_nominal($1.market_id, $1."price");
+$$ LANGUAGE SQL STABLE;
+
+
+-- Parameters:
+-- * `prices`: A row of `api.prices`
+--
+-- Returns:
+-- * The price of the first fill of the minute in quote coins per base coin
+CREATE FUNCTION api.open_nominal (prices api.prices)
+RETURNS numeric AS $$
+    SELECT integer_price_to_quote_nominal($1.market_id, $1."open");
+$$ LANGUAGE SQL STABLE;
+
+
+-- Parameters:
+-- * `prices`: A row of `api.prices`
+--
+-- Returns:
+-- * The highest fill price of the minute in quote coins per base coin
+CREATE FUNCTION api.high_nominal (prices api.prices)
+RETURNS numeric AS $$
+    SELECT integer_price_to_quote_nominal($1.market_id, $1."high");
+$$ LANGUAGE SQL STABLE;
+
+
+-- Parameters:
+-- * `prices`: A row of `api.prices`
+--
+-- Returns:
+-- * The lowest fill price of the minute in quote coins per base coin
+CREATE FUNCTION api.low_nominal (prices api.prices)
+RETURNS numeric AS $$
+    SELECT integer_price_to_quote_nominal($1.market_id, $1."low");
+$$ LANGUAGE SQL STABLE;
+
+
+-- Parameters:
+-- * `prices`: A row of `api.prices`
+--
+-- Returns:
+-- * The price of the last fill of the minute in quote coins per base coin
+CREATE FUNCTION api.close_nominal (prices api.prices)
+RETURNS numeric AS $$
+    SELECT integer_price_to_quote_nominal($1.market_id, $1."close");
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,50 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market_id`: The market ID of the order book
+--
+-- Returns:
+-- * A JSON object with the `market_id`, the whole order book of the market as
+--   `bids` and `asks`, arrays of `{"price": ..., "size": ...}` price levels
+--   sorted from the best price, and the `txn_version` the snapshot reflects.
+--   Clients can keep the snapshot up to date by applying order updates of
+--   later transaction versions only.
+--
+-- Raises a 404 if the market does not exist.
+CREATE FUNCTION api.orderbook_snapshot (
+  market_id numeric(20,0)
+) RETURNS json AS $$
+DECLARE
+  snapshot json;
+BEGIN
+  PERFORM FROM api.registered_market($1);
+  -- A single statement reads the levels and the watermark from the same
+  -- snapshot, and the aggregator updates both in the same transaction, so
+  -- the levels reflect exactly the transactions up to the watermark.
+  WITH levels AS (
+    SELECT
+      o.direction,
+      o.price,
+      SUM(o.remaining_size) AS "size"
+    FROM aggregator.user_history AS o
+    WHERE o.market_id = $1
+    AND o.order_status = 'open'
+    AND o.order_type = 'limit'
+    GROUP BY o.direction, o.price
+  )
+  SELECT json_build_object(
+    'market_id', $1,
+    'txn_version', (SELECT MAX(txn_version) FROM aggregator.user_history_last_indexed_txn),
+    'bids', COALESCE(
+      (SELECT json_agg(json_build_object('price', price, 'size', "size") ORDER BY price DESC)
+      FROM levels WHERE direction = 'bid'),
+      '[]'::json
+    ),
+    'asks', COALESCE(
+      (SELECT json_agg(json_build_object('price', price, 'size', "size") ORDER BY price)
+      FROM levels WHERE direction = 'ask'),
+      '[]'::json
+    )
+  ) INTO snapshot;
+  RETURN snapshot;
+END;
+$$ LANGUAGE plpgsql STABLE SECURITY DEFINER SET search_path = '';
@@ -0,0 +1,37 @@
+-- Your SQL goes here
+-- Parameters:
+-- * `market_id`: The market ID to get the info of
+--
+-- Returns:
+-- * `data`: The registration event of the market (coin types, `lot_size`,
+--   `tick_size`, `min_size`, ...), along with the last transaction version
+--   aggregated by the `UserHistory` pipeline as `aggregated_txn_version`, and
+--   the time of the last fill of the market as `last_fill_time`, with its
+--   age in seconds as `last_fill_age_seconds` (both null if it has no fill)
+-- * `warnings`: The `UserHistory` pipeline if it is lagging, as returned by
+--   `api.stale_data_warnings`
+--
+-- Raises a 404 if the market is not registered.
+CREATE FUNCTION api.market_info (
+    market_id numeric(20,0)
+) RETURNS json AS $$
+DECLARE
+    market api.market_registration_events;
+    last_fill_time timestamptz;
+BEGIN
+    SELECT * INTO market FROM api.registered_market($1);
+    SELECT f."time" INTO last_fill_time
+    FROM public.fill_events AS f
+    WHERE f.market_id = $1
+    ORDER BY f.txn_version DESC
+    LIMIT 1;
+    RETURN json_build_object(
+        'data', to_jsonb(market) || jsonb_build_object(
+            'aggregated_txn_version', (SELECT MAX(txn_version) FROM aggregator.user_history_last_indexed_txn),
+            'last_fill_time', last_fill_time,
+            'last_fill_age_seconds', EXTRACT(EPOCH FROM CURRENT_TIMESTAMP - last_fill_time)
+        ),
+        'warnings', api.stale_data_warnings(ARRAY['UserHistory'])
+    );
+END;
+$$ LANGUAGE plpgsql STABLE SECURITY DEFINER SET search_path = '';
@@ -0,0 +1,100 @@
+-- This file should undo anything in `up.sql`
+CREATE OR REPLACE FUNCTION api.set_data_as_of () RETURNS void AS $$
+DECLARE
+  endpoint text;
+  pipeline text;
+  version numeric;
+  as_of timestamptz;
+BEGIN
+  endpoint := regexp_replace(
+    COALESCE(current_setting('request.path', true), ''),
+    '^/(rpc/)?',
+    ''
+  );
+  pipeline := CASE
+    WHEN endpoint IN (
+      'orders', 'limit_orders', 'market_orders', 'swap_orders', 'get_order',
+      'orderbook', 'price_levels', 'average_execution_price', 'pruned_orders'
+    ) THEN 'UserHistory'
+    WHEN endpoint IN ('candlesticks', 'market_candlesticks') THEN 'Candlesticks(%)'
+    WHEN endpoint IN ('fees', 'fees_24h') THEN 'Fees'
+    WHEN endpoint = 'prices' THEN 'Prices'
+    WHEN endpoint IN ('user_balances', 'user_balance') THEN 'UserBalances'
+    WHEN endpoint IN ('enumerated_volume', 'enumerated_volume_24h') THEN 'EnumeratedVolume'
+    WHEN endpoint = 'integrator_volume' THEN 'IntegratorVolume'
+  END;
+
+  IF pipeline IS NULL THEN
+    SELECT last_success_version INTO version
+    FROM public.processor_status
+    WHERE processor = 'econia_processor';
+  ELSE
+    SELECT MIN(txn_version) INTO version
+    FROM aggregator.pipeline_watermarks
+    WHERE model_name LIKE pipeline;
+  END IF;
+  IF version IS NULL THEN
+    RETURN;
+  END IF;
+
+  -- One backward scan of the primary key of each event table.
+  SELECT MAX(t) INTO as_of FROM (
+    (SELECT "time" AS t FROM public.fill_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_limit_order_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_market_order_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_swap_order_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.change_order_size_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.cancel_order_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+  ) AS last_events;
+
+  -- No event at all can only happen on an empty database.
+  PERFORM set_config(
+    'response.headers',
+    json_build_array(
+      json_build_object('x-data-as-of-txn-version', version::text),
+      json_build_object(
+        'x-data-as-of',
+        COALESCE(
+          to_char(as_of AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"'),
+          ''
+        )
+      )
+    )::text,
+    true
+  );
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = '';
+
+
+DROP VIEW api.pipeline_runs;
+
+
+ALTER TABLE aggregator.pipeline_runs
+DROP COLUMN txn_version;
+
+
+CREATE VIEW api.pipeline_runs AS
+SELECT
+  id,
+  model_name,
+  requested_at,
+  started_at,
+  finished_at,
+  error IS NOT NULL AS failed
+FROM
+  aggregator.pipeline_runs;
+
+
+GRANT
+SELECT
+  ON api.pipeline_runs TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.pipeline_runs TO grafana;
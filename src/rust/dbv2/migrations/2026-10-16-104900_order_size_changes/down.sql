@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP VIEW api.order_size_changes;
+
+
+DROP TABLE aggregator.order_size_changes;
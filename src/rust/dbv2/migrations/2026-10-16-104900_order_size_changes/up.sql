@@ -0,0 +1,40 @@
+-- Your SQL goes here
+-- Every size change aggregated into `aggregator.user_history`, with the
+-- remaining size of the order before and after it, so that the size of an
+-- order can be traced over time.
+CREATE TABLE aggregator.order_size_changes (
+  market_id NUMERIC(20,0) NOT NULL,
+  order_id NUMERIC(39,0) NOT NULL,
+  old_size NUMERIC(20,0) NOT NULL,
+  new_size NUMERIC(20,0) NOT NULL,
+  txn_version NUMERIC(20,0) NOT NULL,
+  event_idx NUMERIC(20,0) NOT NULL,
+  "time" TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (txn_version, event_idx)
+);
+
+
+CREATE INDEX order_size_changes_market_id_order_id
+ON aggregator.order_size_changes (market_id, order_id, txn_version, event_idx);
+
+
+GRANT
+SELECT
+  ON aggregator.order_size_changes TO grafana;
+
+
+CREATE VIEW api.order_size_changes AS
+SELECT
+  *
+FROM
+  aggregator.order_size_changes;
+
+
+GRANT
+SELECT
+  ON api.order_size_changes TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.order_size_changes TO grafana;
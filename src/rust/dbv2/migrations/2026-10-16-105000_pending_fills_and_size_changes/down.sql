@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE aggregator.pending_size_changes;
+
+
+DROP TABLE aggregator.pending_fills;
@@ -0,0 +1,33 @@
+-- Your SQL goes here
+-- Fill events whose order was not in `aggregator.user_history` yet when they
+-- were aggregated, one row per order the fill is missing for. Like
+-- `aggregator.pending_cancels`, they are retried on every run of the user
+-- history pipeline until the placement of their order has been aggregated.
+CREATE TABLE aggregator.pending_fills (
+  txn_version NUMERIC(20,0) NOT NULL,
+  event_idx NUMERIC(20,0) NOT NULL,
+  market_id NUMERIC(20,0) NOT NULL,
+  order_id NUMERIC(39,0) NOT NULL,
+  PRIMARY KEY (txn_version, event_idx, order_id)
+);
+
+
+-- Size change events whose order was not in `aggregator.user_history` yet
+-- when they were aggregated, retried like `aggregator.pending_fills`.
+CREATE TABLE aggregator.pending_size_changes (
+  txn_version NUMERIC(20,0) NOT NULL,
+  event_idx NUMERIC(20,0) NOT NULL,
+  market_id NUMERIC(20,0) NOT NULL,
+  order_id NUMERIC(39,0) NOT NULL,
+  PRIMARY KEY (txn_version, event_idx)
+);
+
+
+GRANT
+SELECT
+  ON aggregator.pending_fills TO grafana;
+
+
+GRANT
+SELECT
+  ON aggregator.pending_size_changes TO grafana;
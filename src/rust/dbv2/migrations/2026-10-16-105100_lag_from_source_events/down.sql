@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+CREATE OR REPLACE VIEW aggregator.pipeline_lag AS
+WITH processor AS (
+  SELECT last_success_version
+  FROM processor_status
+  WHERE processor = 'econia_processor'
+)
+SELECT
+  model_name AS pipeline,
+  GREATEST((SELECT last_success_version FROM processor) - txn_version, 0) AS lag
+FROM aggregator.pipeline_watermarks
+WHERE txn_version IS NOT NULL;
+
+
+DROP VIEW aggregator.pipeline_source_versions;
+
+
+DROP FUNCTION aggregator.last_event_txn_version;
@@ -0,0 +1,59 @@
+-- Your SQL goes here
+-- Returns:
+-- * The last transaction version with an order event, that is the version
+--   the `UserHistory` pipeline aggregates up to once caught up, or null if
+--   there is none
+CREATE FUNCTION aggregator.last_event_txn_version ()
+RETURNS numeric AS $$
+  -- One backward scan of the primary key of each event table.
+  SELECT MAX(txn_version) FROM (
+    (SELECT txn_version FROM public.fill_events ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT txn_version FROM public.place_limit_order_events ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT txn_version FROM public.place_market_order_events ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT txn_version FROM public.place_swap_order_events ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT txn_version FROM public.change_order_size_events ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT txn_version FROM public.cancel_order_events ORDER BY txn_version DESC LIMIT 1)
+  ) AS last_events;
+$$ LANGUAGE sql STABLE SECURITY DEFINER SET search_path = '';
+
+
+-- The watermark of each pipeline tracking one, next to the last transaction
+-- version of the source it aggregates. Watermarks only move to versions with
+-- source rows, so comparing them to the processor version would grow without
+-- bound while the market is quiet.
+CREATE VIEW aggregator.pipeline_source_versions AS
+WITH source AS (
+  SELECT
+    aggregator.last_event_txn_version () AS order_events,
+    (SELECT MAX(txn_version) FROM public.fill_events) AS fill_events,
+    (SELECT MAX(txn_version) FROM public.balance_updates_by_handle) AS balance_updates
+)
+SELECT
+  w.model_name,
+  w.txn_version,
+  CASE
+    WHEN w.model_name IN ('UserHistory', 'IntegratorVolume') THEN source.order_events
+    WHEN w.model_name = 'UserBalances' THEN source.balance_updates
+    ELSE source.fill_events
+  END AS source_txn_version
+FROM
+  aggregator.pipeline_watermarks AS w,
+  source;
+
+
+GRANT
+SELECT
+  ON aggregator.pipeline_source_versions TO grafana;
+
+
+CREATE OR REPLACE VIEW aggregator.pipeline_lag AS
+SELECT
+  model_name AS pipeline,
+  GREATEST(source_txn_version - txn_version, 0) AS lag
+FROM aggregator.pipeline_source_versions
+WHERE txn_version IS NOT NULL;
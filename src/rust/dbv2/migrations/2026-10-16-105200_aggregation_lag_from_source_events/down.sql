@@ -0,0 +1,29 @@
+-- This file should undo anything in `up.sql`
+DROP VIEW api.aggregation_lag;
+
+
+CREATE VIEW api.aggregation_lag AS
+WITH processor AS (
+  SELECT last_success_version
+  FROM public.processor_status
+  WHERE processor = 'econia_processor'
+)
+SELECT
+  w.model_name,
+  w.txn_version,
+  (SELECT last_success_version FROM processor) AS source_txn_version,
+  l.lag,
+  EXTRACT(EPOCH FROM CURRENT_TIMESTAMP - aggregator.last_event_time(w.txn_version)) AS age_seconds
+FROM
+  aggregator.pipeline_watermarks AS w
+  LEFT JOIN aggregator.pipeline_lag AS l ON l.pipeline = w.model_name;
+
+
+GRANT
+SELECT
+  ON api.aggregation_lag TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.aggregation_lag TO grafana;
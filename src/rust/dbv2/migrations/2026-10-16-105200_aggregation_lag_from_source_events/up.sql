@@ -0,0 +1,27 @@
+-- Your SQL goes here
+-- Same as before, except that `source_txn_version` is the last transaction
+-- version of the events the pipeline aggregates rather than the last one
+-- indexed by the processor, so that `lag` is their difference.
+DROP VIEW api.aggregation_lag;
+
+
+CREATE VIEW api.aggregation_lag AS
+SELECT
+  s.model_name,
+  s.txn_version,
+  s.source_txn_version,
+  l.lag,
+  EXTRACT(EPOCH FROM CURRENT_TIMESTAMP - aggregator.last_event_time(s.txn_version)) AS age_seconds
+FROM
+  aggregator.pipeline_source_versions AS s
+  LEFT JOIN aggregator.pipeline_lag AS l ON l.pipeline = s.model_name;
+
+
+GRANT
+SELECT
+  ON api.aggregation_lag TO web_anon;
+
+
+GRANT
+SELECT
+  ON api.aggregation_lag TO grafana;
@@ -0,0 +1,99 @@
+-- Your SQL goes here
+-- Same as before, except that endpoints of a pipeline that aggregated every
+-- event of its source report the last transaction indexed by the processor,
+-- like raw event endpoints do. Responses as of that transaction report its
+-- block time rather than the one of the last event, which made the data look
+-- stale while the market is quiet.
+CREATE OR REPLACE FUNCTION api.set_data_as_of () RETURNS void AS $$
+DECLARE
+  endpoint text;
+  pipeline text;
+  version numeric;
+  min_version numeric;
+  processor_version numeric;
+  processor_time timestamptz;
+  as_of timestamptz;
+BEGIN
+  endpoint := regexp_replace(
+    COALESCE(current_setting('request.path', true), ''),
+    '^/(rpc/)?',
+    ''
+  );
+  pipeline := CASE
+    WHEN endpoint IN (
+      'orders', 'limit_orders', 'market_orders', 'swap_orders', 'get_order',
+      'orderbook', 'price_levels', 'average_execution_price', 'pruned_orders'
+    ) THEN 'UserHistory'
+    WHEN endpoint IN ('candlesticks', 'market_candlesticks') THEN 'Candlesticks(%)'
+    WHEN endpoint IN ('fees', 'fees_24h') THEN 'Fees'
+    WHEN endpoint = 'prices' THEN 'Prices'
+    WHEN endpoint IN ('user_balances', 'user_balance') THEN 'UserBalances'
+    WHEN endpoint IN ('enumerated_volume', 'enumerated_volume_24h') THEN 'EnumeratedVolume'
+    WHEN endpoint = 'integrator_volume' THEN 'IntegratorVolume'
+  END;
+
+  SELECT last_success_version, last_transaction_timestamp AT TIME ZONE 'UTC'
+  INTO processor_version, processor_time
+  FROM public.processor_status
+  WHERE processor = 'econia_processor';
+  IF pipeline IS NULL THEN
+    version := processor_version;
+  ELSE
+    -- A pipeline that aggregated every event of its source reflects every
+    -- transaction indexed since, even in a quiet market.
+    SELECT MIN(
+      CASE
+        WHEN txn_version >= COALESCE(source_txn_version, 0)
+          THEN GREATEST(txn_version, processor_version)
+        ELSE txn_version
+      END
+    ) INTO version
+    FROM aggregator.pipeline_source_versions
+    WHERE model_name LIKE pipeline;
+  END IF;
+  min_version := current_setting('request.headers', true)::json->>'x-min-txn-version';
+  IF min_version IS NOT NULL AND (version IS NULL OR version < min_version) THEN
+    RAISE sqlstate 'PT503' USING
+      message = 'Data is not caught up yet',
+      detail = 'Data as of txn version ' || COALESCE(version::text, 'none')
+        || ', ' || min_version || ' requested';
+  END IF;
+  IF version IS NULL THEN
+    RETURN;
+  END IF;
+
+  -- One backward scan of the primary key of each event table.
+  SELECT MAX(t) INTO as_of FROM (
+    (SELECT "time" AS t FROM public.fill_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_limit_order_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_market_order_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.place_swap_order_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.change_order_size_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+    UNION ALL
+    (SELECT "time" FROM public.cancel_order_events WHERE txn_version <= version ORDER BY txn_version DESC LIMIT 1)
+  ) AS last_events;
+  IF version = processor_version THEN
+    as_of := GREATEST(as_of, processor_time);
+  END IF;
+
+  -- No event at all can only happen on an empty database.
+  PERFORM set_config(
+    'response.headers',
+    json_build_array(
+      json_build_object('x-data-as-of-txn-version', version::text),
+      json_build_object(
+        'x-data-as-of',
+        COALESCE(
+          to_char(as_of AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"'),
+          ''
+        )
+      )
+    )::text,
+    true
+  );
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER SET search_path = '';
@@ -0,0 +1,14 @@
+SELECT
+    market_id,
+    direction::text AS "direction!",
+    price AS "price!",
+    SUM(remaining_size) AS "size!"
+FROM
+    aggregator.user_history
+WHERE
+    order_status = 'open'
+    AND order_type = 'limit'
+GROUP BY
+    market_id,
+    direction,
+    price
@@ -0,0 +1,176 @@
+use std::{cell::Cell, fmt, str::FromStr};
+
+use bigdecimal::Zero;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
+use sqlx::types::BigDecimal;
+
+thread_local! {
+    /// Whether [`Amount`]s are serialized as JSON numbers, only set by [`AmountFormat::to_json`].
+    static AS_NUMBER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// How [`Amount`]s are written in published payloads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AmountFormat {
+    /// JSON strings (e.g. `"12345"`), which every client parses exactly.
+    #[default]
+    String,
+    /// JSON numbers, which clients parsing them as doubles (e.g. JavaScript) round past 2^53.
+    Number,
+}
+
+impl AmountFormat {
+    /// Serializes `value` as JSON, writing every [`Amount`] in it in this format.
+    pub fn to_json<T: Serialize>(self, value: &T) -> serde_json::Result<String> {
+        struct Reset(bool);
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                AS_NUMBER.with(|as_number| as_number.set(self.0));
+            }
+        }
+        let _reset = Reset(AS_NUMBER.with(|as_number| as_number.replace(self == Self::Number)));
+        serde_json::to_string(value)
+    }
+}
+
+/// An on-chain integer (size, price, ID...) in a published payload.
+///
+/// Most of these do not fit in the 53 bits JavaScript numbers hold exactly (order IDs use all
+/// 128 bits), so they are published as strings unless [`AmountFormat::Number`] is used.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(pub BigDecimal);
+
+impl Amount {
+    pub fn zero() -> Self {
+        Self(BigDecimal::zero())
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_integer() {
+            self.0.with_scale(0).fmt(f)
+        } else {
+            self.0.fmt(f)
+        }
+    }
+}
+
+impl From<BigDecimal> for Amount {
+    fn from(value: BigDecimal) -> Self {
+        Self(value)
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // `BigDecimal::to_u128` gives up past 64 bits, the integer is parsed back instead.
+        if AS_NUMBER.with(Cell::get) && self.0.is_integer() {
+            if let Ok(value) = self.to_string().parse() {
+                return serializer.serialize_u128(value);
+            }
+        }
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    /// Accepts JSON numbers (as Postgres notifications carry them) and JSON strings.
+    ///
+    /// serde_json reads numbers over 64 bits as floats unless told to expect one, which cannot
+    /// be done without knowing it is not a string, so the raw JSON value is parsed instead.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        let json = raw.get();
+        let value = match json.strip_prefix('"') {
+            Some(string) => string.strip_suffix('"').unwrap_or(string),
+            None => json,
+        };
+        BigDecimal::from_str(value)
+            .map(Self)
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Other(json), &"an amount"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        size: Amount,
+    }
+
+    /// 2^53 + 1, the first integer a double cannot hold.
+    const PAST_DOUBLES: &str = "9007199254740993";
+    /// The largest order ID, 2^128 - 1.
+    const MAX_ORDER_ID: &str = "340282366920938463463374607431768211455";
+
+    fn payload(size: &str) -> Payload {
+        Payload {
+            size: Amount(BigDecimal::from_str(size).unwrap()),
+        }
+    }
+
+    #[test]
+    fn strings_by_default() {
+        let json = format!(r#"{{"size":"{MAX_ORDER_ID}"}}"#);
+        assert_eq!(
+            AmountFormat::default()
+                .to_json(&payload(MAX_ORDER_ID))
+                .unwrap(),
+            json
+        );
+        assert_eq!(serde_json::to_string(&payload(MAX_ORDER_ID)).unwrap(), json);
+    }
+
+    #[test]
+    fn numbers_round_trip_past_doubles() {
+        for size in [PAST_DOUBLES, MAX_ORDER_ID] {
+            let json = AmountFormat::Number.to_json(&payload(size)).unwrap();
+            assert_eq!(json, format!(r#"{{"size":{size}}}"#));
+            assert_eq!(
+                serde_json::from_str::<Payload>(&json).unwrap(),
+                payload(size)
+            );
+        }
+    }
+
+    #[test]
+    fn strings_round_trip_past_doubles() {
+        for size in [PAST_DOUBLES, MAX_ORDER_ID] {
+            let json = AmountFormat::String.to_json(&payload(size)).unwrap();
+            assert_eq!(json, format!(r#"{{"size":"{size}"}}"#));
+            assert_eq!(
+                serde_json::from_str::<Payload>(&json).unwrap(),
+                payload(size)
+            );
+        }
+    }
+
+    #[test]
+    fn format_is_reset_after_serializing() {
+        AmountFormat::Number.to_json(&payload("1")).unwrap();
+        assert_eq!(
+            serde_json::to_string(&payload("1")).unwrap(),
+            r#"{"size":"1"}"#
+        );
+    }
+
+    #[test]
+    fn integers_are_written_without_scale() {
+        assert_eq!(payload("1e3").size.to_string(), "1000");
+        assert_eq!(
+            AmountFormat::Number.to_json(&payload("1000.00")).unwrap(),
+            r#"{"size":1000}"#
+        );
+    }
+
+    #[test]
+    fn invalid_amounts_are_rejected() {
+        for json in [r#"{"size":"abc"}"#, r#"{"size":true}"#, r#"{"size":null}"#] {
+            assert!(serde_json::from_str::<Payload>(json).is_err(), "{json}");
+        }
+    }
+}
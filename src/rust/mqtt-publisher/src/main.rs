@@ -1,7 +1,10 @@
-use std::{collections::HashSet, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{anyhow, Result};
-use bigdecimal::ToPrimitive;
 use chrono::{DateTime, Utc};
 use rumqttc::{AsyncClient, EventLoop, MqttOptions, QoS, Transport};
 use serde::{Deserialize, Serialize};
@@ -9,94 +12,139 @@ use sqlx::{Executor, PgConnection};
 use sqlx_postgres::{PgListener, PgPool};
 use tokio::sync::RwLock;
 
+use amount::{Amount, AmountFormat};
+
+mod amount;
+
 #[derive(Serialize, Deserialize)]
 struct PlaceLimitOrderNotif {
-    txn_version: u128,
-    event_idx: u128,
+    txn_version: Amount,
+    event_idx: Amount,
     time: DateTime<Utc>,
-    market_id: u128,
+    market_id: Amount,
     user: String,
-    custodian_id: u128,
-    order_id: u128,
+    custodian_id: Amount,
+    order_id: Amount,
     side: bool,
     integrator: String,
-    initial_size: u128,
-    price: u128,
+    initial_size: Amount,
+    price: Amount,
     restriction: i16,
     self_match_behavior: i16,
-    size: u128,
+    size: Amount,
 }
 #[derive(Serialize, Deserialize)]
 struct PlaceMarketOrderNotif {
-    txn_version: u128,
-    event_idx: u128,
+    txn_version: Amount,
+    event_idx: Amount,
     time: DateTime<Utc>,
-    market_id: u128,
+    market_id: Amount,
     user: String,
-    custodian_id: u128,
-    order_id: u128,
+    custodian_id: Amount,
+    order_id: Amount,
     direction: bool,
     integrator: String,
     self_match_behavior: i16,
-    size: u128,
+    size: Amount,
 }
 #[derive(Serialize, Deserialize)]
 struct PlaceSwapOrderNotif {
-    txn_version: u128,
-    event_idx: u128,
+    txn_version: Amount,
+    event_idx: Amount,
     time: DateTime<Utc>,
-    market_id: u128,
-    order_id: u128,
+    market_id: Amount,
+    order_id: Amount,
     direction: bool,
     signing_account: String,
     integrator: String,
-    min_base: u128,
-    max_base: u128,
-    min_quote: u128,
-    max_quote: u128,
-    limit_price: u128,
+    min_base: Amount,
+    max_base: Amount,
+    min_quote: Amount,
+    max_quote: Amount,
+    limit_price: Amount,
 }
 #[derive(Serialize, Deserialize)]
 struct ChangeOrderSizeNotif {
-    txn_version: u128,
-    event_idx: u128,
+    txn_version: Amount,
+    event_idx: Amount,
     time: DateTime<Utc>,
-    market_id: u128,
+    market_id: Amount,
     user: String,
-    custodian_id: u128,
-    order_id: u128,
+    custodian_id: Amount,
+    order_id: Amount,
     side: bool,
-    new_size: u128,
+    new_size: Amount,
 }
 #[derive(Serialize, Deserialize)]
 struct CancelOrderNotif {
-    txn_version: u128,
-    event_idx: u128,
+    txn_version: Amount,
+    event_idx: Amount,
     time: DateTime<Utc>,
-    market_id: u128,
+    market_id: Amount,
     user: String,
-    custodian_id: u128,
-    order_id: u128,
+    custodian_id: Amount,
+    order_id: Amount,
     reason: i16,
 }
 #[derive(Serialize, Deserialize)]
 struct FillNotif {
-    txn_version: u128,
-    event_idx: u128,
+    txn_version: Amount,
+    event_idx: Amount,
     emit_address: String,
     time: DateTime<Utc>,
     maker_address: String,
-    maker_custodian_id: u128,
-    maker_order_id: u128,
+    maker_custodian_id: Amount,
+    maker_order_id: Amount,
     maker_side: bool,
-    market_id: u128,
-    price: u128,
-    sequence_number_for_trade: u128,
-    size: u128,
+    market_id: Amount,
+    price: Amount,
+    sequence_number_for_trade: Amount,
+    size: Amount,
     taker_address: String,
-    taker_custodian_id: u128,
-    taker_order_id: u128,
-    taker_quote_fees_paid: u128,
+    taker_custodian_id: Amount,
+    taker_order_id: Amount,
+    taker_quote_fees_paid: Amount,
+}
+
+/// A fill as seen by both sides of the trade, with the same fields as the `api.trades` endpoint.
+#[derive(Serialize)]
+struct FillView {
+    txn_version: Amount,
+    event_idx: Amount,
+    time: DateTime<Utc>,
+    price: Amount,
+    size: Amount,
+    /// Side of the taker, `buy` or `sell`.
+    side: &'static str,
+    maker_address: String,
+    maker_custodian_id: Amount,
+    maker_order_id: Amount,
+    taker_address: String,
+    taker_custodian_id: Amount,
+    taker_order_id: Amount,
+    /// Only the taker pays a fee, makers pay none and get no rebate.
+    taker_quote_fees_paid: Amount,
+}
+
+impl From<&FillNotif> for FillView {
+    fn from(fill: &FillNotif) -> Self {
+        Self {
+            txn_version: fill.txn_version.clone(),
+            event_idx: fill.event_idx.clone(),
+            time: fill.time,
+            price: fill.price.clone(),
+            size: fill.size.clone(),
+            // The taker buys from an ask maker and sells to a bid maker.
+            side: if fill.maker_side { "buy" } else { "sell" },
+            maker_address: fill.maker_address.clone(),
+            maker_custodian_id: fill.maker_custodian_id.clone(),
+            maker_order_id: fill.maker_order_id.clone(),
+            taker_address: fill.taker_address.clone(),
+            taker_custodian_id: fill.taker_custodian_id.clone(),
+            taker_order_id: fill.taker_order_id.clone(),
+            taker_quote_fees_paid: fill.taker_quote_fees_paid.clone(),
+        }
+    }
 }
 
 #[tokio::main]
@@ -105,6 +153,14 @@ async fn main() -> Result<()> {
     let mqtt_password = std::env::var("MQTT_PASSWORD")?;
     let db_url = std::env::var("DATABASE_URL")?;
     let mqtt_price_levels = std::env::var("MQTT_PRICE_LEVELS").unwrap_or(String::from("no"));
+    let mqtt_order_book = std::env::var("MQTT_ORDER_BOOK").unwrap_or(String::from("no"));
+    let mqtt_numbers_as_strings =
+        std::env::var("MQTT_NUMBERS_AS_STRINGS").unwrap_or(String::from("yes"));
+    let format = if mqtt_numbers_as_strings == "no" {
+        AmountFormat::Number
+    } else {
+        AmountFormat::String
+    };
 
     let mut mqttoptions =
         MqttOptions::parse_url(format!("{mqtt_url}/?client_id=mqtt_publisher")).unwrap();
@@ -115,14 +171,23 @@ async fn main() -> Result<()> {
 
     let mqtt_client = Arc::new(RwLock::new(client));
 
-    let pnl = postgres_notif_loop(&db_url, mqtt_client.clone());
+    let pnl = postgres_notif_loop(&db_url, mqtt_client.clone(), format);
     let epl = eventpoll_loop(eventloop);
-    if mqtt_price_levels == "yes" {
-        let pll = price_level_loop(&db_url, mqtt_client);
-        tokio::try_join!(epl, pnl, pll)?;
-    } else {
-        tokio::try_join!(epl, pnl)?;
-    }
+    let pll = async {
+        if mqtt_price_levels == "yes" {
+            price_level_loop(&db_url, mqtt_client.clone(), format).await
+        } else {
+            Ok(())
+        }
+    };
+    let obl = async {
+        if mqtt_order_book == "yes" {
+            order_book_loop(&db_url, mqtt_client.clone(), format).await
+        } else {
+            Ok(())
+        }
+    };
+    tokio::try_join!(epl, pnl, pll, obl)?;
 
     Ok(())
 }
@@ -135,27 +200,32 @@ async fn eventpoll_loop(mut eventloop: EventLoop) -> Result<()> {
 
 #[derive(Serialize)]
 struct PriceLevel {
-    price: u128,
-    size: u128,
-    txn_version: u128,
+    price: Amount,
+    size: Amount,
+    txn_version: Amount,
 }
 
-async fn price_level_loop(db_url: &str, mqtt_client: Arc<RwLock<AsyncClient>>) -> Result<()> {
+async fn price_level_loop(
+    db_url: &str,
+    mqtt_client: Arc<RwLock<AsyncClient>>,
+    format: AmountFormat,
+) -> Result<()> {
     let pool = PgPool::connect(&db_url).await?;
 
     loop {
         tokio::time::sleep(Duration::from_millis(50)).await;
         let mut tx = pool.begin().await?;
-        tx.execute("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ;").await?;
+        tx.execute("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ;")
+            .await?;
         let data = sqlx::query_file!("sqlx_queries/get_price_levels.sql")
             .fetch_all(&mut tx as &mut PgConnection)
             .await?;
-        let txn_version = sqlx::query_file!("sqlx_queries/get_user_history_last_indexed_txn.sql")
-            .fetch_one(&mut tx as &mut PgConnection)
-            .await?
-            .txn_version
-            .to_u128()
-            .ok_or(anyhow!("txn_version is too big"))?;
+        let txn_version: Amount =
+            sqlx::query_file!("sqlx_queries/get_user_history_last_indexed_txn.sql")
+                .fetch_one(&mut tx as &mut PgConnection)
+                .await?
+                .txn_version
+                .into();
         tx.rollback().await?;
         let mqtt_client = mqtt_client.read().await;
         for row in data {
@@ -166,33 +236,199 @@ async fn price_level_loop(db_url: &str, mqtt_client: Arc<RwLock<AsyncClient>>) -
                 row.level.ok_or(anyhow!("level is None"))?
             );
             let payload = PriceLevel {
-                price: row
-                    .price
-                    .ok_or(anyhow!("price is None"))?
-                    .to_u128()
-                    .ok_or(anyhow!("price is too big"))?,
-                size: row
-                    .total_size
-                    .ok_or(anyhow!("total_size is None"))?
-                    .to_u128()
-                    .ok_or(anyhow!("total_size is too big"))?,
-                txn_version,
+                price: row.price.ok_or(anyhow!("price is None"))?.into(),
+                size: row.total_size.ok_or(anyhow!("total_size is None"))?.into(),
+                txn_version: txn_version.clone(),
             };
             mqtt_client
-                .publish(
-                    topic,
-                    QoS::AtLeastOnce,
-                    false,
-                    serde_json::to_string(&payload)?,
-                )
+                .publish(topic, QoS::AtLeastOnce, false, format.to_json(&payload)?)
                 .await?;
         }
     }
 }
 
+/// The size of every price level of one side of a market, keyed by price.
+type Levels = BTreeMap<Amount, Amount>;
+
+/// The price levels of a market.
+#[derive(Default)]
+struct Book {
+    asks: Levels,
+    bids: Levels,
+}
+
+impl Book {
+    /// Returns the levels of `new` that differ from those of `self`, with a size of zero for
+    /// levels that are gone.
+    fn diff(&self, new: &Book) -> Book {
+        let diff = |old: &Levels, new: &Levels| -> Levels {
+            let gone = old
+                .keys()
+                .filter(|price| !new.contains_key(price))
+                .map(|price| (price.clone(), Amount::zero()));
+            let changed = new
+                .iter()
+                .filter(|(price, size)| old.get(price) != Some(size))
+                .map(|(price, size)| (price.clone(), size.clone()));
+            gone.chain(changed).collect()
+        };
+        Book {
+            asks: diff(&self.asks, &new.asks),
+            bids: diff(&self.bids, &new.bids),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.asks.is_empty() && self.bids.is_empty()
+    }
+}
+
+#[derive(Serialize)]
+struct BookLevel {
+    price: Amount,
+    size: Amount,
+}
+
+fn to_book_levels(levels: &Levels) -> Vec<BookLevel> {
+    levels
+        .iter()
+        .map(|(price, size)| BookLevel {
+            price: price.clone(),
+            size: size.clone(),
+        })
+        .collect()
+}
+
+/// Every price level of a market as of `txn_version`, asks and bids sorted by price.
+#[derive(Serialize)]
+struct BookSnapshot {
+    txn_version: Amount,
+    asks: Vec<BookLevel>,
+    bids: Vec<BookLevel>,
+}
+
+/// The price levels of a market that changed between `previous_txn_version` and `txn_version`,
+/// with their new size (zero if the level is gone).
+#[derive(Serialize)]
+struct BookDiff {
+    previous_txn_version: Amount,
+    txn_version: Amount,
+    asks: Vec<BookLevel>,
+    bids: Vec<BookLevel>,
+}
+
+/// Returns the `(topic, retain, payload)` of the messages publishing `new_books`, read at
+/// `txn_version`, to clients holding `books`: for each market that changed, a diff if a message
+/// of the market was `published` before, then a retained snapshot. Records the messages in
+/// `published`.
+fn book_messages(
+    books: &HashMap<Amount, Book>,
+    new_books: &HashMap<Amount, Book>,
+    published: &mut HashMap<Amount, Amount>,
+    txn_version: &Amount,
+    format: AmountFormat,
+) -> Result<Vec<(String, bool, String)>> {
+    let mut messages = vec![];
+    let empty = Book::default();
+    let market_ids: HashSet<&Amount> = books.keys().chain(new_books.keys()).collect();
+    for market_id in market_ids {
+        let old = books.get(market_id).unwrap_or(&empty);
+        let new = new_books.get(market_id).unwrap_or(&empty);
+        let diff = old.diff(new);
+        if let Some(previous_txn_version) = published.get(market_id) {
+            if diff.is_empty() {
+                continue;
+            }
+            let payload = BookDiff {
+                previous_txn_version: previous_txn_version.clone(),
+                txn_version: txn_version.clone(),
+                asks: to_book_levels(&diff.asks),
+                bids: to_book_levels(&diff.bids),
+            };
+            messages.push((
+                format!("book_diff/{market_id}"),
+                false,
+                format.to_json(&payload)?,
+            ));
+        }
+        let payload = BookSnapshot {
+            txn_version: txn_version.clone(),
+            asks: to_book_levels(&new.asks),
+            bids: to_book_levels(&new.bids),
+        };
+        messages.push((format!("book/{market_id}"), true, format.to_json(&payload)?));
+        published.insert(market_id.clone(), txn_version.clone());
+    }
+    Ok(messages)
+}
+
+/// Publishes the order book of every market as a retained snapshot on `book/MARKET_ID`, and the
+/// price levels that changed on `book_diff/MARKET_ID`, each time the user history aggregates new
+/// transactions.
+///
+/// Messages of a market are chained by transaction version, so that a client holding a snapshot
+/// can tell which diffs to apply and when it missed one.
+async fn order_book_loop(
+    db_url: &str,
+    mqtt_client: Arc<RwLock<AsyncClient>>,
+    format: AmountFormat,
+) -> Result<()> {
+    let pool = PgPool::connect(&db_url).await?;
+    let mut last_txn_version = None;
+    let mut books: HashMap<Amount, Book> = HashMap::new();
+    // Transaction version of the last message published for each market.
+    let mut published: HashMap<Amount, Amount> = HashMap::new();
+
+    loop {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut tx = pool.begin().await?;
+        tx.execute("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ;")
+            .await?;
+        let Some(txn_version) =
+            sqlx::query_file!("sqlx_queries/get_user_history_last_indexed_txn.sql")
+                .fetch_optional(&mut tx as &mut PgConnection)
+                .await?
+        else {
+            continue;
+        };
+        let txn_version: Amount = txn_version.txn_version.into();
+        if last_txn_version.as_ref() == Some(&txn_version) {
+            continue;
+        }
+        let rows = sqlx::query_file!("sqlx_queries/get_order_book.sql")
+            .fetch_all(&mut tx as &mut PgConnection)
+            .await?;
+        tx.rollback().await?;
+
+        let mut new_books: HashMap<Amount, Book> = HashMap::new();
+        for row in rows {
+            let market_id: Amount = row.market_id.into();
+            let price: Amount = row.price.into();
+            let size: Amount = row.size.into();
+            let book = new_books.entry(market_id).or_default();
+            match row.direction.as_str() {
+                "ask" => book.asks.insert(price, size),
+                _ => book.bids.insert(price, size),
+            };
+        }
+
+        let mqtt_client = mqtt_client.read().await;
+        for (topic, retain, payload) in
+            book_messages(&books, &new_books, &mut published, &txn_version, format)?
+        {
+            mqtt_client
+                .publish(topic, QoS::AtLeastOnce, retain, payload)
+                .await?;
+        }
+        books = new_books;
+        last_txn_version = Some(txn_version);
+    }
+}
+
 async fn postgres_notif_loop(
     db_url: &str,
     mqtt_client: Arc<RwLock<AsyncClient>>,
+    format: AmountFormat,
 ) -> anyhow::Result<()> {
     let mut listener = PgListener::connect(&db_url).await?;
     let channels = vec![
@@ -204,7 +440,7 @@ async fn postgres_notif_loop(
         "fill",
     ];
     listener.listen_all(channels).await?;
-    let mut emitted_fills: HashSet<(u128, u128, u128)> = Default::default();
+    let mut emitted_fills: HashSet<(Amount, Amount, Amount)> = Default::default();
     loop {
         let notification = listener.recv().await?;
         let mqtt_client = mqtt_client.read().await;
@@ -219,7 +455,7 @@ async fn postgres_notif_loop(
                         ),
                         QoS::AtLeastOnce,
                         false,
-                        serde_json::to_string(&data)?,
+                        format.to_json(&data)?,
                     )
                     .await?;
             }
@@ -233,7 +469,7 @@ async fn postgres_notif_loop(
                         ),
                         QoS::AtLeastOnce,
                         false,
-                        serde_json::to_string(&data)?,
+                        format.to_json(&data)?,
                     )
                     .await?;
             }
@@ -247,7 +483,7 @@ async fn postgres_notif_loop(
                         ),
                         QoS::AtLeastOnce,
                         false,
-                        serde_json::to_string(&data)?,
+                        format.to_json(&data)?,
                     )
                     .await?;
             }
@@ -261,7 +497,7 @@ async fn postgres_notif_loop(
                         ),
                         QoS::AtLeastOnce,
                         false,
-                        serde_json::to_string(&data)?,
+                        format.to_json(&data)?,
                     )
                     .await?;
             }
@@ -275,7 +511,7 @@ async fn postgres_notif_loop(
                         ),
                         QoS::AtLeastOnce,
                         false,
-                        serde_json::to_string(&data)?,
+                        format.to_json(&data)?,
                     )
                     .await?;
             }
@@ -294,7 +530,7 @@ async fn postgres_notif_loop(
                             ),
                             QoS::AtLeastOnce,
                             false,
-                            serde_json::to_string(&data)?,
+                            format.to_json(&data)?,
                         )
                         .await?;
                     mqtt_client
@@ -305,7 +541,15 @@ async fn postgres_notif_loop(
                             ),
                             QoS::AtLeastOnce,
                             false,
-                            serde_json::to_string(&data)?,
+                            format.to_json(&data)?,
+                        )
+                        .await?;
+                    mqtt_client
+                        .publish(
+                            format!("trade/{}", data.market_id),
+                            QoS::AtLeastOnce,
+                            false,
+                            format.to_json(&FillView::from(&data))?,
                         )
                         .await?;
                     emitted_fills.insert((
@@ -319,3 +563,160 @@ async fn postgres_notif_loop(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::types::BigDecimal;
+
+    use super::*;
+
+    /// A notification of the `fill` channel, filling an ask maker order when `maker_side` is true.
+    fn fill(maker_side: bool) -> FillNotif {
+        serde_json::from_value(json!({
+            "txn_version": 100,
+            "event_idx": 2,
+            "emit_address": "0xa",
+            "time": "2024-01-01T00:00:00Z",
+            "maker_address": "0xa",
+            "maker_custodian_id": 0,
+            "maker_order_id": 1,
+            "maker_side": maker_side,
+            "market_id": 3,
+            "price": 10,
+            "sequence_number_for_trade": 0,
+            "size": 5,
+            "taker_address": "0xb",
+            "taker_custodian_id": 7,
+            "taker_order_id": 2,
+            "taker_quote_fees_paid": 25,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn fill_view_has_the_roles_and_fees_of_the_trade() {
+        assert_eq!(
+            serde_json::to_value(FillView::from(&fill(true))).unwrap(),
+            json!({
+                "txn_version": "100",
+                "event_idx": "2",
+                "time": "2024-01-01T00:00:00Z",
+                "price": "10",
+                "size": "5",
+                "side": "buy",
+                "maker_address": "0xa",
+                "maker_custodian_id": "0",
+                "maker_order_id": "1",
+                "taker_address": "0xb",
+                "taker_custodian_id": "7",
+                "taker_order_id": "2",
+                "taker_quote_fees_paid": "25",
+            })
+        );
+    }
+
+    #[test]
+    fn taker_sells_to_a_bid_maker() {
+        assert_eq!(FillView::from(&fill(false)).side, "sell");
+    }
+
+    fn amount(value: u64) -> Amount {
+        Amount(BigDecimal::from(value))
+    }
+
+    /// The books of market 3, with the `(price, size)` of its asks and bids.
+    fn books(asks: &[(u64, u64)], bids: &[(u64, u64)]) -> HashMap<Amount, Book> {
+        let levels = |levels: &[(u64, u64)]| {
+            levels
+                .iter()
+                .map(|&(price, size)| (amount(price), amount(size)))
+                .collect()
+        };
+        HashMap::from([(
+            amount(3),
+            Book {
+                asks: levels(asks),
+                bids: levels(bids),
+            },
+        )])
+    }
+
+    /// Returns the `(topic, retain, payload)` of the messages publishing `new` to clients
+    /// holding `old`.
+    fn messages(
+        old: &HashMap<Amount, Book>,
+        new: &HashMap<Amount, Book>,
+        published: &mut HashMap<Amount, Amount>,
+        txn_version: u64,
+    ) -> Vec<(String, bool, serde_json::Value)> {
+        book_messages(
+            old,
+            new,
+            published,
+            &amount(txn_version),
+            AmountFormat::Number,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|(topic, retain, payload)| (topic, retain, serde_json::from_str(&payload).unwrap()))
+        .collect()
+    }
+
+    #[test]
+    fn snapshot_is_followed_by_the_diffs_of_the_changed_levels() {
+        let mut published = HashMap::new();
+        let first = books(&[(11, 5), (12, 1)], &[(9, 2)]);
+        assert_eq!(
+            messages(&HashMap::new(), &first, &mut published, 100),
+            [(
+                "book/3".to_string(),
+                true,
+                json!({
+                    "txn_version": 100,
+                    "asks": [{ "price": 11, "size": 5 }, { "price": 12, "size": 1 }],
+                    "bids": [{ "price": 9, "size": 2 }],
+                })
+            )]
+        );
+
+        // The ask at 11 is partially filled, the one at 12 cancelled, and a bid placed at 8.
+        let second = books(&[(11, 3)], &[(8, 4), (9, 2)]);
+        assert_eq!(
+            messages(&first, &second, &mut published, 120),
+            [
+                (
+                    "book_diff/3".to_string(),
+                    false,
+                    json!({
+                        "previous_txn_version": 100,
+                        "txn_version": 120,
+                        "asks": [{ "price": 11, "size": 3 }, { "price": 12, "size": 0 }],
+                        "bids": [{ "price": 8, "size": 4 }],
+                    })
+                ),
+                (
+                    "book/3".to_string(),
+                    true,
+                    json!({
+                        "txn_version": 120,
+                        "asks": [{ "price": 11, "size": 3 }],
+                        "bids": [{ "price": 8, "size": 4 }, { "price": 9, "size": 2 }],
+                    })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged_books_are_not_published_again() {
+        let mut published = HashMap::new();
+        let book = books(&[(11, 5)], &[]);
+        messages(&HashMap::new(), &book, &mut published, 100);
+        assert!(messages(&book, &book, &mut published, 120).is_empty());
+        // The next diff chains to the last message published, not to the last read.
+        let changed = books(&[(11, 4)], &[]);
+        let messages = messages(&book, &changed, &mut published, 130);
+        assert_eq!(messages[0].2["previous_txn_version"], 100);
+    }
+}
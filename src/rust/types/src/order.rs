@@ -75,6 +75,84 @@ impl From<bool> for Direction {
     }
 }
 
+impl From<Direction> for bool {
+    fn from(value: Direction) -> Self {
+        match value {
+            Direction::Buy => false,
+            Direction::Sell => true,
+        }
+    }
+}
+
+/// Human-readable side of an order, as stored in the `order_direction` database type.
+///
+/// Limit orders have a [`Side`] (`ask` or `bid`) while market orders and swaps have a
+/// [`Direction`] (`buy` or `sell`). Unlike those two, which use the on-chain `bool` encoding,
+/// this type is serialized as a string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[cfg_attr(
+    feature = "sqlx",
+    derive(sqlx::Type),
+    sqlx(type_name = "order_direction", rename_all = "lowercase")
+)]
+pub enum OrderDirection {
+    Ask,
+    Bid,
+    Sell,
+    Buy,
+}
+
+impl From<Side> for OrderDirection {
+    fn from(value: Side) -> Self {
+        match value {
+            Side::Ask => Self::Ask,
+            Side::Bid => Self::Bid,
+        }
+    }
+}
+
+impl From<Direction> for OrderDirection {
+    fn from(value: Direction) -> Self {
+        match value {
+            Direction::Sell => Self::Sell,
+            Direction::Buy => Self::Buy,
+        }
+    }
+}
+
+impl TryFrom<OrderDirection> for Side {
+    type Error = TypeError;
+
+    fn try_from(value: OrderDirection) -> Result<Self, Self::Error> {
+        match value {
+            OrderDirection::Ask => Ok(Self::Ask),
+            OrderDirection::Bid => Ok(Self::Bid),
+            _ => Err(TypeError::ConversionError {
+                name: "Side".to_string(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<OrderDirection> for Direction {
+    type Error = TypeError;
+
+    fn try_from(value: OrderDirection) -> Result<Self, Self::Error> {
+        match value {
+            OrderDirection::Sell => Ok(Self::Sell),
+            OrderDirection::Buy => Ok(Self::Buy),
+            _ => Err(TypeError::ConversionError {
+                name: "Direction".to_string(),
+            }),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
@@ -301,3 +379,60 @@ pub const SHIFT_MARKET_ID: u64 = 64;
 pub const NO_CUSTODIAN: u64 = 0;
 pub const NO_UNDERWRITER: u64 = 0;
 pub const NIL: u64 = 0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_direction_from_side_round_trips() {
+        for side in [Side::Ask, Side::Bid] {
+            let direction = OrderDirection::from(side);
+            assert_eq!(Side::try_from(direction).unwrap(), side);
+            assert!(Direction::try_from(direction).is_err());
+        }
+        assert_eq!(OrderDirection::from(Side::Ask), OrderDirection::Ask);
+        assert_eq!(OrderDirection::from(Side::Bid), OrderDirection::Bid);
+    }
+
+    #[test]
+    fn order_direction_from_direction_round_trips() {
+        for direction in [Direction::Buy, Direction::Sell] {
+            let order_direction = OrderDirection::from(direction);
+            assert_eq!(Direction::try_from(order_direction).unwrap(), direction);
+            assert!(Side::try_from(order_direction).is_err());
+        }
+        assert_eq!(OrderDirection::from(Direction::Buy), OrderDirection::Buy);
+        assert_eq!(OrderDirection::from(Direction::Sell), OrderDirection::Sell);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn order_direction_serializes_as_string() {
+        for (direction, json) in [
+            (OrderDirection::Ask, "\"ask\""),
+            (OrderDirection::Bid, "\"bid\""),
+            (OrderDirection::Sell, "\"sell\""),
+            (OrderDirection::Buy, "\"buy\""),
+        ] {
+            assert_eq!(serde_json::to_string(&direction).unwrap(), json);
+            assert_eq!(
+                serde_json::from_str::<OrderDirection>(json).unwrap(),
+                direction
+            );
+        }
+        assert!(serde_json::from_str::<OrderDirection>("\"Ask\"").is_err());
+        assert!(serde_json::from_str::<OrderDirection>("true").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn side_and_direction_keep_bool_encoding() {
+        assert_eq!(serde_json::to_string(&Side::Ask).unwrap(), "true");
+        assert_eq!(serde_json::from_str::<Side>("false").unwrap(), Side::Bid);
+        assert_eq!(
+            serde_json::from_str::<Direction>("true").unwrap(),
+            Direction::Sell
+        );
+    }
+}
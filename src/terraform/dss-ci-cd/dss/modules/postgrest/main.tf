@@ -13,6 +13,10 @@ resource "google_cloud_run_v2_service" "postgrest" {
         name  = "PGRST_DB_MAX_ROWS"
         value = var.postgrest_max_rows
       }
+      env {
+        name  = "PGRST_DB_PRE_REQUEST"
+        value = "api.set_data_as_of"
+      }
       env {
         name  = "PGRST_DB_SCHEMA"
         value = "api"